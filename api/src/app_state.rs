@@ -1,15 +1,50 @@
 /// Application state
 
+use crate::auth_audit::AuthAudit;
 use crate::config::ServiceConfig;
 use crate::infra::kafka::KafkaClient;
+use crate::infra::postgres::QueryLimits;
+use crate::ingest::{IngestStats, WsEvent};
+use crate::nonce_store::NonceStore;
+use crate::runtime_stats::RuntimeStats;
+use crate::signature_filter::SignatureFilter;
+use crate::summary_cache::SummaryCache;
+use crate::waf_lists::{IpReputation, WafLists};
+use crate::ws::{cursor::CursorStore, fanout::WsFanout, registry::SubscriptionRegistry};
 use redis::aio::ConnectionManager;
 use sqlx::PgPool;
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
+
+/// Buffered transaction events per subscriber before it lags
+const TX_EVENT_CAPACITY: usize = 1024;
 
 #[derive(Clone)]
 pub struct AppState {
     pub service_config: ServiceConfig,
     pub postgres: Option<PgPool>,
     pub redis: Option<ConnectionManager>,
+    pub ingest_stats: Arc<Mutex<IngestStats>>,
+    pub signature_filter: Option<Arc<SignatureFilter>>,
+    /// List summaries refreshed in the background, with `cache.summary_refresh_ms`
+    pub summary_cache: Option<Arc<SummaryCache>>,
+    pub auth_audit: Option<Arc<AuthAudit>>,
+    pub nonce_store: Option<Arc<NonceStore>>,
+    pub waf_lists: Option<Arc<WafLists>>,
+    pub waf_reputation: Option<Arc<IpReputation>>,
+    pub runtime_stats: Arc<RuntimeStats>,
+    /// Concurrent heavy queries per route
+    pub query_limits: Arc<QueryLimits>,
+    /// Ingested transaction events, fanned out to long-poll waiters
+    pub tx_events: broadcast::Sender<WsEvent>,
+    /// Transaction subscriptions of WebSocket connections
+    pub ws_fanout: Arc<WsFanout>,
+    /// Subscription count across all WebSocket connections
+    pub ws_subscriptions: Arc<SubscriptionRegistry>,
+    /// Last delivered slot per `client_id` subscription, for auto-resume
+    pub ws_cursors: Option<Arc<CursorStore>>,
+    /// Tells open WebSocket connections the server is shutting down
+    pub ws_shutdown: broadcast::Sender<()>,
     // Kafka client is not included in AppState due to Clone limitations
 }
 
@@ -24,6 +59,20 @@ impl AppState {
             service_config,
             postgres,
             redis,
+            ingest_stats: Arc::new(Mutex::new(IngestStats::default())),
+            signature_filter: None,
+            summary_cache: None,
+            auth_audit: None,
+            nonce_store: None,
+            waf_lists: None,
+            waf_reputation: None,
+            runtime_stats: Arc::new(RuntimeStats::new()),
+            query_limits: Arc::new(QueryLimits::default()),
+            tx_events: broadcast::channel(TX_EVENT_CAPACITY).0,
+            ws_fanout: Arc::new(WsFanout::new(1)),
+            ws_subscriptions: Arc::new(SubscriptionRegistry::new(0, None)),
+            ws_cursors: None,
+            ws_shutdown: broadcast::channel(1).0,
         }
     }
 }
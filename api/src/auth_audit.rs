@@ -0,0 +1,209 @@
+// Audit log of wallet authentication attempts
+// Supports Redis stream, Postgres and in-memory backends
+
+use crate::config::AuthConfig;
+use crate::repository::{AuthAttempt, AuthAuditRepository};
+use crate::infra::redis::RedisClient;
+use sqlx::PgPool;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+/// Stream shared by attempts whose address isn't a key of any scheme; not
+/// base58, so no address maps to it
+const SHARED_STREAM: &str = "_other";
+
+enum AuditBackend {
+    /// One capped, expiring stream per address: `{prefix}:{address}`
+    Redis {
+        client: Box<RedisClient>,
+        prefix: String,
+        ttl_ms: i64,
+    },
+    Postgres(PgPool),
+    Memory(Mutex<VecDeque<AuthAttempt>>),
+}
+
+/// Best-effort, non-blocking sink for authentication attempts
+pub struct AuthAudit {
+    backend: AuditBackend,
+    max_entries: usize,
+}
+
+impl AuthAudit {
+    /// Build the audit sink for `auth.audit_backend`, if enabled and available
+    pub fn from_config(
+        config: &AuthConfig,
+        postgres: Option<PgPool>,
+        redis: Option<RedisClient>,
+    ) -> Option<Self> {
+        let backend = match config.audit_backend.as_str() {
+            "none" => return None,
+            "redis" => AuditBackend::Redis {
+                client: Box::new(redis.or_else(|| {
+                    tracing::warn!("Auth audit backend is redis but Redis is unavailable");
+                    None
+                })?),
+                prefix: config.audit_redis_stream_prefix.clone(),
+                ttl_ms: (config.audit_redis_ttl_secs * 1000) as i64,
+            },
+            "postgres" => AuditBackend::Postgres(postgres.or_else(|| {
+                tracing::warn!("Auth audit backend is postgres but PostgreSQL is unavailable");
+                None
+            })?),
+            "memory" => AuditBackend::Memory(Mutex::new(VecDeque::new())),
+            other => {
+                tracing::warn!("Unknown auth audit backend '{}', audit disabled", other);
+                return None;
+            }
+        };
+
+        Some(Self {
+            backend,
+            max_entries: config.audit_max_entries,
+        })
+    }
+
+    /// In-memory audit sink
+    #[cfg(test)]
+    pub fn memory(max_entries: usize) -> Self {
+        Self {
+            backend: AuditBackend::Memory(Mutex::new(VecDeque::new())),
+            max_entries,
+        }
+    }
+
+    /// Record an attempt without blocking the request
+    pub fn record(&self, attempt: AuthAttempt) {
+        match &self.backend {
+            AuditBackend::Redis { client, prefix, ttl_ms } => {
+                let client = client.clone();
+                let key = stream_key(prefix, &attempt.address);
+                let max_entries = self.max_entries;
+                let ttl_ms = *ttl_ms;
+                actix_web::rt::spawn(async move {
+                    let data = serde_json::to_string(&attempt).unwrap_or_default();
+                    if let Err(e) = client.xadd_capped(&key, max_entries, "data", &data, ttl_ms).await {
+                        tracing::warn!(error = %e, "Failed to write auth audit entry to Redis");
+                    }
+                });
+            }
+            AuditBackend::Postgres(pool) => {
+                let repo = AuthAuditRepository::new(pool.clone());
+                actix_web::rt::spawn(async move {
+                    if let Err(e) = repo.insert(&attempt).await {
+                        tracing::warn!(error = %e, "Failed to write auth audit entry to PostgreSQL");
+                    }
+                });
+            }
+            AuditBackend::Memory(entries) => {
+                let mut entries = entries.lock().unwrap();
+                if entries.len() >= self.max_entries {
+                    entries.pop_front();
+                }
+                entries.push_back(attempt);
+            }
+        }
+    }
+
+    /// Most recent attempts for an address, newest first
+    pub async fn recent(&self, address: &str, limit: usize) -> Result<Vec<AuthAttempt>, String> {
+        match &self.backend {
+            AuditBackend::Redis { client, prefix, .. } => {
+                // The shared stream holds other addresses too, so read all of it
+                let count = if is_wallet_address(address) { limit } else { self.max_entries };
+                let entries = client
+                    .xrevrange(&stream_key(prefix, address), count)
+                    .await
+                    .map_err(|e| format!("Redis XREVRANGE error: {}", e))?;
+
+                // Each entry is (id, [field, value, ...]) with a single "data" field
+                Ok(entries
+                    .into_iter()
+                    .filter_map(|(_, fields)| fields.get(1).and_then(|data| serde_json::from_str(data).ok()))
+                    .filter(|attempt: &AuthAttempt| attempt.address == address)
+                    .take(limit)
+                    .collect())
+            }
+            AuditBackend::Postgres(pool) => AuthAuditRepository::new(pool.clone())
+                .recent_for_address(address, limit as i64)
+                .await
+                .map_err(|e| format!("Database query failed: {}", e)),
+            AuditBackend::Memory(entries) => Ok(entries
+                .lock()
+                .unwrap()
+                .iter()
+                .rev()
+                .filter(|attempt| attempt.address == address)
+                .take(limit)
+                .cloned()
+                .collect()),
+        }
+    }
+}
+
+/// Whether `address` is a key of some scheme; a client may sign with any of them
+fn is_wallet_address(address: &str) -> bool {
+    blockchain_auth::SCHEMES.iter().any(|scheme| scheme.decode_pubkey(address).is_ok())
+}
+
+/// Redis stream for an address; anything that isn't a key shares one stream,
+/// so arbitrary header values can't each create a key
+fn stream_key(prefix: &str, address: &str) -> String {
+    if is_wallet_address(address) {
+        format!("{}:{}", prefix, address)
+    } else {
+        format!("{}:{}", prefix, SHARED_STREAM)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn attempt(address: &str, reason: Option<&str>) -> AuthAttempt {
+        AuthAttempt {
+            address: address.to_string(),
+            method: "GET".to_string(),
+            path: "/api/transactions".to_string(),
+            result: if reason.is_some() { "failure" } else { "success" }.to_string(),
+            reason: reason.map(str::to_string),
+            ip: Some("127.0.0.1".to_string()),
+            created_at: chrono::Utc::now(),
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_records_failed_and_successful_attempts() {
+        let audit = AuthAudit::memory(100);
+        audit.record(attempt("addr1", Some("invalid_signature")));
+        audit.record(attempt("addr2", Some("nonce_missing")));
+        audit.record(attempt("addr1", None));
+
+        let recent = audit.recent("addr1", 10).await.unwrap();
+        assert_eq!(recent.len(), 2);
+        assert_eq!(recent[0].result, "success");
+        assert_eq!(recent[1].result, "failure");
+        assert_eq!(recent[1].reason.as_deref(), Some("invalid_signature"));
+    }
+
+    #[actix_web::test]
+    async fn test_memory_backend_is_bounded() {
+        let audit = AuthAudit::memory(2);
+        for _ in 0..5 {
+            audit.record(attempt("addr1", None));
+        }
+        assert_eq!(audit.recent("addr1", 10).await.unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_only_wallet_addresses_get_their_own_stream() {
+        let key = ed25519_dalek::SigningKey::from_bytes(&[9u8; 32]);
+        let address = bs58::encode(key.verifying_key().to_bytes()).into_string();
+        assert_eq!(stream_key("auth:audit", &address), format!("auth:audit:{}", address));
+
+        let oversized = "1".repeat(4096);
+        for address in ["", "api_key:0123abcd", "not-base58!", oversized.as_str(), "_other"] {
+            assert_eq!(stream_key("auth:audit", address), "auth:audit:_other", "{}", address);
+        }
+    }
+}
@@ -1,8 +1,12 @@
 // Cache layer for transaction lists
 // Supports in-memory and Redis backends
 
+use crate::config::CacheConfig;
+use crate::metrics::AppMetrics;
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::io::{Read, Write};
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, SystemTime};
 
@@ -23,6 +27,22 @@ pub trait Cache: Send + Sync {
 pub struct MemoryCache {
     store: Arc<Mutex<HashMap<String, (CachedResponse, SystemTime)>>>,
     max_entries: usize,
+    /// Bodies are stored gzipped and inflated on `get` (`cache.compress_entries`)
+    compress: bool,
+    /// Compression ratio and entries that fail to decode
+    metrics: Option<Arc<AppMetrics>>,
+}
+
+fn gzip(data: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::fast());
+    encoder.write_all(data)?;
+    encoder.finish()
+}
+
+fn gunzip(data: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut inflated = Vec::new();
+    GzDecoder::new(data).read_to_end(&mut inflated)?;
+    Ok(inflated)
 }
 
 impl MemoryCache {
@@ -30,6 +50,38 @@ impl MemoryCache {
         Self {
             store: Arc::new(Mutex::new(HashMap::new())),
             max_entries,
+            compress: false,
+            metrics: None,
+        }
+    }
+
+    pub fn with_metrics(mut self, metrics: Arc<AppMetrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Store bodies gzipped, trading CPU on every `get` and `set` for memory;
+    /// each `set` observes `cache_compression_ratio`
+    pub fn with_compression(mut self, metrics: Option<Arc<AppMetrics>>) -> Self {
+        self.compress = true;
+        self.metrics = metrics.or(self.metrics);
+        self
+    }
+
+    /// A stored entry that can't be decoded: counted, logged and dropped, so
+    /// the caller sees a miss and the next `set` replaces it
+    ///
+    /// Decoding happens outside the lock, so the entry is only removed if it
+    /// is still the one that was read (`data` as stored, `expires_at`); a
+    /// fresh `set` in between is kept.
+    fn discard_undecodable(&self, key: &str, data: &[u8], expires_at: SystemTime, error: &dyn std::fmt::Display) {
+        tracing::warn!(key = %key, error = %error, "Failed to decode cached entry, treating as a miss");
+        if let Some(metrics) = &self.metrics {
+            metrics.record_cache_deserialize_error();
+        }
+        let mut store = self.store.lock().unwrap();
+        if store.get(key).is_some_and(|(cached, stored_expiry)| *stored_expiry == expires_at && cached.data == data) {
+            store.remove(key);
         }
     }
 
@@ -60,18 +112,44 @@ impl MemoryCache {
 
 impl Cache for MemoryCache {
     fn get(&self, key: &str) -> Option<CachedResponse> {
-        let store = self.store.lock().unwrap();
-        store.get(key).and_then(|(cached, expires_at)| {
-            let now = SystemTime::now();
-            if now < *expires_at {
-                Some(cached.clone())
-            } else {
-                None
+        let (mut cached, expires_at) = {
+            let store = self.store.lock().unwrap();
+            store.get(key).and_then(|(cached, expires_at)| {
+                let now = SystemTime::now();
+                if now < *expires_at {
+                    Some((cached.clone(), *expires_at))
+                } else {
+                    None
+                }
+            })?
+        };
+        if self.compress {
+            // Inflate outside the lock; a corrupt entry is just a miss
+            match gunzip(&cached.data) {
+                Ok(inflated) => cached.data = inflated,
+                Err(e) => {
+                    self.discard_undecodable(key, &cached.data, expires_at, &e);
+                    return None;
+                }
             }
-        })
+        }
+        Some(cached)
     }
 
-    fn set(&self, key: &str, value: CachedResponse, ttl_secs: u64) {
+    fn set(&self, key: &str, mut value: CachedResponse, ttl_secs: u64) {
+        if self.compress {
+            let raw_len = value.data.len();
+            match gzip(&value.data) {
+                Ok(compressed) => value.data = compressed,
+                Err(e) => {
+                    tracing::warn!(key = %key, error = %e, "Failed to compress cached body, not caching");
+                    return;
+                }
+            }
+            if let Some(metrics) = &self.metrics {
+                metrics.record_cache_compression(raw_len, value.data.len());
+            }
+        }
         self.evict_if_needed();
         let mut store = self.store.lock().unwrap();
         let expires_at = SystemTime::now() + Duration::from_secs(ttl_secs);
@@ -84,20 +162,120 @@ impl Cache for MemoryCache {
     }
 }
 
-// Factory for creating cache instances
-pub fn create_cache(backend: &str, max_entries: usize) -> Arc<dyn Cache> {
+fn memory_cache(backend: &str, max_entries: usize) -> MemoryCache {
     match backend {
-        "memory" => Arc::new(MemoryCache::new(max_entries)),
+        "memory" => MemoryCache::new(max_entries),
         "redis" => {
             // TODO: Implement Redis cache if needed
             tracing::warn!("Redis cache not implemented yet, falling back to memory");
-            Arc::new(MemoryCache::new(max_entries))
+            MemoryCache::new(max_entries)
         }
         _ => {
             tracing::warn!("Unknown cache backend '{}', using memory", backend);
-            Arc::new(MemoryCache::new(max_entries))
+            MemoryCache::new(max_entries)
         }
     }
 }
 
+// Factory for creating cache instances
+pub fn create_cache(backend: &str, max_entries: usize) -> Arc<dyn Cache> {
+    Arc::new(memory_cache(backend, max_entries))
+}
+
+/// The cache for `cache.backend`, compressing entries with `cache.compress_entries`
+pub fn create_cache_from_config(config: &CacheConfig, metrics: Arc<AppMetrics>) -> Arc<dyn Cache> {
+    let cache = memory_cache(&config.backend, config.max_entries).with_metrics(metrics.clone());
+    if config.compress_entries {
+        Arc::new(cache.with_compression(Some(metrics)))
+    } else {
+        Arc::new(cache)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compressed_entry_round_trips_and_records_ratio() {
+        let metrics = Arc::new(AppMetrics::new().unwrap());
+        let cache = MemoryCache::new(10).with_compression(Some(metrics.clone()));
+        let body: Vec<u8> = serde_json::to_vec(&serde_json::json!({
+            "items": (0..200).map(|slot| serde_json::json!({ "signature": format!("sig{}", slot), "slot": slot })).collect::<Vec<_>>()
+        }))
+        .unwrap();
+
+        cache.set(
+            "tx:list:etag",
+            CachedResponse {
+                data: body.clone(),
+                etag: "etag".to_string(),
+                cached_at: SystemTime::now(),
+            },
+            60,
+        );
+
+        // Smaller at rest, identical on the way out
+        let stored = cache.store.lock().unwrap()["tx:list:etag"].0.data.len();
+        assert!(stored < body.len() / 2, "stored {} of {} bytes", stored, body.len());
+        let cached = cache.get("tx:list:etag").unwrap();
+        assert_eq!(cached.data, body);
+        assert_eq!(cached.etag, "etag");
+        assert!(cache.get("tx:list:other").is_none());
+
+        assert_eq!(metrics.cache_compression_ratio.get_sample_count(), 1);
+        assert!(metrics.cache_compression_ratio.get_sample_sum() < 0.5);
+    }
+
+    #[test]
+    fn test_corrupt_entry_is_a_counted_miss() {
+        let metrics = Arc::new(AppMetrics::new().unwrap());
+        let cache = MemoryCache::new(10).with_metrics(metrics.clone()).with_compression(None);
+        let entry = |data: Vec<u8>| CachedResponse {
+            data,
+            etag: "etag".to_string(),
+            cached_at: SystemTime::now(),
+        };
+
+        // Not gzip, as if written before compress_entries was turned on
+        cache.store.lock().unwrap().insert(
+            "tx:list:etag".to_string(),
+            (entry(b"{\"items\":[]}".to_vec()), SystemTime::now() + Duration::from_secs(60)),
+        );
+        assert!(cache.get("tx:list:etag").is_none());
+        assert_eq!(metrics.cache_deserialize_errors_total.get(), 1);
+
+        // Dropped, so it fails once rather than on every request
+        assert!(cache.get("tx:list:etag").is_none());
+        assert_eq!(metrics.cache_deserialize_errors_total.get(), 1);
+
+        cache.set("tx:list:etag", entry(b"{\"items\":[]}".to_vec()), 60);
+        assert_eq!(cache.get("tx:list:etag").unwrap().data, b"{\"items\":[]}");
+        assert_eq!(metrics.cache_deserialize_errors_total.get(), 1);
+    }
+
+    #[test]
+    fn test_discarding_a_corrupt_read_keeps_an_entry_set_since() {
+        let cache = MemoryCache::new(10).with_compression(None);
+        let corrupt = b"not gzip".to_vec();
+        let expires_at = SystemTime::now() + Duration::from_secs(60);
+        let entry = CachedResponse {
+            data: corrupt.clone(),
+            etag: "etag".to_string(),
+            cached_at: SystemTime::now(),
+        };
+        cache.store.lock().unwrap().insert("tx:list:etag".to_string(), (entry.clone(), expires_at));
+
+        // A fresh entry lands between reading the corrupt one and discarding it
+        cache.set("tx:list:etag", entry, 60);
+        cache.discard_undecodable("tx:list:etag", &corrupt, expires_at, &"invalid gzip header");
+        assert!(cache.get("tx:list:etag").is_some());
+
+        // The entry that was read is still removed when nothing replaced it
+        let fresh = cache.store.lock().unwrap()["tx:list:etag"].clone();
+        cache.discard_undecodable("tx:list:etag", &fresh.0.data, fresh.1, &"invalid gzip header");
+        assert!(cache.store.lock().unwrap().is_empty());
+    }
+}
+
 
@@ -1,10 +1,12 @@
 /// Error handling module
 ///
 /// Provides unified error responses
-use actix_web::{http::StatusCode, HttpResponse, ResponseError};
-use serde::Serialize;
+use actix_web::{error::QueryPayloadError, http::StatusCode, HttpRequest, HttpResponse, ResponseError};
+use serde::{de::DeserializeOwned, Serialize};
 use std::fmt;
 
+use crate::config::ServerConfig;
+
 #[derive(Debug)]
 pub enum ApiError {
     Internal {
@@ -14,12 +16,43 @@ pub enum ApiError {
         missing: Vec<String>,
         reason: Option<String>,
     },
+    /// Every problem found with the request, reported together
+    Validation {
+        errors: Vec<String>,
+    },
     NotFound {
         resource: String,
     },
     ServiceUnavailable {
         details: String,
     },
+    PayloadTooLarge {
+        details: String,
+    },
+    /// A dependency other than the database (Kafka, ...) failed or is unreachable
+    UpstreamUnavailable {
+        service: String,
+        details: String,
+    },
+    /// A surface switched off in config, answered with `server.disabled_feature_status`
+    FeatureDisabled {
+        feature: String,
+        status: StatusCode,
+    },
+}
+
+impl ApiError {
+    /// Disabled-feature error for `feature`, with the configured status
+    /// (503 when the server config isn't registered)
+    pub fn feature_disabled(feature: &str, server: Option<&ServerConfig>) -> Self {
+        let status = server
+            .and_then(|server| StatusCode::from_u16(server.disabled_feature_status).ok())
+            .unwrap_or(StatusCode::SERVICE_UNAVAILABLE);
+        ApiError::FeatureDisabled {
+            feature: feature.to_string(),
+            status,
+        }
+    }
 }
 
 #[derive(Serialize)]
@@ -29,6 +62,12 @@ pub struct ErrorResponse {
     pub details: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub missing: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub errors: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub service: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub feature: Option<String>,
 }
 
 impl fmt::Display for ApiError {
@@ -38,10 +77,16 @@ impl fmt::Display for ApiError {
             ApiError::BadRequest { missing, reason } => {
                 write!(f, "Bad request: {:?}, {:?}", missing, reason)
             }
+            ApiError::Validation { errors } => write!(f, "Bad request: {}", errors.join("; ")),
             ApiError::NotFound { resource } => write!(f, "Not found: {}", resource),
             ApiError::ServiceUnavailable { details } => {
                 write!(f, "Service unavailable: {}", details)
             }
+            ApiError::PayloadTooLarge { details } => write!(f, "Payload too large: {}", details),
+            ApiError::UpstreamUnavailable { service, details } => {
+                write!(f, "Upstream {} unavailable: {}", service, details)
+            }
+            ApiError::FeatureDisabled { feature, .. } => write!(f, "Feature disabled: {}", feature),
         }
     }
 }
@@ -50,9 +95,12 @@ impl ResponseError for ApiError {
     fn status_code(&self) -> StatusCode {
         match self {
             ApiError::Internal { .. } => StatusCode::INTERNAL_SERVER_ERROR,
-            ApiError::BadRequest { .. } => StatusCode::BAD_REQUEST,
+            ApiError::BadRequest { .. } | ApiError::Validation { .. } => StatusCode::BAD_REQUEST,
             ApiError::NotFound { .. } => StatusCode::NOT_FOUND,
             ApiError::ServiceUnavailable { .. } => StatusCode::SERVICE_UNAVAILABLE,
+            ApiError::PayloadTooLarge { .. } => StatusCode::PAYLOAD_TOO_LARGE,
+            ApiError::UpstreamUnavailable { .. } => StatusCode::BAD_GATEWAY,
+            ApiError::FeatureDisabled { status, .. } => *status,
         }
     }
 
@@ -63,6 +111,9 @@ impl ResponseError for ApiError {
                 error: "Internal server error".to_string(),
                 details: Some(reason.clone()),
                 missing: None,
+                errors: None,
+                service: None,
+                feature: None,
             },
             ApiError::BadRequest { missing, reason } => ErrorResponse {
                 error: "Bad request".to_string(),
@@ -72,18 +123,90 @@ impl ResponseError for ApiError {
                 } else {
                     Some(missing.clone())
                 },
+                errors: None,
+                service: None,
+                feature: None,
+            },
+            ApiError::Validation { errors } => ErrorResponse {
+                error: "Bad request".to_string(),
+                details: Some(errors.join("; ")),
+                missing: None,
+                errors: Some(errors.clone()),
+                service: None,
+                feature: None,
             },
             ApiError::NotFound { resource } => ErrorResponse {
                 error: format!("{} not found", resource),
                 details: None,
                 missing: None,
+                errors: None,
+                service: None,
+                feature: None,
             },
             ApiError::ServiceUnavailable { details } => ErrorResponse {
                 error: "Service unavailable".to_string(),
                 details: Some(details.clone()),
                 missing: None,
+                errors: None,
+                service: None,
+                feature: None,
+            },
+            ApiError::PayloadTooLarge { details } => ErrorResponse {
+                error: "Response too large".to_string(),
+                details: Some(details.clone()),
+                missing: None,
+                errors: None,
+                service: None,
+                feature: None,
+            },
+            ApiError::UpstreamUnavailable { service, details } => ErrorResponse {
+                error: "Upstream unavailable".to_string(),
+                details: Some(details.clone()),
+                missing: None,
+                errors: None,
+                service: Some(service.clone()),
+                feature: None,
+            },
+            ApiError::FeatureDisabled { feature, .. } => ErrorResponse {
+                error: "feature_disabled".to_string(),
+                details: None,
+                missing: None,
+                errors: None,
+                service: None,
+                feature: Some(feature.clone()),
             },
         };
         HttpResponse::build(status).json(response)
     }
 }
+
+/// Map query string deserialization failures to a structured 400 naming the parameter
+///
+/// Register per route with `web::QueryConfig::default().error_handler(query_error_handler::<T>)`.
+pub fn query_error_handler<T: DeserializeOwned>(err: QueryPayloadError, req: &HttpRequest) -> actix_web::Error {
+    // Re-run deserialization with path tracking to find the offending field
+    let deserializer = serde_urlencoded::Deserializer::new(form_urlencoded::parse(req.query_string().as_bytes()));
+    let reason = match serde_path_to_error::deserialize::<_, T>(deserializer) {
+        Err(e) if e.path().to_string() != "." => {
+            format!("invalid value for query parameter '{}': {}", e.path(), e.inner())
+        }
+        _ => format!("invalid query string: {}", err),
+    };
+
+    ApiError::BadRequest {
+        missing: vec![],
+        reason: Some(reason),
+    }
+    .into()
+}
+
+/// Query parameter names not in `known`, once each, in order of appearance
+pub fn unknown_query_params(query_string: &str, known: &[&str]) -> Vec<String> {
+    let mut unknown: Vec<String> = Vec::new();
+    for (name, _) in form_urlencoded::parse(query_string.as_bytes()) {
+        if !known.contains(&name.as_ref()) && !unknown.iter().any(|seen| *seen == name) {
+            unknown.push(name.into_owned());
+        }
+    }
+    unknown
+}
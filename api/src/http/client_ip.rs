@@ -0,0 +1,138 @@
+/// Client IP resolution shared by the WAF and rate limiter
+///
+/// Order: the configured provider header (e.g. `CF-Connecting-IP`) when the
+/// peer is a trusted proxy, then `X-Forwarded-For` when the middleware
+/// respects it, then the peer address.
+
+use crate::config::ServerConfig;
+use actix_web::{http::header::HeaderName, HttpRequest};
+use ipnet::IpNet;
+use std::net::IpAddr;
+use std::sync::Arc;
+
+#[derive(Clone, Default)]
+pub struct ClientIpResolver {
+    header: Option<HeaderName>,
+    trusted_proxies: Arc<Vec<IpNet>>,
+}
+
+impl ClientIpResolver {
+    pub fn from_config(config: &ServerConfig) -> Self {
+        let header = match config.client_ip_header.trim() {
+            "" => None,
+            name => HeaderName::try_from(name)
+                .map_err(|e| tracing::warn!(header = %name, error = %e, "Ignoring invalid client_ip_header"))
+                .ok(),
+        };
+
+        let trusted_proxies = config
+            .trusted_proxies
+            .iter()
+            .filter_map(|entry| {
+                let parsed = entry
+                    .parse::<IpNet>()
+                    .or_else(|_| entry.parse::<IpAddr>().map(IpNet::from));
+                if parsed.is_err() {
+                    tracing::warn!(entry = %entry, "Ignoring invalid trusted_proxies entry");
+                }
+                parsed.ok()
+            })
+            .collect();
+
+        Self {
+            header,
+            trusted_proxies: Arc::new(trusted_proxies),
+        }
+    }
+
+    fn is_trusted_proxy(&self, peer: IpAddr) -> bool {
+        self.trusted_proxies.iter().any(|net| net.contains(&peer))
+    }
+
+    /// The client IP for the request, if it can be determined
+    pub fn resolve(&self, req: &HttpRequest, respect_x_forwarded_for: bool) -> Option<IpAddr> {
+        let peer = req.peer_addr().map(|addr| addr.ip());
+
+        // The provider header is only meaningful when a proxy we trust set it
+        if let (Some(header), Some(peer)) = (&self.header, peer) {
+            if self.is_trusted_proxy(peer) {
+                let ip = req
+                    .headers()
+                    .get(header)
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(|value| value.trim().parse::<IpAddr>().ok());
+                if ip.is_some() {
+                    return ip;
+                }
+            }
+        }
+
+        if respect_x_forwarded_for {
+            let forwarded = req
+                .headers()
+                .get("X-Forwarded-For")
+                .and_then(|value| value.to_str().ok())
+                .and_then(|chain| chain.split(',').find_map(|ip| ip.trim().parse::<IpAddr>().ok()));
+            if forwarded.is_some() {
+                return forwarded;
+            }
+        }
+
+        peer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+
+    fn resolver() -> ClientIpResolver {
+        ClientIpResolver::from_config(&ServerConfig {
+            client_ip_header: "CF-Connecting-IP".to_string(),
+            trusted_proxies: vec!["173.245.48.0/20".to_string(), "10.0.0.5".to_string()],
+            ..ServerConfig::default()
+        })
+    }
+
+    fn request(peer: &str) -> TestRequest {
+        TestRequest::default().peer_addr(format!("{}:443", peer).parse().unwrap())
+    }
+
+    #[test]
+    fn test_configured_header_from_trusted_proxy() {
+        let req = request("173.245.48.7")
+            .insert_header(("CF-Connecting-IP", "203.0.113.9"))
+            .insert_header(("X-Forwarded-For", "198.51.100.1"))
+            .to_http_request();
+        assert_eq!(resolver().resolve(&req, true), Some("203.0.113.9".parse().unwrap()));
+
+        let req = request("10.0.0.5")
+            .insert_header(("CF-Connecting-IP", "2001:db8::1"))
+            .to_http_request();
+        assert_eq!(resolver().resolve(&req, false), Some("2001:db8::1".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_fallback_chain() {
+        // Untrusted peer: the provider header is ignored, XFF then peer are used
+        let req = request("192.0.2.50")
+            .insert_header(("CF-Connecting-IP", "203.0.113.9"))
+            .insert_header(("X-Forwarded-For", "198.51.100.1, 10.0.0.5"))
+            .to_http_request();
+        assert_eq!(resolver().resolve(&req, true), Some("198.51.100.1".parse().unwrap()));
+        assert_eq!(resolver().resolve(&req, false), Some("192.0.2.50".parse().unwrap()));
+
+        // Trusted peer with a malformed header value falls through as well
+        let req = request("10.0.0.5")
+            .insert_header(("CF-Connecting-IP", "not-an-ip"))
+            .to_http_request();
+        assert_eq!(resolver().resolve(&req, true), Some("10.0.0.5".parse().unwrap()));
+
+        // Nothing configured keeps the previous XFF-then-peer behaviour
+        let req = request("192.0.2.50")
+            .insert_header(("X-Forwarded-For", "junk, 198.51.100.1"))
+            .to_http_request();
+        assert_eq!(ClientIpResolver::default().resolve(&req, true), Some("198.51.100.1".parse().unwrap()));
+    }
+}
@@ -1,6 +1,7 @@
 /// Logger middleware
 ///
-/// Logs HTTP requests with structured fields
+/// Logs HTTP requests with structured fields, including the rate limiter's
+/// decision and the authenticating API key when they are in the request extensions
 use actix_web::{
     dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
     Error, HttpMessage,
@@ -12,7 +13,7 @@ use std::{
     time::Instant,
 };
 
-use super::request_id::RequestIdValue;
+use super::{ratelimit::RateLimitDecision, request_id::RequestIdValue, wallet_auth::AuthenticatedApiKey};
 
 pub struct Logger;
 
@@ -73,6 +74,8 @@ where
             let res = service.call(req).await?;
             let duration_ms = start.elapsed().as_millis();
             let status = res.status().as_u16();
+            let rate_limit = res.request().extensions().get::<RateLimitDecision>().copied();
+            let api_key_id = res.request().extensions().get::<AuthenticatedApiKey>().map(|key| key.key_id.clone());
 
             tracing::info!(
                 request_id = %request_id,
@@ -81,6 +84,10 @@ where
                 status = %status,
                 duration_ms = %duration_ms,
                 remote_addr = %remote_addr,
+                rate_limited = rate_limit.is_some_and(|d| d.rate_limited),
+                rate_limit_scope = rate_limit.map(|d| d.scope),
+                rate_limit_remaining = rate_limit.map(|d| d.remaining),
+                api_key_id = api_key_id.as_deref(),
                 "HTTP request"
             );
 
@@ -89,3 +96,122 @@ where
     }
 }
 
+
+/// In-memory log sink for tests asserting on what was logged
+#[cfg(test)]
+#[derive(Clone, Default)]
+pub(crate) struct CapturedLogs(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+
+#[cfg(test)]
+impl CapturedLogs {
+    pub(crate) fn output(&self) -> String {
+        String::from_utf8_lossy(&self.0.lock().unwrap()).into_owned()
+    }
+}
+
+#[cfg(test)]
+impl std::io::Write for CapturedLogs {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::RateLimitConfig;
+    use crate::http::middleware::ratelimit::RateLimit;
+    use actix_web::{test, web, App, HttpResponse};
+
+    /// Fields of each "HTTP request" line in `output`
+    fn access_log(output: &str) -> Vec<serde_json::Value> {
+        output
+            .lines()
+            .filter_map(|line| serde_json::from_str::<serde_json::Value>(line).ok())
+            .filter(|entry| entry["fields"]["message"] == "HTTP request")
+            .map(|entry| entry["fields"].clone())
+            .collect()
+    }
+
+    fn capture_logs() -> (CapturedLogs, tracing::subscriber::DefaultGuard) {
+        let captured = CapturedLogs::default();
+        let writer = captured.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .json()
+            .with_writer(move || writer.clone())
+            .finish();
+        (captured, tracing::subscriber::set_default(subscriber))
+    }
+
+    #[actix_web::test]
+    async fn test_blocked_request_access_log_carries_rate_limit() {
+        let (captured, _guard) = capture_logs();
+
+        let limiter = RateLimit::new(RateLimitConfig {
+            ip_max_requests: 1,
+            ..RateLimitConfig::default()
+        });
+        let app = test::init_service(
+            App::new()
+                .wrap(limiter)
+                .wrap(Logger)
+                .route("/api/transactions", web::get().to(HttpResponse::Ok)),
+        )
+        .await;
+
+        for expected in [200, 429] {
+            let req = test::TestRequest::get()
+                .uri("/api/transactions")
+                .peer_addr("203.0.113.7:4000".parse().unwrap())
+                .to_request();
+            assert_eq!(test::call_service(&app, req).await.status(), expected);
+        }
+
+        let access_log = access_log(&captured.output());
+        assert_eq!(access_log.len(), 2);
+
+        assert_eq!(access_log[0]["rate_limited"], false);
+        assert_eq!(access_log[0]["rate_limit_scope"], "ip");
+        assert_eq!(access_log[0]["rate_limit_remaining"], 0);
+
+        assert_eq!(access_log[1]["status"], "429");
+        assert_eq!(access_log[1]["rate_limited"], true);
+        assert_eq!(access_log[1]["rate_limit_scope"], "ip");
+    }
+
+    #[actix_web::test]
+    async fn test_api_key_request_access_log_carries_key_id() {
+        use crate::config::AuthConfig;
+        use crate::http::middleware::wallet_auth::{api_key_digest, WalletAuth, API_KEY_HEADER};
+
+        let (captured, _guard) = capture_logs();
+        let config = AuthConfig {
+            enabled: true,
+            bypass_paths: vec![],
+            api_keys: vec![api_key_digest("analytics-job-key")],
+            ..AuthConfig::default()
+        };
+        let app = test::init_service(
+            App::new()
+                .wrap(WalletAuth::new(config))
+                .wrap(Logger)
+                .route("/api/transactions", web::get().to(HttpResponse::Ok)),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/api/transactions")
+            .insert_header((API_KEY_HEADER, "analytics-job-key"))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 200);
+
+        let access_log = access_log(&captured.output());
+        assert_eq!(access_log.len(), 1);
+        assert_eq!(access_log[0]["api_key_id"], &api_key_digest("analytics-job-key")[..8]);
+    }
+}
@@ -2,6 +2,7 @@
 
 pub mod logger;
 pub mod otel;
+pub mod protection;
 pub mod ratelimit;
 pub mod request_id;
 pub mod security_headers;
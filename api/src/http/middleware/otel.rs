@@ -1,19 +1,56 @@
 use actix_web::{
     dev::{Service, ServiceRequest, ServiceResponse, Transform},
+    http::header::HeaderMap,
     Error,
 };
 use futures_util::future::{self, LocalBoxFuture, Ready};
-use std::{rc::Rc, time::Instant};
+use opentelemetry::{
+    propagation::{Extractor, TextMapPropagator},
+    trace::TraceContextExt,
+};
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use std::{rc::Rc, sync::Arc, time::Instant};
+
+use crate::metrics::AppMetrics;
 
 #[derive(Clone)]
-pub struct OtelMiddleware;
+pub struct OtelMiddleware {
+    metrics: Option<Arc<AppMetrics>>,
+}
 
 impl OtelMiddleware {
     pub fn new() -> Self {
-        OtelMiddleware
+        OtelMiddleware { metrics: None }
+    }
+
+    /// Record request durations, with the trace as exemplar, in the given metrics
+    pub fn with_metrics(mut self, metrics: Arc<AppMetrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
     }
 }
 
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|name| name.as_str()).collect()
+    }
+}
+
+/// The sampled trace the caller propagated in `traceparent`, if any; an
+/// unsampled trace never reaches the backend, so it makes no exemplar
+fn sampled_trace_id(headers: &HeaderMap) -> Option<String> {
+    let cx = TraceContextPropagator::new().extract(&HeaderExtractor(headers));
+    let span = cx.span();
+    let span_context = span.span_context();
+    (span_context.is_valid() && span_context.is_sampled()).then(|| span_context.trace_id().to_string())
+}
+
 impl<S, B> Transform<S, ServiceRequest> for OtelMiddleware
 where
     S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
@@ -29,12 +66,14 @@ where
     fn new_transform(&self, service: S) -> Self::Future {
         future::ready(Ok(OtelService {
             service: Rc::new(service),
+            metrics: self.metrics.clone(),
         }))
     }
 }
 
 pub struct OtelService<S> {
     service: Rc<S>,
+    metrics: Option<Arc<AppMetrics>>,
 }
 
 impl<S, B> Service<ServiceRequest> for OtelService<S>
@@ -53,19 +92,30 @@ where
 
     fn call(&self, req: ServiceRequest) -> Self::Future {
         let service = self.service.clone();
+        let metrics = self.metrics.clone();
         let start_time = Instant::now();
+        let method = req.method().clone();
+        let trace_id = metrics.as_ref().and_then(|_| sampled_trace_id(req.headers()));
 
         Box::pin(async move {
             let res = service.call(req).await;
             let duration = start_time.elapsed();
-            
+
             // Log request with timing
             tracing::info!(
                 duration_ms = duration.as_millis(),
                 "HTTP request processed"
             );
-            
+
+            if let Some(metrics) = metrics {
+                let status = match &res {
+                    Ok(res) => res.status(),
+                    Err(e) => e.as_response_error().status_code(),
+                };
+                metrics.record_http_request(method.as_str(), status.as_u16(), duration, trace_id.as_deref());
+            }
+
             res
         })
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,244 @@
+/// Protection-chain guard
+///
+/// The WAF, rate limiter and wallet auth mark every request they see, whether
+/// or not they act on it. `RequireProtection` wraps the protected routes and
+/// fails a request missing a required mark with a 500, so a route registered
+/// outside the middleware chain shows up in tests and staging instead of
+/// quietly serving unprotected.
+
+use actix_web::{
+    body::EitherBody,
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    web, Error, HttpMessage, ResponseError,
+};
+use futures_util::future::LocalBoxFuture;
+use std::{
+    future::{ready, Ready},
+    rc::Rc,
+};
+
+use crate::config::SecurityConfig;
+use crate::errors::ApiError;
+
+/// A middleware protected routes rely on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Layer {
+    Waf,
+    RateLimit,
+    Auth,
+}
+
+impl Layer {
+    pub const ALL: [Layer; 3] = [Layer::Waf, Layer::RateLimit, Layer::Auth];
+
+    /// Name used in `security.required_middlewares`
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Layer::Waf => "waf",
+            Layer::RateLimit => "rate_limit",
+            Layer::Auth => "auth",
+        }
+    }
+
+    fn bit(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+/// Layers that have seen the request, kept in its extensions
+#[derive(Debug, Clone, Copy, Default)]
+struct Marks(u8);
+
+/// Record that `layer` ran for this request
+pub fn mark(req: &ServiceRequest, layer: Layer) {
+    let mut extensions = req.extensions_mut();
+    match extensions.get_mut::<Marks>() {
+        Some(marks) => marks.0 |= layer.bit(),
+        None => {
+            extensions.insert(Marks(layer.bit()));
+        }
+    }
+}
+
+/// Layers a protected route requires, from `security.required_middlewares`
+#[derive(Debug, Clone)]
+pub struct ProtectionPolicy {
+    required: Vec<Layer>,
+}
+
+impl Default for ProtectionPolicy {
+    fn default() -> Self {
+        Self {
+            required: Layer::ALL.to_vec(),
+        }
+    }
+}
+
+impl ProtectionPolicy {
+    pub fn from_config(config: &SecurityConfig) -> Result<Self, String> {
+        let required = config
+            .required_middlewares
+            .iter()
+            .map(|name| {
+                Layer::ALL
+                    .into_iter()
+                    .find(|layer| layer.as_str() == name)
+                    .ok_or_else(|| format!("Unknown required middleware '{}', expected one of: waf, rate_limit, auth", name))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { required })
+    }
+
+    /// Required layers that didn't mark the request
+    fn missing(&self, req: &ServiceRequest) -> Vec<&'static str> {
+        let marks = req.extensions().get::<Marks>().copied().unwrap_or_default();
+        self.required
+            .iter()
+            .filter(|layer| marks.0 & layer.bit() == 0)
+            .map(|layer| layer.as_str())
+            .collect()
+    }
+}
+
+/// Fails requests that skipped a layer the `ProtectionPolicy` in the app
+/// data requires; without one, every layer is required
+#[derive(Clone, Default)]
+pub struct RequireProtection;
+
+impl<S, B> Transform<S, ServiceRequest> for RequireProtection
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = RequireProtectionMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequireProtectionMiddleware {
+            service: Rc::new(service),
+        }))
+    }
+}
+
+pub struct RequireProtectionMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for RequireProtectionMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let missing = match req.app_data::<web::Data<ProtectionPolicy>>() {
+            Some(policy) => policy.missing(&req),
+            None => ProtectionPolicy::default().missing(&req),
+        };
+
+        if !missing.is_empty() {
+            tracing::error!(
+                path = %req.path(),
+                missing = ?missing,
+                "Protected route reached without required middleware, check the middleware chain"
+            );
+            let response = ApiError::Internal {
+                reason: "Request bypassed required middleware".to_string(),
+            }
+            .error_response();
+            let (req, _) = req.into_parts();
+            return Box::pin(async move { Ok(ServiceResponse::new(req, response).map_into_right_body()) });
+        }
+
+        let service = self.service.clone();
+        Box::pin(async move {
+            let res = service.call(req).await?;
+            Ok(res.map_into_left_body())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{AuthConfig, RateLimitConfig, WafConfig};
+    use crate::http::middleware::ratelimit::RateLimit;
+    use crate::http::middleware::waf::WafMiddleware;
+    use crate::http::middleware::wallet_auth::WalletAuth;
+    use actix_web::{http::StatusCode, test, App, HttpResponse};
+
+    fn protected_routes(cfg: &mut web::ServiceConfig) {
+        cfg.service(
+            web::scope("/api")
+                .wrap(RequireProtection)
+                .route("/transactions", web::get().to(|| async { HttpResponse::Ok().finish() })),
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_guard_trips_when_a_protected_route_skips_the_waf() {
+        // Disabled layers still mark what they see, so only placement matters
+        let auth = WalletAuth::new(AuthConfig { enabled: false, ..AuthConfig::default() });
+        let rate_limit = RateLimit::new(RateLimitConfig { enabled: false, ..RateLimitConfig::default() });
+        let waf = WafMiddleware::new(WafConfig { enabled: false, ..WafConfig::default() }, None).unwrap();
+
+        let full_chain = test::init_service(
+            App::new()
+                .app_data(web::Data::new(ProtectionPolicy::default()))
+                .wrap(auth.clone())
+                .wrap(rate_limit.clone())
+                .wrap(waf)
+                .configure(protected_routes),
+        )
+        .await;
+        let req = test::TestRequest::get().uri("/api/transactions").to_request();
+        assert_eq!(test::call_service(&full_chain, req).await.status(), StatusCode::OK);
+
+        let without_waf = test::init_service(
+            App::new()
+                .app_data(web::Data::new(ProtectionPolicy::default()))
+                .wrap(auth.clone())
+                .wrap(rate_limit.clone())
+                .configure(protected_routes),
+        )
+        .await;
+        let req = test::TestRequest::get().uri("/api/transactions").to_request();
+        assert_eq!(test::call_service(&without_waf, req).await.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+        // A policy that leaves the WAF out lets the same wiring through
+        let config = SecurityConfig {
+            required_middlewares: vec!["rate_limit".to_string(), "auth".to_string()],
+            ..SecurityConfig::default()
+        };
+        let relaxed = test::init_service(
+            App::new()
+                .app_data(web::Data::new(ProtectionPolicy::from_config(&config).unwrap()))
+                .wrap(auth)
+                .wrap(rate_limit)
+                .configure(protected_routes),
+        )
+        .await;
+        let req = test::TestRequest::get().uri("/api/transactions").to_request();
+        assert_eq!(test::call_service(&relaxed, req).await.status(), StatusCode::OK);
+    }
+
+    #[actix_web::test]
+    async fn test_unknown_required_middleware_is_rejected() {
+        let config = SecurityConfig {
+            required_middlewares: vec!["waf".to_string(), "cors".to_string()],
+            ..SecurityConfig::default()
+        };
+        let err = ProtectionPolicy::from_config(&config).unwrap_err();
+        assert!(err.contains("'cors'"), "{}", err);
+    }
+}
@@ -5,20 +5,22 @@
 use actix_web::{
     body::EitherBody,
     dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
-    Error, HttpResponse,
+    Error, HttpMessage, HttpResponse,
 };
 use futures_util::future::LocalBoxFuture;
 use serde::Serialize;
 use std::{
     collections::HashMap,
     future::{ready, Ready},
-    net::IpAddr,
     rc::Rc,
     sync::{Arc, Mutex},
     time::{Duration, Instant},
 };
 
 use crate::config::RateLimitConfig;
+use crate::http::client_ip::ClientIpResolver;
+use crate::http::middleware::protection::{self, Layer};
+use crate::http::middleware::wallet_auth::{api_key_digest, API_KEY_HEADER};
 
 #[derive(Serialize)]
 struct RateLimitErrorResponse {
@@ -30,84 +32,135 @@ struct RateLimitErrorResponse {
 struct WindowEntry {
     count: u32,
     window_start: Instant,
+    window: Duration,
 }
 
-type RateLimitStore = Arc<Mutex<HashMap<String, WindowEntry>>>;
+#[derive(Default)]
+struct Windows {
+    entries: HashMap<String, WindowEntry>,
+    swept_at: Option<Instant>,
+}
+
+/// How often `hit` drops windows that have run out
+const SWEEP_INTERVAL: Duration = Duration::from_secs(1);
+
+type RateLimitStore = Arc<Mutex<Windows>>;
+
+/// Rate-limit outcome left in the request extensions for the access log
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimitDecision {
+    pub rate_limited: bool,
+    /// "ip" or "user": the scope that blocked the request, or else the one closest to its limit
+    pub scope: &'static str,
+    /// Requests left in that scope's window
+    pub remaining: u32,
+}
+
+/// 429 with `Retry-After`
+pub(crate) fn rate_limited_response(retry_after: u64) -> HttpResponse {
+    HttpResponse::TooManyRequests()
+        .insert_header(("Retry-After", retry_after.to_string()))
+        .json(RateLimitErrorResponse {
+            error: "rate_limited".to_string(),
+            retry_after,
+        })
+}
 
 #[derive(Clone)]
 pub struct RateLimit {
     config: RateLimitConfig,
     store: RateLimitStore,
+    client_ip: ClientIpResolver,
 }
 
 impl RateLimit {
     pub fn new(config: RateLimitConfig) -> Self {
         Self {
             config,
-            store: Arc::new(Mutex::new(HashMap::new())),
+            store: Arc::default(),
+            client_ip: ClientIpResolver::default(),
         }
     }
 
-    fn extract_client_ip(req: &ServiceRequest, respect_xff: bool) -> Option<IpAddr> {
-        if respect_xff {
-            if let Some(xff) = req.headers().get("x-forwarded-for") {
-                if let Ok(xff_str) = xff.to_str() {
-                    // Parse first valid IP from X-Forwarded-For
-                    for ip_str in xff_str.split(',') {
-                        if let Ok(ip) = ip_str.trim().parse::<IpAddr>() {
-                            return Some(ip);
-                        }
-                    }
-                }
-            }
-        }
-
-        // Fallback to peer_addr
-        req.peer_addr().map(|addr| addr.ip())
+    /// Resolve client IPs with the shared trusted-header settings
+    pub fn with_client_ip(mut self, client_ip: ClientIpResolver) -> Self {
+        self.client_ip = client_ip;
+        self
     }
 
+    /// The wallet address, or for API key callers a digest of the key
     fn extract_user_id(req: &ServiceRequest) -> Option<String> {
-        req.headers()
-            .get("x-wallet-address")
-            .and_then(|h| h.to_str().ok())
-            .filter(|s| !s.is_empty())
+        let header = |name: &str| {
+            req.headers()
+                .get(name)
+                .and_then(|h| h.to_str().ok())
+                .filter(|s| !s.is_empty())
+        };
+        header("x-wallet-address")
             .map(|s| s.to_string())
+            .or_else(|| header(API_KEY_HEADER).map(|key| format!("api_key:{}", api_key_digest(key))))
     }
 
     fn is_whitelisted_path(path: &str) -> bool {
         matches!(path, "/healthz" | "/readyz")
     }
 
-    fn check_limit(
+    /// Count a hit against `key`; `Err(retry_after_secs)` once over the limit
+    pub(crate) fn check_limit(
         &self,
         key: String,
         max_requests: u32,
         window_duration: Duration,
     ) -> Result<(), u64> {
-        let mut store = self.store.lock().unwrap();
-        let now = Instant::now();
-
-        let entry = store.entry(key).or_insert_with(|| WindowEntry {
-            count: 0,
-            window_start: now,
-        });
-
-        // Check if window expired
-        if now.duration_since(entry.window_start) >= window_duration {
-            entry.count = 0;
-            entry.window_start = now;
-        }
+        hit(&self.store, key, 1, max_requests, window_duration).map(|_| ())
+    }
 
-        // Check limit
-        if entry.count >= max_requests {
-            let elapsed = now.duration_since(entry.window_start);
-            let retry_after = window_duration.saturating_sub(elapsed).as_secs();
-            return Err(retry_after);
-        }
+    /// Like [`check_limit`](Self::check_limit), for a request worth `cost` hits
+    pub(crate) fn check_cost(
+        &self,
+        key: String,
+        cost: u32,
+        max_requests: u32,
+        window_duration: Duration,
+    ) -> Result<(), u64> {
+        hit(&self.store, key, cost, max_requests, window_duration).map(|_| ())
+    }
+}
+
+/// Count `cost` hits against `key`, returning the hits left in the window or
+/// `Err(retry_after_secs)` if they don't fit
+fn hit(store: &RateLimitStore, key: String, cost: u32, max_requests: u32, window_duration: Duration) -> Result<u32, u64> {
+    let mut store = store.lock().unwrap();
+    let now = Instant::now();
+
+    // Keys seen once would otherwise stay in the map for good
+    if store.swept_at.is_none_or(|at| now.duration_since(at) >= SWEEP_INTERVAL) {
+        store.entries.retain(|_, entry| now.duration_since(entry.window_start) < entry.window);
+        store.swept_at = Some(now);
+    }
 
-        entry.count += 1;
-        Ok(())
+    let entry = store.entries.entry(key).or_insert_with(|| WindowEntry {
+        count: 0,
+        window_start: now,
+        window: window_duration,
+    });
+
+    // Check if window expired
+    if now.duration_since(entry.window_start) >= window_duration {
+        entry.count = 0;
+        entry.window_start = now;
+    }
+    entry.window = window_duration;
+
+    // Check limit
+    if entry.count.saturating_add(cost) > max_requests {
+        let elapsed = now.duration_since(entry.window_start);
+        let retry_after = window_duration.saturating_sub(elapsed).as_secs();
+        return Err(retry_after);
     }
+
+    entry.count += cost;
+    Ok(max_requests - entry.count)
 }
 
 impl<S, B> Transform<S, ServiceRequest> for RateLimit
@@ -127,6 +180,7 @@ where
             service: Rc::new(service),
             config: self.config.clone(),
             store: self.store.clone(),
+            client_ip: self.client_ip.clone(),
         }))
     }
 }
@@ -135,6 +189,7 @@ pub struct RateLimitMiddleware<S> {
     service: Rc<S>,
     config: RateLimitConfig,
     store: RateLimitStore,
+    client_ip: ClientIpResolver,
 }
 
 impl<S, B> Service<ServiceRequest> for RateLimitMiddleware<S>
@@ -150,6 +205,8 @@ where
     forward_ready!(service);
 
     fn call(&self, req: ServiceRequest) -> Self::Future {
+        protection::mark(&req, Layer::RateLimit);
+
         // Skip if disabled
         if !self.config.enabled {
             let service = self.service.clone();
@@ -174,120 +231,61 @@ where
         let store = self.store.clone();
 
         // Extract identifiers
-        let client_ip = RateLimit::extract_client_ip(&req, config.respect_x_forwarded_for);
+        let client_ip = self.client_ip.resolve(req.request(), config.respect_x_forwarded_for);
         let user_id = RateLimit::extract_user_id(&req);
 
-        // IP-based rate limiting
+        let mut limits = Vec::with_capacity(2);
         if let Some(ip) = client_ip {
-            let ip_key = format!("ip:{}", ip);
-            let window_duration = Duration::from_secs(config.ip_window_secs);
-
-            let result = {
-                let mut store_lock = store.lock().unwrap();
-                let now = Instant::now();
-
-                let entry = store_lock.entry(ip_key.clone()).or_insert_with(|| WindowEntry {
-                    count: 0,
-                    window_start: now,
-                });
-
-                if now.duration_since(entry.window_start) >= window_duration {
-                    entry.count = 0;
-                    entry.window_start = now;
-                }
-
-                if entry.count >= config.ip_max_requests {
-                    let elapsed = now.duration_since(entry.window_start);
-                    let retry_after = window_duration.saturating_sub(elapsed).as_secs();
-                    Err(retry_after)
-                } else {
-                    entry.count += 1;
-                    Ok(())
-                }
-            };
-
-            if let Err(retry_after) = result {
-                tracing::warn!(
-                    scope = "ip",
-                    key = %ip,
-                    limit = config.ip_max_requests,
-                    window = config.ip_window_secs,
-                    retry_after = retry_after,
-                    method = %method,
-                    path = %path,
-                    "Rate limit exceeded"
-                );
-
-                let response = HttpResponse::TooManyRequests()
-                    .insert_header(("Retry-After", retry_after.to_string()))
-                    .json(RateLimitErrorResponse {
-                        error: "rate_limited".to_string(),
-                        retry_after,
-                    });
-
-                let (req, _) = req.into_parts();
-                return Box::pin(async move {
-                    Ok(ServiceResponse::new(req, response).map_into_right_body())
-                });
-            }
+            limits.push(("ip", ip.to_string(), config.ip_max_requests, config.ip_window_secs));
         } else {
             tracing::warn!("Unable to determine client IP for rate limiting");
         }
-
         // User-based rate limiting (if wallet address present)
         if let Some(user) = user_id {
-            let user_key = format!("user:{}", user);
-            let window_duration = Duration::from_secs(config.user_window_secs);
-
-            let result = {
-                let mut store_lock = store.lock().unwrap();
-                let now = Instant::now();
-
-                let entry = store_lock.entry(user_key.clone()).or_insert_with(|| WindowEntry {
-                    count: 0,
-                    window_start: now,
-                });
-
-                if now.duration_since(entry.window_start) >= window_duration {
-                    entry.count = 0;
-                    entry.window_start = now;
-                }
+            limits.push(("user", user, config.user_max_requests, config.user_window_secs));
+        }
 
-                if entry.count >= config.user_max_requests {
-                    let elapsed = now.duration_since(entry.window_start);
-                    let retry_after = window_duration.saturating_sub(elapsed).as_secs();
-                    Err(retry_after)
-                } else {
-                    entry.count += 1;
-                    Ok(())
+        let mut decision: Option<RateLimitDecision> = None;
+        for (scope, key, limit, window) in limits {
+            match hit(&store, format!("{}:{}", scope, key), 1, limit, Duration::from_secs(window)) {
+                Ok(remaining) => {
+                    if decision.is_none_or(|d| remaining < d.remaining) {
+                        decision = Some(RateLimitDecision {
+                            rate_limited: false,
+                            scope,
+                            remaining,
+                        });
+                    }
                 }
-            };
-
-            if let Err(retry_after) = result {
-                tracing::warn!(
-                    scope = "user",
-                    key = %user,
-                    limit = config.user_max_requests,
-                    window = config.user_window_secs,
-                    retry_after = retry_after,
-                    method = %method,
-                    path = %path,
-                    "Rate limit exceeded"
-                );
-
-                let response = HttpResponse::TooManyRequests()
-                    .insert_header(("Retry-After", retry_after.to_string()))
-                    .json(RateLimitErrorResponse {
-                        error: "rate_limited".to_string(),
-                        retry_after,
+                Err(retry_after) => {
+                    tracing::warn!(
+                        scope = scope,
+                        key = %key,
+                        limit = limit,
+                        window = window,
+                        retry_after = retry_after,
+                        method = %method,
+                        path = %path,
+                        "Rate limit exceeded"
+                    );
+
+                    req.extensions_mut().insert(RateLimitDecision {
+                        rate_limited: true,
+                        scope,
+                        remaining: 0,
                     });
+                    let response = rate_limited_response(retry_after);
 
-                let (req, _) = req.into_parts();
-                return Box::pin(async move {
-                    Ok(ServiceResponse::new(req, response).map_into_right_body())
-                });
+                    let (req, _) = req.into_parts();
+                    return Box::pin(async move {
+                        Ok(ServiceResponse::new(req, response).map_into_right_body())
+                    });
+                }
             }
         }
+        if let Some(decision) = decision {
+            req.extensions_mut().insert(decision);
+        }
 
         // Pass through if all limits OK
         let service = self.service.clone();
@@ -298,3 +296,25 @@ where
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expired_windows_are_evicted() {
+        let store = RateLimitStore::default();
+        assert_eq!(hit(&store, "short".to_string(), 1, 5, Duration::from_millis(1)), Ok(4));
+        assert_eq!(hit(&store, "long".to_string(), 1, 5, Duration::from_secs(60)), Ok(4));
+        std::thread::sleep(Duration::from_millis(5));
+
+        // Not swept again within the interval
+        assert_eq!(hit(&store, "other".to_string(), 1, 5, Duration::from_secs(60)), Ok(4));
+        assert!(store.lock().unwrap().entries.contains_key("short"));
+
+        store.lock().unwrap().swept_at = Instant::now().checked_sub(SWEEP_INTERVAL);
+        assert_eq!(hit(&store, "other".to_string(), 1, 5, Duration::from_secs(60)), Ok(3));
+        let windows = store.lock().unwrap();
+        assert!(!windows.entries.contains_key("short"));
+        assert_eq!(windows.entries["long"].count, 1);
+    }
+}
@@ -61,6 +61,10 @@ where
         let service = self.service.clone();
         let config = self.config.clone();
         let start_time = Instant::now();
+        let skip_csp = config
+            .csp_skip_paths
+            .iter()
+            .any(|prefix| req.path().starts_with(prefix.as_str()));
 
         Box::pin(async move {
             let res = service.call(req).await;
@@ -102,8 +106,8 @@ where
                         config.permissions_policy.parse().unwrap(),
                     );
 
-                    // Content-Security-Policy (if enabled)
-                    if config.csp_enabled {
+                    // Content-Security-Policy (if enabled and path not skipped)
+                    if config.csp_enabled && !skip_csp {
                         headers.insert(
                             actix_web::http::header::HeaderName::from_static("content-security-policy"),
                             config.csp.parse().unwrap(),
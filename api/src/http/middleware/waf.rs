@@ -6,7 +6,10 @@
 use crate::{
     app_state::AppState,
     config::WafConfig,
-    infra::redis,
+    http::client_ip::ClientIpResolver,
+    http::middleware::protection::{self, Layer},
+    metrics::AppMetrics,
+    waf_lists::{IpReputation, WafLists},
 };
 use actix_web::{
     body::EitherBody,
@@ -15,28 +18,96 @@ use actix_web::{
     Error, HttpRequest, HttpResponse,
 };
 use regex::RegexSet;
+use serde::Serialize;
 use serde_json::json;
 use std::{
-    collections::HashMap,
+    collections::{HashMap, VecDeque},
     future::{ready, Ready},
     rc::Rc,
-    sync::Arc,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
     time::{Duration, Instant},
 };
-use tracing::{error, info, warn};
+use tracing::{debug, error, info, warn};
 
 /// WAF middleware state
 #[derive(Clone)]
 pub struct WafMiddleware {
     config: WafConfig,
     patterns: Arc<WafPatterns>,
-    event_counts: Arc<std::sync::Mutex<HashMap<String, (u32, Instant)>>>,
+    /// Suppresses WAF event log floods per IP
+    log_limiter: Arc<SlidingWindow>,
+    /// Counts high-score events per IP for automatic grey/ban escalation
+    escalation_limiter: Arc<SlidingWindow>,
+    /// Shadow-mode "would block" events seen, for log sampling
+    would_block_events: Arc<AtomicU64>,
+    redis_unavailable_policy: RedisUnavailablePolicy,
     app_state: Option<Arc<AppState>>,
+    metrics: Option<Arc<AppMetrics>>,
+    client_ip: ClientIpResolver,
+}
+
+/// Sliding-window event counter keyed by client IP
+pub struct SlidingWindow {
+    window: Duration,
+    events: Mutex<HashMap<String, VecDeque<Instant>>>,
+}
+
+/// Drop stale keys once the map grows past this size
+const SLIDING_WINDOW_SWEEP_THRESHOLD: usize = 10_000;
+
+impl SlidingWindow {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            events: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Record an event if fewer than `limit` happened within the window.
+    ///
+    /// Returns the number of events in the window including this one,
+    /// or `None` when the limit was already reached. Rejected events are
+    /// not recorded, so memory per key stays bounded by `limit`.
+    pub fn try_record(&self, key: &str, limit: u32, now: Instant) -> Option<u32> {
+        let mut events = self.events.lock().unwrap();
+
+        if events.len() > SLIDING_WINDOW_SWEEP_THRESHOLD {
+            let window = self.window;
+            events.retain(|_, times| {
+                times.back().is_some_and(|last| now.duration_since(*last) < window)
+            });
+        }
+
+        let times = events.entry(key.to_string()).or_default();
+        while times.front().is_some_and(|t| now.duration_since(*t) >= self.window) {
+            times.pop_front();
+        }
+
+        if times.len() >= limit as usize {
+            return None;
+        }
+
+        times.push_back(now);
+        Some(times.len() as u32)
+    }
+}
+
+/// Version handed to the last compiled rule set
+static RULESET_VERSION: AtomicU64 = AtomicU64::new(0);
+
+/// Version of the most recently compiled rule set, 0 before the first compile
+pub fn current_ruleset_version() -> u64 {
+    RULESET_VERSION.load(Ordering::Relaxed)
 }
 
 /// Precompiled regex patterns for efficient matching
 #[derive(Debug)]
 pub struct WafPatterns {
+    /// Increases with every (re)compile, to tie decisions to the rules behind them
+    pub ruleset_version: u64,
     pub blocked_paths: RegexSet,
     pub sqli: RegexSet,
     pub xss: RegexSet,
@@ -54,88 +125,203 @@ pub struct WafResult {
 }
 
 /// Individual pattern match
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct WafMatch {
-    pub category: String,
+    pub category: WafCategory,
     pub pattern: String,
     pub weight: u32,
 }
 
+/// Why a request scored; serialized names are stable for log and metric consumers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WafCategory {
+    Sqli,
+    Xss,
+    Rce,
+    Traversal,
+    BadUa,
+    BadPath,
+    Oversize,
+    BadMethod,
+    Banned,
+    Grey,
+    Reputation,
+}
+
+impl WafCategory {
+    pub const ALL: [WafCategory; 11] = [
+        WafCategory::Sqli,
+        WafCategory::Xss,
+        WafCategory::Rce,
+        WafCategory::Traversal,
+        WafCategory::BadUa,
+        WafCategory::BadPath,
+        WafCategory::Oversize,
+        WafCategory::BadMethod,
+        WafCategory::Banned,
+        WafCategory::Grey,
+        WafCategory::Reputation,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WafCategory::Sqli => "sqli",
+            WafCategory::Xss => "xss",
+            WafCategory::Rce => "rce",
+            WafCategory::Traversal => "traversal",
+            WafCategory::BadUa => "bad_ua",
+            WafCategory::BadPath => "bad_path",
+            WafCategory::Oversize => "oversize",
+            WafCategory::BadMethod => "bad_method",
+            WafCategory::Banned => "banned",
+            WafCategory::Grey => "grey",
+            WafCategory::Reputation => "reputation",
+        }
+    }
+}
+
+/// What the WAF does when the ban/grey lists can't be consulted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RedisUnavailablePolicy {
+    /// Let requests through unchecked
+    FailOpen,
+    /// Reject requests (block mode) until the lists are back
+    FailClosed,
+    /// Keep scoring with pattern matching, without the lists
+    LocalOnly,
+}
+
+impl RedisUnavailablePolicy {
+    pub fn parse(policy: &str) -> Result<Self, String> {
+        match policy {
+            "fail_open" => Ok(Self::FailOpen),
+            "fail_closed" => Ok(Self::FailClosed),
+            "local_only" => Ok(Self::LocalOnly),
+            other => Err(format!(
+                "waf.redis_unavailable_policy must be fail_open, fail_closed or local_only, got {:?}",
+                other
+            )),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::FailOpen => "fail_open",
+            Self::FailClosed => "fail_closed",
+            Self::LocalOnly => "local_only",
+        }
+    }
+}
+
+/// Whether the client IP is on the ban or grey list, and what the reputation feed scores it
+#[derive(Debug, Default, Clone, Copy)]
+struct ListStatus {
+    banned: bool,
+    grey: bool,
+    reputation: u32,
+}
+
 /// WAF action to take
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum WafAction {
     Pass,
     Grey,
     Block,
 }
 
+impl WafAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WafAction::Pass => "pass",
+            WafAction::Grey => "grey",
+            WafAction::Block => "block",
+        }
+    }
+}
+
 impl WafMiddleware {
     /// Create new WAF middleware
     pub fn new(config: WafConfig, app_state: Option<Arc<AppState>>) -> Result<Self, String> {
         let patterns = Arc::new(Self::compile_patterns(&config)?);
-        let event_counts = Arc::new(std::sync::Mutex::new(HashMap::new()));
+        let redis_unavailable_policy = RedisUnavailablePolicy::parse(&config.redis_unavailable_policy)?;
+        let log_limiter = Arc::new(SlidingWindow::new(Duration::from_secs(60)));
+        let escalation_limiter = Arc::new(SlidingWindow::new(Duration::from_secs(60)));
 
         Ok(Self {
             config,
             patterns,
-            event_counts,
+            log_limiter,
+            escalation_limiter,
+            would_block_events: Arc::new(AtomicU64::new(0)),
+            redis_unavailable_policy,
             app_state,
+            metrics: None,
+            client_ip: ClientIpResolver::default(),
         })
     }
 
-    /// Compile regex patterns for efficient matching
-    fn compile_patterns(config: &WafConfig) -> Result<WafPatterns, String> {
-        let blocked_paths = RegexSet::new(&config.blocked_path_patterns)
-            .map_err(|e| format!("Invalid blocked path patterns: {}", e))?;
-
-        let sqli = RegexSet::new(&config.sqli_patterns)
-            .map_err(|e| format!("Invalid SQLi patterns: {}", e))?;
-
-        let xss = RegexSet::new(&config.xss_patterns)
-            .map_err(|e| format!("Invalid XSS patterns: {}", e))?;
-
-        let rce = RegexSet::new(&config.rce_patterns)
-            .map_err(|e| format!("Invalid RCE patterns: {}", e))?;
-
-        let path_traversal = RegexSet::new(&config.path_traversal_patterns)
-            .map_err(|e| format!("Invalid path traversal patterns: {}", e))?;
+    /// Count matches and decisions in the given metrics
+    pub fn with_metrics(mut self, metrics: Arc<AppMetrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
 
-        Ok(WafPatterns {
-            blocked_paths,
-            sqli,
-            xss,
-            rce,
-            path_traversal,
-        })
+    /// Resolve client IPs with the shared trusted-header settings
+    pub fn with_client_ip(mut self, client_ip: ClientIpResolver) -> Self {
+        self.client_ip = client_ip;
+        self
     }
 
-    /// Extract client IP from request
-    fn extract_client_ip(&self, req: &HttpRequest) -> String {
-        if self.config.respect_x_forwarded_for {
-            if let Some(forwarded) = req.headers().get("X-Forwarded-For") {
-                if let Ok(forwarded_str) = forwarded.to_str() {
-                    // Take the first IP from the chain
-                    if let Some(first_ip) = forwarded_str.split(',').next() {
-                        let ip = first_ip.trim();
-                        if self.is_valid_ip(ip) {
-                            return ip.to_string();
-                        }
-                    }
+    /// Compile every pattern category, reporting all invalid patterns at once
+    fn compile_patterns(config: &WafConfig) -> Result<WafPatterns, String> {
+        let mut errors = Vec::new();
+        let mut compile = |category: &str, patterns: &[String]| {
+            let reported = errors.len();
+            for pattern in patterns {
+                if let Err(e) = regex::Regex::new(pattern) {
+                    // Regex errors span several lines; keep one line per pattern
+                    let error = e.to_string().lines().last().unwrap_or_default().trim().to_string();
+                    errors.push(format!("{}: {:?}: {}", category, pattern, error));
                 }
             }
-        }
+            RegexSet::new(patterns).unwrap_or_else(|e| {
+                // Individually valid patterns can still exceed the set's size limit
+                if errors.len() == reported {
+                    errors.push(format!("{}: {}", category, e));
+                }
+                RegexSet::empty()
+            })
+        };
 
-        // Fallback to peer address
-        if let Some(peer_addr) = req.peer_addr() {
-            peer_addr.ip().to_string()
-        } else {
-            "unknown".to_string()
+        let mut patterns = WafPatterns {
+            ruleset_version: 0,
+            blocked_paths: compile("blocked_path_patterns", &config.blocked_path_patterns),
+            sqli: compile("sqli_patterns", &config.sqli_patterns),
+            xss: compile("xss_patterns", &config.xss_patterns),
+            rce: compile("rce_patterns", &config.rce_patterns),
+            path_traversal: compile("path_traversal_patterns", &config.path_traversal_patterns),
+        };
+
+        if !errors.is_empty() {
+            return Err(format!(
+                "{} invalid WAF pattern(s):\n  {}",
+                errors.len(),
+                errors.join("\n  ")
+            ));
         }
+        patterns.ruleset_version = RULESET_VERSION.fetch_add(1, Ordering::Relaxed) + 1;
+        info!(ruleset_version = patterns.ruleset_version, "WAF rule set compiled");
+        Ok(patterns)
     }
 
-    /// Validate IP address format
-    fn is_valid_ip(&self, ip: &str) -> bool {
-        ip.parse::<std::net::IpAddr>().is_ok()
+    /// Extract client IP from request
+    fn extract_client_ip(&self, req: &HttpRequest) -> String {
+        self.client_ip
+            .resolve(req, self.config.respect_x_forwarded_for)
+            .map(|ip| ip.to_string())
+            .unwrap_or_else(|| "unknown".to_string())
     }
 
     /// Check if path should be bypassed
@@ -145,14 +331,22 @@ impl WafMiddleware {
         })
     }
 
+    /// Check if the path is under a `waf.bypass_prefixes` entry, matched on
+    /// whole segments so `/api/auth/nonce` doesn't cover `/api/auth/nonces`
+    fn is_prefix_bypassed(&self, path: &str) -> bool {
+        self.config.bypass_prefixes.iter().any(|prefix| {
+            path.strip_prefix(prefix.trim_end_matches('/'))
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+        })
+    }
+
     /// Check if method is allowed
     fn is_method_allowed(&self, method: &str) -> bool {
         self.config.allowed_methods.contains(&method.to_string())
     }
 
-    /// Analyze request for anomalies
-    fn analyze_request(&self, req: &HttpRequest) -> WafResult {
-        let client_ip = self.extract_client_ip(req);
+    /// Analyze request for anomalies, given the client's list status
+    fn analyze_request(&self, req: &HttpRequest, client_ip: String, listed: ListStatus) -> WafResult {
         let method = req.method().as_str();
         let path = req.path();
         let query = req.query_string();
@@ -165,12 +359,12 @@ impl WafMiddleware {
         let mut score = 0u32;
         let mut matches = Vec::new();
 
-        // Check if IP is in ban list (would be checked separately)
-        if self.is_banned(&client_ip) {
+        // Check if IP is in ban list
+        if listed.banned {
             return WafResult {
                 score: 999, // High score for banned IPs
                 matches: vec![WafMatch {
-                    category: "banned".to_string(),
+                    category: WafCategory::Banned,
                     pattern: "banned_ip".to_string(),
                     weight: 999,
                 }],
@@ -180,16 +374,30 @@ impl WafMiddleware {
         }
 
         // Check if IP is in grey list
-        let is_grey = self.is_grey(&client_ip);
-        if is_grey {
+        if listed.grey {
             score += 2; // Start with +2 for grey IPs
+            matches.push(WafMatch {
+                category: WafCategory::Grey,
+                pattern: "grey_ip".to_string(),
+                weight: 2,
+            });
+        }
+
+        // Known-bad IPs from the reputation feed
+        if listed.reputation > 0 {
+            score += listed.reputation;
+            matches.push(WafMatch {
+                category: WafCategory::Reputation,
+                pattern: "ip_reputation".to_string(),
+                weight: listed.reputation,
+            });
         }
 
         // Check method
         if !self.is_method_allowed(method) {
             score += 3;
             matches.push(WafMatch {
-                category: "bad_method".to_string(),
+                category: WafCategory::BadMethod,
                 pattern: method.to_string(),
                 weight: 3,
             });
@@ -199,7 +407,7 @@ impl WafMiddleware {
         if query.len() > self.config.max_query_length {
             score += self.config.score_weights.get("oversize").copied().unwrap_or(5);
             matches.push(WafMatch {
-                category: "oversize".to_string(),
+                category: WafCategory::Oversize,
                 pattern: "query_too_long".to_string(),
                 weight: self.config.score_weights.get("oversize").copied().unwrap_or(5),
             });
@@ -211,7 +419,7 @@ impl WafMiddleware {
                 let weight = self.config.score_weights.get("bad_ua").copied().unwrap_or(4);
                 score += weight;
                 matches.push(WafMatch {
-                    category: "bad_ua".to_string(),
+                    category: WafCategory::BadUa,
                     pattern: blocked_ua.clone(),
                     weight,
                 });
@@ -223,7 +431,7 @@ impl WafMiddleware {
             let weight = self.config.score_weights.get("bad_path").copied().unwrap_or(4);
             score += weight;
             matches.push(WafMatch {
-                category: "bad_path".to_string(),
+                category: WafCategory::BadPath,
                 pattern: "blocked_path".to_string(),
                 weight,
             });
@@ -235,7 +443,7 @@ impl WafMiddleware {
             let weight = self.config.score_weights.get("sqli").copied().unwrap_or(8);
             score += weight;
             matches.push(WafMatch {
-                category: "sqli".to_string(),
+                category: WafCategory::Sqli,
                 pattern: "sqli_detected".to_string(),
                 weight,
             });
@@ -246,7 +454,7 @@ impl WafMiddleware {
             let weight = self.config.score_weights.get("xss").copied().unwrap_or(6);
             score += weight;
             matches.push(WafMatch {
-                category: "xss".to_string(),
+                category: WafCategory::Xss,
                 pattern: "xss_detected".to_string(),
                 weight,
             });
@@ -257,7 +465,7 @@ impl WafMiddleware {
             let weight = self.config.score_weights.get("rce").copied().unwrap_or(8);
             score += weight;
             matches.push(WafMatch {
-                category: "rce".to_string(),
+                category: WafCategory::Rce,
                 pattern: "rce_detected".to_string(),
                 weight,
             });
@@ -268,7 +476,7 @@ impl WafMiddleware {
             let weight = self.config.score_weights.get("traversal").copied().unwrap_or(6);
             score += weight;
             matches.push(WafMatch {
-                category: "traversal".to_string(),
+                category: WafCategory::Traversal,
                 pattern: "path_traversal_detected".to_string(),
                 weight,
             });
@@ -291,32 +499,78 @@ impl WafMiddleware {
         }
     }
 
-    /// Check if IP is banned
-    fn is_banned(&self, _ip: &str) -> bool {
-        // Temporarily disabled to avoid runtime conflicts
-        // TODO: Implement proper async Redis checks
-        false
+    /// Ban/grey lists, if enabled and available
+    fn lists(&self) -> Option<&WafLists> {
+        self.app_state.as_ref()?.waf_lists.as_deref()
+    }
+
+    /// Look the IP up in the ban and grey lists
+    ///
+    /// Fails when the lists are enabled but Redis was unreachable at startup
+    /// or a lookup errors.
+    async fn check_lists(&self, ip: &str) -> Result<ListStatus, String> {
+        if !self.config.use_redis_lists {
+            return Ok(ListStatus::default());
+        }
+        let Some(lists) = self.lists() else {
+            return Err("WAF lists unavailable".to_string());
+        };
+        let banned = lists
+            .ban
+            .contains(ip)
+            .await
+            .map_err(|e| format!("WAF ban list check failed: {}", e))?;
+        if banned {
+            return Ok(ListStatus { banned, ..ListStatus::default() });
+        }
+        let grey = lists
+            .grey
+            .contains(ip)
+            .await
+            .map_err(|e| format!("WAF grey list check failed: {}", e))?;
+        Ok(ListStatus { banned, grey, reputation: 0 })
+    }
+
+    /// Reputation feed, if configured and available
+    fn reputation(&self) -> Option<&IpReputation> {
+        self.app_state.as_ref()?.waf_reputation.as_deref()
     }
 
-    /// Check if IP is in grey list
-    fn is_grey(&self, _ip: &str) -> bool {
-        // Temporarily disabled to avoid runtime conflicts
-        // TODO: Implement proper async Redis checks
-        false
+    /// Score from the reputation feed; a failed lookup scores 0 rather than
+    /// affecting the request
+    async fn check_reputation(&self, ip: &str) -> u32 {
+        let Some(reputation) = self.reputation() else {
+            return 0;
+        };
+        match reputation.score(ip).await {
+            Ok(score) => score,
+            Err(e) => {
+                warn!(ip = %ip, "WAF reputation lookup failed, ignoring feed: {}", e);
+                0
+            }
+        }
     }
 
     /// Add IP to ban list
-    fn add_to_ban(&self, _ip: &str) {
-        // Temporarily disabled to avoid runtime conflicts
-        // TODO: Implement proper async Redis operations
-        info!("Would add IP to ban list (disabled)");
+    async fn add_to_ban(&self, ip: &str) {
+        let Some(lists) = self.lists() else {
+            return;
+        };
+        match lists.ban.add(ip).await {
+            Ok(()) => info!(ip = %ip, "Added IP to WAF ban list"),
+            Err(e) => error!("Failed to add IP to WAF ban list: {}", e),
+        }
     }
 
     /// Add IP to grey list
-    fn add_to_grey(&self, _ip: &str) {
-        // Temporarily disabled to avoid runtime conflicts
-        // TODO: Implement proper async Redis operations
-        info!("Would add IP to grey list (disabled)");
+    async fn add_to_grey(&self, ip: &str) {
+        let Some(lists) = self.lists() else {
+            return;
+        };
+        match lists.grey.add(ip).await {
+            Ok(()) => info!(ip = %ip, "Added IP to WAF grey list"),
+            Err(e) => error!("Failed to add IP to WAF grey list: {}", e),
+        }
     }
 
     /// Log WAF event
@@ -329,45 +583,69 @@ impl WafMiddleware {
             .and_then(|h| h.to_str().ok())
             .unwrap_or("");
 
-        let action_str = match result.action {
-            WafAction::Pass => "pass",
-            WafAction::Grey => "grey",
-            WafAction::Block => "block",
-        };
-
         info!(
             ip = %result.client_ip,
             method = %method,
             path = %path,
             ua_hash = %format!("{:x}", md5::compute(user_agent.as_bytes())),
             score = %result.score,
-            matches = ?result.matches,
+            matches = %serde_json::to_string(&result.matches).unwrap_or_default(),
             mode = %self.config.mode,
-            action = %action_str,
+            action = %result.action.as_str(),
+            ruleset_version = self.patterns.ruleset_version,
             "WAF event"
         );
     }
 
-    /// Check rate limit for events per IP
-    fn check_event_rate_limit(&self, ip: &str) -> bool {
-        let mut counts = self.event_counts.lock().unwrap();
-        let now = Instant::now();
-        let window = Duration::from_secs(60);
+    /// Count a shadow-mode "would block" and decide whether it is logged at WARN
+    ///
+    /// Sampling is deterministic: with a ratio of 0.25 every fourth event is
+    /// logged, so the WARN volume is exactly the configured fraction.
+    fn sample_would_block(&self, reason: &'static str) -> bool {
+        if let Some(metrics) = &self.metrics {
+            metrics.record_waf_would_block(reason);
+        }
+        let ratio = self.config.shadow_log_sample_ratio.clamp(0.0, 1.0);
+        let n = self.would_block_events.fetch_add(1, Ordering::Relaxed) + 1;
+        (n as f64 * ratio).floor() > ((n - 1) as f64 * ratio).floor()
+    }
 
-        if let Some((count, last_reset)) = counts.get(ip) {
-            if now.duration_since(*last_reset) < window {
-                if *count >= self.config.max_events_per_ip_per_min {
-                    return false; // Rate limited
-                }
-                *counts.get_mut(ip).unwrap() = (*count + 1, *last_reset);
-            } else {
-                counts.insert(ip.to_string(), (1, now));
-            }
-        } else {
-            counts.insert(ip.to_string(), (1, now));
+    /// Check whether another WAF event may be logged for this IP
+    fn check_event_rate_limit(&self, ip: &str, now: Instant) -> bool {
+        self.log_limiter
+            .try_record(ip, self.config.max_events_per_ip_per_min, now)
+            .is_some()
+    }
+
+    /// Count a high-score event and decide whether the IP should be escalated.
+    ///
+    /// Returns `Block` when the IP's high-score events within a minute reach
+    /// `ban_escalation_events_per_min`, `Grey` when they reach
+    /// `grey_escalation_events_per_min`, and `None` otherwise, so each list
+    /// is written once per crossing rather than on every later event.
+    fn check_escalation(&self, result: &WafResult, now: Instant) -> Option<WafAction> {
+        if result.score < self.config.grey_threshold {
+            return None;
         }
 
-        true
+        let ban_limit = self.config.ban_escalation_events_per_min;
+        match self.escalation_limiter.try_record(&result.client_ip, ban_limit, now) {
+            Some(count) if count == ban_limit => Some(WafAction::Block),
+            Some(count) if count == self.config.grey_escalation_events_per_min => Some(WafAction::Grey),
+            _ => None,
+        }
+    }
+
+    /// Escalate an IP to the grey or ban list
+    async fn escalate(&self, ip: &str, action: &WafAction) {
+        match action {
+            WafAction::Block => {
+                warn!(ip = %ip, "WAF escalating IP to ban list");
+                self.add_to_ban(ip).await;
+            }
+            WafAction::Grey => self.add_to_grey(ip).await,
+            WafAction::Pass => {}
+        }
     }
 }
 
@@ -410,6 +688,7 @@ where
     forward_ready!(service);
 
     fn call(&self, req: ServiceRequest) -> Self::Future {
+        protection::mark(&req, Layer::Waf);
         let service = self.service.clone();
         let waf = self.waf.clone();
 
@@ -429,43 +708,130 @@ where
                 return Ok(res.map_into_left_body());
             }
 
+            // WAF-only bypass; the other middlewares have their own lists
+            if waf.is_prefix_bypassed(path) {
+                debug!(path = %path, "WAF skipped for bypassed prefix");
+                let res = service.call(req).await?;
+                return Ok(res.map_into_left_body());
+            }
+
+            // Reject over-long query strings before the full analysis pass
+            let query_len = req.query_string().len();
+            if query_len > waf.config.max_query_length {
+                if waf.config.mode == "block" {
+                    warn!(
+                        "WAF rejected over-long query: length={}, max={}",
+                        query_len, waf.config.max_query_length
+                    );
+                    let error_response = HttpResponse::build(StatusCode::URI_TOO_LONG)
+                        .json(json!({
+                            "error": "uri_too_long",
+                            "reason": "query_too_long",
+                            "max_query_length": waf.config.max_query_length
+                        }));
+                    return Ok(ServiceResponse::new(req.into_parts().0, error_response).map_into_right_body());
+                }
+                if waf.sample_would_block("query_too_long") {
+                    warn!(
+                        "WAF would reject over-long query in block mode: length={}, max={}",
+                        query_len, waf.config.max_query_length
+                    );
+                } else {
+                    debug!(
+                        "WAF would reject over-long query in block mode: length={}, max={}",
+                        query_len, waf.config.max_query_length
+                    );
+                }
+            }
+
+            // Consult the ban/grey lists, or apply the policy for when they're down
+            let client_ip = waf.extract_client_ip(req.request());
+            let mut listed = match waf.check_lists(&client_ip).await {
+                Ok(listed) => listed,
+                Err(e) => {
+                    let policy = waf.redis_unavailable_policy;
+                    if let Some(metrics) = &waf.metrics {
+                        metrics.record_waf_redis_skipped(policy.as_str());
+                    }
+                    error!(policy = policy.as_str(), "{}, skipping ban/grey list checks", e);
+                    match policy {
+                        RedisUnavailablePolicy::LocalOnly => ListStatus::default(),
+                        RedisUnavailablePolicy::FailOpen => {
+                            let res = service.call(req).await?;
+                            return Ok(res.map_into_left_body());
+                        }
+                        RedisUnavailablePolicy::FailClosed if waf.config.mode == "block" => {
+                            let error_response = HttpResponse::build(StatusCode::SERVICE_UNAVAILABLE)
+                                .json(json!({
+                                    "error": "service_unavailable",
+                                    "reason": "waf_unavailable"
+                                }));
+                            return Ok(ServiceResponse::new(req.into_parts().0, error_response).map_into_right_body());
+                        }
+                        RedisUnavailablePolicy::FailClosed => {
+                            if waf.sample_would_block("waf_unavailable") {
+                                warn!("WAF would reject request in block mode while its lists are unavailable: IP={}", client_ip);
+                            } else {
+                                debug!("WAF would reject request in block mode while its lists are unavailable: IP={}", client_ip);
+                            }
+                            let res = service.call(req).await?;
+                            return Ok(res.map_into_left_body());
+                        }
+                    }
+                }
+            };
+            if !listed.banned {
+                listed.reputation = waf.check_reputation(&client_ip).await;
+            }
+
             // Analyze request
-            let result = waf.analyze_request(req.request());
+            let result = waf.analyze_request(req.request(), client_ip, listed);
+            let now = Instant::now();
+
+            if let Some(metrics) = &waf.metrics {
+                for waf_match in &result.matches {
+                    metrics.record_waf_match(waf_match.category);
+                }
+                metrics.record_waf_action(&result.action, &waf.config.mode);
+            }
 
-            // Check event rate limit
-            if !waf.check_event_rate_limit(&result.client_ip) {
-                warn!("Event rate limit exceeded for IP: {}", result.client_ip);
+            // Log event unless this IP is flooding the log
+            if waf.check_event_rate_limit(&result.client_ip, now) {
+                waf.log_event(&result, req.request());
             }
 
-            // Log event
-            waf.log_event(&result, req.request());
+            // Escalate repeat offenders to the grey/ban lists; the lists are
+            // shared, so shadow mode leaves them alone
+            if waf.config.mode == "block" {
+                if let Some(escalation) = waf.check_escalation(&result, now) {
+                    waf.escalate(&result.client_ip, &escalation).await;
+                }
+            }
 
             // Handle action based on mode
             match (waf.config.mode.as_str(), &result.action) {
                 ("shadow", WafAction::Block) => {
                     // In shadow mode, log but don't block
-                    warn!(
-                        "WAF would block request in block mode: IP={}, Score={}",
-                        result.client_ip, result.score
-                    );
-                    if result.score >= waf.config.grey_threshold {
-                        waf.add_to_grey(&result.client_ip);
+                    if waf.sample_would_block("score") {
+                        warn!(
+                            "WAF would block request in block mode: IP={}, Score={}",
+                            result.client_ip, result.score
+                        );
+                    } else {
+                        debug!(
+                            "WAF would block request in block mode: IP={}, Score={}",
+                            result.client_ip, result.score
+                        );
                     }
                     let res = service.call(req).await?;
                     Ok(res.map_into_left_body())
                 }
-                ("shadow", WafAction::Grey) => {
-                    waf.add_to_grey(&result.client_ip);
-                    let res = service.call(req).await?;
-                    Ok(res.map_into_left_body())
-                }
-                ("shadow", WafAction::Pass) => {
+                ("shadow", WafAction::Grey) | ("shadow", WafAction::Pass) => {
                     let res = service.call(req).await?;
                     Ok(res.map_into_left_body())
                 }
                 ("block", WafAction::Block) => {
                     // Block the request
-                    waf.add_to_ban(&result.client_ip);
                     let error_response = HttpResponse::build(StatusCode::FORBIDDEN)
                         .json(json!({
                             "error": "forbidden",
@@ -474,12 +840,7 @@ where
                         }));
                     Ok(ServiceResponse::new(req.into_parts().0, error_response).map_into_right_body())
                 }
-                ("block", WafAction::Grey) => {
-                    waf.add_to_grey(&result.client_ip);
-                    let res = service.call(req).await?;
-                    Ok(res.map_into_left_body())
-                }
-                ("block", WafAction::Pass) => {
+                ("block", WafAction::Grey) | ("block", WafAction::Pass) => {
                     let res = service.call(req).await?;
                     Ok(res.map_into_left_body())
                 }
@@ -492,3 +853,471 @@ where
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(ip: &str, score: u32) -> WafResult {
+        WafResult {
+            score,
+            matches: vec![],
+            action: WafAction::Pass,
+            client_ip: ip.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_sliding_window_expires_old_events() {
+        let window = SlidingWindow::new(Duration::from_secs(60));
+        let start = Instant::now();
+
+        assert_eq!(window.try_record("1.2.3.4", 2, start), Some(1));
+        assert_eq!(window.try_record("1.2.3.4", 2, start + Duration::from_secs(30)), Some(2));
+        assert_eq!(window.try_record("1.2.3.4", 2, start + Duration::from_secs(59)), None);
+        // The first event slides out of the window
+        assert_eq!(window.try_record("1.2.3.4", 2, start + Duration::from_secs(61)), Some(2));
+    }
+
+    #[test]
+    fn test_escalation_threshold_triggers_ban() {
+        let config = WafConfig {
+            grey_escalation_events_per_min: 2,
+            ban_escalation_events_per_min: 4,
+            ..WafConfig::default()
+        };
+        let waf = WafMiddleware::new(config, None).unwrap();
+        let now = Instant::now();
+        let high = result("10.0.0.1", waf.config.grey_threshold);
+
+        assert!(waf.check_escalation(&high, now).is_none());
+        assert!(matches!(waf.check_escalation(&high, now), Some(WafAction::Grey)));
+        assert!(waf.check_escalation(&high, now).is_none());
+        assert!(matches!(waf.check_escalation(&high, now), Some(WafAction::Block)));
+        // Past the limit the IP is already banned; nothing is rewritten
+        assert!(waf.check_escalation(&high, now).is_none());
+        assert!(waf.check_escalation(&high, now).is_none());
+
+        // Other IPs are tracked separately
+        assert!(waf.check_escalation(&result("10.0.0.2", 99), now).is_none());
+    }
+
+    /// Statuses of `requests` block-scored requests from one IP, and whether it
+    /// ended up grey-listed and banned
+    async fn call_repeat_offender(mode: &str, requests: usize) -> (Vec<StatusCode>, bool, bool) {
+        use crate::config::ServiceConfig;
+        use actix_web::{test, web, App};
+
+        let config = WafConfig {
+            mode: mode.to_string(),
+            use_redis_lists: true,
+            lists_backend: "memory".to_string(),
+            grey_escalation_events_per_min: 2,
+            ban_escalation_events_per_min: 3,
+            ..WafConfig::default()
+        };
+        let mut app_state = AppState::new(ServiceConfig::default(), None, None);
+        app_state.waf_lists = WafLists::from_config(&config, None).map(Arc::new);
+        let app_state = Arc::new(app_state);
+        let app = test::init_service(
+            App::new()
+                .wrap(WafMiddleware::new(config, Some(app_state.clone())).unwrap())
+                .route("/api/transactions", web::get().to(HttpResponse::Ok)),
+        )
+        .await;
+
+        let mut statuses = Vec::new();
+        for _ in 0..requests {
+            let req = test::TestRequest::get()
+                .uri("/api/transactions?q=SLEEP(5)")
+                .insert_header(("X-Forwarded-For", "203.0.113.9"))
+                .insert_header(("User-Agent", "sqlmap/1.7"))
+                .to_request();
+            statuses.push(test::call_service(&app, req).await.status());
+        }
+        let lists = app_state.waf_lists.as_ref().unwrap();
+        let greyed = lists.grey.contains("203.0.113.9").await.unwrap();
+        let banned = lists.ban.contains("203.0.113.9").await.unwrap();
+        (statuses, greyed, banned)
+    }
+
+    #[actix_web::test]
+    async fn test_shadow_mode_never_bans() {
+        let (statuses, greyed, banned) = call_repeat_offender("shadow", 5).await;
+        assert_eq!(statuses, vec![StatusCode::OK; 5]);
+        assert!(!greyed);
+        assert!(!banned);
+
+        // The same traffic in block mode ends up on the ban list
+        let (_, _, banned) = call_repeat_offender("block", 5).await;
+        assert!(banned);
+    }
+
+    #[actix_web::test]
+    async fn test_single_blocked_request_does_not_list_the_ip() {
+        let (statuses, greyed, banned) = call_repeat_offender("block", 1).await;
+        assert_eq!(statuses, vec![StatusCode::FORBIDDEN]);
+        assert!(!greyed);
+        assert!(!banned);
+    }
+
+    #[test]
+    fn test_low_score_events_do_not_escalate() {
+        let config = WafConfig {
+            grey_escalation_events_per_min: 1,
+            ban_escalation_events_per_min: 1,
+            ..WafConfig::default()
+        };
+        let waf = WafMiddleware::new(config, None).unwrap();
+        let low = result("10.0.0.1", 0);
+
+        for _ in 0..10 {
+            assert!(waf.check_escalation(&low, Instant::now()).is_none());
+        }
+    }
+
+    async fn call_with_long_query(mode: &str) -> StatusCode {
+        use actix_web::{test, web, App};
+
+        let config = WafConfig {
+            mode: mode.to_string(),
+            max_query_length: 32,
+            use_redis_lists: false,
+            ..WafConfig::default()
+        };
+        let app = test::init_service(
+            App::new()
+                .wrap(WafMiddleware::new(config, None).unwrap())
+                .route("/api/transactions", web::get().to(HttpResponse::Ok)),
+        )
+        .await;
+
+        let uri = format!("/api/transactions?program_id={}", "1".repeat(64));
+        let req = test::TestRequest::get().uri(&uri).to_request();
+        test::call_service(&app, req).await.status()
+    }
+
+    #[actix_web::test]
+    async fn test_overlong_query_rejected_in_block_mode() {
+        assert_eq!(call_with_long_query("block").await, StatusCode::URI_TOO_LONG);
+    }
+
+    #[actix_web::test]
+    async fn test_overlong_query_passes_in_shadow_mode() {
+        assert_eq!(call_with_long_query("shadow").await, StatusCode::OK);
+    }
+
+    #[test]
+    fn test_verify_endpoints_not_bypassed_by_default() {
+        let waf = WafMiddleware::new(WafConfig::default(), None).unwrap();
+        for path in ["/api/auth/verify", "/api/auth/verify-batch"] {
+            assert!(!waf.is_bypassed(path), "{}", path);
+        }
+        assert!(waf.is_bypassed("/api/auth/nonce"));
+    }
+
+    #[actix_web::test]
+    async fn test_bypassed_prefix_skips_waf_only_there() {
+        use actix_web::{test, web, App};
+
+        let config = WafConfig {
+            mode: "block".to_string(),
+            use_redis_lists: false,
+            bypass_paths: vec!["/healthz".to_string()],
+            bypass_prefixes: vec!["/api/auth/nonce".to_string()],
+            ..WafConfig::default()
+        };
+        let app = test::init_service(
+            App::new()
+                .wrap(WafMiddleware::new(config, None).unwrap())
+                .route("/api/auth/nonce", web::post().to(HttpResponse::Ok))
+                .route("/api/auth/noncex", web::post().to(HttpResponse::Ok))
+                .route("/api/transactions", web::post().to(HttpResponse::Ok)),
+        )
+        .await;
+
+        let payload = "?q=SLEEP(5)&r=javascript:alert(1)";
+        for (path, status) in [
+            ("/api/auth/nonce", StatusCode::OK),
+            ("/api/auth/noncex", StatusCode::FORBIDDEN),
+            ("/api/transactions", StatusCode::FORBIDDEN),
+        ] {
+            let req = test::TestRequest::post().uri(&format!("{}{}", path, payload)).to_request();
+            assert_eq!(test::call_service(&app, req).await.status(), status, "{}", path);
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_matches_and_actions_are_counted_per_category() {
+        use actix_web::{test, web, App};
+
+        let config = WafConfig {
+            mode: "shadow".to_string(),
+            use_redis_lists: false,
+            ..WafConfig::default()
+        };
+        let metrics = Arc::new(AppMetrics::new().unwrap());
+        let app = test::init_service(
+            App::new()
+                .wrap(WafMiddleware::new(config, None).unwrap().with_metrics(metrics.clone()))
+                .route("/api/transactions", web::get().to(HttpResponse::Ok)),
+        )
+        .await;
+
+        for uri in [
+            "/api/transactions?q=SLEEP(5)",
+            "/api/transactions?q=SLEEP(10)",
+            "/api/transactions?q=javascript:alert(1)",
+            "/api/transactions",
+        ] {
+            let req = test::TestRequest::get().uri(uri).to_request();
+            test::call_service(&app, req).await;
+        }
+
+        let matches = |category: &str| metrics.waf_matches_total.with_label_values(&[category]).get();
+        assert_eq!(matches("sqli"), 2);
+        assert_eq!(matches("xss"), 1);
+        assert_eq!(matches("rce"), 0);
+
+        let actions = |action: &str| metrics.waf_actions_total.with_label_values(&[action, "shadow"]).get();
+        assert_eq!(actions("pass") + actions("grey") + actions("block"), 4);
+        assert!(metrics.render().contains("waf_matches_total{category=\"sqli\"} 2"));
+    }
+
+    #[actix_web::test]
+    async fn test_shadow_would_block_logs_are_sampled_but_always_counted() {
+        use crate::http::middleware::logger::CapturedLogs;
+        use actix_web::{test, web, App};
+
+        let logs = CapturedLogs::default();
+        let writer = logs.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_max_level(tracing::Level::DEBUG)
+            .with_writer(move || writer.clone())
+            .finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let config = WafConfig {
+            mode: "shadow".to_string(),
+            use_redis_lists: false,
+            shadow_log_sample_ratio: 0.25,
+            ..WafConfig::default()
+        };
+        let metrics = Arc::new(AppMetrics::new().unwrap());
+        let app = test::init_service(
+            App::new()
+                .wrap(WafMiddleware::new(config, None).unwrap().with_metrics(metrics.clone()))
+                .route("/api/transactions", web::get().to(HttpResponse::Ok)),
+        )
+        .await;
+
+        for _ in 0..8 {
+            let req = test::TestRequest::get()
+                .uri("/api/transactions?q=SLEEP(5)&r=javascript:alert(1)")
+                .to_request();
+            assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+        }
+
+        assert_eq!(metrics.waf_shadow_would_block_total.with_label_values(&["score"]).get(), 8);
+        let output = logs.output();
+        let would_block = |level: &str| {
+            output
+                .lines()
+                .filter(|line| line.contains(level) && line.contains("WAF would block request"))
+                .count()
+        };
+        assert_eq!(would_block("WARN"), 2);
+        assert_eq!(would_block("DEBUG"), 6);
+    }
+
+    #[actix_web::test]
+    async fn test_ruleset_version_increments_and_is_logged() {
+        use crate::http::middleware::logger::CapturedLogs;
+        use actix_web::{test, web, App};
+
+        let logs = CapturedLogs::default();
+        let writer = logs.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_ansi(false)
+            .with_writer(move || writer.clone())
+            .finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let config = WafConfig {
+            mode: "shadow".to_string(),
+            use_redis_lists: false,
+            ..WafConfig::default()
+        };
+        let first = WafMiddleware::new(config.clone(), None).unwrap();
+        // Recompiling, as a reload does, hands out a newer version
+        let reloaded = WafMiddleware::new(config, None).unwrap();
+        let version = reloaded.patterns.ruleset_version;
+        assert!(version > first.patterns.ruleset_version);
+        assert!(current_ruleset_version() >= version);
+
+        let app = test::init_service(
+            App::new()
+                .wrap(reloaded)
+                .route("/api/transactions", web::get().to(HttpResponse::Ok)),
+        )
+        .await;
+        let req = test::TestRequest::get().uri("/api/transactions?q=SLEEP(5)").to_request();
+        test::call_service(&app, req).await;
+
+        let output = logs.output();
+        let event = output.lines().find(|line| line.contains("WAF event")).unwrap();
+        assert!(event.contains(&format!("ruleset_version={}", version)), "{}", event);
+    }
+
+    /// Status of a benign and an attacking request with Redis down for the lists
+    async fn call_with_redis_down(policy: &str) -> (StatusCode, StatusCode, u64) {
+        use crate::config::ServiceConfig;
+        use actix_web::{test, web, App};
+
+        let config = WafConfig {
+            mode: "block".to_string(),
+            use_redis_lists: true,
+            redis_unavailable_policy: policy.to_string(),
+            ..WafConfig::default()
+        };
+        // Lists enabled but never built: Redis was unreachable
+        let app_state = Arc::new(AppState::new(ServiceConfig::default(), None, None));
+        let metrics = Arc::new(AppMetrics::new().unwrap());
+        let app = test::init_service(
+            App::new()
+                .wrap(WafMiddleware::new(config, Some(app_state)).unwrap().with_metrics(metrics.clone()))
+                .route("/api/transactions", web::get().to(HttpResponse::Ok)),
+        )
+        .await;
+
+        let mut statuses = Vec::new();
+        for uri in ["/api/transactions", "/api/transactions?q=SLEEP(5)&r=javascript:alert(1)"] {
+            let req = test::TestRequest::get().uri(uri).to_request();
+            statuses.push(test::call_service(&app, req).await.status());
+        }
+        let skipped = metrics.waf_redis_checks_skipped_total.with_label_values(&[policy]).get();
+        (statuses[0], statuses[1], skipped)
+    }
+
+    #[actix_web::test]
+    async fn test_redis_down_policies() {
+        // Pattern scoring still blocks attacks, ordinary traffic passes
+        assert_eq!(
+            call_with_redis_down("local_only").await,
+            (StatusCode::OK, StatusCode::FORBIDDEN, 2)
+        );
+        // Nothing is checked
+        assert_eq!(call_with_redis_down("fail_open").await, (StatusCode::OK, StatusCode::OK, 2));
+        // Nothing gets through
+        assert_eq!(
+            call_with_redis_down("fail_closed").await,
+            (StatusCode::SERVICE_UNAVAILABLE, StatusCode::SERVICE_UNAVAILABLE, 2)
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_reputation_feed_score_can_tip_a_request_into_a_block() {
+        use crate::config::ServiceConfig;
+        use actix_web::{test, web, App};
+
+        let config = WafConfig {
+            mode: "block".to_string(),
+            use_redis_lists: false,
+            ..WafConfig::default()
+        };
+        let mut app_state = AppState::new(ServiceConfig::default(), None, None);
+        app_state.waf_reputation = Some(Arc::new(IpReputation::from_scores(HashMap::from([(
+            "203.0.113.7".to_string(),
+            4,
+        )]))));
+        let waf = WafMiddleware::new(config, Some(Arc::new(app_state))).unwrap();
+
+        // Scored directly: the flagged IP carries the feed's score on top of the patterns
+        let req = test::TestRequest::get().uri("/api/transactions?q=SLEEP(5)").to_http_request();
+        let flagged = ListStatus {
+            reputation: waf.check_reputation("203.0.113.7").await,
+            ..ListStatus::default()
+        };
+        let clean = ListStatus {
+            reputation: waf.check_reputation("198.51.100.1").await,
+            ..ListStatus::default()
+        };
+        let flagged = waf.analyze_request(&req, "203.0.113.7".to_string(), flagged);
+        let clean = waf.analyze_request(&req, "198.51.100.1".to_string(), clean);
+        assert_eq!(flagged.score, clean.score + 4);
+        assert!(flagged.matches.iter().any(|m| m.category == WafCategory::Reputation && m.weight == 4));
+        assert!(matches!(clean.action, WafAction::Grey));
+        assert!(matches!(flagged.action, WafAction::Block));
+
+        // End to end: the same SQLi probe passes for a clean IP and is blocked for the flagged one
+        let app = test::init_service(
+            App::new()
+                .wrap(waf)
+                .route("/api/transactions", web::get().to(|| async { HttpResponse::Ok().finish() })),
+        )
+        .await;
+        for (ip, status) in [("198.51.100.1", StatusCode::OK), ("203.0.113.7", StatusCode::FORBIDDEN)] {
+            let req = test::TestRequest::get()
+                .uri("/api/transactions?q=SLEEP(5)")
+                .insert_header(("X-Forwarded-For", ip))
+                .to_request();
+            assert_eq!(test::call_service(&app, req).await.status(), status, "{}", ip);
+        }
+    }
+
+    #[test]
+    fn test_unknown_redis_unavailable_policy_is_rejected() {
+        let config = WafConfig {
+            redis_unavailable_policy: "ignore".to_string(),
+            ..WafConfig::default()
+        };
+        let err = WafMiddleware::new(config, None).err().unwrap();
+        assert!(err.contains("waf.redis_unavailable_policy"), "{}", err);
+    }
+
+    #[test]
+    fn test_all_invalid_patterns_reported_together() {
+        let config = WafConfig {
+            sqli_patterns: vec!["(?i)union\\s+select".to_string(), "(unclosed".to_string()],
+            xss_patterns: vec!["[a-".to_string()],
+            path_traversal_patterns: vec!["\\.\\./".to_string(), "*bad".to_string()],
+            ..WafConfig::default()
+        };
+
+        let err = WafMiddleware::new(config, None).err().unwrap();
+        assert!(err.starts_with("3 invalid WAF pattern(s):"), "{}", err);
+        assert!(err.contains("sqli_patterns: \"(unclosed\": "));
+        assert!(err.contains("xss_patterns: \"[a-\": "));
+        assert!(err.contains("path_traversal_patterns: \"*bad\": "));
+        assert!(!err.contains("union"));
+    }
+
+    #[test]
+    fn test_categories_serialize_to_stable_names() {
+        let names: Vec<_> = WafCategory::ALL
+            .iter()
+            .map(|category| serde_json::to_value(category).unwrap())
+            .collect();
+        assert_eq!(
+            serde_json::Value::Array(names),
+            json!([
+                "sqli", "xss", "rce", "traversal", "bad_ua", "bad_path", "oversize", "bad_method", "banned", "grey",
+                "reputation"
+            ])
+        );
+        for category in WafCategory::ALL {
+            assert_eq!(serde_json::to_value(category).unwrap(), category.as_str());
+        }
+
+        let waf_match = WafMatch {
+            category: WafCategory::BadUa,
+            pattern: "sqlmap".to_string(),
+            weight: 4,
+        };
+        assert_eq!(
+            serde_json::to_value(&waf_match).unwrap(),
+            json!({ "category": "bad_ua", "pattern": "sqlmap", "weight": 4 })
+        );
+        assert_eq!(serde_json::to_value(WafAction::Block).unwrap(), "block");
+    }
+}
@@ -1,21 +1,33 @@
 /// Wallet authentication middleware
 /// 
-/// Verifies Ed25519 signatures from Solana wallets
+/// Verifies Ed25519 signatures from Solana wallets, or secp256k1 signatures
+/// from wallets that say so in `X-Wallet-Scheme`
 
 use actix_web::{
     body::EitherBody,
     dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
-    Error, HttpResponse,
+    http::header,
+    Error, HttpMessage, HttpRequest, HttpResponse,
 };
+use blockchain_auth::{AuthError, SignatureScheme, WalletVerifier};
 use futures_util::future::LocalBoxFuture;
 use serde::Serialize;
+use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
 use std::{
     future::{ready, Ready},
     rc::Rc,
+    sync::Arc,
 };
 
 use crate::app_state::AppState;
+use crate::auth_audit::AuthAudit;
 use crate::config::AuthConfig;
+use crate::http::client_ip::ClientIpResolver;
+use crate::http::middleware::protection::{self, Layer};
+use crate::metrics::AppMetrics;
+use crate::nonce_store::{NonceCheck, NonceStoreError};
+use crate::repository::AuthAttempt;
 
 #[derive(Serialize)]
 struct AuthErrorResponse {
@@ -26,14 +38,287 @@ struct AuthErrorResponse {
     missing: Option<Vec<String>>,
 }
 
+/// Header carrying a read-only API key, an alternative to wallet signatures
+pub(crate) const API_KEY_HEADER: &str = "X-API-Key";
+
+/// SHA-256 hex digest of an API key, as listed in `auth.api_keys`
+pub(crate) fn api_key_digest(key: &str) -> String {
+    format!("{:x}", Sha256::digest(key.as_bytes()))
+}
+
+/// Whether a presented API key matches one of the configured digests
+fn api_key_valid(config: &AuthConfig, key: &str) -> bool {
+    let digest = api_key_digest(key);
+    // Check every entry so the position of a match isn't leaked either
+    config.api_keys.iter().fold(false, |valid, expected| {
+        let expected = expected.trim().to_ascii_lowercase();
+        valid | bool::from(digest.as_bytes().ct_eq(expected.as_bytes()))
+    })
+}
+
+/// Methods whose signature covers the request body
+fn signs_body(method: &actix_web::http::Method) -> bool {
+    use actix_web::http::Method;
+    matches!(*method, Method::POST | Method::PUT | Method::PATCH)
+}
+
+/// Methods an API key may be used for
+fn is_read_only(method: &actix_web::http::Method) -> bool {
+    matches!(*method, actix_web::http::Method::GET | actix_web::http::Method::HEAD)
+}
+
+/// Marks a request whose wallet signature verified, in the request extensions
+#[derive(Debug, Clone)]
+pub struct AuthenticatedWallet;
+
+/// API key verified for this request, stored in the request extensions
+#[derive(Debug, Clone)]
+pub struct AuthenticatedApiKey {
+    /// Leading hex digits of the key's digest, as logged and audited
+    pub key_id: String,
+}
+
+/// Whether the request was authenticated, by wallet signature or API key
+pub fn is_authenticated(req: &HttpRequest) -> bool {
+    let extensions = req.extensions();
+    extensions.contains::<AuthenticatedWallet>() || extensions.contains::<AuthenticatedApiKey>()
+}
+
+/// Per-request context for the authentication audit log
+struct AttemptAudit {
+    audit: Option<Arc<AuthAudit>>,
+    /// Set for wallet signatures, whose outcomes feed `auth_verify_total`
+    metrics: Option<Arc<AppMetrics>>,
+    address: String,
+    method: String,
+    path: String,
+    ip: Option<String>,
+}
+
+impl AttemptAudit {
+    /// Record the outcome; `None` means success, otherwise the failure reason
+    fn record(&self, failure: Option<&str>) {
+        if let (Some(metrics), Some(result)) = (&self.metrics, verify_result(failure)) {
+            metrics.record_auth_verify(result);
+        }
+        if let Some(audit) = &self.audit {
+            audit.record(AuthAttempt {
+                address: self.address.clone(),
+                method: self.method.clone(),
+                path: self.path.clone(),
+                result: if failure.is_some() { "failure" } else { "success" }.to_string(),
+                reason: failure.map(str::to_string),
+                ip: self.ip.clone(),
+                created_at: chrono::Utc::now(),
+            });
+        }
+    }
+}
+
+/// `auth_verify_total` result for an audited outcome; header, timestamp and
+/// store failures never reach verification and aren't counted
+fn verify_result(failure: Option<&str>) -> Option<&'static str> {
+    match failure {
+        None => Some("success"),
+        Some("nonce_missing") => Some("nonce_missing"),
+        Some("nonce_mismatch") => Some("nonce_mismatch"),
+        Some("invalid_signature" | "signature_bad_encoding" | "signature_bad_length") => Some("invalid_signature"),
+        Some("invalid_pubkey") => Some("invalid_pubkey"),
+        Some(_) => None,
+    }
+}
+
+/// Verifier for the configured default scheme, encodings and signing-string layout
+pub(crate) fn wallet_verifier(config: &AuthConfig) -> WalletVerifier {
+    WalletVerifier {
+        // Validated when the config loaded
+        scheme: blockchain_auth::signature_scheme(&config.scheme).unwrap_or(&blockchain_auth::Ed25519Scheme),
+        accept_b58: config.accept_signature_b58,
+        accept_b64: config.accept_signature_b64,
+        canon_method: config.canonicalize_method.clone(),
+        canon_path: config.canonicalize_path.clone(),
+        separator: config.signing_separator.clone(),
+        domain: Some(config.signing_domain.clone()).filter(|d| !d.is_empty()),
+    }
+}
+
+/// Check a unix-seconds timestamp header against `now`, returning the failure reason
+fn check_timestamp(timestamp: &str, now: i64, skew_secs: u64) -> Result<(), (AuthFailure, &'static str)> {
+    let timestamp = timestamp
+        .trim()
+        .parse::<i64>()
+        .map_err(|_| (AuthFailure::Malformed, "invalid_timestamp"))?;
+    if timestamp.abs_diff(now) > skew_secs {
+        return Err((AuthFailure::Unauthenticated, "timestamp_skew"));
+    }
+    Ok(())
+}
+
+/// Scheme a request signs with: `auth.header_wallet_scheme` if sent, else `auth.scheme`
+pub(crate) fn request_scheme(
+    config: &AuthConfig,
+    header: Option<&str>,
+) -> Result<&'static dyn SignatureScheme, AuthError> {
+    blockchain_auth::signature_scheme(header.unwrap_or(&config.scheme))
+}
+
+/// How a signed request that didn't verify failed, with its response and
+/// audit reason; `None` for an internal failure
+fn verify_failure(error: &AuthError) -> Option<(AuthFailure, &'static str)> {
+    let malformed = |reason| Some((AuthFailure::Malformed, reason));
+    match error {
+        AuthError::InvalidPubkey(_) | AuthError::InvalidPubkeyLength(_) => malformed("invalid_pubkey"),
+        AuthError::InvalidBase58(_) | AuthError::InvalidBase64(_) => malformed("signature_bad_encoding"),
+        AuthError::InvalidSignatureLength(_) => malformed("signature_bad_length"),
+        AuthError::SignatureFormatDisabled => malformed("signature_format_disabled"),
+        AuthError::VerificationFailed => Some((AuthFailure::Unauthenticated, "invalid_signature")),
+        AuthError::UnknownScheme(_) => None,
+    }
+}
+
+/// Check a verified address against `auth.address_denylist` and
+/// `auth.address_allowlist`, returning the failure reason
+fn check_address_access(config: &AuthConfig, address: &str) -> Result<(), &'static str> {
+    if config.address_denylist.iter().any(|denied| denied == address) {
+        return Err("address_denied");
+    }
+    if !config.address_allowlist.is_empty() && !config.address_allowlist.iter().any(|allowed| allowed == address) {
+        return Err("address_not_allowed");
+    }
+    Ok(())
+}
+
+/// How a request failed authentication, which decides the status code
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum AuthFailure {
+    /// Malformed headers or inputs (400)
+    Malformed,
+    /// Missing or invalid credentials (401, with a `WWW-Authenticate` challenge)
+    Unauthenticated,
+    /// Authenticated, but not permitted (403)
+    Forbidden,
+}
+
+/// `WWW-Authenticate` challenge naming the headers a signed request needs
+fn auth_challenge(config: &AuthConfig) -> String {
+    let mut headers = vec![
+        config.header_wallet_address.as_str(),
+        config.header_wallet_signature.as_str(),
+        config.header_wallet_nonce.as_str(),
+    ];
+    if config.require_timestamp {
+        headers.push(config.header_timestamp.as_str());
+    }
+    format!(r#"Wallet headers="{}", nonce_uri="/api/auth/nonce""#, headers.join(" "))
+}
+
+fn auth_failure_response(config: &AuthConfig, failure: AuthFailure, body: AuthErrorResponse) -> HttpResponse {
+    let mut builder = match failure {
+        AuthFailure::Malformed => HttpResponse::BadRequest(),
+        AuthFailure::Unauthenticated => HttpResponse::Unauthorized(),
+        AuthFailure::Forbidden => HttpResponse::Forbidden(),
+    };
+    if failure == AuthFailure::Unauthenticated {
+        builder.insert_header((header::WWW_AUTHENTICATE, auth_challenge(config)));
+    }
+    builder.json(body)
+}
+
+/// Error response for an authentication failure
+pub(crate) fn auth_failure(config: &AuthConfig, failure: AuthFailure, reason: &str) -> HttpResponse {
+    let error = match failure {
+        AuthFailure::Malformed => "bad_request",
+        AuthFailure::Unauthenticated => "unauthorized",
+        AuthFailure::Forbidden => "forbidden",
+    };
+    auth_failure_response(
+        config,
+        failure,
+        AuthErrorResponse {
+            error: error.to_string(),
+            reason: Some(reason.to_string()),
+            missing: None,
+        },
+    )
+}
+
+/// 401 for a request lacking some of the authentication headers
+fn missing_credentials_response(config: &AuthConfig, missing: Vec<String>) -> HttpResponse {
+    auth_failure_response(
+        config,
+        AuthFailure::Unauthenticated,
+        AuthErrorResponse {
+            error: "unauthorized".to_string(),
+            reason: None,
+            missing: Some(missing),
+        },
+    )
+}
+
+/// Response and audit reason for a failed nonce-store call
+fn nonce_store_error_response(error: &NonceStoreError) -> (HttpResponse, &'static str) {
+    let (mut builder, error, reason) = match error {
+        NonceStoreError::Saturated => (HttpResponse::ServiceUnavailable(), "service_unavailable", "redis_saturated"),
+        NonceStoreError::Redis(_) => (HttpResponse::InternalServerError(), "internal", "redis_error"),
+    };
+    let response = builder.json(AuthErrorResponse {
+        error: error.to_string(),
+        reason: Some(reason.to_string()),
+        missing: None,
+    });
+    (response, reason)
+}
+
+/// Finish a request that failed authentication: with `response` where
+/// credentials are required, or by serving it anonymously where they are optional
+async fn refuse<S, B>(
+    service: Rc<S>,
+    req: ServiceRequest,
+    response: HttpResponse,
+    optional: bool,
+) -> Result<ServiceResponse<EitherBody<B>>, Error>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+{
+    if optional {
+        tracing::debug!(path = %req.path(), "Credentials didn't verify on a bypassed path, serving anonymously");
+        let res = service.call(req).await?;
+        return Ok(res.map_into_left_body());
+    }
+    let (req, _) = req.into_parts();
+    Ok(ServiceResponse::new(req, response).map_into_right_body())
+}
+
 #[derive(Clone)]
 pub struct WalletAuth {
     config: AuthConfig,
+    metrics: Option<Arc<AppMetrics>>,
+    client_ip: ClientIpResolver,
+    respect_x_forwarded_for: bool,
 }
 
 impl WalletAuth {
     pub fn new(config: AuthConfig) -> Self {
-        Self { config }
+        Self {
+            config,
+            metrics: None,
+            client_ip: ClientIpResolver::default(),
+            respect_x_forwarded_for: false,
+        }
+    }
+
+    /// Count verification outcomes in the given metrics
+    pub fn with_metrics(mut self, metrics: Arc<AppMetrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Resolve the audited client IP with the shared trusted-header settings;
+    /// otherwise it's the peer address
+    pub fn with_client_ip(mut self, client_ip: ClientIpResolver, respect_x_forwarded_for: bool) -> Self {
+        self.client_ip = client_ip;
+        self.respect_x_forwarded_for = respect_x_forwarded_for;
+        self
     }
 
     fn is_bypassed(&self, path: &str) -> bool {
@@ -61,6 +346,9 @@ where
         ready(Ok(WalletAuthMiddleware {
             service: Rc::new(service),
             config: self.config.clone(),
+            metrics: self.metrics.clone(),
+            client_ip: self.client_ip.clone(),
+            respect_x_forwarded_for: self.respect_x_forwarded_for,
         }))
     }
 }
@@ -68,9 +356,18 @@ where
 pub struct WalletAuthMiddleware<S> {
     service: Rc<S>,
     config: AuthConfig,
+    metrics: Option<Arc<AppMetrics>>,
+    client_ip: ClientIpResolver,
+    respect_x_forwarded_for: bool,
 }
 
 impl<S> WalletAuthMiddleware<S> {
+    fn client_ip(&self, req: &ServiceRequest) -> Option<String> {
+        self.client_ip
+            .resolve(req.request(), self.respect_x_forwarded_for)
+            .map(|ip| ip.to_string())
+    }
+
     fn is_bypassed(&self, path: &str) -> bool {
         self.config.bypass_paths.iter().any(|bp| path == bp)
     }
@@ -93,6 +390,8 @@ where
     forward_ready!(service);
 
     fn call(&self, req: ServiceRequest) -> Self::Future {
+        protection::mark(&req, Layer::Auth);
+
         // Skip if disabled
         if !self.config.enabled {
             let service = self.service.clone();
@@ -107,8 +406,14 @@ where
             .map(|pq| pq.as_str().to_string())
             .unwrap_or_else(|| path.clone());
 
-        // Skip bypassed paths
-        if self.is_bypassed(&path) {
+        // Credentials are optional on bypassed paths: they are checked when
+        // presented, so handlers can tell authenticated callers apart, but a
+        // failure serves the request anonymously rather than refusing it. As on
+        // protected paths, only a request that authenticates consumes its nonce
+        let optional = self.is_bypassed(&path);
+        let presents_credentials = req.headers().contains_key(&self.config.header_wallet_address)
+            || req.headers().contains_key(API_KEY_HEADER);
+        if optional && !presents_credentials {
             let service = self.service.clone();
             return Box::pin(async move {
                 let res = service.call(req).await?;
@@ -125,6 +430,40 @@ where
             });
         }
 
+        // Read-only requests may present an API key instead of a wallet signature
+        let api_key = req.headers()
+            .get(API_KEY_HEADER)
+            .and_then(|h| h.to_str().ok())
+            .filter(|_| is_read_only(req.method()) && !self.config.api_keys.is_empty());
+        if let Some(api_key) = api_key {
+            let key_id = api_key_digest(api_key)[..8].to_string();
+            let attempt = AttemptAudit {
+                audit: req.app_data::<actix_web::web::Data<AppState>>()
+                    .and_then(|state| state.auth_audit.clone()),
+                metrics: None,
+                address: format!("api_key:{}", key_id),
+                method: req.method().to_string(),
+                path: path.clone(),
+                ip: self.client_ip(&req),
+            };
+
+            if !api_key_valid(&self.config, api_key) {
+                tracing::warn!(key_id = %key_id, path = %path, "Invalid API key");
+                attempt.record(Some("invalid_api_key"));
+                let response = auth_failure(&self.config, AuthFailure::Unauthenticated, "invalid_api_key");
+                return Box::pin(refuse(self.service.clone(), req, response, optional));
+            }
+
+            tracing::debug!(key_id = %key_id, path = %path, "API key authentication successful");
+            attempt.record(None);
+            req.extensions_mut().insert(AuthenticatedApiKey { key_id });
+            let service = self.service.clone();
+            return Box::pin(async move {
+                let res = service.call(req).await?;
+                Ok(res.map_into_left_body())
+            });
+        }
+
         // Extract headers
         let config = self.config.clone();
         let method = req.method().to_string();
@@ -144,8 +483,30 @@ where
             .and_then(|h| h.to_str().ok())
             .map(|s| s.to_string());
 
+        let wallet_timestamp = req.headers()
+            .get(&config.header_timestamp)
+            .and_then(|h| h.to_str().ok())
+            .map(|s| s.to_string())
+            .filter(|_| config.require_timestamp);
+
+        let wallet_scheme = req.headers()
+            .get(&config.header_wallet_scheme)
+            .and_then(|h| h.to_str().ok())
+            .map(|s| s.to_string());
+
+        let attempt = AttemptAudit {
+            audit: req.app_data::<actix_web::web::Data<AppState>>()
+                .and_then(|state| state.auth_audit.clone()),
+            metrics: self.metrics.clone(),
+            address: wallet_address.clone().unwrap_or_default(),
+            method: method.clone(),
+            path: path.clone(),
+            ip: self.client_ip(&req),
+        };
+
         // Check required headers
-        if wallet_address.is_none() || wallet_signature.is_none() || wallet_nonce.is_none() {
+        let timestamp_missing = config.require_timestamp && wallet_timestamp.is_none();
+        if wallet_address.is_none() || wallet_signature.is_none() || wallet_nonce.is_none() || timestamp_missing {
             let mut missing = Vec::new();
             if wallet_address.is_none() {
                 missing.push(config.header_wallet_address.clone());
@@ -156,184 +517,211 @@ where
             if wallet_nonce.is_none() {
                 missing.push(config.header_wallet_nonce.clone());
             }
+            if timestamp_missing {
+                missing.push(config.header_timestamp.clone());
+            }
+            attempt.record(Some("headers_missing"));
 
-            let response = HttpResponse::BadRequest().json(AuthErrorResponse {
-                error: "bad_request".to_string(),
-                reason: None,
-                missing: Some(missing),
-            });
+            let response = missing_credentials_response(&config, missing);
 
-            let (req, _) = req.into_parts();
-            return Box::pin(async move {
-                Ok(ServiceResponse::new(req, response).map_into_right_body())
-            });
+            return Box::pin(refuse(self.service.clone(), req, response, optional));
         }
 
         let address = wallet_address.unwrap();
         let signature = wallet_signature.unwrap();
         let nonce = wallet_nonce.unwrap();
 
-        // Get Redis connection from state
-        let redis_conn = req.app_data::<actix_web::web::Data<AppState>>()
-            .and_then(|state| state.redis.clone());
+        // Reject stale or future-dated requests before touching the nonce store
+        if let Some(timestamp) = &wallet_timestamp {
+            if let Err((failure, reason)) =
+                check_timestamp(timestamp, chrono::Utc::now().timestamp(), config.timestamp_skew_secs)
+            {
+                tracing::warn!(
+                    address = %address,
+                    timestamp = %timestamp,
+                    reason = %reason,
+                    "Request timestamp rejected"
+                );
+                attempt.record(Some(reason));
+                let response = auth_failure(&config, failure, reason);
+                return Box::pin(refuse(self.service.clone(), req, response, optional));
+            }
+        }
+
+        let scheme = match request_scheme(&config, wallet_scheme.as_deref()) {
+            Ok(scheme) => scheme,
+            Err(e) => {
+                tracing::warn!(error = %e, address = %address, "Unsupported signature scheme");
+                attempt.record(Some("unsupported_scheme"));
+                let response = auth_failure(&config, AuthFailure::Malformed, "unsupported_scheme");
+                return Box::pin(refuse(self.service.clone(), req, response, optional));
+            }
+        };
+
+        // Get nonce store from state
+        let nonce_store = req.app_data::<actix_web::web::Data<AppState>>()
+            .and_then(|state| state.nonce_store.clone());
 
         let service = self.service.clone();
 
         Box::pin(async move {
-            // Check Redis connection
-            let mut redis_conn = match redis_conn {
-                Some(conn) => conn,
+            // Check nonce store
+            let nonce_store = match nonce_store {
+                Some(store) => store,
                 None => {
                     tracing::error!("Redis not available for auth");
+                    attempt.record(Some("redis_unavailable"));
                     let response = HttpResponse::InternalServerError().json(AuthErrorResponse {
                         error: "internal".to_string(),
                         reason: Some("redis_unavailable".to_string()),
                         missing: None,
                     });
-                    let (req, _) = req.into_parts();
-                    return Ok(ServiceResponse::new(req, response).map_into_right_body());
+                    return refuse(service.clone(), req, response, optional).await;
                 }
             };
 
-            // Verify nonce in Redis
-            let redis_key = format!("{}:{}", config.redis_key_prefix, address);
-            let stored_nonce: Option<String> = match redis::cmd("GET")
-                .arg(&redis_key)
-                .query_async(&mut redis_conn)
-                .await
-            {
-                Ok(n) => n,
+            // Verify nonce is one of the outstanding nonces for the address
+            let nonce_check = match nonce_store.check(&address, &nonce).await {
+                Ok(check) => check,
                 Err(e) => {
                     tracing::error!(error = %e, "Failed to get nonce from Redis");
-                    let response = HttpResponse::InternalServerError().json(AuthErrorResponse {
-                        error: "internal".to_string(),
-                        reason: Some("redis_error".to_string()),
-                        missing: None,
-                    });
-                    let (req, _) = req.into_parts();
-                    return Ok(ServiceResponse::new(req, response).map_into_right_body());
+                    let (response, reason) = nonce_store_error_response(&e);
+                    attempt.record(Some(reason));
+                    return refuse(service.clone(), req, response, optional).await;
                 }
             };
 
             // Check nonce exists
-            let stored_nonce = match stored_nonce {
-                Some(n) => n,
-                None => {
-                    tracing::warn!(
-                        address = %address,
-                        path = %path,
-                        "Nonce not found or expired"
-                    );
-                    let response = HttpResponse::Unauthorized().json(AuthErrorResponse {
-                        error: "unauthorized".to_string(),
-                        reason: Some("nonce_missing".to_string()),
-                        missing: None,
-                    });
-                    let (req, _) = req.into_parts();
-                    return Ok(ServiceResponse::new(req, response).map_into_right_body());
-                }
-            };
+            if nonce_check == NonceCheck::Missing {
+                tracing::warn!(
+                    address = %address,
+                    path = %path,
+                    "Nonce not found or expired"
+                );
+                attempt.record(Some("nonce_missing"));
+                let response = auth_failure(&config, AuthFailure::Unauthenticated, "nonce_missing");
+                return refuse(service.clone(), req, response, optional).await;
+            }
 
             // Check nonce matches
-            if stored_nonce != nonce {
+            if nonce_check == NonceCheck::Mismatch {
                 tracing::warn!(
                     address = %address,
                     path = %path,
                     "Nonce mismatch"
                 );
-                let response = HttpResponse::Unauthorized().json(AuthErrorResponse {
-                    error: "unauthorized".to_string(),
-                    reason: Some("nonce_mismatch".to_string()),
-                    missing: None,
-                });
-                let (req, _) = req.into_parts();
-                return Ok(ServiceResponse::new(req, response).map_into_right_body());
+                attempt.record(Some("nonce_mismatch"));
+                let response = auth_failure(&config, AuthFailure::Unauthenticated, "nonce_mismatch");
+                return refuse(service.clone(), req, response, optional).await;
             }
 
-            // Decode public key
-            let pubkey = match blockchain_auth::decode_pubkey_b58(&address) {
-                Ok(pk) => pk,
-                Err(e) => {
-                    tracing::warn!(error = %e, address = %address, "Invalid public key");
-                    let response = HttpResponse::BadRequest().json(AuthErrorResponse {
-                        error: "bad_request".to_string(),
-                        reason: Some(format!("invalid_pubkey: {}", e)),
-                        missing: None,
-                    });
-                    let (req, _) = req.into_parts();
-                    return Ok(ServiceResponse::new(req, response).map_into_right_body());
-                }
-            };
+            if nonce_check == NonceCheck::Grace {
+                tracing::warn!(
+                    address = %address,
+                    path = %path,
+                    grace_secs = config.nonce_grace_secs,
+                    "Accepting nonce within grace period after expiry"
+                );
+            }
 
-            // Decode signature
-            let sig_bytes = if config.accept_signature_b58 {
-                blockchain_auth::decode_sig_b58(&signature)
-            } else if config.accept_signature_b64 {
-                blockchain_auth::decode_sig_b64(&signature)
-            } else {
-                Err(blockchain_auth::AuthError::InvalidBase58("No signature format enabled".to_string()))
+            let verifier = WalletVerifier {
+                scheme,
+                ..wallet_verifier(&config)
             };
 
-            let sig_bytes = match sig_bytes {
-                Ok(s) => s,
-                Err(e) => {
-                    tracing::warn!(error = %e, "Invalid signature format");
-                    let response = HttpResponse::BadRequest().json(AuthErrorResponse {
-                        error: "bad_request".to_string(),
-                        reason: Some(format!("invalid_signature: {}", e)),
-                        missing: None,
-                    });
-                    let (req, _) = req.into_parts();
-                    return Ok(ServiceResponse::new(req, response).map_into_right_body());
-                }
+            // Hash the body so the signature can't be replayed with another one;
+            // the buffered body is put back for the handler
+            let mut req = req;
+            let body_sha256 = if signs_body(req.method()) {
+                let body = match req.extract::<actix_web::web::Bytes>().await {
+                    Ok(body) => body,
+                    Err(e) => {
+                        tracing::warn!(error = %e, "Failed to read request body for signature");
+                        attempt.record(Some("body_unreadable"));
+                        return refuse(service.clone(), req, e.error_response(), optional).await;
+                    }
+                };
+                req.set_payload(body.clone().into());
+                Some(format!("{:x}", Sha256::digest(&body)))
+            } else {
+                None
             };
 
-            // Build signing string
-            let signing_string = blockchain_auth::build_signing_string(
+            // Verify the signature over the request, its body hash and timestamp
+            let verified = verifier.verify_request(
+                &address,
+                &signature,
                 &method,
                 &path_with_query,
                 &nonce,
-                &config.canonicalize_method,
-                &config.canonicalize_path,
+                wallet_timestamp.as_deref(),
+                body_sha256.as_deref(),
             );
+            if let Err(e) = verified {
+                let response = match verify_failure(&e) {
+                    Some((failure, reason)) => {
+                        tracing::warn!(
+                            error = %e,
+                            address = %address,
+                            method = %method,
+                            path = %path,
+                            reason = %reason,
+                            "Signature rejected"
+                        );
+                        attempt.record(Some(reason));
+                        let detail = if reason == "invalid_pubkey" {
+                            format!("{}: {}", reason, e)
+                        } else {
+                            reason.to_string()
+                        };
+                        auth_failure(&config, failure, &detail)
+                    }
+                    None => {
+                        tracing::error!(error = %e, "Signature verification error");
+                        attempt.record(Some("verification_error"));
+                        HttpResponse::InternalServerError().json(AuthErrorResponse {
+                            error: "internal".to_string(),
+                            reason: Some("verification_error".to_string()),
+                            missing: None,
+                        })
+                    }
+                };
+                return refuse(service.clone(), req, response, optional).await;
+            }
 
-            // Verify signature
-            let is_valid = match blockchain_auth::verify_ed25519(&pubkey, signing_string.as_bytes(), &sig_bytes) {
-                Ok(v) => v,
-                Err(e) => {
-                    tracing::error!(error = %e, "Signature verification error");
-                    let response = HttpResponse::InternalServerError().json(AuthErrorResponse {
-                        error: "internal".to_string(),
-                        reason: Some("verification_error".to_string()),
-                        missing: None,
-                    });
-                    let (req, _) = req.into_parts();
-                    return Ok(ServiceResponse::new(req, response).map_into_right_body());
-                }
-            };
-
-            if !is_valid {
+            // The wallet proved who it is; now whether it may come in
+            if let Err(reason) = check_address_access(&config, &address) {
                 tracing::warn!(
                     address = %address,
-                    method = %method,
                     path = %path,
-                    "Invalid signature"
+                    reason = %reason,
+                    "Wallet address refused"
                 );
-                let response = HttpResponse::Unauthorized().json(AuthErrorResponse {
-                    error: "unauthorized".to_string(),
-                    reason: Some("invalid_signature".to_string()),
-                    missing: None,
-                });
-                let (req, _) = req.into_parts();
-                return Ok(ServiceResponse::new(req, response).map_into_right_body());
+                attempt.record(Some(reason));
+                let response = auth_failure(&config, AuthFailure::Forbidden, reason);
+                return refuse(service.clone(), req, response, optional).await;
             }
 
-            // Delete nonce (one-time use)
-            let _: () = redis::cmd("DEL")
-                .arg(&redis_key)
-                .query_async(&mut redis_conn)
-                .await
-                .unwrap_or_default();
+            // Consume just this nonce (one-time use); losing a race means it was replayed
+            match nonce_store.consume(&address, &nonce).await {
+                Ok(true) => {}
+                Ok(false) => {
+                    tracing::warn!(
+                        address = %address,
+                        path = %path,
+                        "Nonce already consumed"
+                    );
+                    attempt.record(Some("nonce_missing"));
+                    let response = auth_failure(&config, AuthFailure::Unauthenticated, "nonce_missing");
+                    return refuse(service.clone(), req, response, optional).await;
+                }
+                Err(e) => {
+                    tracing::error!(error = %e, "Failed to consume nonce");
+                    let (response, reason) = nonce_store_error_response(&e);
+                    attempt.record(Some(reason));
+                    return refuse(service.clone(), req, response, optional).await;
+                }
+            }
 
             tracing::info!(
                 address = %address,
@@ -341,6 +729,8 @@ where
                 path = %path,
                 "Authentication successful"
             );
+            attempt.record(None);
+            req.extensions_mut().insert(AuthenticatedWallet);
 
             // Pass through
             let res = service.call(req).await?;
@@ -349,3 +739,696 @@ where
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ServiceConfig;
+    use crate::nonce_store::NonceStore;
+    use actix_web::{body::MessageBody, test, web, App};
+
+    /// Nonce store, audit log and metrics behind a `test_app`
+    struct Fixture {
+        store: Arc<NonceStore>,
+        audit: Arc<AuthAudit>,
+        metrics: Arc<AppMetrics>,
+    }
+
+    /// `WalletAuth` enabled with `config` over an in-memory nonce store, in
+    /// front of the routes `routes` registers
+    async fn test_app_with(
+        config: AuthConfig,
+        routes: impl FnOnce(&mut web::ServiceConfig),
+    ) -> (
+        impl Service<actix_http::Request, Response = ServiceResponse<impl MessageBody>, Error = Error>,
+        Fixture,
+    ) {
+        let config = AuthConfig {
+            enabled: true,
+            nonce_backend: "memory".to_string(),
+            ..config
+        };
+        let fixture = Fixture {
+            store: Arc::new(NonceStore::from_config(&config, None).unwrap()),
+            audit: Arc::new(AuthAudit::memory(100)),
+            metrics: Arc::new(AppMetrics::new().unwrap()),
+        };
+        let mut state = AppState::new(ServiceConfig::default(), None, None);
+        state.nonce_store = Some(fixture.store.clone());
+        state.auth_audit = Some(fixture.audit.clone());
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(state))
+                .app_data(web::Data::new(config.clone()))
+                .app_data(web::Data::from(fixture.metrics.clone()))
+                .wrap(WalletAuth::new(config).with_metrics(fixture.metrics.clone()))
+                .configure(routes),
+        )
+        .await;
+        (app, fixture)
+    }
+
+    /// `test_app_with` serving GET /api/transactions
+    async fn test_app(
+        config: AuthConfig,
+    ) -> (
+        impl Service<actix_http::Request, Response = ServiceResponse<impl MessageBody>, Error = Error>,
+        Fixture,
+    ) {
+        test_app_with(config, |cfg| {
+            cfg.route("/api/transactions", web::get().to(HttpResponse::Ok));
+        })
+        .await
+    }
+
+    #[actix_web::test]
+    async fn test_failed_attempts_are_audited() {
+        let audit = Arc::new(AuthAudit::memory(100));
+        let mut state = AppState::new(ServiceConfig::default(), None, None);
+        state.auth_audit = Some(audit.clone());
+        let config = AuthConfig {
+            enabled: true,
+            ..AuthConfig::default()
+        };
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(state))
+                .wrap(WalletAuth::new(config))
+                .route("/api/transactions", web::get().to(HttpResponse::Ok)),
+        )
+        .await;
+
+        let address = "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM";
+        let req = test::TestRequest::get()
+            .uri("/api/transactions")
+            .insert_header(("X-Wallet-Address", address))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 401);
+
+        let req = test::TestRequest::get()
+            .uri("/api/transactions")
+            .insert_header(("X-Wallet-Address", address))
+            .insert_header(("X-Wallet-Signature", "sig"))
+            .insert_header(("X-Nonce", "nonce"))
+            .insert_header(("X-Forwarded-For", "198.51.100.7"))
+            .peer_addr("192.0.2.50:443".parse().unwrap())
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 500);
+
+        let attempts = audit.recent(address, 10).await.unwrap();
+        assert_eq!(attempts.len(), 2);
+        assert!(attempts.iter().all(|a| a.result == "failure" && a.path == "/api/transactions"));
+        assert_eq!(attempts[0].reason.as_deref(), Some("redis_unavailable"));
+        assert_eq!(attempts[1].reason.as_deref(), Some("headers_missing"));
+        // The forwarding header isn't trusted unless the middleware is told to
+        assert_eq!(attempts[0].ip.as_deref(), Some("192.0.2.50"));
+    }
+
+    #[actix_web::test]
+    async fn test_wallet_scheme_header_selects_the_scheme() {
+        let audit = Arc::new(AuthAudit::memory(100));
+        let mut state = AppState::new(ServiceConfig::default(), None, None);
+        state.auth_audit = Some(audit.clone());
+        let config = AuthConfig {
+            enabled: true,
+            ..AuthConfig::default()
+        };
+        assert_eq!(request_scheme(&config, None).unwrap().name(), "ed25519");
+        assert_eq!(request_scheme(&config, Some("secp256k1")).unwrap().name(), "secp256k1");
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(state))
+                .wrap(WalletAuth::new(config))
+                .route("/api/transactions", web::get().to(HttpResponse::Ok)),
+        )
+        .await;
+
+        let address = "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM";
+        let req = test::TestRequest::get()
+            .uri("/api/transactions")
+            .insert_header(("X-Wallet-Address", address))
+            .insert_header(("X-Wallet-Signature", "sig"))
+            .insert_header(("X-Nonce", "nonce"))
+            .insert_header(("X-Wallet-Scheme", "rsa"))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 400);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["reason"], "unsupported_scheme");
+        let attempts = audit.recent(address, 1).await.unwrap();
+        assert_eq!(attempts[0].reason.as_deref(), Some("unsupported_scheme"));
+
+        // Lengths are checked per scheme: 65-byte r||s||v is secp256k1 only, and
+        // a 32-byte Solana address isn't a SEC1 key
+        let verifier = wallet_verifier(&AuthConfig::default());
+        let rsv = bs58::encode([1u8; 65]).into_string();
+        assert!(verifier.decode_signature(&rsv).is_err());
+        let secp256k1 = WalletVerifier { scheme: &blockchain_auth::Secp256k1Scheme, ..verifier };
+        assert!(secp256k1.decode_signature(&rsv).is_ok());
+        assert!(secp256k1.decode_pubkey(address).is_err());
+    }
+
+    #[actix_web::test]
+    async fn test_keys_off_the_curve_are_bad_requests() {
+        let (app, Fixture { store, .. }) = test_app(AuthConfig::default()).await;
+
+        // Keys of the right length for their scheme that aren't curve points
+        let ed25519 = (0u8..=255)
+            .map(|seed| [seed; 32])
+            .find(|bytes| ed25519_dalek::VerifyingKey::from_bytes(bytes).is_err())
+            .unwrap();
+        let mut secp256k1 = [0xffu8; 33];
+        secp256k1[0] = 0x02;
+
+        for (scheme, key, signature) in [
+            ("ed25519", &ed25519[..], [1u8; 64].to_vec()),
+            ("secp256k1", &secp256k1[..], [1u8; 65].to_vec()),
+        ] {
+            let address = bs58::encode(key).into_string();
+            store.issue(&address, "nonce-1").await.unwrap();
+            let req = test::TestRequest::get()
+                .uri("/api/transactions")
+                .insert_header(("X-Wallet-Address", address.as_str()))
+                .insert_header(("X-Wallet-Signature", bs58::encode(signature).into_string()))
+                .insert_header(("X-Nonce", "nonce-1"))
+                .insert_header(("X-Wallet-Scheme", scheme))
+                .to_request();
+            let resp = test::call_service(&app, req).await;
+            assert_eq!(resp.status(), 400, "{}", scheme);
+            let body: serde_json::Value = test::read_body_json(resp).await;
+            assert!(body["reason"].as_str().unwrap().starts_with("invalid_pubkey"), "{}: {}", scheme, body);
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_signature_over_older_outstanding_nonce_verifies() {
+        use ed25519_dalek::{Signer, SigningKey};
+
+        let config = AuthConfig::default();
+        let (app, Fixture { store, audit, .. }) = test_app(config.clone()).await;
+
+        let key = SigningKey::from_bytes(&[7u8; 32]);
+        let address = bs58::encode(key.verifying_key().to_bytes()).into_string();
+        store.issue(&address, "older-nonce").await.unwrap();
+        store.issue(&address, "newer-nonce").await.unwrap();
+
+        let signing_string = wallet_verifier(&config).signing_string("GET", "/api/transactions", "older-nonce", None, None);
+        let signature = bs58::encode(key.sign(signing_string.as_bytes()).to_bytes()).into_string();
+        let signed_request = || {
+            test::TestRequest::get()
+                .uri("/api/transactions")
+                .insert_header(("X-Wallet-Address", address.as_str()))
+                .insert_header(("X-Wallet-Signature", signature.as_str()))
+                .insert_header(("X-Nonce", "older-nonce"))
+                .to_request()
+        };
+
+        let resp = test::call_service(&app, signed_request()).await;
+        assert_eq!(resp.status(), 200);
+
+        // The older nonce is consumed; replaying it fails while the newer one remains
+        let resp = test::call_service(&app, signed_request()).await;
+        assert_eq!(resp.status(), 401);
+        assert_eq!(store.check(&address, "newer-nonce").await.unwrap(), NonceCheck::Valid);
+
+        let attempts = audit.recent(&address, 10).await.unwrap();
+        assert_eq!(attempts[1].result, "success");
+        assert_eq!(attempts[0].reason.as_deref(), Some("nonce_mismatch"));
+    }
+
+    #[actix_web::test]
+    async fn test_body_is_bound_to_the_signature() {
+        use ed25519_dalek::{Signer, SigningKey};
+
+        let config = AuthConfig::default();
+        let (app, Fixture { store, .. }) = test_app_with(config.clone(), |cfg| {
+            cfg.route("/api/orders", web::post().to(|body: web::Bytes| async move { HttpResponse::Ok().body(body) }));
+        })
+        .await;
+
+        let key = SigningKey::from_bytes(&[11u8; 32]);
+        let address = bs58::encode(key.verifying_key().to_bytes()).into_string();
+        let body = r#"{"amount":1}"#;
+        let body_sha256 = format!("{:x}", Sha256::digest(body.as_bytes()));
+        let signed = |nonce: &str, body_sha256: Option<&str>| {
+            let message = wallet_verifier(&config).signing_string("POST", "/api/orders", nonce, None, body_sha256);
+            bs58::encode(key.sign(message.as_bytes()).to_bytes()).into_string()
+        };
+        let post = |nonce: &str, signature: String, body: &'static str| {
+            test::TestRequest::post()
+                .uri("/api/orders")
+                .insert_header(("X-Wallet-Address", address.as_str()))
+                .insert_header(("X-Wallet-Signature", signature))
+                .insert_header(("X-Nonce", nonce))
+                .set_payload(body)
+                .to_request()
+        };
+
+        // The handler still reads the body the middleware hashed
+        store.issue(&address, "n1").await.unwrap();
+        let resp = test::call_service(&app, post("n1", signed("n1", Some(&body_sha256)), body)).await;
+        assert_eq!(resp.status(), 200);
+        assert_eq!(test::read_body(resp).await, body.as_bytes());
+
+        // The same signature over another body, or a signature without the
+        // body line (as a GET would sign), doesn't verify
+        store.issue(&address, "n2").await.unwrap();
+        let resp = test::call_service(&app, post("n2", signed("n2", Some(&body_sha256)), r#"{"amount":1000}"#)).await;
+        assert_eq!(resp.status(), 401);
+        store.issue(&address, "n3").await.unwrap();
+        let resp = test::call_service(&app, post("n3", signed("n3", None), body)).await;
+        assert_eq!(resp.status(), 401);
+    }
+
+    #[actix_web::test]
+    async fn test_address_lists_are_enforced_after_verification() {
+        use ed25519_dalek::{Signer, SigningKey};
+
+        let [denied, allowed, absent] = [1u8, 2, 3].map(|seed| SigningKey::from_bytes(&[seed; 32]));
+        let address = |key: &SigningKey| bs58::encode(key.verifying_key().to_bytes()).into_string();
+        let config = AuthConfig {
+            // The denylist wins even for an allowlisted address
+            address_allowlist: vec![address(&denied), address(&allowed)],
+            address_denylist: vec![address(&denied)],
+            ..AuthConfig::default()
+        };
+        let (app, Fixture { store, audit, .. }) = test_app(config.clone()).await;
+
+        for (key, status, reason) in [
+            (&denied, 403, Some("address_denied")),
+            (&allowed, 200, None),
+            (&absent, 403, Some("address_not_allowed")),
+        ] {
+            let address = address(key);
+            store.issue(&address, "nonce-1").await.unwrap();
+            let signing_string = wallet_verifier(&config).signing_string("GET", "/api/transactions", "nonce-1", None, None);
+            let signature = bs58::encode(key.sign(signing_string.as_bytes()).to_bytes()).into_string();
+            let req = test::TestRequest::get()
+                .uri("/api/transactions")
+                .insert_header(("X-Wallet-Address", address.as_str()))
+                .insert_header(("X-Wallet-Signature", signature))
+                .insert_header(("X-Nonce", "nonce-1"))
+                .to_request();
+
+            let resp = test::call_service(&app, req).await;
+            assert_eq!(resp.status(), status);
+            if let Some(reason) = reason {
+                assert!(resp.headers().get(header::WWW_AUTHENTICATE).is_none());
+                let body: serde_json::Value = test::read_body_json(resp).await;
+                assert_eq!(body["reason"], reason);
+            }
+            let attempts = audit.recent(&address, 1).await.unwrap();
+            assert_eq!(attempts[0].reason.as_deref(), reason);
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_signature_decode_failures_have_specific_reasons() {
+        let address = "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM";
+        let short = bs58::encode([1u8; 10]).into_string();
+
+        for (b58, b64, signature, reason) in [
+            (true, false, "0OIl", "signature_bad_encoding"),
+            (false, true, "not base64!", "signature_bad_encoding"),
+            (true, false, short.as_str(), "signature_bad_length"),
+            (false, true, "AQID", "signature_bad_length"),
+            (false, false, short.as_str(), "signature_format_disabled"),
+        ] {
+            let config = AuthConfig {
+                accept_signature_b58: b58,
+                accept_signature_b64: b64,
+                ..AuthConfig::default()
+            };
+            let (app, Fixture { store, audit, .. }) = test_app(config).await;
+            store.issue(address, "nonce-1").await.unwrap();
+
+            let req = test::TestRequest::get()
+                .uri("/api/transactions")
+                .insert_header(("X-Wallet-Address", address))
+                .insert_header(("X-Wallet-Signature", signature))
+                .insert_header(("X-Nonce", "nonce-1"))
+                .to_request();
+            let resp = test::call_service(&app, req).await;
+            assert_eq!(resp.status(), 400, "{}", signature);
+            let body: serde_json::Value = test::read_body_json(resp).await;
+            assert_eq!(body["reason"], reason, "{}", signature);
+            assert_eq!(audit.recent(address, 1).await.unwrap()[0].reason.as_deref(), Some(reason));
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_api_key_grants_read_access_only() {
+        let audit = Arc::new(AuthAudit::memory(100));
+        let mut state = AppState::new(ServiceConfig::default(), None, None);
+        state.auth_audit = Some(audit.clone());
+        let config = AuthConfig {
+            enabled: true,
+            bypass_paths: vec![],
+            api_keys: vec![api_key_digest("analytics-job-key").to_uppercase()],
+            ..AuthConfig::default()
+        };
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(state))
+                .wrap(WalletAuth::new(config))
+                .route(
+                    "/api/transactions",
+                    web::get().to(|req: HttpRequest| async move {
+                        let key_id = req.extensions().get::<AuthenticatedApiKey>().map(|key| key.key_id.clone());
+                        assert!(is_authenticated(&req));
+                        HttpResponse::Ok().body(key_id.unwrap_or_default())
+                    }),
+                )
+                .route("/api/transactions", web::post().to(HttpResponse::Ok)),
+        )
+        .await;
+        let request = |method: actix_web::http::Method, key: &str| {
+            test::TestRequest::default()
+                .method(method)
+                .uri("/api/transactions")
+                .insert_header((API_KEY_HEADER, key))
+                .to_request()
+        };
+
+        // The handler sees an authenticated caller, identified by the key
+        let key_id = &api_key_digest("analytics-job-key")[..8];
+        let resp = test::call_service(&app, request(actix_web::http::Method::GET, "analytics-job-key")).await;
+        assert_eq!(resp.status(), 200);
+        assert_eq!(test::read_body(resp).await, key_id.as_bytes());
+
+        let resp = test::call_service(&app, request(actix_web::http::Method::GET, "guessed-key")).await;
+        assert_eq!(resp.status(), 401);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["reason"], "invalid_api_key");
+
+        // Writes still need a wallet signature
+        let resp = test::call_service(&app, request(actix_web::http::Method::POST, "analytics-job-key")).await;
+        assert_eq!(resp.status(), 401);
+
+        let attempts = audit.recent(&format!("api_key:{}", key_id), 10).await.unwrap();
+        assert_eq!(attempts.len(), 1);
+        assert_eq!(attempts[0].result, "success");
+    }
+
+    #[actix_web::test]
+    async fn test_nonce_and_verification_outcomes_are_counted() {
+        use crate::http::routes::auth::get_nonce;
+        use ed25519_dalek::{Signer, SigningKey};
+
+        let config = AuthConfig {
+            bypass_paths: vec!["/api/auth/nonce".to_string()],
+            ..AuthConfig::default()
+        };
+        let (app, Fixture { metrics, .. }) = test_app_with(config.clone(), |cfg| {
+            cfg.route("/api/auth/nonce", web::post().to(get_nonce))
+                .route("/api/transactions", web::get().to(HttpResponse::Ok));
+        })
+        .await;
+
+        let key = SigningKey::from_bytes(&[5u8; 32]);
+        let address = bs58::encode(key.verifying_key().to_bytes()).into_string();
+        let req = test::TestRequest::post()
+            .uri("/api/auth/nonce")
+            .set_json(serde_json::json!({ "address": address }))
+            .to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        let nonce = body["nonce"].as_str().unwrap().to_string();
+        assert_eq!(metrics.auth_nonce_issued_total.get(), 1);
+
+        let signed_request = |signer: &SigningKey| {
+            let signing_string = wallet_verifier(&config).signing_string("GET", "/api/transactions", &nonce, None, None);
+            let signature = bs58::encode(signer.sign(signing_string.as_bytes()).to_bytes()).into_string();
+            test::TestRequest::get()
+                .uri("/api/transactions")
+                .insert_header(("X-Wallet-Address", address.as_str()))
+                .insert_header(("X-Wallet-Signature", signature))
+                .insert_header(("X-Nonce", nonce.as_str()))
+                .to_request()
+        };
+
+        // Signed by another key, then by the wallet, then replayed
+        let resp = test::call_service(&app, signed_request(&SigningKey::from_bytes(&[6u8; 32]))).await;
+        assert_eq!(resp.status(), 401);
+        let resp = test::call_service(&app, signed_request(&key)).await;
+        assert_eq!(resp.status(), 200);
+        let resp = test::call_service(&app, signed_request(&key)).await;
+        assert_eq!(resp.status(), 401);
+
+        let verified = |result: &str| metrics.auth_verify_total.with_label_values(&[result]).get();
+        assert_eq!(verified("invalid_signature"), 1);
+        assert_eq!(verified("success"), 1);
+        assert_eq!(verified("nonce_missing"), 1);
+        assert!(metrics.render().contains("auth_verify_total{result=\"success\"} 1"));
+    }
+
+    #[actix_web::test]
+    async fn test_bypassed_path_still_authenticates_presented_credentials() {
+        use crate::http::routes::auth::get_nonce;
+        use ed25519_dalek::{Signer, SigningKey};
+
+        let config = AuthConfig {
+            bypass_paths: vec!["/api/transactions".to_string(), "/api/auth/nonce".to_string()],
+            ..AuthConfig::default()
+        };
+        let (app, Fixture { store, .. }) = test_app_with(config.clone(), |cfg| {
+            cfg.route("/api/auth/nonce", web::post().to(get_nonce)).route(
+                "/api/transactions",
+                web::get().to(|req: HttpRequest| async move {
+                    let caller = if is_authenticated(&req) { "authenticated" } else { "anonymous" };
+                    HttpResponse::Ok().body(caller)
+                }),
+            );
+        })
+        .await;
+
+        let req = test::TestRequest::get().uri("/api/transactions").to_request();
+        assert_eq!(test::call_and_read_body(&app, req).await, "anonymous");
+
+        // A client that sends its address on every request can still get a nonce
+        let key = SigningKey::from_bytes(&[9u8; 32]);
+        let address = bs58::encode(key.verifying_key().to_bytes()).into_string();
+        let req = test::TestRequest::post()
+            .uri("/api/auth/nonce")
+            .insert_header(("X-Wallet-Address", address.as_str()))
+            .set_json(serde_json::json!({ "address": address }))
+            .to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        let nonce = body["nonce"].as_str().unwrap().to_string();
+
+        let signed_request = |signer: &SigningKey| {
+            let signing_string = wallet_verifier(&config).signing_string("GET", "/api/transactions", &nonce, None, None);
+            let signature = bs58::encode(signer.sign(signing_string.as_bytes()).to_bytes()).into_string();
+            test::TestRequest::get()
+                .uri("/api/transactions")
+                .insert_header(("X-Wallet-Address", address.as_str()))
+                .insert_header(("X-Wallet-Signature", signature))
+                .insert_header(("X-Nonce", nonce.as_str()))
+                .to_request()
+        };
+
+        // Credentials that don't verify, or an unknown API key, leave the caller
+        // anonymous and the nonce outstanding
+        let req = signed_request(&SigningKey::from_bytes(&[10u8; 32]));
+        assert_eq!(test::call_and_read_body(&app, req).await, "anonymous");
+        assert_eq!(store.check(&address, &nonce).await.unwrap(), NonceCheck::Valid);
+        let req = test::TestRequest::get()
+            .uri("/api/transactions")
+            .insert_header((API_KEY_HEADER, "guessed-key"))
+            .to_request();
+        assert_eq!(test::call_and_read_body(&app, req).await, "anonymous");
+
+        // Authenticating uses the nonce up, as on a protected path, so a replay is anonymous
+        let req = signed_request(&key);
+        assert_eq!(test::call_and_read_body(&app, req).await, "authenticated");
+        assert_eq!(store.check(&address, &nonce).await.unwrap(), NonceCheck::Missing);
+        let req = signed_request(&key);
+        assert_eq!(test::call_and_read_body(&app, req).await, "anonymous");
+    }
+
+    #[actix_web::test]
+    async fn test_unreadable_body_is_refused_only_where_auth_is_required() {
+        let config = AuthConfig {
+            bypass_paths: vec!["/api/open".to_string()],
+            ..AuthConfig::default()
+        };
+        let (app, Fixture { store, audit, .. }) = test_app_with(config, |cfg| {
+            cfg.app_data(web::PayloadConfig::new(8))
+                .route("/api/open", web::post().to(HttpResponse::Ok))
+                .route("/api/transactions", web::post().to(HttpResponse::Ok));
+        })
+        .await;
+
+        let address = "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM";
+        store.issue(address, "nonce-1").await.unwrap();
+        let request = |path: &str| {
+            test::TestRequest::post()
+                .uri(path)
+                .insert_header(("X-Wallet-Address", address))
+                .insert_header(("X-Wallet-Signature", "sig"))
+                .insert_header(("X-Nonce", "nonce-1"))
+                .set_payload("a body longer than the payload limit")
+                .to_request()
+        };
+
+        let resp = test::call_service(&app, request("/api/open")).await;
+        assert_eq!(resp.status(), 200);
+        let resp = test::call_service(&app, request("/api/transactions")).await;
+        assert_eq!(resp.status(), 413);
+
+        let attempts = audit.recent(address, 10).await.unwrap();
+        assert_eq!(attempts.len(), 2);
+        assert!(attempts.iter().all(|a| a.reason.as_deref() == Some("body_unreadable")));
+    }
+
+    #[actix_web::test]
+    async fn test_signature_for_other_domain_is_rejected() {
+        use ed25519_dalek::{Signer, SigningKey};
+
+        let config = AuthConfig {
+            signing_domain: "api.pumpnex.example".to_string(),
+            ..AuthConfig::default()
+        };
+        let (app, Fixture { store, .. }) = test_app(config.clone()).await;
+
+        let key = SigningKey::from_bytes(&[11u8; 32]);
+        let address = bs58::encode(key.verifying_key().to_bytes()).into_string();
+        store.issue(&address, "nonce-1").await.unwrap();
+        let signed_request = |domain: &str| {
+            let signing_config = AuthConfig {
+                signing_domain: domain.to_string(),
+                ..config.clone()
+            };
+            let message = wallet_verifier(&signing_config).signing_string("GET", "/api/transactions", "nonce-1", None, None);
+            let signature = bs58::encode(key.sign(message.as_bytes()).to_bytes()).into_string();
+            test::TestRequest::get()
+                .uri("/api/transactions")
+                .insert_header(("X-Wallet-Address", address.as_str()))
+                .insert_header(("X-Wallet-Signature", signature))
+                .insert_header(("X-Nonce", "nonce-1"))
+                .to_request()
+        };
+
+        // Signed for another app, or without the domain line at all
+        for domain in ["other.example", ""] {
+            let resp = test::call_service(&app, signed_request(domain)).await;
+            assert_eq!(resp.status(), 401);
+        }
+
+        let resp = test::call_service(&app, signed_request("api.pumpnex.example")).await;
+        assert_eq!(resp.status(), 200);
+    }
+
+    #[actix_web::test]
+    async fn test_request_timestamp_must_be_within_skew() {
+        use ed25519_dalek::{Signer, SigningKey};
+
+        let config = AuthConfig {
+            require_timestamp: true,
+            timestamp_skew_secs: 300,
+            ..AuthConfig::default()
+        };
+        let (app, Fixture { store, audit, .. }) = test_app(config.clone()).await;
+
+        let key = SigningKey::from_bytes(&[13u8; 32]);
+        let address = bs58::encode(key.verifying_key().to_bytes()).into_string();
+        store.issue(&address, "nonce-1").await.unwrap();
+        let signed_request = |timestamp: i64| {
+            let timestamp = timestamp.to_string();
+            let message = wallet_verifier(&config).signing_string("GET", "/api/transactions", "nonce-1", Some(&timestamp), None);
+            let signature = bs58::encode(key.sign(message.as_bytes()).to_bytes()).into_string();
+            test::TestRequest::get()
+                .uri("/api/transactions")
+                .insert_header(("X-Wallet-Address", address.as_str()))
+                .insert_header(("X-Wallet-Signature", signature))
+                .insert_header(("X-Nonce", "nonce-1"))
+                .insert_header(("X-Timestamp", timestamp))
+                .to_request()
+        };
+
+        // A correctly signed but stale request is refused and leaves the nonce usable
+        let now = chrono::Utc::now().timestamp();
+        let resp = test::call_service(&app, signed_request(now - 600)).await;
+        assert_eq!(resp.status(), 401);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["reason"], "timestamp_skew");
+        assert_eq!(store.check(&address, "nonce-1").await.unwrap(), NonceCheck::Valid);
+
+        let resp = test::call_service(&app, signed_request(now - 30)).await;
+        assert_eq!(resp.status(), 200);
+
+        let attempts = audit.recent(&address, 10).await.unwrap();
+        assert_eq!(attempts[1].reason.as_deref(), Some("timestamp_skew"));
+        assert_eq!(attempts[0].result, "success");
+    }
+
+    #[actix_web::test]
+    async fn test_failure_statuses_and_challenge() {
+        let config = AuthConfig {
+            require_timestamp: true,
+            ..AuthConfig::default()
+        };
+        let (app, Fixture { store, .. }) = test_app(config.clone()).await;
+
+        let address = "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM";
+        store.issue(address, "nonce-1").await.unwrap();
+        store.issue("not-a-pubkey", "nonce-1").await.unwrap();
+        let now = chrono::Utc::now().timestamp().to_string();
+        let request = |address: &str, signature: &str, timestamp: &str| {
+            test::TestRequest::get()
+                .uri("/api/transactions")
+                .insert_header(("X-Wallet-Address", address))
+                .insert_header(("X-Wallet-Signature", signature))
+                .insert_header(("X-Nonce", "nonce-1"))
+                .insert_header(("X-Timestamp", timestamp))
+                .to_request()
+        };
+        let challenge = |resp: &ServiceResponse<_>| {
+            resp.headers()
+                .get(header::WWW_AUTHENTICATE)
+                .map(|value| value.to_str().unwrap().to_string())
+        };
+
+        // Missing credentials: 401 with a challenge naming every required header
+        let req = test::TestRequest::get()
+            .uri("/api/transactions")
+            .insert_header(("X-Wallet-Address", address))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 401);
+        assert_eq!(
+            challenge(&resp).as_deref(),
+            Some(r#"Wallet headers="X-Wallet-Address X-Wallet-Signature X-Nonce X-Timestamp", nonce_uri="/api/auth/nonce""#)
+        );
+
+        // Malformed inputs: 400 without a challenge
+        for (address, signature, timestamp, reason) in [
+            (address, "sig", "yesterday", "invalid_timestamp"),
+            ("not-a-pubkey", "sig", now.as_str(), "invalid_pubkey"),
+        ] {
+            let resp = test::call_service(&app, request(address, signature, timestamp)).await;
+            assert_eq!(resp.status(), 400);
+            assert_eq!(challenge(&resp), None);
+            let body: serde_json::Value = test::read_body_json(resp).await;
+            assert!(body["reason"].as_str().unwrap().starts_with(reason));
+        }
+
+        // Well-formed but invalid credentials: 401 with a challenge
+        let wrong_signature = bs58::encode([1u8; 64]).into_string();
+        let resp = test::call_service(&app, request(address, &wrong_signature, &now)).await;
+        assert_eq!(resp.status(), 401);
+        assert!(challenge(&resp).is_some());
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["reason"], "invalid_signature");
+
+        // Authenticated but not permitted: 403 without a challenge
+        let resp = auth_failure(&config, AuthFailure::Forbidden, "not_permitted");
+        assert_eq!(resp.status(), 403);
+        assert!(resp.headers().get(header::WWW_AUTHENTICATE).is_none());
+    }
+}
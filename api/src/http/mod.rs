@@ -1,10 +1,16 @@
 /// HTTP server module
 
+pub mod client_ip;
 pub mod middleware;
 pub mod routes;
 
 use actix_cors::Cors;
-use actix_web::{http, web, App, HttpServer};
+use actix_web::{
+    dev::{Server, ServerHandle},
+    http,
+    http::KeepAlive,
+    web, App, HttpServer,
+};
 use std::io;
 use rustls::{ServerConfig, pki_types::{CertificateDer, PrivateKeyDer}};
 use rustls_pemfile::{certs, pkcs8_private_keys};
@@ -13,11 +19,12 @@ use std::io::BufReader;
 
 use crate::app_state::AppState;
 use crate::cache;
-use crate::config::Config;
+use crate::config::{Config, SecurityConfig};
 use crate::metrics::AppMetrics;
 use crate::ws::tx::tx_websocket;
-use middleware::{logger::Logger, otel::OtelMiddleware, ratelimit::RateLimit, request_id::RequestId, security_headers::SecurityHeadersMiddleware, wallet_auth::WalletAuth, waf::WafMiddleware};
+use middleware::{logger::Logger, otel::OtelMiddleware, protection::ProtectionPolicy, ratelimit::RateLimit, request_id::RequestId, security_headers::SecurityHeadersMiddleware, wallet_auth::WalletAuth, waf::WafMiddleware};
 use std::sync::Arc;
+use std::time::Duration;
 
 /// Load TLS certificates from files
 fn load_tls_config(cert_path: &str, key_path: &str) -> io::Result<ServerConfig> {
@@ -53,7 +60,102 @@ fn load_tls_config(cert_path: &str, key_path: &str) -> io::Result<ServerConfig>
     Ok(config)
 }
 
-pub async fn start_server(config: Config, app_state: AppState, metrics: AppMetrics) -> io::Result<()> {
+/// Build the CORS middleware from security settings
+fn build_cors(security: &SecurityConfig) -> Cors {
+    let mut cors = Cors::default();
+    for origin in &security.cors_allowed_origins {
+        if origin == "*" {
+            cors = cors.allow_any_origin();
+            break;
+        } else {
+            cors = cors.allowed_origin(origin);
+        }
+    }
+    
+    for method_str in &security.cors_allowed_methods {
+        let method = match method_str.as_str() {
+            "GET" => http::Method::GET,
+            "POST" => http::Method::POST,
+            "PUT" => http::Method::PUT,
+            "PATCH" => http::Method::PATCH,
+            "DELETE" => http::Method::DELETE,
+            "OPTIONS" => http::Method::OPTIONS,
+            _ => continue,
+        };
+        cors = cors.allowed_methods(vec![method]);
+    }
+    
+    if security.cors_allowed_headers.contains(&"*".to_string()) {
+        cors = cors.allow_any_header();
+    } else {
+        cors = cors.allowed_headers(
+            security
+                .cors_allowed_headers
+                .iter()
+                .filter_map(|h| h.parse::<http::header::HeaderName>().ok())
+                .collect::<Vec<_>>(),
+        );
+    }
+
+    // Let browser JS read caching, rate-limit and tracing headers
+    let expose_headers: Vec<http::header::HeaderName> = security
+        .cors_expose_headers
+        .iter()
+        .filter_map(|h| h.parse().ok())
+        .collect();
+    if !expose_headers.is_empty() {
+        cors = cors.expose_headers(expose_headers);
+    }
+
+    cors
+}
+
+/// Connection timeouts from `server.*`, in the form `HttpServer` takes them
+#[derive(Debug, PartialEq)]
+struct ConnectionTimeouts {
+    keep_alive: KeepAlive,
+    client_request: Duration,
+    client_disconnect: Duration,
+}
+
+impl ConnectionTimeouts {
+    // A zero duration already means "no timeout" to actix-web
+    fn from_config(config: &crate::config::ServerConfig) -> Self {
+        Self {
+            keep_alive: match config.keep_alive_secs {
+                0 => KeepAlive::Disabled,
+                secs => KeepAlive::Timeout(Duration::from_secs(secs)),
+            },
+            client_request: Duration::from_millis(config.client_request_timeout_ms),
+            client_disconnect: Duration::from_millis(config.client_shutdown_ms),
+        }
+    }
+}
+
+/// The API listener, plus the probe listener with `deploy.metrics_listener`
+pub struct HttpServers {
+    api: Server,
+    probes: Option<Server>,
+}
+
+impl HttpServers {
+    /// Handles for pausing and stopping every listener
+    pub fn handles(&self) -> Vec<ServerHandle> {
+        std::iter::once(&self.api).chain(&self.probes).map(Server::handle).collect()
+    }
+
+    /// Serve until every listener has stopped
+    pub async fn run(self) -> io::Result<()> {
+        match self.probes {
+            Some(probes) => futures_util::future::try_join(self.api, probes).await.map(|_| ()),
+            None => self.api.await,
+        }
+    }
+}
+
+/// Bind the listeners; they stop only through their handles, as part of the
+/// ordered shutdown in `main`
+pub fn start_server(config: Config, app_state: AppState, metrics: Arc<AppMetrics>) -> io::Result<HttpServers> {
     let bind_addr = format!("{}:{}", config.server.host, config.server.port);
     
     tracing::info!(
@@ -68,87 +170,235 @@ pub async fn start_server(config: Config, app_state: AppState, metrics: AppMetri
 
     let app_state = web::Data::new(app_state);
     let auth_config = web::Data::new(config.auth.clone());
+    let admin_config = web::Data::new(config.admin.clone());
+    let server_config = web::Data::new(config.server.clone());
+    let security_data = web::Data::new(config.security.clone());
     let cache_config = web::Data::new(config.cache.clone());
-    let cache = web::Data::new(cache::create_cache(
-        &config.cache.backend,
-        config.cache.max_entries,
-    ));
+    let pagination_config = web::Data::new(config.pagination.clone());
+    let cache = web::Data::new(cache::create_cache_from_config(&config.cache, metrics.clone()));
+    routes::transactions::spawn_list_cache_warmer(app_state.get_ref(), &config, cache.get_ref().clone());
     let ws_config = web::Data::new(config.ws.clone());
     let kafka_config = web::Data::new(config.kafka.clone());
-    let metrics_data = web::Data::new(metrics);
+    let metrics_data = web::Data::from(metrics.clone());
     let request_id_header = config.telemetry.request_id_header.clone();
-    let rate_limiter = RateLimit::new(config.rate_limit.clone());
-    let wallet_auth = WalletAuth::new(config.auth.clone());
-    let waf_middleware = WafMiddleware::new(config.waf.clone(), Some(Arc::new(app_state.get_ref().clone()))).unwrap_or_else(|e| {
-        tracing::error!("Failed to initialize WAF middleware: {}", e);
-        std::process::exit(1);
-    });
-    let otel_middleware = OtelMiddleware::new();
+    let client_ip = client_ip::ClientIpResolver::from_config(&config.server);
+    // For handlers that limit or log by client IP themselves
+    let client_ip_data = web::Data::new(client_ip.clone());
+    let rate_limit_config = web::Data::new(config.rate_limit.clone());
+    let rate_limiter = RateLimit::new(config.rate_limit.clone()).with_client_ip(client_ip.clone());
+    let wallet_auth = WalletAuth::new(config.auth.clone())
+        .with_metrics(metrics.clone())
+        .with_client_ip(client_ip.clone(), config.rate_limit.respect_x_forwarded_for);
+    let waf_middleware = WafMiddleware::new(config.waf.clone(), Some(Arc::new(app_state.get_ref().clone())))
+        .map(|waf| waf.with_metrics(metrics.clone()).with_client_ip(client_ip))
+        .unwrap_or_else(|e| {
+            tracing::error!("Failed to initialize WAF middleware: {}", e);
+            std::process::exit(1);
+        });
+    let otel_middleware = OtelMiddleware::new().with_metrics(metrics.clone());
     let security_headers = SecurityHeadersMiddleware::new(config.security.clone());
-    let cors_origins = config.security.cors_allowed_origins.clone();
-    let cors_methods = config.security.cors_allowed_methods.clone();
-    let cors_headers = config.security.cors_allowed_headers.clone();
+    let security_config = config.security.clone();
+    let protection_policy = ProtectionPolicy::from_config(&config.security)
+        .map(web::Data::new)
+        .unwrap_or_else(|e| {
+            tracing::error!("Invalid security.required_middlewares: {}", e);
+            std::process::exit(1);
+        });
     let body_limit = config.server.request_body_limit_bytes;
+    let runtime_stats = app_state.runtime_stats.clone();
+    let timeouts = ConnectionTimeouts::from_config(&config.server);
+    let metrics_listener = config.deploy.metrics_listener;
+    let probes = metrics_listener
+        .then(|| probe_server(&config, app_state.clone(), metrics_data.clone()))
+        .transpose()?;
 
     // TODO: Implement TLS support later
     tracing::info!("Starting HTTP server (no TLS)");
-    HttpServer::new(move || {
+    let server = HttpServer::new(move || {
         // Configure CORS inside the closure
-        let mut cors = Cors::default();
-        for origin in &cors_origins {
-            if origin == "*" {
-                cors = cors.allow_any_origin();
-                break;
-            } else {
-                cors = cors.allowed_origin(origin);
-            }
-        }
-        
-        for method_str in &cors_methods {
-            let method = match method_str.as_str() {
-                "GET" => http::Method::GET,
-                "POST" => http::Method::POST,
-                "PUT" => http::Method::PUT,
-                "PATCH" => http::Method::PATCH,
-                "DELETE" => http::Method::DELETE,
-                "OPTIONS" => http::Method::OPTIONS,
-                _ => continue,
-            };
-            cors = cors.allowed_methods(vec![method]);
-        }
-        
-        if cors_headers.contains(&"*".to_string()) {
-            cors = cors.allow_any_header();
-        } else {
-            cors = cors.allowed_headers(
-                cors_headers
-                    .iter()
-                    .filter_map(|h| h.parse::<http::header::HeaderName>().ok())
-                    .collect::<Vec<_>>(),
-            );
-        }
+        let cors = build_cors(&security_config);
 
         App::new()
             .app_data(app_state.clone())
             .app_data(auth_config.clone())
+            .app_data(admin_config.clone())
+            .app_data(server_config.clone())
+            .app_data(security_data.clone())
             .app_data(cache_config.clone())
+            .app_data(pagination_config.clone())
             .app_data(cache.clone())
             .app_data(ws_config.clone())
             .app_data(kafka_config.clone())
             .app_data(metrics_data.clone())
+            .app_data(web::Data::new(rate_limiter.clone()))
+            .app_data(client_ip_data.clone())
+            .app_data(rate_limit_config.clone())
+            .app_data(protection_policy.clone())
             .app_data(web::PayloadConfig::new(body_limit))
             .wrap(cors)
             .wrap(otel_middleware.clone())
-            .wrap(Logger)
             .wrap(wallet_auth.clone())
             .wrap(rate_limiter.clone())
             .wrap(waf_middleware.clone())
             .wrap(security_headers.clone())
+            // Outside the limiter and WAF so requests they reject are logged too
+            .wrap(Logger)
             .wrap(RequestId::new(request_id_header.clone()))
-            .configure(|cfg| routes::configure(cfg))
+            .configure(|cfg| routes::configure(cfg, metrics_listener))
             .route(&config.ws.path, web::get().to(tx_websocket))
     })
+    // The guard lives in the connection's extensions and is dropped on close
+    .on_connect(move |_, extensions| {
+        extensions.insert(runtime_stats.track_connection());
+    })
+    .keep_alive(timeouts.keep_alive)
+    .client_request_timeout(timeouts.client_request)
+    .client_disconnect_timeout(timeouts.client_disconnect)
+    .disable_signals()
+    .shutdown_timeout(config.deploy.graceful_shutdown_secs)
+    .bind(&bind_addr)?
+    .run();
+
+    Ok(HttpServers { api: server, probes })
+}
+
+/// Second listener on `deploy.metrics_port` with only the probe routes, so the
+/// API port can be firewalled separately from the scraper
+fn probe_server(
+    config: &Config,
+    app_state: web::Data<AppState>,
+    metrics: web::Data<AppMetrics>,
+) -> io::Result<Server> {
+    let bind_addr = format!("{}:{}", config.server.host, config.deploy.metrics_port);
+    tracing::info!(bind_addr = %bind_addr, "Starting metrics listener");
+
+    Ok(HttpServer::new(move || {
+        App::new()
+            .app_data(app_state.clone())
+            .app_data(metrics.clone())
+            .configure(routes::configure_probes)
+    })
+    .workers(1)
+    .disable_signals()
+    .shutdown_timeout(config.deploy.graceful_shutdown_secs)
     .bind(&bind_addr)?
-    .run()
-    .await
+    .run())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ServiceConfig;
+    use actix_web::{test, HttpResponse};
+
+    #[actix_web::test]
+    async fn test_cors_exposes_etag() {
+        let app = test::init_service(
+            App::new()
+                .wrap(build_cors(&SecurityConfig::default()))
+                .route(
+                    "/api/transactions",
+                    web::get().to(|| async { HttpResponse::Ok().insert_header(("ETag", "\"abc\"")).finish() }),
+                ),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/api/transactions")
+            .insert_header(("Origin", "https://app.example.com"))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 200);
+
+        let exposed = resp
+            .headers()
+            .get("Access-Control-Expose-Headers")
+            .and_then(|h| h.to_str().ok())
+            .unwrap_or_default()
+            .to_lowercase();
+        assert!(exposed.split(',').any(|h| h.trim() == "etag"), "exposed: {}", exposed);
+    }
+
+    #[actix_web::test]
+    async fn test_metrics_listener_serves_probes_only() {
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState::new(ServiceConfig::default(), None, None)))
+                .app_data(web::Data::new(AppMetrics::new().unwrap()))
+                .configure(routes::configure_probes),
+        )
+        .await;
+
+        let req = test::TestRequest::get().uri("/metrics").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 200);
+
+        let req = test::TestRequest::get().uri("/livez").to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 200);
+
+        let req = test::TestRequest::get().uri("/api/transactions").to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 404);
+
+        // ...and the API port stops serving /metrics
+        for (metrics_listener, status) in [(true, 404), (false, 200)] {
+            let api = test::init_service(
+                App::new()
+                    .app_data(web::Data::new(AppState::new(ServiceConfig::default(), None, None)))
+                    .app_data(web::Data::new(AppMetrics::new().unwrap()))
+                    .configure(|cfg| routes::configure(cfg, metrics_listener)),
+            )
+            .await;
+            let req = test::TestRequest::get().uri("/metrics").to_request();
+            assert_eq!(test::call_service(&api, req).await.status(), status, "metrics_listener = {}", metrics_listener);
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_slow_request_head_is_cut_off_at_the_configured_timeout() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let config = crate::config::ServerConfig {
+            keep_alive_secs: 0,
+            client_request_timeout_ms: 200,
+            client_shutdown_ms: 300,
+            ..crate::config::ServerConfig::default()
+        };
+        let timeouts = ConnectionTimeouts::from_config(&config);
+        assert_eq!(
+            timeouts,
+            ConnectionTimeouts {
+                keep_alive: KeepAlive::Disabled,
+                client_request: Duration::from_millis(200),
+                client_disconnect: Duration::from_millis(300),
+            }
+        );
+        let defaults = ConnectionTimeouts::from_config(&crate::config::ServerConfig::default());
+        assert_eq!(defaults.keep_alive, KeepAlive::Timeout(Duration::from_secs(5)));
+
+        let server = HttpServer::new(|| App::new().route("/", web::get().to(|| async { HttpResponse::Ok().finish() })))
+            .workers(1)
+            .keep_alive(timeouts.keep_alive)
+            .client_request_timeout(timeouts.client_request)
+            .client_disconnect_timeout(timeouts.client_disconnect)
+            .bind("127.0.0.1:0")
+            .unwrap();
+        let addr = server.addrs()[0];
+        let server = server.run();
+        let handle = server.handle();
+        actix_web::rt::spawn(server);
+
+        // Half a request head, then nothing
+        let mut client = tokio::net::TcpStream::connect(addr).await.unwrap();
+        client.write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n").await.unwrap();
+        let started = std::time::Instant::now();
+        let mut response = Vec::new();
+        tokio::time::timeout(Duration::from_secs(3), client.read_to_end(&mut response))
+            .await
+            .expect("slow client was not disconnected")
+            .unwrap();
+
+        assert!(started.elapsed() >= Duration::from_millis(150));
+        assert!(response.starts_with(b"HTTP/1.1 408"), "{}", String::from_utf8_lossy(&response));
+        handle.stop(false).await;
+    }
 }
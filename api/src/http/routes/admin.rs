@@ -0,0 +1,134 @@
+/// Shared admin authorization
+///
+/// Every admin endpoint goes through `require_admin`, which checks the
+/// configured admin header against either a stored argon2/bcrypt hash or a
+/// plaintext token, without leaking timing information. Hashes are checked
+/// on the blocking pool, since they are slow by design.
+
+use crate::config::AdminConfig;
+use actix_web::{web, HttpRequest, HttpResponse};
+use argon2::password_hash::{PasswordHash, PasswordVerifier};
+use argon2::Argon2;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
+
+fn forbidden(message: &str) -> HttpResponse {
+    HttpResponse::Forbidden().json(json!({
+        "error": "forbidden",
+        "message": message
+    }))
+}
+
+/// Require a valid admin token on the request.
+///
+/// Denies access when neither `admin_token_hash` nor `admin_token` is configured.
+pub async fn require_admin(req: &HttpRequest, admin_config: &AdminConfig) -> Result<(), HttpResponse> {
+    if admin_config.admin_token_hash.is_empty() && admin_config.admin_token.is_empty() {
+        return Err(forbidden("Admin token not configured"));
+    }
+
+    let Some(token) = req.headers().get(&admin_config.admin_header) else {
+        return Err(forbidden("Admin token required"));
+    };
+    let Ok(token) = token.to_str() else {
+        return Err(forbidden("Invalid admin token format"));
+    };
+
+    let presented = token.trim().to_string();
+    let admin_config = admin_config.clone();
+    let verified = web::block(move || verify_admin_token(&presented, &admin_config))
+        .await
+        .unwrap_or_else(|e| {
+            tracing::error!(error = %e, "Admin token verification did not complete");
+            false
+        });
+    if verified {
+        Ok(())
+    } else {
+        Err(forbidden("Invalid admin token"))
+    }
+}
+
+/// Verify a presented token, preferring the stored hash over the plaintext token
+fn verify_admin_token(presented: &str, admin_config: &AdminConfig) -> bool {
+    let stored_hash = admin_config.admin_token_hash.trim();
+    if stored_hash.is_empty() {
+        return tokens_match(presented, &admin_config.admin_token);
+    }
+
+    if stored_hash.starts_with("$2") {
+        return bcrypt::verify(presented, stored_hash).unwrap_or_else(|e| {
+            tracing::error!(error = %e, "Invalid bcrypt admin_token_hash");
+            false
+        });
+    }
+
+    match PasswordHash::new(stored_hash) {
+        Ok(hash) => Argon2::default().verify_password(presented.as_bytes(), &hash).is_ok(),
+        Err(e) => {
+            tracing::error!(error = %e, "Invalid argon2 admin_token_hash");
+            false
+        }
+    }
+}
+
+/// Constant-time comparison; digests first so the token length isn't leaked either
+fn tokens_match(presented: &str, expected: &str) -> bool {
+    let presented = Sha256::digest(presented.as_bytes());
+    let expected = Sha256::digest(expected.as_bytes());
+    presented.ct_eq(&expected).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+    use argon2::password_hash::{PasswordHasher, SaltString};
+
+    fn hashed_config(token: &str) -> AdminConfig {
+        let salt = SaltString::encode_b64(b"admin-token-salt").unwrap();
+        AdminConfig {
+            admin_token_hash: Argon2::default()
+                .hash_password(token.as_bytes(), &salt)
+                .unwrap()
+                .to_string(),
+            ..AdminConfig::default()
+        }
+    }
+
+    fn request_with_token(token: &str) -> HttpRequest {
+        TestRequest::default()
+            .insert_header(("X-Admin-Token", token))
+            .to_http_request()
+    }
+
+    #[actix_web::test]
+    async fn test_hashed_token_grants_access() {
+        let config = hashed_config("s3cret-admin");
+        assert!(require_admin(&request_with_token("s3cret-admin"), &config).await.is_ok());
+    }
+
+    #[actix_web::test]
+    async fn test_incorrect_token_is_forbidden() {
+        let config = hashed_config("s3cret-admin");
+        let response = require_admin(&request_with_token("wrong"), &config).await.unwrap_err();
+        assert_eq!(response.status(), actix_web::http::StatusCode::FORBIDDEN);
+
+        let missing = require_admin(&TestRequest::default().to_http_request(), &config).await.unwrap_err();
+        assert_eq!(missing.status(), actix_web::http::StatusCode::FORBIDDEN);
+    }
+
+    #[actix_web::test]
+    async fn test_plaintext_token_and_unconfigured() {
+        let config = AdminConfig {
+            admin_token: "plain".to_string(),
+            ..AdminConfig::default()
+        };
+        assert!(require_admin(&request_with_token("plain"), &config).await.is_ok());
+        assert!(require_admin(&request_with_token("plain-but-longer"), &config).await.is_err());
+
+        // No token configured at all denies rather than allowing everyone
+        assert!(require_admin(&request_with_token("plain"), &AdminConfig::default()).await.is_err());
+    }
+}
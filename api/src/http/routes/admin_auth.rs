@@ -0,0 +1,111 @@
+/// Admin authentication audit routes
+///
+/// Exposes recent wallet authentication attempts for security forensics.
+
+use crate::{
+    app_state::AppState,
+    config::{AdminConfig, PaginationConfig, ServerConfig},
+    errors::ApiError,
+    http::routes::admin::require_admin,
+};
+use actix_web::{web, HttpRequest, HttpResponse, Responder, ResponseError};
+use serde::Deserialize;
+use serde_json::json;
+
+#[derive(Debug, Deserialize)]
+pub struct AttemptsQuery {
+    pub address: String,
+    #[serde(default = "default_attempts_limit")]
+    pub limit: usize,
+}
+
+fn default_attempts_limit() -> usize {
+    50
+}
+
+/// Get recent authentication attempts for an address, at most `pagination.max_limit`
+pub async fn auth_attempts(
+    req: HttpRequest,
+    query: web::Query<AttemptsQuery>,
+    app_state: web::Data<AppState>,
+    admin_config: web::Data<AdminConfig>,
+    pagination_config: web::Data<PaginationConfig>,
+    server_config: Option<web::Data<ServerConfig>>,
+) -> impl Responder {
+    if let Err(response) = require_admin(&req, &admin_config).await {
+        return response;
+    }
+
+    let Some(audit) = &app_state.auth_audit else {
+        return ApiError::feature_disabled("auth_audit", server_config.as_ref().map(|c| c.get_ref())).error_response();
+    };
+
+    let limit = query.limit.clamp(1, (pagination_config.max_limit as usize).max(1));
+    match audit.recent(&query.address, limit).await {
+        Ok(attempts) => HttpResponse::Ok().json(json!({
+            "address": query.address,
+            "attempts": attempts
+        })),
+        Err(e) => {
+            tracing::error!(error = %e, "Failed to query authentication audit log");
+            HttpResponse::InternalServerError().json(json!({
+                "error": "internal",
+                "message": "Failed to query audit log"
+            }))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth_audit::AuthAudit;
+    use crate::config::ServiceConfig;
+    use crate::repository::AuthAttempt;
+    use actix_web::{http::StatusCode, test, App};
+    use std::sync::Arc;
+
+    #[actix_web::test]
+    async fn test_attempts_need_admin_and_are_capped_at_max_limit() {
+        let audit = Arc::new(AuthAudit::memory(100));
+        for _ in 0..5 {
+            audit.record(AuthAttempt {
+                address: "wallet-1".to_string(),
+                method: "GET".to_string(),
+                path: "/api/transactions".to_string(),
+                result: "failure".to_string(),
+                reason: Some("invalid_signature".to_string()),
+                ip: None,
+                created_at: chrono::Utc::now(),
+            });
+        }
+        let mut state = AppState::new(ServiceConfig::default(), None, None);
+        state.auth_audit = Some(audit);
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(state))
+                .app_data(web::Data::new(AdminConfig {
+                    admin_token: "audit-token".to_string(),
+                    ..AdminConfig::default()
+                }))
+                .app_data(web::Data::new(PaginationConfig {
+                    max_limit: 3,
+                    ..PaginationConfig::default()
+                }))
+                .route("/api/admin/auth/attempts", web::get().to(auth_attempts)),
+        )
+        .await;
+
+        let uri = "/api/admin/auth/attempts?address=wallet-1&limit=1000";
+        let resp = test::call_service(&app, test::TestRequest::get().uri(uri).to_request()).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+        let req = test::TestRequest::get()
+            .uri(uri)
+            .insert_header(("X-Admin-Token", "audit-token"))
+            .to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["attempts"].as_array().unwrap().len(), 3);
+    }
+}
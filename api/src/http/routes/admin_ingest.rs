@@ -0,0 +1,234 @@
+/// Admin ingestion routes
+///
+/// DLQ inspection for triage, and replay for recovering messages that failed
+/// during a transient outage.
+
+use crate::{
+    config::{AdminConfig, KafkaConfig, ServerConfig},
+    errors::ApiError,
+    http::routes::admin::require_admin,
+    ingest::{dlq_inspect, replay},
+};
+use actix_web::{web, HttpRequest, HttpResponse, Responder, ResponseError};
+use serde::Deserialize;
+use serde_json::json;
+
+/// Records replayed when `max` isn't given
+const DEFAULT_REPLAY_MAX: usize = 100;
+
+/// Records inspected when `max` isn't given
+const DEFAULT_INSPECT_MAX: usize = 20;
+
+#[derive(Debug, Deserialize)]
+pub struct ReplayQuery {
+    pub max: Option<usize>,
+}
+
+fn kafka_disabled(server_config: Option<web::Data<ServerConfig>>) -> HttpResponse {
+    ApiError::feature_disabled("kafka_ingest", server_config.as_ref().map(|c| c.get_ref())).error_response()
+}
+
+// 502 naming Kafka, so a broker outage reads differently from a DB-backed 503
+fn kafka_unavailable(details: String) -> HttpResponse {
+    ApiError::UpstreamUnavailable {
+        service: "kafka".to_string(),
+        details,
+    }
+    .error_response()
+}
+
+/// Show up to `max` DLQ messages without consuming them
+pub async fn inspect_dlq(
+    req: HttpRequest,
+    query: web::Query<ReplayQuery>,
+    kafka_config: web::Data<KafkaConfig>,
+    admin_config: web::Data<AdminConfig>,
+    server_config: Option<web::Data<ServerConfig>>,
+) -> impl Responder {
+    if let Err(response) = require_admin(&req, &admin_config).await {
+        return response;
+    }
+    if !kafka_config.enabled {
+        return kafka_disabled(server_config);
+    }
+
+    let max = query
+        .max
+        .unwrap_or(DEFAULT_INSPECT_MAX)
+        .clamp(1, kafka_config.dlq_replay_max_batch.max(1));
+
+    match dlq_inspect::inspect_dlq(&kafka_config, max).await {
+        Ok(inspection) => HttpResponse::Ok().json(json!({
+            "max": max,
+            "messages": inspection.messages,
+            "malformed": inspection.malformed,
+        })),
+        Err(e) => {
+            tracing::error!(error = %e, "DLQ inspection failed");
+            kafka_unavailable(e)
+        }
+    }
+}
+
+/// Re-publish up to `max` DLQ messages to the input topic
+pub async fn replay_dlq(
+    req: HttpRequest,
+    query: web::Query<ReplayQuery>,
+    kafka_config: web::Data<KafkaConfig>,
+    admin_config: web::Data<AdminConfig>,
+    server_config: Option<web::Data<ServerConfig>>,
+) -> impl Responder {
+    if let Err(response) = require_admin(&req, &admin_config).await {
+        return response;
+    }
+
+    if !kafka_config.enabled {
+        return kafka_disabled(server_config);
+    }
+
+    let max = query
+        .max
+        .unwrap_or(DEFAULT_REPLAY_MAX)
+        .clamp(1, kafka_config.dlq_replay_max_batch.max(1));
+
+    match replay::replay_dlq(&kafka_config, max).await {
+        Ok(report) => {
+            tracing::info!(replayed = report.replayed, malformed = report.malformed, "DLQ replay requested by admin");
+            HttpResponse::Ok().json(json!({
+                "max": max,
+                "replayed": report.replayed,
+                "malformed": report.malformed,
+                "exhausted": report.exhausted,
+            }))
+        }
+        Err(e) => {
+            tracing::error!(error = %e, "DLQ replay failed");
+            kafka_unavailable(e)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{http::StatusCode, test, App};
+
+    #[actix_web::test]
+    async fn test_replay_requires_admin_and_enabled_kafka() {
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(KafkaConfig {
+                    enabled: false,
+                    ..KafkaConfig::default()
+                }))
+                .app_data(web::Data::new(AdminConfig {
+                    admin_token: "ingest-token".to_string(),
+                    ..AdminConfig::default()
+                }))
+                .route("/api/admin/ingest/dlq", web::get().to(inspect_dlq))
+                .route("/api/admin/ingest/dlq/replay", web::post().to(replay_dlq)),
+        )
+        .await;
+
+        for method in [actix_web::http::Method::GET, actix_web::http::Method::POST] {
+            let uri = if method == actix_web::http::Method::GET {
+                "/api/admin/ingest/dlq?max=10"
+            } else {
+                "/api/admin/ingest/dlq/replay?max=10"
+            };
+            let req = test::TestRequest::default().method(method.clone()).uri(uri).to_request();
+            assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+
+            let req = test::TestRequest::default()
+                .method(method)
+                .uri(uri)
+                .insert_header(("X-Admin-Token", "ingest-token"))
+                .to_request();
+            let resp = test::call_service(&app, req).await;
+            assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+            let body: serde_json::Value = test::read_body_json(resp).await;
+            assert_eq!(body, json!({ "error": "feature_disabled", "feature": "kafka_ingest" }));
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_replay_with_kafka_down_is_a_bad_gateway() {
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(KafkaConfig {
+                    enabled: true,
+                    // Nothing listens here
+                    brokers: "127.0.0.1:1".to_string(),
+                    ..KafkaConfig::default()
+                }))
+                .app_data(web::Data::new(AdminConfig {
+                    admin_token: "ingest-token".to_string(),
+                    ..AdminConfig::default()
+                }))
+                .route("/api/admin/ingest/dlq/replay", web::post().to(replay_dlq)),
+        )
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/api/admin/ingest/dlq/replay?max=10")
+            .insert_header(("X-Admin-Token", "ingest-token"))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_GATEWAY);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["error"], "Upstream unavailable");
+        assert_eq!(body["service"], "kafka");
+        assert!(body["details"].as_str().unwrap().contains("unreachable"));
+    }
+
+    #[actix_web::test]
+    #[ignore = "needs Kafka at KAFKA_BROKERS"]
+    async fn test_inspect_returns_dlq_messages_without_consuming_them() {
+        use crate::ingest::{dlq::encode_dlq_message, kafka::client_config, DlqMessage};
+        use rdkafka::producer::{FutureProducer, FutureRecord};
+
+        let kafka_config = KafkaConfig {
+            enabled: true,
+            brokers: std::env::var("KAFKA_BROKERS").unwrap(),
+            dlq_topic: format!("tx.dlq.inspect-{}", uuid::Uuid::new_v4()),
+            ..KafkaConfig::default()
+        };
+        let producer: FutureProducer = client_config(&kafka_config).create().unwrap();
+        let message = DlqMessage {
+            original_message: json!(r#"{"signature":"sig-dlq","slot":1}"#),
+            error: "ValidationError".to_string(),
+            timestamp: chrono::Utc::now(),
+            retry_count: 0,
+        };
+        let encoded = encode_dlq_message(&message, false).unwrap();
+        producer
+            .send(
+                FutureRecord::<(), _>::to(&kafka_config.dlq_topic).payload(&encoded.payload),
+                std::time::Duration::from_secs(5),
+            )
+            .await
+            .unwrap();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(kafka_config))
+                .app_data(web::Data::new(AdminConfig {
+                    admin_token: "ingest-token".to_string(),
+                    ..AdminConfig::default()
+                }))
+                .route("/api/admin/ingest/dlq", web::get().to(inspect_dlq)),
+        )
+        .await;
+
+        // Inspecting twice sees the same message: nothing is committed
+        for _ in 0..2 {
+            let req = test::TestRequest::get()
+                .uri("/api/admin/ingest/dlq?max=5")
+                .insert_header(("X-Admin-Token", "ingest-token"))
+                .to_request();
+            let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+            assert_eq!(body["messages"][0]["error"], "ValidationError");
+            assert_eq!(body["messages"][0]["original"], r#"{"signature":"sig-dlq","slot":1}"#);
+        }
+    }
+}
@@ -0,0 +1,110 @@
+/// Admin runtime introspection routes
+///
+/// Read-only snapshot of Tokio runtime metrics, process memory, open
+/// connections and uptime for diagnosing latency spikes.
+
+use crate::{
+    app_state::AppState, config::AdminConfig, http::routes::admin::require_admin,
+    runtime_stats::process_rss_bytes,
+};
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use serde_json::json;
+
+/// Get a runtime snapshot
+pub async fn runtime(
+    req: HttpRequest,
+    app_state: web::Data<AppState>,
+    admin_config: web::Data<AdminConfig>,
+) -> impl Responder {
+    if let Err(response) = require_admin(&req, &admin_config).await {
+        return response;
+    }
+
+    HttpResponse::Ok().json(runtime_snapshot(&app_state))
+}
+
+/// Build the snapshot; every value is a cheap counter read
+fn runtime_snapshot(app_state: &AppState) -> serde_json::Value {
+    // Each actix worker has its own runtime, so these describe the serving worker
+    let metrics = tokio::runtime::Handle::current().metrics();
+
+    #[cfg(tokio_unstable)]
+    let blocking = json!({
+        "threads": metrics.num_blocking_threads(),
+        "idle_threads": metrics.num_idle_blocking_threads(),
+        "queue_depth": metrics.blocking_queue_depth(),
+    });
+    // Blocking pool metrics require building with --cfg tokio_unstable
+    #[cfg(not(tokio_unstable))]
+    let blocking = serde_json::Value::Null;
+
+    let postgres = app_state.postgres.as_ref().map(|pool| {
+        json!({
+            "size": pool.size(),
+            "idle": pool.num_idle(),
+        })
+    });
+
+    json!({
+        "uptime_secs": app_state.runtime_stats.uptime_secs(),
+        "started_at": app_state.runtime_stats.started_at().to_rfc3339(),
+        "tokio": {
+            "workers": metrics.num_workers(),
+            "alive_tasks": metrics.num_alive_tasks(),
+            "global_queue_depth": metrics.global_queue_depth(),
+            "blocking_pool": blocking,
+        },
+        "memory": {
+            "rss_bytes": process_rss_bytes(),
+        },
+        "connections": {
+            "http": app_state.runtime_stats.http_connections(),
+            "postgres": postgres,
+            "redis_connected": app_state.redis.is_some(),
+            "redis_inflight": app_state.nonce_store.as_ref().map(|store| store.limiter().inflight()),
+            "ws_subscriptions": app_state.ws_subscriptions.count(),
+            "ws_fanout_targets": app_state.ws_fanout.subscription_count(),
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ServiceConfig;
+    use actix_web::{http::StatusCode, test, App};
+
+    fn admin_config() -> AdminConfig {
+        AdminConfig {
+            admin_token: "runtime-token".to_string(),
+            ..AdminConfig::default()
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_runtime_requires_admin_token_and_reports_keys() {
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState::new(ServiceConfig::default(), None, None)))
+                .app_data(web::Data::new(admin_config()))
+                .route("/api/admin/runtime", web::get().to(runtime)),
+        )
+        .await;
+
+        let req = test::TestRequest::get().uri("/api/admin/runtime").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+        let req = test::TestRequest::get()
+            .uri("/api/admin/runtime")
+            .insert_header(("X-Admin-Token", "runtime-token"))
+            .to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        for key in ["uptime_secs", "started_at", "tokio", "memory", "connections"] {
+            assert!(body.get(key).is_some(), "missing key {}", key);
+        }
+        assert!(body["tokio"]["alive_tasks"].is_u64());
+        assert!(body["tokio"].get("blocking_pool").is_some());
+        assert_eq!(body["connections"]["http"], 0);
+    }
+}
@@ -0,0 +1,261 @@
+/// Admin user permission routes
+///
+/// Grant and revoke `user_permissions` rows without going through SQL.
+
+use crate::{
+    app_state::AppState,
+    config::AdminConfig,
+    http::routes::admin::require_admin,
+    repository::UserRepository,
+};
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use serde::Deserialize;
+use serde_json::json;
+
+/// Route prefixes a permission may be granted on
+const KNOWN_ENDPOINTS: &[&str] = &["/api/transactions", "/api/auth", "/api/admin", "/ws"];
+
+const PERMISSIONS: &[&str] = &["read", "write", "admin"];
+
+#[derive(Debug, Deserialize)]
+pub struct PermissionBody {
+    pub endpoint: String,
+    pub permission: String,
+}
+
+fn bad_request(message: String) -> HttpResponse {
+    HttpResponse::BadRequest().json(json!({
+        "error": "validation_error",
+        "message": message
+    }))
+}
+
+// The endpoint must be a known route prefix or a path beneath one
+fn is_known_endpoint(endpoint: &str) -> bool {
+    KNOWN_ENDPOINTS.iter().any(|prefix| {
+        endpoint
+            .strip_prefix(prefix)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+    })
+}
+
+fn validate(pubkey: &str, body: &PermissionBody) -> Result<(), String> {
+    // A wallet may sign with any scheme, so any scheme's address can hold permissions
+    if !blockchain_auth::SCHEMES.iter().any(|scheme| scheme.decode_pubkey(pubkey).is_ok()) {
+        return Err("pubkey must be a base58 encoded wallet address".to_string());
+    }
+    if !is_known_endpoint(&body.endpoint) {
+        return Err(format!(
+            "endpoint must be one of {} or a path beneath one",
+            KNOWN_ENDPOINTS.join(", ")
+        ));
+    }
+    if !PERMISSIONS.contains(&body.permission.as_str()) {
+        return Err(format!("permission must be one of: {}", PERMISSIONS.join(", ")));
+    }
+    Ok(())
+}
+
+// Admin check, input validation and the repository, in that order
+async fn users_repository(
+    req: &HttpRequest,
+    pubkey: &str,
+    body: &PermissionBody,
+    app_state: &AppState,
+    admin_config: &AdminConfig,
+) -> Result<UserRepository, Box<HttpResponse>> {
+    require_admin(req, admin_config).await?;
+    validate(pubkey, body).map_err(|message| Box::new(bad_request(message)))?;
+    let Some(pool) = app_state.postgres.clone() else {
+        return Err(Box::new(HttpResponse::ServiceUnavailable().json(json!({
+            "error": "service_unavailable",
+            "message": "Database not available"
+        }))));
+    };
+    Ok(UserRepository::new(pool))
+}
+
+fn database_error(e: sqlx::Error) -> HttpResponse {
+    tracing::error!(error = %e, "Failed to update user permissions");
+    HttpResponse::InternalServerError().json(json!({
+        "error": "internal",
+        "message": "Failed to update user permissions"
+    }))
+}
+
+/// Grant `permission` on `endpoint`, creating the user if needed
+pub async fn grant_permission(
+    req: HttpRequest,
+    path: web::Path<String>,
+    body: web::Json<PermissionBody>,
+    app_state: web::Data<AppState>,
+    admin_config: web::Data<AdminConfig>,
+) -> impl Responder {
+    let pubkey = path.into_inner();
+    let repo = match users_repository(&req, &pubkey, &body, &app_state, &admin_config).await {
+        Ok(repo) => repo,
+        Err(response) => return *response,
+    };
+
+    // Permissions reference users(pubkey)
+    if let Err(e) = repo.insert_if_missing(&pubkey, "user").await {
+        return database_error(e);
+    }
+    if let Err(e) = repo.add_permission(&pubkey, &body.endpoint, &body.permission).await {
+        return database_error(e);
+    }
+
+    tracing::info!(pubkey = %pubkey, endpoint = %body.endpoint, permission = %body.permission, "Permission granted by admin");
+    HttpResponse::Ok().json(json!({
+        "pubkey": pubkey,
+        "endpoint": body.endpoint,
+        "permission": body.permission,
+        "granted": true
+    }))
+}
+
+/// Revoke `permission` on `endpoint`; revoking a permission the user lacks is a no-op
+pub async fn revoke_permission(
+    req: HttpRequest,
+    path: web::Path<String>,
+    body: web::Json<PermissionBody>,
+    app_state: web::Data<AppState>,
+    admin_config: web::Data<AdminConfig>,
+) -> impl Responder {
+    let pubkey = path.into_inner();
+    let repo = match users_repository(&req, &pubkey, &body, &app_state, &admin_config).await {
+        Ok(repo) => repo,
+        Err(response) => return *response,
+    };
+
+    let removed = match repo.remove_permission(&pubkey, &body.endpoint, &body.permission).await {
+        Ok(removed) => removed,
+        Err(e) => return database_error(e),
+    };
+
+    tracing::info!(pubkey = %pubkey, endpoint = %body.endpoint, permission = %body.permission, removed, "Permission revoked by admin");
+    HttpResponse::Ok().json(json!({
+        "pubkey": pubkey,
+        "endpoint": body.endpoint,
+        "permission": body.permission,
+        "removed": removed
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ServiceConfig;
+    use actix_web::{http::{Method, StatusCode}, test as actix_test, App};
+
+    const PUBKEY: &str = "Vote111111111111111111111111111111111111111";
+
+    fn secp256k1_address() -> String {
+        let key = k256::ecdsa::SigningKey::from_slice(&[21u8; 32]).unwrap();
+        bs58::encode(key.verifying_key().to_sec1_bytes()).into_string()
+    }
+
+    fn admin_config() -> AdminConfig {
+        AdminConfig {
+            admin_token: "users-token".to_string(),
+            ..AdminConfig::default()
+        }
+    }
+
+    async fn call(
+        app_state: AppState,
+        method: actix_web::http::Method,
+        pubkey: &str,
+        body: serde_json::Value,
+    ) -> actix_web::dev::ServiceResponse {
+        let app = actix_test::init_service(
+            App::new()
+                .app_data(web::Data::new(app_state))
+                .app_data(web::Data::new(admin_config()))
+                .service(
+                    web::resource("/api/admin/users/{pubkey}/permissions")
+                        .route(web::post().to(grant_permission))
+                        .route(web::delete().to(revoke_permission)),
+                ),
+        )
+        .await;
+        let req = actix_test::TestRequest::default()
+            .method(method)
+            .uri(&format!("/api/admin/users/{}/permissions", pubkey))
+            .insert_header(("X-Admin-Token", "users-token"))
+            .set_json(body)
+            .to_request();
+        actix_test::call_service(&app, req).await
+    }
+
+    #[test]
+    fn test_endpoint_must_be_a_known_route_prefix() {
+        assert!(is_known_endpoint("/api/transactions"));
+        assert!(is_known_endpoint("/api/transactions/poll"));
+        assert!(!is_known_endpoint("/api/transactionsx"));
+        assert!(!is_known_endpoint("/api/users"));
+        assert!(!is_known_endpoint(""));
+    }
+
+    #[actix_web::test]
+    async fn test_invalid_grants_are_rejected_before_the_database() {
+        let state = || AppState::new(ServiceConfig::default(), None, None);
+        let valid = json!({ "endpoint": "/api/transactions", "permission": "read" });
+
+        for (pubkey, body, reason) in [
+            ("not-base58-0OIl", valid.clone(), "pubkey"),
+            (PUBKEY, json!({ "endpoint": "/api/users", "permission": "read" }), "endpoint"),
+            (PUBKEY, json!({ "endpoint": "/api/transactions", "permission": "owner" }), "permission"),
+        ] {
+            let resp = call(state(), Method::POST, pubkey, body).await;
+            assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+            let body: serde_json::Value = actix_test::read_body_json(resp).await;
+            assert!(body["message"].as_str().unwrap().starts_with(reason));
+        }
+
+        // A valid request only then needs the database, whatever the key's scheme
+        for pubkey in [PUBKEY.to_string(), secp256k1_address()] {
+            let resp = call(state(), Method::DELETE, &pubkey, valid.clone()).await;
+            assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+        }
+    }
+
+    #[actix_web::test]
+    #[ignore = "needs Postgres at DATABASE_URL"]
+    async fn test_grant_then_revoke_updates_has_permission() {
+        let pool = sqlx::PgPool::connect(&std::env::var("DATABASE_URL").unwrap()).await.unwrap();
+        let state = AppState::new(ServiceConfig::default(), Some(pool.clone()), None);
+        let repo = UserRepository::new(pool);
+        let body = json!({ "endpoint": "/api/transactions", "permission": "write" });
+
+        let resp = call(state.clone(), Method::POST, PUBKEY, body.clone()).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert!(repo.has_permission(PUBKEY, "/api/transactions", "write").await.unwrap());
+
+        let resp = call(state.clone(), Method::DELETE, PUBKEY, body.clone()).await;
+        let removed: serde_json::Value = actix_test::read_body_json(resp).await;
+        assert_eq!(removed["removed"], true);
+        assert!(!repo.has_permission(PUBKEY, "/api/transactions", "write").await.unwrap());
+
+        // Revoking again is a no-op
+        let resp = call(state, Method::DELETE, PUBKEY, body).await;
+        let removed: serde_json::Value = actix_test::read_body_json(resp).await;
+        assert_eq!(removed["removed"], false);
+    }
+
+    #[actix_web::test]
+    #[ignore = "needs Postgres at DATABASE_URL"]
+    async fn test_secp256k1_address_can_be_granted() {
+        let pool = sqlx::PgPool::connect(&std::env::var("DATABASE_URL").unwrap()).await.unwrap();
+        let state = AppState::new(ServiceConfig::default(), Some(pool.clone()), None);
+        let repo = UserRepository::new(pool);
+        let address = secp256k1_address();
+        let body = json!({ "endpoint": "/api/transactions", "permission": "read" });
+
+        let resp = call(state.clone(), Method::POST, &address, body.clone()).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert!(repo.has_permission(&address, "/api/transactions", "read").await.unwrap());
+
+        call(state, Method::DELETE, &address, body).await;
+    }
+}
@@ -6,10 +6,12 @@
 use crate::{
     app_state::AppState,
     config::{AdminConfig, WafConfig},
-    infra::redis,
+    http::{
+        middleware::waf::{current_ruleset_version, WafAction, WafCategory},
+        routes::admin::require_admin,
+    },
 };
 use actix_web::{web, HttpRequest, HttpResponse, Responder};
-use serde_json::json;
 use std::collections::HashMap;
 
 /// WAF debug information
@@ -24,6 +26,8 @@ pub struct WafDebugInfo {
 pub struct WafDebugConfig {
     pub enabled: bool,
     pub mode: String,
+    /// Version of the compiled rule set the WAF is using
+    pub ruleset_version: u64,
     pub bypass_paths: Vec<String>,
     pub max_request_body_bytes: usize,
     pub max_query_length: usize,
@@ -56,8 +60,8 @@ pub struct WafEvent {
     pub method: String,
     pub path: String,
     pub score: u32,
-    pub action: String,
-    pub matches: Vec<String>,
+    pub action: WafAction,
+    pub matches: Vec<WafCategory>,
 }
 
 /// Get WAF debug information
@@ -67,34 +71,15 @@ pub async fn waf_debug(
     waf_config: web::Data<WafConfig>,
     admin_config: web::Data<AdminConfig>,
 ) -> impl Responder {
-    // Check admin authentication if token is configured
-    if !admin_config.admin_token.is_empty() {
-        if let Some(token) = req.headers().get(&admin_config.admin_header) {
-            if let Ok(token_str) = token.to_str() {
-                if token_str != admin_config.admin_token {
-                    return HttpResponse::Forbidden().json(json!({
-                        "error": "forbidden",
-                        "message": "Invalid admin token"
-                    }));
-                }
-            } else {
-                return HttpResponse::Forbidden().json(json!({
-                    "error": "forbidden",
-                    "message": "Invalid admin token format"
-                }));
-            }
-        } else {
-            return HttpResponse::Forbidden().json(json!({
-                "error": "forbidden",
-                "message": "Admin token required"
-            }));
-        }
+    if let Err(response) = require_admin(&req, &admin_config).await {
+        return response;
     }
 
     // Get WAF configuration (without sensitive data)
     let config = WafDebugConfig {
         enabled: waf_config.enabled,
         mode: waf_config.mode.clone(),
+        ruleset_version: current_ruleset_version(),
         bypass_paths: waf_config.bypass_paths.clone(),
         max_request_body_bytes: waf_config.max_request_body_bytes,
         max_query_length: waf_config.max_query_length,
@@ -111,7 +96,7 @@ pub async fn waf_debug(
     };
 
     // Get statistics
-    let stats = get_waf_stats(&app_state, &waf_config).await;
+    let stats = get_waf_stats(&app_state).await;
 
     // Get recent events (placeholder - would come from actual event storage)
     let recent_events = get_recent_events().await;
@@ -138,23 +123,13 @@ fn get_pattern_counts(waf_config: &WafConfig) -> HashMap<String, usize> {
 }
 
 /// Get WAF statistics
-async fn get_waf_stats(app_state: &AppState, waf_config: &WafConfig) -> WafStats {
+async fn get_waf_stats(app_state: &AppState) -> WafStats {
     let mut ban_list_size = 0;
     let mut grey_list_size = 0;
 
-    // Get Redis list sizes if Redis is available
-    if waf_config.use_redis_lists {
-        if let Some(redis_manager) = &app_state.redis {
-            // Get ban list size
-            if let Ok(size) = redis::get_set_size(&mut redis_manager.clone(), &waf_config.redis_ban_set).await {
-                ban_list_size = size;
-            }
-
-            // Get grey list size
-            if let Ok(size) = redis::get_set_size(&mut redis_manager.clone(), &waf_config.redis_grey_set).await {
-                grey_list_size = size;
-            }
-        }
+    if let Some(lists) = &app_state.waf_lists {
+        ban_list_size = lists.ban.count().await.unwrap_or(0);
+        grey_list_size = lists.grey.count().await.unwrap_or(0);
     }
 
     WafStats {
@@ -177,7 +152,7 @@ async fn get_recent_events() -> Vec<WafEvent> {
             method: "GET".to_string(),
             path: "/test".to_string(),
             score: 0,
-            action: "pass".to_string(),
+            action: WafAction::Pass,
             matches: vec![],
         },
     ]
@@ -1,10 +1,19 @@
 /// Authentication routes
 
-use actix_web::{web, HttpResponse, Responder};
+use actix_web::{web, HttpRequest, HttpResponse, Responder, ResponseError};
+use blockchain_auth::{AuthError, SignatureScheme, WalletVerifier};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::Duration;
 
 use crate::app_state::AppState;
-use crate::config::AuthConfig;
+use crate::config::{AuthConfig, RateLimitConfig};
+use crate::errors::ApiError;
+use crate::http::client_ip::ClientIpResolver;
+use crate::http::middleware::ratelimit::{rate_limited_response, RateLimit};
+use crate::http::middleware::wallet_auth::{request_scheme, wallet_verifier};
+use crate::metrics::AppMetrics;
+use crate::nonce_store::NonceStoreError;
 
 #[derive(Deserialize)]
 pub struct NonceRequest {
@@ -17,6 +26,23 @@ pub struct NonceResponse {
     ttl_secs: u64,
 }
 
+#[derive(Deserialize)]
+pub struct VerifyRequest {
+    address: String,
+    message: String,
+    signature: String,
+}
+
+#[derive(Serialize)]
+pub struct VerifyResponse {
+    valid: bool,
+}
+
+#[derive(Serialize)]
+pub struct VerifyBatchResponse {
+    results: Vec<VerifyResponse>,
+}
+
 #[derive(Serialize)]
 struct ErrorResponse {
     error: String,
@@ -24,23 +50,37 @@ struct ErrorResponse {
     details: Option<String>,
 }
 
+/// The scheme a request names in `auth.header_wallet_scheme`, else
+/// `auth.scheme`; 400 if it's unknown
+fn scheme_for(req: &HttpRequest, config: &AuthConfig) -> Result<&'static dyn SignatureScheme, ApiError> {
+    let wallet_scheme = req
+        .headers()
+        .get(&config.header_wallet_scheme)
+        .and_then(|h| h.to_str().ok());
+    request_scheme(config, wallet_scheme).map_err(|e| ApiError::BadRequest {
+        missing: vec![],
+        reason: Some(e.to_string()),
+    })
+}
+
+/// Issue a nonce for `address`, a key of the scheme the wallet will sign with
+/// (`auth.header_wallet_scheme` if sent, else `auth.scheme`)
 pub async fn get_nonce(
-    req: web::Json<NonceRequest>,
+    req: HttpRequest,
+    body: web::Json<NonceRequest>,
     state: web::Data<AppState>,
     config: web::Data<AuthConfig>,
+    metrics: Option<web::Data<AppMetrics>>,
 ) -> impl Responder {
-    let address = &req.address;
+    let address = &body.address;
 
-    // Basic validation: check if address looks like base58
-    if address.len() < 32 || address.len() > 44 {
-        return HttpResponse::BadRequest().json(ErrorResponse {
-            error: "bad_request".to_string(),
-            details: Some("Invalid address format".to_string()),
-        });
-    }
+    let scheme = match scheme_for(&req, &config) {
+        Ok(scheme) => scheme,
+        Err(e) => return e.error_response(),
+    };
 
-    // Validate address is valid base58
-    if let Err(e) = blockchain_auth::decode_pubkey_b58(address) {
+    // Validate address is a base58 key for the scheme
+    if let Err(e) = scheme.decode_pubkey(address) {
         return HttpResponse::BadRequest().json(ErrorResponse {
             error: "bad_request".to_string(),
             details: Some(format!("Invalid address: {}", e)),
@@ -50,27 +90,35 @@ pub async fn get_nonce(
     // Generate nonce
     let nonce = blockchain_auth::generate_nonce();
 
-    // Store in Redis (if available)
-    if let Some(ref redis_conn) = state.redis {
-        let redis_key = format!("{}:{}", config.redis_key_prefix, address);
-        let ttl = config.nonce_ttl_secs as i64;
-
-        let mut conn = redis_conn.clone();
-        match redis::cmd("SETEX")
-            .arg(&redis_key)
-            .arg(ttl)
-            .arg(&nonce)
-            .query_async::<String>(&mut conn)
-            .await
-        {
-            Ok(_) => {
+    // Add to the outstanding nonces for this address (if store available)
+    if let Some(ref nonce_store) = state.nonce_store {
+        // Global ceiling, so a flood spread over many addresses can't swamp Redis
+        if !nonce_store.admit_issue() {
+            tracing::debug!(
+                max_per_sec = config.nonce_global_max_per_sec,
+                "Global nonce issuance limit reached, shedding nonce request"
+            );
+            return rate_limited_response(1);
+        }
+        match nonce_store.issue(address, &nonce).await {
+            Ok(()) => {
+                if let Some(metrics) = &metrics {
+                    metrics.record_auth_nonce_issued();
+                }
                 tracing::info!(
                     address = %address,
-                    ttl_secs = ttl,
-                    redis_key = %redis_key,
+                    ttl_secs = config.nonce_ttl_secs,
+                    max_outstanding = config.max_outstanding_nonces,
                     "Nonce generated and stored"
                 );
             }
+            Err(NonceStoreError::Saturated) => {
+                tracing::warn!("Nonce store saturated, shedding nonce request");
+                return HttpResponse::ServiceUnavailable().json(ErrorResponse {
+                    error: "service_unavailable".to_string(),
+                    details: Some("Authentication service is busy, retry shortly".to_string()),
+                });
+            }
             Err(e) => {
                 tracing::error!(error = %e, "Failed to store nonce in Redis");
                 return HttpResponse::InternalServerError().json(ErrorResponse {
@@ -93,4 +141,466 @@ pub async fn get_nonce(
     })
 }
 
+/// Check a signature over an arbitrary message, statelessly
+///
+/// Lets clients surface signing errors before making an authenticated call.
+/// The scheme is picked as for [`get_nonce`].
+///
+/// No nonce is read or consumed. Calls are limited per IP and per address so
+/// the endpoint can't be used as a verification oracle.
+pub async fn verify_signature(
+    req: HttpRequest,
+    body: web::Json<VerifyRequest>,
+    config: web::Data<AuthConfig>,
+    limiter: web::Data<RateLimit>,
+    client_ip: web::Data<ClientIpResolver>,
+    rate_limit: web::Data<RateLimitConfig>,
+) -> impl Responder {
+    let verifier = match scheme_for(&req, &config) {
+        Ok(scheme) => WalletVerifier { scheme, ..wallet_verifier(&config) },
+        Err(e) => return e.error_response(),
+    };
+
+    // Checked before anything is counted, so arbitrary strings never become
+    // limiter keys; base58 is canonical, so a decoded address is one per key
+    if let Err(e) = verifier.decode_pubkey(&body.address) {
+        return HttpResponse::BadRequest().json(ErrorResponse {
+            error: "bad_request".to_string(),
+            details: Some(format!("Invalid address: {}", e)),
+        });
+    }
+
+    let window = Duration::from_secs(config.verify_window_secs);
+    let ip = client_ip
+        .resolve(&req, rate_limit.respect_x_forwarded_for)
+        .map_or_else(|| "unknown".to_string(), |ip| ip.to_string());
+    for key in [format!("verify:ip:{}", ip), format!("verify:address:{}", body.address)] {
+        if let Err(retry_after) = limiter.check_limit(key, config.verify_max_requests, window) {
+            tracing::warn!(ip = %ip, address = %body.address, "Signature verify rate limit exceeded");
+            return rate_limited_response(retry_after);
+        }
+    }
+
+    let valid = match verifier.verify_message(&body.address, &body.signature, body.message.as_bytes()) {
+        Ok(()) => true,
+        Err(AuthError::VerificationFailed) => false,
+        // Includes a key of the right length that isn't a curve point
+        Err(e @ (AuthError::InvalidPubkey(_) | AuthError::InvalidPubkeyLength(_))) => {
+            return HttpResponse::BadRequest().json(ErrorResponse {
+                error: "bad_request".to_string(),
+                details: Some(format!("Invalid address: {}", e)),
+            });
+        }
+        Err(e) => {
+            return HttpResponse::BadRequest().json(ErrorResponse {
+                error: "bad_request".to_string(),
+                details: Some(format!("Invalid signature: {}", e)),
+            });
+        }
+    };
+    HttpResponse::Ok().json(VerifyResponse { valid })
+}
+
+/// Check a batch of signatures, statelessly
+///
+/// Items are checked as by [`verify_signature`], all under the one scheme,
+/// but one that doesn't decode is just reported invalid. Items from the same
+/// address share one batch verification. The caller's IP is charged one hit
+/// per item.
+pub async fn verify_batch(
+    req: HttpRequest,
+    body: web::Json<Vec<VerifyRequest>>,
+    config: web::Data<AuthConfig>,
+    limiter: web::Data<RateLimit>,
+    client_ip: web::Data<ClientIpResolver>,
+    rate_limit: web::Data<RateLimitConfig>,
+) -> impl Responder {
+    let verifier = match scheme_for(&req, &config) {
+        Ok(scheme) => WalletVerifier { scheme, ..wallet_verifier(&config) },
+        Err(e) => return e.error_response(),
+    };
+    let items = body.into_inner();
+    if items.is_empty() || items.len() > config.verify_batch_max_items {
+        return HttpResponse::BadRequest().json(ErrorResponse {
+            error: "bad_request".to_string(),
+            details: Some(format!("Batch must hold 1 to {} items", config.verify_batch_max_items)),
+        });
+    }
+
+    // Resolved like the rate limiter does, so a forwarding header is only
+    // believed where the deployment says it can be
+    let ip = client_ip
+        .resolve(&req, rate_limit.respect_x_forwarded_for)
+        .map_or_else(|| "unknown".to_string(), |ip| ip.to_string());
+    let window = Duration::from_secs(config.verify_window_secs);
+    if let Err(retry_after) =
+        limiter.check_cost(format!("verify_batch:ip:{}", ip), items.len() as u32, config.verify_batch_max_cost, window)
+    {
+        tracing::warn!(ip = %ip, items = items.len(), "Batch signature verify rate limit exceeded");
+        return rate_limited_response(retry_after);
+    }
+
+    let mut by_address: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (i, item) in items.iter().enumerate() {
+        by_address.entry(item.address.as_str()).or_default().push(i);
+    }
 
+    let mut valid = vec![false; items.len()];
+    for (address, indices) in by_address {
+        let signed: Vec<(&[u8], &str)> = indices
+            .iter()
+            .map(|i| (items[*i].message.as_bytes(), items[*i].signature.as_str()))
+            .collect();
+        if let Ok(results) = verifier.verify_batch(address, &signed) {
+            for (i, result) in indices.into_iter().zip(results) {
+                valid[i] = result;
+            }
+        }
+    }
+
+    HttpResponse::Ok().json(VerifyBatchResponse {
+        results: valid.into_iter().map(|valid| VerifyResponse { valid }).collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{RateLimitConfig, ServiceConfig};
+    use crate::nonce_store::NonceStore;
+    use actix_web::{http::StatusCode, test, App};
+    use ed25519_dalek::{Signer, SigningKey};
+    use std::sync::Arc;
+
+    #[actix_web::test]
+    async fn test_saturated_nonce_store_returns_503() {
+        let config = AuthConfig {
+            nonce_backend: "memory".to_string(),
+            redis_max_inflight: 2,
+            ..AuthConfig::default()
+        };
+        let store = Arc::new(NonceStore::from_config(&config, None).unwrap());
+        let mut state = AppState::new(ServiceConfig::default(), None, None);
+        state.nonce_store = Some(store.clone());
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(state))
+                .app_data(web::Data::new(config))
+                .route("/api/auth/nonce", web::post().to(get_nonce)),
+        )
+        .await;
+        let nonce_request = || {
+            test::TestRequest::post()
+                .uri("/api/auth/nonce")
+                .set_json(serde_json::json!({ "address": "11111111111111111111111111111111" }))
+                .to_request()
+        };
+
+        let resp = test::call_service(&app, nonce_request()).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        // Two slow commands hold every permit; further requests are shed at once
+        let held = [store.limiter().try_acquire().unwrap(), store.limiter().try_acquire().unwrap()];
+        assert_eq!(store.limiter().inflight(), 2);
+        for _ in 0..10 {
+            let resp = test::call_service(&app, nonce_request()).await;
+            assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+        }
+
+        drop(held);
+        let resp = test::call_service(&app, nonce_request()).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    #[actix_web::test]
+    async fn test_global_nonce_limit_sheds_requests_from_any_address() {
+        let config = AuthConfig {
+            nonce_backend: "memory".to_string(),
+            nonce_global_max_per_sec: 5,
+            ..AuthConfig::default()
+        };
+        let mut state = AppState::new(ServiceConfig::default(), None, None);
+        state.nonce_store = Some(Arc::new(NonceStore::from_config(&config, None).unwrap()));
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(state))
+                .app_data(web::Data::new(config))
+                .route("/api/auth/nonce", web::post().to(get_nonce)),
+        )
+        .await;
+
+        // Every request comes from a different address, so no per-address limit applies
+        let mut statuses = Vec::new();
+        for seed in 0..20u8 {
+            let address = bs58::encode(SigningKey::from_bytes(&[seed; 32]).verifying_key().to_bytes()).into_string();
+            let req = test::TestRequest::post()
+                .uri("/api/auth/nonce")
+                .set_json(serde_json::json!({ "address": address }))
+                .to_request();
+            let resp = test::call_service(&app, req).await;
+            if resp.status() == StatusCode::TOO_MANY_REQUESTS {
+                assert_eq!(resp.headers().get("Retry-After").unwrap(), "1");
+            }
+            statuses.push(resp.status());
+        }
+
+        let issued = statuses.iter().filter(|s| **s == StatusCode::OK).count();
+        let shed = statuses.iter().filter(|s| **s == StatusCode::TOO_MANY_REQUESTS).count();
+        // The bucket holds five; the loop may outlast a refill or two, never many
+        assert!((5..=7).contains(&issued), "{:?}", statuses);
+        assert_eq!(issued + shed, 20);
+        assert_eq!(statuses[..5], [StatusCode::OK; 5]);
+    }
+
+    #[actix_web::test]
+    async fn test_secp256k1_wallet_signs_in_with_an_issued_nonce() {
+        use crate::http::middleware::wallet_auth::{wallet_verifier, WalletAuth};
+
+        let config = AuthConfig {
+            enabled: true,
+            nonce_backend: "memory".to_string(),
+            ..AuthConfig::default()
+        };
+        let mut state = AppState::new(ServiceConfig::default(), None, None);
+        state.nonce_store = Some(Arc::new(NonceStore::from_config(&config, None).unwrap()));
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(state))
+                .app_data(web::Data::new(config.clone()))
+                .wrap(WalletAuth::new(config.clone()))
+                .route("/api/auth/nonce", web::post().to(get_nonce))
+                .route("/api/transactions", web::get().to(HttpResponse::Ok)),
+        )
+        .await;
+
+        let key = k256::ecdsa::SigningKey::from_slice(&[21u8; 32]).unwrap();
+        let address = bs58::encode(key.verifying_key().to_sec1_bytes()).into_string();
+        let nonce_request = |scheme: Option<&str>| {
+            let mut req = test::TestRequest::post()
+                .uri("/api/auth/nonce")
+                .set_json(serde_json::json!({ "address": address }));
+            if let Some(scheme) = scheme {
+                req = req.insert_header(("X-Wallet-Scheme", scheme));
+            }
+            req.to_request()
+        };
+
+        // A SEC1 key isn't an Ed25519 address, so the scheme has to be named
+        let resp = test::call_service(&app, nonce_request(None)).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let body: serde_json::Value = test::call_and_read_body_json(&app, nonce_request(Some("secp256k1"))).await;
+        let nonce = body["nonce"].as_str().unwrap();
+
+        // Signed as personal_sign does: r || s || v over the EIP-191 digest
+        let message = wallet_verifier(&config).signing_string("GET", "/api/transactions", nonce, None, None);
+        let digest = blockchain_auth::eip191_digest(message.as_bytes());
+        let (signature, recovery_id) = key.sign_prehash_recoverable(&digest).unwrap();
+        let mut rsv = signature.to_bytes().to_vec();
+        rsv.push(27 + recovery_id.to_byte());
+
+        let req = test::TestRequest::get()
+            .uri("/api/transactions")
+            .insert_header(("X-Wallet-Address", address.as_str()))
+            .insert_header(("X-Wallet-Signature", bs58::encode(rsv).into_string()))
+            .insert_header(("X-Nonce", nonce))
+            .insert_header(("X-Wallet-Scheme", "secp256k1"))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+    }
+
+    fn verify_request(address: &str, message: &str, signature: &str) -> test::TestRequest {
+        test::TestRequest::post()
+            .uri("/api/auth/verify")
+            .set_json(serde_json::json!({ "address": address, "message": message, "signature": signature }))
+    }
+
+    #[actix_web::test]
+    async fn test_verify_reports_valid_and_tampered_signatures() {
+        let config = AuthConfig {
+            verify_max_requests: 3,
+            ..AuthConfig::default()
+        };
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(config))
+                .app_data(web::Data::new(RateLimit::new(RateLimitConfig::default())))
+                .app_data(web::Data::new(ClientIpResolver::default()))
+                .app_data(web::Data::new(RateLimitConfig::default()))
+                .route("/api/auth/verify", web::post().to(verify_signature)),
+        )
+        .await;
+
+        let key = SigningKey::from_bytes(&[9u8; 32]);
+        let address = bs58::encode(key.verifying_key().to_bytes()).into_string();
+        let message = "GET\n/api/transactions\nnonce-1";
+        let signature = bs58::encode(key.sign(message.as_bytes()).to_bytes()).into_string();
+
+        let body: serde_json::Value =
+            test::call_and_read_body_json(&app, verify_request(&address, message, &signature).to_request()).await;
+        assert_eq!(body["valid"], true);
+
+        let tampered = message.replace("nonce-1", "nonce-2");
+        let body: serde_json::Value =
+            test::call_and_read_body_json(&app, verify_request(&address, &tampered, &signature).to_request()).await;
+        assert_eq!(body["valid"], false);
+
+        // Bad addresses are refused before they're counted, however many there are
+        for i in 0..5 {
+            let junk = format!("not-base58!{}", "x".repeat(i * 1000));
+            let resp = test::call_service(&app, verify_request(&junk, message, &signature).to_request()).await;
+            assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        }
+        let resp = test::call_service(&app, verify_request(&address, message, &signature).to_request()).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        // Limited per address (and IP) regardless of the outcome
+        let resp = test::call_service(&app, verify_request(&address, message, &signature).to_request()).await;
+        assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert!(resp.headers().contains_key("Retry-After"));
+    }
+
+    #[actix_web::test]
+    async fn test_verify_batch_reports_each_item() {
+        let config = AuthConfig {
+            verify_batch_max_items: 4,
+            verify_batch_max_cost: 6,
+            ..AuthConfig::default()
+        };
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(config))
+                .app_data(web::Data::new(RateLimit::new(RateLimitConfig::default())))
+                .app_data(web::Data::new(ClientIpResolver::default()))
+                .app_data(web::Data::new(RateLimitConfig::default()))
+                .route("/api/auth/verify-batch", web::post().to(verify_batch)),
+        )
+        .await;
+
+        let signed = |seed: u8, message: &str| {
+            let key = SigningKey::from_bytes(&[seed; 32]);
+            serde_json::json!({
+                "address": bs58::encode(key.verifying_key().to_bytes()).into_string(),
+                "message": message,
+                "signature": bs58::encode(key.sign(message.as_bytes()).to_bytes()).into_string(),
+            })
+        };
+        let mut tampered = signed(1, "attestation-2");
+        tampered["message"] = "attestation-3".into();
+        let batch = serde_json::json!([
+            signed(1, "attestation-1"),
+            tampered,
+            signed(2, "attestation-1"),
+            { "address": "not-base58!", "message": "m", "signature": "not-base58!" },
+        ]);
+
+        let req = test::TestRequest::post().uri("/api/auth/verify-batch").set_json(&batch).to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        let valid: Vec<bool> = body["results"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|result| result["valid"].as_bool().unwrap())
+            .collect();
+        assert_eq!(valid, vec![true, false, true, false]);
+
+        // Over the item cap, before any cost is charged
+        let oversized = serde_json::Value::Array(vec![signed(1, "m"); 5]);
+        let req = test::TestRequest::post().uri("/api/auth/verify-batch").set_json(&oversized).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::BAD_REQUEST);
+
+        // 4 of 6 items used; a batch of 3 no longer fits the window
+        let req = test::TestRequest::post()
+            .uri("/api/auth/verify-batch")
+            .set_json(serde_json::json!([signed(1, "a"), signed(1, "b"), signed(1, "c")]))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::TOO_MANY_REQUESTS);
+    }
+
+    #[actix_web::test]
+    async fn test_verify_batch_limit_holds_across_rotating_forwarded_for() {
+        let config = AuthConfig {
+            verify_batch_max_cost: 4,
+            ..AuthConfig::default()
+        };
+        // Not behind a proxy, so X-Forwarded-For is the client's own claim
+        let rate_limit = RateLimitConfig {
+            respect_x_forwarded_for: false,
+            ..RateLimitConfig::default()
+        };
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(config))
+                .app_data(web::Data::new(RateLimit::new(rate_limit.clone())))
+                .app_data(web::Data::new(ClientIpResolver::default()))
+                .app_data(web::Data::new(rate_limit))
+                .route("/api/auth/verify-batch", web::post().to(verify_batch)),
+        )
+        .await;
+
+        let item = serde_json::json!({ "address": "11111111111111111111111111111111", "message": "m", "signature": "s" });
+        // Each batch claims a new address; all of them come from the same peer
+        let mut statuses = Vec::new();
+        for i in 0..3 {
+            let req = test::TestRequest::post()
+                .uri("/api/auth/verify-batch")
+                .peer_addr("192.0.2.50:443".parse().unwrap())
+                .insert_header(("X-Forwarded-For", format!("198.51.100.{}", i)))
+                .set_json(serde_json::json!([item, item]))
+                .to_request();
+            statuses.push(test::call_service(&app, req).await.status());
+        }
+        assert_eq!(statuses, vec![StatusCode::OK, StatusCode::OK, StatusCode::TOO_MANY_REQUESTS]);
+    }
+
+    #[actix_web::test]
+    async fn test_verify_endpoints_take_the_wallet_scheme() {
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AuthConfig::default()))
+                .app_data(web::Data::new(RateLimit::new(RateLimitConfig::default())))
+                .app_data(web::Data::new(ClientIpResolver::default()))
+                .app_data(web::Data::new(RateLimitConfig::default()))
+                .route("/api/auth/verify", web::post().to(verify_signature))
+                .route("/api/auth/verify-batch", web::post().to(verify_batch)),
+        )
+        .await;
+
+        let key = k256::ecdsa::SigningKey::from_slice(&[21u8; 32]).unwrap();
+        let address = bs58::encode(key.verifying_key().to_sec1_bytes()).into_string();
+        let signed = |message: &str| {
+            let digest = blockchain_auth::eip191_digest(message.as_bytes());
+            let (signature, recovery_id) = key.sign_prehash_recoverable(&digest).unwrap();
+            let mut rsv = signature.to_bytes().to_vec();
+            rsv.push(27 + recovery_id.to_byte());
+            serde_json::json!({ "address": address, "message": message, "signature": bs58::encode(rsv).into_string() })
+        };
+
+        // A SEC1 key isn't an Ed25519 address under the default scheme
+        let req = test::TestRequest::post().uri("/api/auth/verify").set_json(signed("attestation-1"));
+        assert_eq!(test::call_service(&app, req.to_request()).await.status(), StatusCode::BAD_REQUEST);
+
+        let req = test::TestRequest::post()
+            .uri("/api/auth/verify")
+            .insert_header(("X-Wallet-Scheme", "secp256k1"))
+            .set_json(signed("attestation-1"));
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req.to_request()).await;
+        assert_eq!(body["valid"], true);
+
+        let mut tampered = signed("attestation-2");
+        tampered["message"] = "attestation-3".into();
+        let req = test::TestRequest::post()
+            .uri("/api/auth/verify-batch")
+            .insert_header(("X-Wallet-Scheme", "secp256k1"))
+            .set_json(serde_json::json!([signed("attestation-1"), tampered]));
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req.to_request()).await;
+        assert_eq!(body["results"], serde_json::json!([{ "valid": true }, { "valid": false }]));
+
+        let req = test::TestRequest::post()
+            .uri("/api/auth/verify-batch")
+            .insert_header(("X-Wallet-Scheme", "rsa"))
+            .set_json(serde_json::json!([signed("attestation-1")]));
+        assert_eq!(test::call_service(&app, req.to_request()).await.status(), StatusCode::BAD_REQUEST);
+    }
+}
@@ -1,18 +1,101 @@
-use actix_web::{HttpResponse, Responder};
+use crate::{
+    app_state::AppState,
+    metrics::{AppMetrics, ExpositionFormat},
+};
+use actix_web::{http::header, web, HttpRequest, HttpResponse, Responder};
+
+pub async fn get_metrics(
+    req: HttpRequest,
+    app_state: web::Data<AppState>,
+    app_metrics: web::Data<AppMetrics>,
+) -> impl Responder {
+    let format = ExpositionFormat::negotiate(
+        req.headers()
+            .get(header::ACCEPT)
+            .and_then(|accept| accept.to_str().ok())
+            .unwrap_or_default(),
+    );
 
-pub async fn get_metrics() -> impl Responder {
     // Simplified metrics endpoint
     // In a full implementation, this would return Prometheus metrics
-    let metrics = "# HELP blockchain_api_info Information about the blockchain API
+    let mut metrics = "# HELP blockchain_api_info Information about the blockchain API
 # TYPE blockchain_api_info gauge
 blockchain_api_info{version=\"0.2.0\"} 1
 
 # HELP http_requests_total Total number of HTTP requests
 # TYPE http_requests_total counter
 http_requests_total{method=\"GET\",path=\"/healthz\",status=\"200\"} 1
-";
+"
+    .to_string();
+
+    if let Some(nonce_store) = &app_state.nonce_store {
+        metrics.push_str(&format!(
+            "
+# HELP redis_inflight Nonce-store Redis commands currently in flight
+# TYPE redis_inflight gauge
+redis_inflight {}
+",
+            nonce_store.limiter().inflight()
+        ));
+    }
+
+    metrics.push('\n');
+    metrics.push_str(&app_metrics.render_as(format));
 
     HttpResponse::Ok()
-        .content_type("text/plain; version=0.0.4; charset=utf-8")
-        .body(metrics)
-}
\ No newline at end of file
+        .content_type(format.content_type())
+        .body(format.finish(metrics))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ServiceConfig;
+    use crate::http::middleware::otel::OtelMiddleware;
+    use actix_web::{test, App};
+    use std::sync::Arc;
+
+    #[actix_web::test]
+    async fn test_openmetrics_scrape_links_request_duration_to_the_trace() {
+        let metrics = Arc::new(AppMetrics::new().unwrap());
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState::new(ServiceConfig::default(), None, None)))
+                .app_data(web::Data::from(metrics.clone()))
+                .wrap(OtelMiddleware::new().with_metrics(metrics.clone()))
+                .route("/api/transactions", web::get().to(|| async { HttpResponse::Ok().finish() }))
+                .route("/metrics", web::get().to(get_metrics)),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/api/transactions")
+            .insert_header(("traceparent", "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 200);
+
+        let req = test::TestRequest::get()
+            .uri("/metrics")
+            .insert_header(("Accept", "application/openmetrics-text; version=1.0.0,text/plain;version=0.0.4;q=0.5"))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert!(resp.headers().get("Content-Type").unwrap().to_str().unwrap().starts_with("application/openmetrics-text"));
+        let body = String::from_utf8(test::read_body(resp).await.to_vec()).unwrap();
+
+        // The bucket the request landed in carries its trace
+        let bucket = body
+            .lines()
+            .filter(|line| line.starts_with(r#"http_request_duration_seconds_bucket{method="GET",status="200",le="#))
+            .find(|line| line.contains(" # {"))
+            .expect(&body);
+        assert!(bucket.contains(r#"} 1 # {trace_id="4bf92f3577b34da6a3ce929d0e0e4736"} "#), "{}", bucket);
+        assert!(body.contains("# TYPE http_requests counter\n"), "{}", body);
+        assert!(!body.contains("\n\n") && body.ends_with("# EOF\n"), "{}", body);
+
+        // Plain Prometheus scrapes get the histogram without exemplars
+        let req = test::TestRequest::get().uri("/metrics").to_request();
+        let body = String::from_utf8(test::read_body(test::call_service(&app, req).await).await.to_vec()).unwrap();
+        assert!(body.contains("http_request_duration_seconds_count{method=\"GET\",status=\"200\"} "), "{}", body);
+        assert!(!body.contains("trace_id"), "{}", body);
+    }
+}
@@ -1,5 +1,10 @@
 /// Route modules
 
+pub mod admin;
+pub mod admin_auth;
+pub mod admin_ingest;
+pub mod admin_runtime;
+pub mod admin_users;
 pub mod admin_waf;
 pub mod auth;
 pub mod health;
@@ -8,29 +13,69 @@ pub mod openapi_routes;
 pub mod transactions;
 pub mod version;
 
+use crate::errors::query_error_handler;
+use crate::http::middleware::protection::RequireProtection;
 use actix_web::web;
 
-pub fn configure(cfg: &mut web::ServiceConfig) {
+/// Routes served on `deploy.metrics_port` when `deploy.metrics_listener` is set
+pub fn configure_probes(cfg: &mut web::ServiceConfig) {
+    cfg
+        .route("/livez", web::get().to(health::healthz))
+        .route("/readyz", web::get().to(health::readyz))
+        .route("/metrics", web::get().to(metrics::get_metrics));
+}
+
+/// API routes; `/metrics` moves to the probe listener with `deploy.metrics_listener`
+pub fn configure(cfg: &mut web::ServiceConfig, metrics_listener: bool) {
+    if !metrics_listener {
+        cfg.route("/metrics", web::get().to(metrics::get_metrics));
+    }
     cfg
         .route("/healthz", web::get().to(health::healthz))
         .route("/readyz", web::get().to(health::readyz))
         .route("/version", web::get().to(version::version))
-        .route("/metrics", web::get().to(metrics::get_metrics))
-        .route("/swagger-ui/", web::get().to(openapi_routes::swagger_ui))
+        .route("/swagger-ui/{tail:.*}", web::get().to(openapi_routes::swagger_ui))
         .service(
             web::scope("/api-docs")
                 .route("/openapi.json", web::get().to(openapi_routes::openapi_json)),
         )
         .service(
             web::scope("/api")
+                .wrap(RequireProtection)
+                .service(
+                    web::scope("/admin")
+                        .route("/runtime", web::get().to(admin_runtime::runtime))
+                        .route("/auth/attempts", web::get().to(admin_auth::auth_attempts))
+                        .route("/ingest/dlq", web::get().to(admin_ingest::inspect_dlq))
+                        .route("/ingest/dlq/replay", web::post().to(admin_ingest::replay_dlq))
+                        .service(
+                            web::resource("/users/{pubkey}/permissions")
+                                .route(web::post().to(admin_users::grant_permission))
+                                .route(web::delete().to(admin_users::revoke_permission)),
+                        )
+                )
                 .service(
                     web::scope("/auth")
                         .route("/nonce", web::post().to(auth::get_nonce))
+                        .route("/verify", web::post().to(auth::verify_signature))
+                        .route("/verify-batch", web::post().to(auth::verify_batch))
                 )
                 .service(
                     web::scope("/transactions")
-                        .route("", web::get().to(transactions::list_transactions))
+                        .service(
+                            web::resource("")
+                                .app_data(
+                                    web::QueryConfig::default()
+                                        .error_handler(query_error_handler::<transactions::ListQuery>),
+                                )
+                                .route(web::get().to(transactions::list_transactions)),
+                        )
+                        .route("/poll", web::get().to(transactions::poll_transactions))
+                        .route("/changes", web::get().to(transactions::list_changes))
+                        .route("/stream", web::get().to(transactions::stream_transactions))
+                        .route("/latest-slot", web::get().to(transactions::get_latest_slot))
                         .route("/{signature}", web::get().to(transactions::get_transaction))
+                        .route("/{signature}/status", web::get().to(transactions::get_transaction_status))
                 )
         );
 }
@@ -1,4 +1,8 @@
 use actix_web::{web, HttpResponse, Responder};
+use std::sync::Arc;
+use utoipa_swagger_ui::Config;
+
+const OPENAPI_JSON_PATH: &str = "/api-docs/openapi.json";
 
 pub async fn openapi_json() -> impl Responder {
     let spec = crate::openapi::generate_openapi_spec();
@@ -7,40 +11,46 @@ pub async fn openapi_json() -> impl Responder {
         .json(spec)
 }
 
-pub async fn swagger_ui() -> impl Responder {
-    let html = r#"
-<!DOCTYPE html>
-<html lang="en">
-<head>
-    <meta charset="UTF-8">
-    <meta name="viewport" content="width=device-width, initial-scale=1.0">
-    <title>Blockchain API - Swagger UI</title>
-    <link rel="stylesheet" href="https://cdn.jsdelivr.net/npm/swagger-ui-dist@5/swagger-ui.css" />
-</head>
-<body>
-    <div id="swagger-ui"></div>
-    <script src="https://cdn.jsdelivr.net/npm/swagger-ui-dist@5/swagger-ui-bundle.js"></script>
-    <script src="https://cdn.jsdelivr.net/npm/swagger-ui-dist@5/swagger-ui-standalone-preset.js"></script>
-    <script>
-        window.onload = function() {
-            window.ui = SwaggerUIBundle({
-                url: '/api-docs/openapi.json',
-                dom_id: '#swagger-ui',
-                presets: [
-                    SwaggerUIBundle.presets.apis,
-                    SwaggerUIStandalonePreset
-                ],
-                layout: "StandaloneLayout"
-            });
-        };
-    </script>
-</body>
-</html>
-    "#;
+/// Serve the Swagger UI from assets embedded at build time (no CDN)
+pub async fn swagger_ui(tail: web::Path<String>) -> impl Responder {
+    let config = Arc::new(Config::from(OPENAPI_JSON_PATH));
 
-    HttpResponse::Ok()
-        .insert_header(("Content-Type", "text/html; charset=utf-8"))
-        .body(html)
+    match utoipa_swagger_ui::serve(&tail, config) {
+        Ok(Some(file)) => HttpResponse::Ok()
+            .insert_header(("Content-Type", file.content_type))
+            .body(file.bytes.into_owned()),
+        Ok(None) => HttpResponse::NotFound().finish(),
+        Err(e) => {
+            tracing::error!(error = %e, "Failed to serve Swagger UI asset");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
 }
 
+#[cfg(test)]
+mod tests {
+    use actix_web::{test, App};
+
+    #[actix_web::test]
+    async fn test_swagger_ui_served_locally() {
+        let app = test::init_service(App::new().configure(|cfg| super::super::configure(cfg, false))).await;
 
+        let req = test::TestRequest::get().uri("/swagger-ui/").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 200);
+        let html = String::from_utf8(test::read_body(resp).await.to_vec()).unwrap();
+        assert!(html.contains("swagger-ui-bundle.js"));
+        assert!(!html.contains("cdn.jsdelivr.net"));
+
+        let req = test::TestRequest::get().uri("/swagger-ui/swagger-ui-bundle.js").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 200);
+        assert!(!test::read_body(resp).await.is_empty());
+
+        let req = test::TestRequest::get().uri("/swagger-ui/swagger-initializer.js").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 200);
+        let js = String::from_utf8(test::read_body(resp).await.to_vec()).unwrap();
+        assert!(js.contains(super::OPENAPI_JSON_PATH));
+    }
+}
@@ -3,5 +3,6 @@
 pub mod kafka;
 pub mod postgres;
 pub mod redis;
+pub mod schema_check;
 
 
@@ -2,7 +2,9 @@
 
 use crate::config::{IntegrationsConfig, DbConfig};
 use sqlx::{postgres::PgPoolOptions, PgPool};
+use std::collections::HashMap;
 use std::time::Duration;
+use tokio::sync::{Semaphore, SemaphorePermit};
 
 pub async fn init_postgres(config: &IntegrationsConfig, db_config: &DbConfig) -> Option<PgPool> {
     if !config.enable_postgres {
@@ -60,3 +62,37 @@ pub async fn check_postgres_health(pool: &PgPool) -> Result<(), String> {
     }
 }
 
+/// Returned when every permit for a route's heavy queries is taken
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QuerySaturated;
+
+/// Caps concurrent heavy queries per route (`db.query_limits`) so a burst on
+/// one endpoint can't take every pool connection
+///
+/// Like `RedisLimiter`, callers never queue: a full route gets
+/// `QuerySaturated` immediately and can answer 503.
+#[derive(Default)]
+pub struct QueryLimits {
+    /// route pattern -> permits
+    routes: HashMap<String, Semaphore>,
+}
+
+impl QueryLimits {
+    pub fn new(limits: &HashMap<String, usize>) -> Self {
+        Self {
+            routes: limits
+                .iter()
+                .map(|(route, max)| (route.clone(), Semaphore::new((*max).max(1))))
+                .collect(),
+        }
+    }
+
+    /// Take a permit for the route's query, held until dropped; `None` if the route is unlimited
+    pub fn try_acquire(&self, route: &str) -> Result<Option<SemaphorePermit<'_>>, QuerySaturated> {
+        match self.routes.get(route) {
+            Some(permits) => permits.try_acquire().map(Some).map_err(|_| QuerySaturated),
+            None => Ok(None),
+        }
+    }
+}
+
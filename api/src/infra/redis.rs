@@ -1,8 +1,12 @@
 /// Redis integration
 
 use crate::config::IntegrationsConfig;
-use redis::{aio::ConnectionManager, Client};
-use std::time::Duration;
+use crate::metrics::AppMetrics;
+use redis::{aio::ConnectionManager, Client, Cmd, ErrorKind, FromRedisValue, Pipeline, RedisError, RedisResult};
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{Semaphore, SemaphorePermit};
 
 pub async fn init_redis(config: &IntegrationsConfig) -> Option<ConnectionManager> {
     if !config.enable_redis {
@@ -63,78 +67,324 @@ pub async fn check_redis_health(manager: &mut ConnectionManager) -> Result<(), S
     }
 }
 
-/// Check if IP is in ban list
-pub async fn is_in_ban_list(
-    manager: &mut ConnectionManager,
-    ip: &str,
-    key: &str,
-) -> Result<bool, String> {
-    redis::cmd("SISMEMBER")
-        .arg(key)
-        .arg(ip)
-        .query_async(manager)
-        .await
-        .map_err(|e| format!("Redis SISMEMBER error: {}", e))
+/// Typed Redis commands, each bounded by `integrations.redis_command_timeout_ms`
+///
+/// Every operation records `redis_command_duration_seconds{op}` and, when it
+/// fails or times out, `redis_command_errors_total{op}`. Cloning is cheap and
+/// shares the connection.
+#[derive(Clone)]
+pub struct RedisClient {
+    conn: ConnectionManager,
+    command_timeout: Duration,
+    metrics: Option<Arc<AppMetrics>>,
 }
 
-/// Check if IP is in grey list
-pub async fn is_in_grey_list(
-    manager: &mut ConnectionManager,
-    ip: &str,
-    key: &str,
-) -> Result<bool, String> {
-    redis::cmd("SISMEMBER")
-        .arg(key)
-        .arg(ip)
-        .query_async(manager)
-        .await
-        .map_err(|e| format!("Redis SISMEMBER error: {}", e))
+// Time out the command and record its latency and outcome under `op`
+async fn instrument<T>(
+    op: &'static str,
+    command_timeout: Duration,
+    metrics: Option<&AppMetrics>,
+    command: impl Future<Output = RedisResult<T>>,
+) -> RedisResult<T> {
+    let started = Instant::now();
+    let result = match tokio::time::timeout(command_timeout, command).await {
+        Ok(result) => result,
+        Err(_) => Err(RedisError::from((ErrorKind::IoError, "Redis command timed out"))),
+    };
+    if let Some(metrics) = metrics {
+        metrics.record_redis_command(op, started.elapsed(), result.is_ok());
+    }
+    result
 }
 
-/// Add IP to ban list
-pub async fn add_to_ban_list(
-    manager: &mut ConnectionManager,
-    ip: &str,
-    ttl_secs: u64,
-    key: &str,
-) -> Result<(), String> {
-    redis::pipe()
-        .cmd("SADD").arg(key).arg(ip).query_async::<()>(manager).await
-        .map_err(|e| format!("Redis SADD error: {}", e))?;
-    redis::cmd("EXPIRE")
-        .arg(key)
-        .arg(ttl_secs)
-        .query_async(manager)
+impl RedisClient {
+    pub fn new(conn: ConnectionManager, config: &IntegrationsConfig) -> Self {
+        Self {
+            conn,
+            command_timeout: Duration::from_millis(config.redis_command_timeout_ms),
+            metrics: None,
+        }
+    }
+
+    pub fn with_metrics(mut self, metrics: Arc<AppMetrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    async fn query<T: FromRedisValue>(&self, op: &'static str, cmd: &Cmd) -> RedisResult<T> {
+        let mut conn = self.conn.clone();
+        instrument(op, self.command_timeout, self.metrics.as_deref(), cmd.query_async(&mut conn)).await
+    }
+
+    async fn query_pipe(&self, op: &'static str, pipe: &Pipeline) -> RedisResult<()> {
+        let mut conn = self.conn.clone();
+        instrument(op, self.command_timeout, self.metrics.as_deref(), pipe.query_async(&mut conn)).await
+    }
+
+    pub async fn get_i64(&self, key: &str) -> RedisResult<Option<i64>> {
+        self.query("get", redis::cmd("GET").arg(key)).await
+    }
+
+    /// SET with a millisecond TTL
+    pub async fn set_px(&self, key: &str, value: i64, ttl_ms: i64) -> RedisResult<()> {
+        self.query("set", redis::cmd("SET").arg(key).arg(value).arg("PX").arg(ttl_ms.max(1)))
+            .await
+    }
+
+    pub async fn del(&self, key: &str) -> RedisResult<u64> {
+        self.query("del", redis::cmd("DEL").arg(key)).await
+    }
+
+    pub async fn zscore(&self, key: &str, member: &str) -> RedisResult<Option<f64>> {
+        self.query("zscore", redis::cmd("ZSCORE").arg(key).arg(member)).await
+    }
+
+    pub async fn sismember(&self, key: &str, member: &str) -> RedisResult<bool> {
+        self.query("sismember", redis::cmd("SISMEMBER").arg(key).arg(member)).await
+    }
+
+    /// Members scored strictly above `min`
+    pub async fn zcount_above(&self, key: &str, min: i64) -> RedisResult<u64> {
+        self.query("zcount", redis::cmd("ZCOUNT").arg(key).arg(format!("({}", min)).arg("+inf"))
+            .await
+    }
+
+    /// Remove members scored at or below `max`, returning how many went
+    pub async fn zremrangebyscore(&self, key: &str, max: i64) -> RedisResult<u64> {
+        self.query("zremrangebyscore", redis::cmd("ZREMRANGEBYSCORE").arg(key).arg("-inf").arg(max))
+            .await
+    }
+
+    /// Add `member` expiring at `expires_at` to a sorted set scored by expiry,
+    /// dropping members that expired by `prune_at` and keeping the key for `ttl_ms`
+    pub async fn zadd_ttl(&self, key: &str, member: &str, expires_at: i64, prune_at: i64, ttl_ms: i64) -> RedisResult<()> {
+        self.query_pipe(
+            "zadd_ttl",
+            redis::pipe()
+                .atomic()
+                .cmd("ZREMRANGEBYSCORE").arg(key).arg("-inf").arg(prune_at).ignore()
+                .cmd("ZADD").arg(key).arg(expires_at).arg(member).ignore()
+                // The newest member expires last, so the set can go with it
+                .cmd("PEXPIRE").arg(key).arg(ttl_ms).ignore(),
+        )
         .await
-        .map_err(|e| format!("Redis EXPIRE error: {}", e))
-}
+    }
 
-/// Add IP to grey list
-pub async fn add_to_grey_list(
-    manager: &mut ConnectionManager,
-    ip: &str,
-    ttl_secs: u64,
-    key: &str,
-) -> Result<(), String> {
-    redis::pipe()
-        .cmd("SADD").arg(key).arg(ip).query_async::<()>(manager).await
-        .map_err(|e| format!("Redis SADD error: {}", e))?;
-    redis::cmd("EXPIRE")
-        .arg(key)
-        .arg(ttl_secs)
-        .query_async(manager)
+    /// Like `zadd_ttl`, also keeping only the newest `max_outstanding` nonces
+    pub async fn set_nonce(
+        &self,
+        key: &str,
+        nonce: &str,
+        expires_at: i64,
+        prune_at: i64,
+        max_outstanding: usize,
+        ttl_ms: i64,
+    ) -> RedisResult<()> {
+        self.query_pipe(
+            "set_nonce",
+            redis::pipe()
+                .atomic()
+                .cmd("ZREMRANGEBYSCORE").arg(key).arg("-inf").arg(prune_at).ignore()
+                .cmd("ZADD").arg(key).arg(expires_at).arg(nonce).ignore()
+                .cmd("ZREMRANGEBYRANK").arg(key).arg(0).arg(-(max_outstanding as i64) - 1).ignore()
+                .cmd("PEXPIRE").arg(key).arg(ttl_ms).ignore(),
+        )
         .await
-        .map_err(|e| format!("Redis EXPIRE error: {}", e))
-}
+    }
+
+    /// Remove a single nonce; `false` if it wasn't there
+    pub async fn consume_nonce(&self, key: &str, nonce: &str) -> RedisResult<bool> {
+        let removed: u64 = self.query("consume_nonce", redis::cmd("ZREM").arg(key).arg(nonce)).await?;
+        Ok(removed > 0)
+    }
 
-/// Get set size
-pub async fn get_set_size(
-    manager: &mut ConnectionManager,
-    key: &str,
-) -> Result<u64, String> {
-    redis::cmd("SCARD")
-        .arg(key)
-        .query_async(manager)
+    /// Append a single-field entry to a stream capped at about `max_len`,
+    /// keeping the stream for `ttl_ms` after its last entry
+    pub async fn xadd_capped(&self, key: &str, max_len: usize, field: &str, value: &str, ttl_ms: i64) -> RedisResult<()> {
+        self.query_pipe(
+            "xadd",
+            redis::pipe()
+                .atomic()
+                .cmd("XADD").arg(key).arg("MAXLEN").arg("~").arg(max_len).arg("*").arg(field).arg(value).ignore()
+                .cmd("PEXPIRE").arg(key).arg(ttl_ms.max(1)).ignore(),
+        )
         .await
-        .map_err(|e| format!("Redis SCARD error: {}", e))
+    }
+
+    /// Newest `count` stream entries as `(id, [field, value, ...])`
+    pub async fn xrevrange(&self, key: &str, count: usize) -> RedisResult<Vec<(String, Vec<String>)>> {
+        self.query("xrevrange", redis::cmd("XREVRANGE").arg(key).arg("+").arg("-").arg("COUNT").arg(count))
+            .await
+    }
+}
+
+/// Returned when every Redis permit is taken
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RedisSaturated;
+
+/// Caps concurrent Redis commands so a slow Redis sheds load instead of
+/// piling up waiting requests
+///
+/// Permits are taken with `try_acquire`: callers never queue, they get
+/// `RedisSaturated` immediately and can answer 503.
+pub struct RedisLimiter {
+    permits: Semaphore,
+    max_inflight: usize,
+}
+
+impl RedisLimiter {
+    pub fn new(max_inflight: usize) -> Self {
+        let max_inflight = max_inflight.max(1);
+        Self {
+            permits: Semaphore::new(max_inflight),
+            max_inflight,
+        }
+    }
+
+    /// Take a permit for one Redis operation, held until dropped
+    pub fn try_acquire(&self) -> Result<SemaphorePermit<'_>, RedisSaturated> {
+        self.permits.try_acquire().map_err(|_| RedisSaturated)
+    }
+
+    /// Redis operations currently in flight (the `redis_inflight` gauge)
+    pub fn inflight(&self) -> usize {
+        self.max_inflight - self.permits.available_permits()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn samples(metrics: &AppMetrics, op: &str) -> (u64, u64) {
+        (
+            metrics.redis_command_duration_seconds.with_label_values(&[op]).get_sample_count(),
+            metrics.redis_command_errors_total.with_label_values(&[op]).get(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_commands_are_timed_out_and_counted_per_op() {
+        let metrics = AppMetrics::new().unwrap();
+        let timeout = Duration::from_millis(20);
+
+        let ok = instrument("get", timeout, Some(&metrics), async { Ok(Some(7i64)) }).await;
+        assert_eq!(ok.unwrap(), Some(7));
+        assert_eq!(samples(&metrics, "get"), (1, 0));
+
+        // A hung command is cut off at the timeout and counted as an error
+        let started = Instant::now();
+        let hung = instrument("zscore", timeout, Some(&metrics), std::future::pending::<RedisResult<()>>()).await;
+        assert_eq!(hung.unwrap_err().kind(), ErrorKind::IoError);
+        assert!(started.elapsed() < Duration::from_millis(500));
+        assert_eq!(samples(&metrics, "zscore"), (1, 1));
+
+        let failed = instrument("zscore", timeout, Some(&metrics), async {
+            Err::<(), _>(RedisError::from((ErrorKind::TypeError, "WRONGTYPE")))
+        })
+        .await;
+        assert!(failed.is_err());
+        assert_eq!(samples(&metrics, "zscore"), (2, 2));
+        assert!(metrics.render().contains(r#"redis_command_errors_total{op="zscore"} 2"#));
+    }
+
+    #[tokio::test]
+    async fn test_failed_command_is_recorded_against_unreachable_redis() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // A server that answers the connection handshake with OKs, then goes away
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = [0u8; 4096];
+            loop {
+                let n = socket.read(&mut buf).await.unwrap();
+                let commands = buf[..n].split(|b| *b == b'\n').filter(|line| line.starts_with(b"*")).count();
+                socket.write_all(&b"+OK\r\n".repeat(commands)).await.unwrap();
+            }
+        });
+        let config = IntegrationsConfig {
+            enable_redis: true,
+            redis_url: format!("redis://{}", addr),
+            redis_connect_timeout_ms: 500,
+            redis_command_timeout_ms: 100,
+            ..IntegrationsConfig::default()
+        };
+        let metrics = Arc::new(AppMetrics::new().unwrap());
+        let conn = init_redis(&config).await.expect("connection manager");
+        server.abort();
+        let _ = server.await;
+
+        let client = RedisClient::new(conn, &config).with_metrics(metrics.clone());
+        assert!(client.get_i64("test:unreachable").await.is_err());
+        assert_eq!(samples(&metrics, "get"), (1, 1));
+    }
+
+    #[tokio::test]
+    #[ignore = "needs Redis at REDIS_URL"]
+    async fn test_typed_commands_round_trip_against_redis() {
+        let config = IntegrationsConfig {
+            enable_redis: true,
+            redis_url: std::env::var("REDIS_URL").unwrap(),
+            ..IntegrationsConfig::default()
+        };
+        let metrics = Arc::new(AppMetrics::new().unwrap());
+        let client = RedisClient::new(init_redis(&config).await.unwrap(), &config).with_metrics(metrics.clone());
+        let key = format!("test:redis-client:{}", uuid::Uuid::new_v4());
+        let now = chrono::Utc::now().timestamp_millis();
+
+        client.set_nonce(&key, "n1", now + 60_000, now, 1, 60_000).await.unwrap();
+        client.set_nonce(&key, "n2", now + 60_000, now, 1, 60_000).await.unwrap();
+        // Only the newest nonce is kept
+        assert_eq!(client.zscore(&key, "n1").await.unwrap(), None);
+        assert_eq!(client.zscore(&key, "n2").await.unwrap(), Some((now + 60_000) as f64));
+        assert_eq!(client.zcount_above(&key, now).await.unwrap(), 1);
+        assert!(client.consume_nonce(&key, "n2").await.unwrap());
+        assert!(!client.consume_nonce(&key, "n2").await.unwrap());
+
+        client.zadd_ttl(&key, "10.0.0.1", now - 1, now - 1_000, 60_000).await.unwrap();
+        assert_eq!(client.zremrangebyscore(&key, now).await.unwrap(), 1);
+
+        client.set_px(&key, 42, 60_000).await.unwrap();
+        assert_eq!(client.get_i64(&key).await.unwrap(), Some(42));
+        assert_eq!(client.del(&key).await.unwrap(), 1);
+        assert_eq!(client.get_i64(&key).await.unwrap(), None);
+
+        assert_eq!(samples(&metrics, "set_nonce"), (2, 0));
+        assert_eq!(samples(&metrics, "consume_nonce"), (2, 0));
+        assert_eq!(samples(&metrics, "get"), (2, 0));
+    }
+
+    #[tokio::test]
+    async fn test_excess_operations_fail_fast_against_slow_redis() {
+        let limiter = Arc::new(RedisLimiter::new(4));
+        let started = std::time::Instant::now();
+
+        let tasks: Vec<_> = (0..20)
+            .map(|_| {
+                let limiter = limiter.clone();
+                tokio::spawn(async move {
+                    let _permit = limiter.try_acquire()?;
+                    // Slow Redis command
+                    tokio::time::sleep(Duration::from_millis(200)).await;
+                    Ok::<_, RedisSaturated>(())
+                })
+            })
+            .collect();
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(limiter.inflight(), 4);
+
+        let mut saturated = 0;
+        for task in tasks {
+            if task.await.unwrap() == Err(RedisSaturated) {
+                saturated += 1;
+            }
+        }
+        assert_eq!(saturated, 16);
+        assert_eq!(limiter.inflight(), 0);
+        // Rejected callers didn't wait behind the slow ones
+        assert!(started.elapsed() < Duration::from_millis(1000));
+    }
 }
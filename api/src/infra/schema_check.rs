@@ -0,0 +1,216 @@
+/// Database schema self-check
+///
+/// Compares the tables, columns and indexes the queries rely on with what
+/// `information_schema` and `pg_indexes` report, so a missed migration or a
+/// hand-edited schema shows up at startup rather than as slow or failing
+/// queries later.
+
+use crate::config::DbConfig;
+use sqlx::PgPool;
+use std::collections::HashSet;
+use std::time::Duration;
+
+/// A table and what the code expects of it
+struct ExpectedTable {
+    name: &'static str,
+    columns: &'static [&'static str],
+    indexes: &'static [&'static str],
+}
+
+/// Kept in step with `migrations/`
+const EXPECTED_TABLES: &[ExpectedTable] = &[
+    ExpectedTable {
+        name: "solana_transactions",
+        columns: &[
+            "signature",
+            "slot",
+            "from_pubkey",
+            "to_pubkey",
+            "lamports",
+            "program_ids",
+            "instructions",
+            "block_time",
+            "created_at",
+            "tx_type",
+        ],
+        indexes: &[
+            "idx_solana_tx_slot",
+            "idx_solana_tx_from_to",
+            "idx_solana_tx_block_time",
+            "idx_solana_tx_program_ids_gin",
+            "idx_solana_tx_instructions_gin",
+            "idx_tx_signature_prefix",
+            "idx_tx_from_pubkey_prefix",
+            "idx_tx_to_pubkey_prefix",
+            "idx_solana_tx_type_slot",
+            "idx_solana_tx_created_sig",
+        ],
+    },
+    ExpectedTable {
+        name: "users",
+        columns: &["id", "pubkey", "role", "created_at"],
+        indexes: &["idx_users_pubkey", "idx_users_role"],
+    },
+    ExpectedTable {
+        name: "user_permissions",
+        columns: &["id", "pubkey", "endpoint", "permission", "created_at"],
+        indexes: &["idx_user_permissions_pubkey", "idx_user_permissions_endpoint"],
+    },
+    ExpectedTable {
+        name: "auth_audit",
+        columns: &["id", "address", "method", "path", "result", "reason", "ip", "created_at"],
+        indexes: &["idx_auth_audit_address_created"],
+    },
+];
+
+/// Describe every way `schema` differs from `EXPECTED_TABLES`; empty when it matches
+pub async fn schema_drift(pool: &PgPool, schema: &str) -> Result<Vec<String>, sqlx::Error> {
+    let tables: HashSet<String> = sqlx::query_scalar(
+        "SELECT table_name::TEXT FROM information_schema.tables WHERE table_schema = $1",
+    )
+    .bind(schema)
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .collect();
+
+    let columns: HashSet<(String, String)> = sqlx::query_as(
+        "SELECT table_name::TEXT, column_name::TEXT FROM information_schema.columns WHERE table_schema = $1",
+    )
+    .bind(schema)
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .collect();
+
+    let indexes: HashSet<(String, String)> = sqlx::query_as(
+        "SELECT tablename::TEXT, indexname::TEXT FROM pg_indexes WHERE schemaname = $1",
+    )
+    .bind(schema)
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .collect();
+
+    let mut drift = Vec::new();
+    for table in EXPECTED_TABLES {
+        if !tables.contains(table.name) {
+            drift.push(format!("missing table {}", table.name));
+            continue;
+        }
+        for column in table.columns {
+            if !columns.contains(&(table.name.to_string(), column.to_string())) {
+                drift.push(format!("missing column {}.{}", table.name, column));
+            }
+        }
+        for index in table.indexes {
+            if !indexes.contains(&(table.name.to_string(), index.to_string())) {
+                drift.push(format!("missing index {} on {}", index, table.name));
+            }
+        }
+    }
+    Ok(drift)
+}
+
+/// Check the connection's current schema, logging each difference as a
+/// warning; with `db.strict_schema_check` any difference is an error
+pub async fn check_schema(pool: &PgPool, config: &DbConfig) -> Result<(), String> {
+    let schema: String = sqlx::query_scalar("SELECT current_schema()::TEXT")
+        .fetch_one(pool)
+        .await
+        .map_err(|e| format!("Schema check failed: {}", e))?;
+    let drift = schema_drift(pool, &schema)
+        .await
+        .map_err(|e| format!("Schema check failed: {}", e))?;
+
+    for problem in &drift {
+        tracing::warn!(schema = %schema, problem = %problem, "Database schema differs from what the service expects");
+    }
+    if drift.is_empty() {
+        tracing::info!(schema = %schema, "Database schema check passed");
+        Ok(())
+    } else if config.strict_schema_check {
+        Err(format!("Database schema check found {} problem(s): {}", drift.len(), drift.join("; ")))
+    } else {
+        Ok(())
+    }
+}
+
+/// Re-run the check every `interval`, logging drift introduced while running
+pub fn spawn_schema_check(pool: PgPool, interval: Duration) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        // The first tick is immediate and startup already checked
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            // A running instance only warns, whatever `db.strict_schema_check` says
+            if let Err(e) = check_schema(&pool, &DbConfig::default()).await {
+                tracing::warn!(error = %e, "Periodic schema check failed");
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MIGRATIONS: &[&str] = &[
+        include_str!("../../../migrations/20251015_solana_schema.sql"),
+        include_str!("../../../migrations/20251020_auth_audit.sql"),
+        include_str!("../../../migrations/20251101_prefix_search.sql"),
+        include_str!("../../../migrations/20251110_tx_type.sql"),
+        include_str!("../../../migrations/20251115_changes_feed.sql"),
+    ];
+
+    /// A throwaway schema with the migrations applied, on a single-connection pool
+    async fn migrated_schema() -> (PgPool, String) {
+        let pool = sqlx::postgres::PgPoolOptions::new()
+            .max_connections(1)
+            .connect(&std::env::var("DATABASE_URL").unwrap())
+            .await
+            .unwrap();
+        let schema = format!("schema_check_{}", uuid::Uuid::new_v4().simple());
+        sqlx::raw_sql(&format!("CREATE SCHEMA {schema}; SET search_path TO {schema}, public"))
+            .execute(&pool)
+            .await
+            .unwrap();
+        for migration in MIGRATIONS {
+            sqlx::raw_sql(migration).execute(&pool).await.unwrap();
+        }
+        (pool, schema)
+    }
+
+    #[tokio::test]
+    #[ignore = "needs Postgres at DATABASE_URL"]
+    async fn test_migrated_schema_passes_and_altered_schema_is_reported() {
+        let (pool, schema) = migrated_schema().await;
+        let strict = DbConfig { strict_schema_check: true, ..DbConfig::default() };
+
+        assert_eq!(schema_drift(&pool, &schema).await.unwrap(), Vec::<String>::new());
+        check_schema(&pool, &strict).await.unwrap();
+
+        sqlx::raw_sql(
+            "DROP INDEX idx_solana_tx_program_ids_gin; \
+             ALTER TABLE solana_transactions RENAME COLUMN block_time TO block_ts",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        assert_eq!(
+            schema_drift(&pool, &schema).await.unwrap(),
+            vec![
+                "missing column solana_transactions.block_time".to_string(),
+                "missing index idx_solana_tx_program_ids_gin on solana_transactions".to_string(),
+            ]
+        );
+        // Lenient mode only warns; strict mode refuses to start
+        check_schema(&pool, &DbConfig::default()).await.unwrap();
+        let err = check_schema(&pool, &strict).await.unwrap_err();
+        assert!(err.contains("2 problem(s)"), "{}", err);
+
+        sqlx::raw_sql(&format!("DROP SCHEMA {schema} CASCADE")).execute(&pool).await.unwrap();
+    }
+}
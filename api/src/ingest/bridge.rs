@@ -4,8 +4,9 @@
 /// and WebSocket layer for real-time transaction events.
 
 use crate::ingest::{WsEvent, IngestStats};
+use crate::ws::fanout::WsFanout;
 use std::sync::Arc;
-use tokio::sync::mpsc;
+use tokio::sync::{broadcast, mpsc};
 use tracing::{debug, error, warn};
 
 /// WebSocket event sender
@@ -21,10 +22,9 @@ pub struct WsBridge {
 }
 
 impl WsBridge {
-    /// Create new WebSocket bridge
-    pub fn new() -> (Self, WsEventReceiver) {
+    /// Create new WebSocket bridge sharing the given ingest statistics
+    pub fn new(stats: Arc<std::sync::Mutex<IngestStats>>) -> (Self, WsEventReceiver) {
         let (sender, receiver) = mpsc::unbounded_channel();
-        let stats = Arc::new(std::sync::Mutex::new(IngestStats::default()));
         
         let bridge = Self {
             sender,
@@ -58,16 +58,7 @@ impl WsBridge {
     /// Get current statistics
     pub fn get_stats(&self) -> IngestStats {
         if let Ok(stats) = self.stats.lock() {
-            IngestStats {
-                messages_received: stats.messages_received,
-                messages_processed: stats.messages_processed,
-                messages_inserted: stats.messages_inserted,
-                messages_skipped: stats.messages_skipped,
-                messages_failed: stats.messages_failed,
-                dlq_messages_sent: stats.dlq_messages_sent,
-                ws_events_emitted: stats.ws_events_emitted,
-                last_processed_at: stats.last_processed_at,
-            }
+            stats.clone()
         } else {
             IngestStats::default()
         }
@@ -86,14 +77,16 @@ impl Clone for WsBridge {
 /// WebSocket event distributor
 pub struct WsEventDistributor {
     receiver: WsEventReceiver,
-    // This would connect to the actual WebSocket layer
-    // For now, we'll just log the events
+    /// Shared bus for in-process subscribers such as long-poll requests
+    events: broadcast::Sender<WsEvent>,
+    /// WebSocket transaction subscriptions
+    fanout: Arc<WsFanout>,
 }
 
 impl WsEventDistributor {
     /// Create new event distributor
-    pub fn new(receiver: WsEventReceiver) -> Self {
-        Self { receiver }
+    pub fn new(receiver: WsEventReceiver, events: broadcast::Sender<WsEvent>, fanout: Arc<WsFanout>) -> Self {
+        Self { receiver, events, fanout }
     }
     
     /// Start distributing events to WebSocket connections
@@ -107,24 +100,21 @@ impl WsEventDistributor {
                 event.transaction.get("signature").and_then(|v| v.as_str()).unwrap_or("unknown")
             );
             
-            // TODO: Integrate with actual WebSocket layer
-            // For now, just log the event
             self.handle_event(event).await;
         }
     }
     
     /// Handle individual event
     async fn handle_event(&self, event: WsEvent) {
-        // This is where we would:
-        // 1. Get all active WebSocket connections
-        // 2. Check if transaction matches their filters
-        // 3. Send EVENT message to matching connections
-        
         debug!("Handling WebSocket event: {:?}", event);
-        
-        // For now, just log the transaction
+
+        // Send to matching WebSocket subscriptions, concurrently up to the fan-out limit
+        let delivered = self.fanout.publish(&event.transaction).await;
         if let Some(signature) = event.transaction.get("signature").and_then(|v| v.as_str()) {
-            debug!("Transaction event for signature: {}", signature);
+            debug!("Transaction event for signature {} sent to {} subscriptions", signature, delivered);
         }
+
+        // No receivers is normal when nobody is waiting
+        let _ = self.events.send(event);
     }
 }
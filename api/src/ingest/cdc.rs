@@ -0,0 +1,99 @@
+/// Change-data-capture of persisted transactions
+///
+/// With `ingest.publish_normalized_topic` set, every transaction a batch
+/// actually inserted is published to that topic as JSON, keyed by signature so
+/// one signature always lands on the same partition. Rows skipped as
+/// duplicates were published when first inserted and aren't sent again.
+/// Publishing is best-effort: the rows are already committed, so a failed send
+/// is logged rather than retried.
+
+use crate::ingest::{BatchResult, NormalizedTransaction};
+use std::collections::HashSet;
+use std::future::Future;
+
+/// The transactions of `batch` that `result` reports as inserted
+pub fn inserted<'a>(batch: &'a [NormalizedTransaction], result: &BatchResult) -> Vec<&'a NormalizedTransaction> {
+    let signatures: HashSet<&str> = result.inserted_signatures.iter().map(String::as_str).collect();
+    batch
+        .iter()
+        .filter(|tx| signatures.contains(tx.signature.as_str()))
+        .collect()
+}
+
+/// Publish each transaction as `(signature, JSON)`; returns how many were sent
+pub async fn publish_inserted<F, Fut>(transactions: &[&NormalizedTransaction], mut publish: F) -> usize
+where
+    F: FnMut(String, Vec<u8>) -> Fut,
+    Fut: Future<Output = Result<(), String>>,
+{
+    let mut published = 0;
+    for tx in transactions {
+        let payload = match serde_json::to_vec(tx) {
+            Ok(payload) => payload,
+            Err(e) => {
+                tracing::error!(signature = %tx.signature, error = %e, "Failed to serialize transaction for CDC");
+                continue;
+            }
+        };
+        match publish(tx.signature.clone(), payload).await {
+            Ok(()) => published += 1,
+            Err(e) => tracing::error!(signature = %tx.signature, error = %e, "Failed to publish normalized transaction"),
+        }
+    }
+    published
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transaction(signature: &str) -> NormalizedTransaction {
+        NormalizedTransaction {
+            signature: signature.to_string(),
+            slot: 7,
+            from_pubkey: Some("alice".to_string()),
+            to_pubkey: None,
+            lamports: Some(5),
+            program_ids: None,
+            instructions: serde_json::json!([]),
+            block_time: None,
+            tx_type: None,
+            instructions_truncated: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_only_inserted_transactions_are_published() {
+        let batch: Vec<_> = ["sig1", "sig2", "sig3"].into_iter().map(transaction).collect();
+        // sig2 was already stored
+        let result = BatchResult {
+            processed: 3,
+            inserted: 2,
+            skipped: 1,
+            inserted_signatures: vec!["sig3".to_string(), "sig1".to_string()],
+            errors: vec![],
+        };
+
+        let mut sent = Vec::new();
+        let published = publish_inserted(&inserted(&batch, &result), |key, payload| {
+            sent.push((key, payload));
+            async { Ok(()) }
+        })
+        .await;
+
+        assert_eq!(published, 2);
+        let keys: Vec<&str> = sent.iter().map(|(key, _)| key.as_str()).collect();
+        assert_eq!(keys, ["sig1", "sig3"]);
+        let body: serde_json::Value = serde_json::from_slice(&sent[0].1).unwrap();
+        assert_eq!(body["signature"], "sig1");
+        assert_eq!(body["from_pubkey"], "alice");
+        assert!(body.get("instructions_truncated").is_none());
+
+        // A failed send is skipped, not retried
+        let published = publish_inserted(&inserted(&batch, &result), |key, _| async move {
+            if key == "sig1" { Err("broker down".to_string()) } else { Ok(()) }
+        })
+        .await;
+        assert_eq!(published, 1);
+    }
+}
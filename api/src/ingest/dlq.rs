@@ -0,0 +1,114 @@
+/// DLQ message encoding
+///
+/// DLQ payloads are JSON-serialized `DlqMessage`s, optionally gzipped when
+/// `kafka.dlq_compress` is set. Compressed records carry a
+/// `content-encoding: gzip` header; consumers can also sniff the gzip magic.
+
+use crate::ingest::{
+    normalize::{decompress_gzip, is_gzip},
+    DlqMessage, ProcessingError,
+};
+use flate2::{write::GzEncoder, Compression};
+use rdkafka::message::{Header, OwnedHeaders};
+use std::io::Write;
+
+/// Header marking the payload encoding
+pub const DLQ_ENCODING_HEADER: &str = "content-encoding";
+
+const GZIP_ENCODING: &str = "gzip";
+
+/// Serialized DLQ record payload
+pub struct EncodedDlq {
+    pub payload: Vec<u8>,
+    pub compressed: bool,
+}
+
+impl EncodedDlq {
+    /// Headers to attach to the record, if any
+    pub fn headers(&self) -> Option<OwnedHeaders> {
+        self.compressed.then(|| {
+            OwnedHeaders::new().insert(Header {
+                key: DLQ_ENCODING_HEADER,
+                value: Some(GZIP_ENCODING),
+            })
+        })
+    }
+}
+
+/// Serialize a DLQ message, gzipping it when `compress` is set
+pub fn encode_dlq_message(message: &DlqMessage, compress: bool) -> Result<EncodedDlq, String> {
+    let json = serde_json::to_vec(message).map_err(|e| format!("Failed to serialize DLQ message: {}", e))?;
+    if !compress {
+        return Ok(EncodedDlq { payload: json, compressed: false });
+    }
+
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder
+        .write_all(&json)
+        .and_then(|_| encoder.finish())
+        .map(|payload| EncodedDlq { payload, compressed: true })
+        .map_err(|e| format!("Failed to compress DLQ message: {}", e))
+}
+
+/// Decode a DLQ record payload, inflating it if the header or magic bytes say gzip
+pub fn decode_dlq_message(
+    payload: &[u8],
+    encoding: Option<&[u8]>,
+    max_bytes: usize,
+) -> Result<DlqMessage, ProcessingError> {
+    let compressed = encoding.is_some_and(|e| e.eq_ignore_ascii_case(GZIP_ENCODING.as_bytes())) || is_gzip(payload);
+    let decompressed;
+    let json = if compressed {
+        decompressed = decompress_gzip(payload, max_bytes)?;
+        &decompressed[..]
+    } else {
+        payload
+    };
+
+    serde_json::from_slice(json).map_err(|e| ProcessingError::ParseError {
+        message: "Invalid DLQ message".to_string(),
+        error: e.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rdkafka::message::Headers;
+
+    fn large_dlq_message() -> DlqMessage {
+        let original = format!(r#"{{"signature":"sig1","slot":42,"pad":"{}"}}"#, "a".repeat(200_000));
+        DlqMessage {
+            original_message: serde_json::Value::String(original),
+            error: "ParseError".to_string(),
+            timestamp: chrono::Utc::now(),
+            retry_count: 0,
+        }
+    }
+
+    #[test]
+    fn test_large_dlq_message_is_compressed_and_round_trips() {
+        let message = large_dlq_message();
+        let plain = encode_dlq_message(&message, false).unwrap();
+        let encoded = encode_dlq_message(&message, true).unwrap();
+
+        assert!(encoded.compressed && is_gzip(&encoded.payload));
+        assert!(encoded.payload.len() < plain.payload.len() / 10);
+
+        let headers = encoded.headers().unwrap();
+        let header = headers.get(0);
+        assert_eq!(header.key, DLQ_ENCODING_HEADER);
+        assert_eq!(header.value, Some(GZIP_ENCODING.as_bytes()));
+
+        let decoded = decode_dlq_message(&encoded.payload, header.value, 1024 * 1024).unwrap();
+        assert_eq!(decoded.original_message, message.original_message);
+        assert_eq!(decoded.error, message.error);
+    }
+
+    #[test]
+    fn test_uncompressed_dlq_message_has_no_header() {
+        let encoded = encode_dlq_message(&large_dlq_message(), false).unwrap();
+        assert!(encoded.headers().is_none());
+        assert!(decode_dlq_message(&encoded.payload, None, 1024).is_ok());
+    }
+}
@@ -0,0 +1,142 @@
+/// DLQ inspection
+///
+/// Reads DLQ records for triage without taking them off the topic: each
+/// inspection uses its own consumer group and never commits, so a real DLQ
+/// drainer or replay still sees every record. Original payloads are cut to
+/// `ORIGINAL_PREVIEW_CHARS` to keep responses small.
+
+use crate::{
+    config::KafkaConfig,
+    ingest::{
+        dlq::decode_dlq_message,
+        kafka::{client_config, probe_brokers},
+        replay::{dlq_records, DlqRecord, MAX_DLQ_RECORD_BYTES},
+    },
+};
+use futures_util::stream::{Stream, StreamExt};
+use rdkafka::consumer::{Consumer, StreamConsumer};
+use serde::Serialize;
+
+/// Characters of the original message kept per entry
+pub const ORIGINAL_PREVIEW_CHARS: usize = 2048;
+
+#[derive(Debug, Serialize)]
+pub struct DlqEntry {
+    pub error: String,
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    pub retry_count: u32,
+    /// The original payload, as the raw string when it was one
+    pub original: String,
+    pub truncated: bool,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct DlqInspection {
+    pub messages: Vec<DlqEntry>,
+    /// Records that didn't decode as a `DlqMessage`
+    pub malformed: usize,
+}
+
+fn preview(original: &serde_json::Value) -> (String, bool) {
+    let text = match original {
+        serde_json::Value::String(raw) => raw.clone(),
+        other => other.to_string(),
+    };
+    match text.char_indices().nth(ORIGINAL_PREVIEW_CHARS) {
+        Some((end, _)) => (text[..end].to_string(), true),
+        None => (text, false),
+    }
+}
+
+/// Decode DLQ records into entries, counting the ones that don't decode
+pub async fn inspect_records<S>(records: S, max_bytes: usize) -> DlqInspection
+where
+    S: Stream<Item = DlqRecord>,
+{
+    let mut inspection = DlqInspection::default();
+    let mut records = std::pin::pin!(records);
+
+    while let Some(record) = records.next().await {
+        match decode_dlq_message(&record.payload, record.encoding.as_deref(), max_bytes) {
+            Ok(message) => {
+                let (original, truncated) = preview(&message.original_message);
+                inspection.messages.push(DlqEntry {
+                    error: message.error,
+                    timestamp: message.timestamp,
+                    retry_count: message.retry_count,
+                    original,
+                    truncated,
+                });
+            }
+            Err(e) => {
+                tracing::debug!("Malformed DLQ record during inspection: {:?}", e);
+                inspection.malformed += 1;
+            }
+        }
+    }
+
+    inspection
+}
+
+/// Read up to `max` records from the start of the DLQ topic, without committing
+pub async fn inspect_dlq(config: &KafkaConfig, max: usize) -> Result<DlqInspection, String> {
+    probe_brokers(config, &config.dlq_topic).await?;
+    let consumer: StreamConsumer = client_config(config)
+        .set("group.id", format!("{}-dlq-inspect-{}", config.group_id, uuid::Uuid::new_v4()))
+        .set("enable.auto.commit", "false")
+        .set("auto.offset.reset", "earliest")
+        .create()
+        .map_err(|e| format!("Failed to create DLQ consumer: {}", e))?;
+    consumer
+        .subscribe(&[&config.dlq_topic])
+        .map_err(|e| format!("Failed to subscribe to topic {}: {}", config.dlq_topic, e))?;
+
+    Ok(inspect_records(dlq_records(&consumer).take(max), MAX_DLQ_RECORD_BYTES).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ingest::{dlq::encode_dlq_message, DlqMessage};
+    use futures_util::stream;
+
+    fn record(original: serde_json::Value, compress: bool) -> DlqRecord {
+        let message = DlqMessage {
+            original_message: original,
+            error: "ParseError".to_string(),
+            timestamp: chrono::Utc::now(),
+            retry_count: 2,
+        };
+        let encoded = encode_dlq_message(&message, compress).unwrap();
+        DlqRecord {
+            encoding: encoded.headers().map(|_| b"gzip".to_vec()),
+            payload: encoded.payload,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_inspection_decodes_and_truncates_originals() {
+        let large = format!(r#"{{"signature":"sig2","pad":"{}"}}"#, "é".repeat(5000));
+        let records = vec![
+            record(serde_json::json!(r#"{"signature":"sig1","slot":1}"#), false),
+            record(serde_json::json!(large), true),
+            record(serde_json::json!({ "signature": "sig3", "slot": 3 }), false),
+            DlqRecord { payload: b"not json".to_vec(), encoding: None },
+        ];
+
+        let inspection = inspect_records(stream::iter(records), 1024 * 1024).await;
+        assert_eq!(inspection.malformed, 1);
+        assert_eq!(inspection.messages.len(), 3);
+
+        let first = &inspection.messages[0];
+        assert_eq!(first.original, r#"{"signature":"sig1","slot":1}"#);
+        assert_eq!((first.error.as_str(), first.retry_count, first.truncated), ("ParseError", 2, false));
+
+        let second = &inspection.messages[1];
+        assert!(second.truncated);
+        assert_eq!(second.original.chars().count(), ORIGINAL_PREVIEW_CHARS);
+        assert!(second.original.starts_with(r#"{"signature":"sig2""#));
+
+        assert!(inspection.messages[2].original.contains(r#""signature":"sig3""#));
+    }
+}
@@ -0,0 +1,134 @@
+/// Instruction schema validation
+///
+/// When `ingest.instruction_schema` points at a JSON Schema file, each
+/// transaction's `instructions` must conform to it or the message goes to the
+/// DLQ. Supported keywords: `type`, `enum`, `const`, `required`,
+/// `properties`, `additionalProperties` (boolean), `items`, `minItems`,
+/// `maxItems`, `minLength`, `maxLength`. Other keywords are ignored.
+
+use serde_json::Value;
+
+/// Loaded instruction schema
+#[derive(Debug, Clone)]
+pub struct InstructionSchema {
+    schema: Value,
+}
+
+impl InstructionSchema {
+    pub fn new(schema: Value) -> Result<Self, String> {
+        if !schema.is_object() && !schema.is_boolean() {
+            return Err("instruction schema must be a JSON object".to_string());
+        }
+        Ok(Self { schema })
+    }
+
+    /// Load the schema from `ingest.instruction_schema`, if set
+    pub fn from_path(path: &str) -> Result<Option<Self>, String> {
+        if path.trim().is_empty() {
+            return Ok(None);
+        }
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read instruction schema {}: {}", path, e))?;
+        let schema = serde_json::from_str(&contents)
+            .map_err(|e| format!("Invalid instruction schema {}: {}", path, e))?;
+        Self::new(schema).map(Some)
+    }
+
+    /// Check `instructions` against the schema, describing the first mismatch
+    pub fn validate(&self, instructions: &Value) -> Result<(), String> {
+        check(&self.schema, instructions, "")
+    }
+}
+
+fn type_matches(expected: &str, value: &Value) -> bool {
+    match expected {
+        "null" => value.is_null(),
+        "boolean" => value.is_boolean(),
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "number" => value.is_number(),
+        "integer" => value.is_i64() || value.is_u64(),
+        _ => false,
+    }
+}
+
+fn check(schema: &Value, value: &Value, path: &str) -> Result<(), String> {
+    let at = |path: &str| if path.is_empty() { "/".to_string() } else { path.to_string() };
+    let schema = match schema {
+        Value::Bool(true) => return Ok(()),
+        Value::Bool(false) => return Err(format!("{}: not allowed", at(path))),
+        Value::Object(schema) => schema,
+        _ => return Ok(()),
+    };
+
+    if let Some(expected) = schema.get("type") {
+        let types: Vec<&str> = match expected {
+            Value::String(t) => vec![t.as_str()],
+            Value::Array(ts) => ts.iter().filter_map(|t| t.as_str()).collect(),
+            _ => vec![],
+        };
+        if !types.is_empty() && !types.iter().any(|t| type_matches(t, value)) {
+            return Err(format!("{}: expected {}", at(path), types.join(" or ")));
+        }
+    }
+
+    if let Some(allowed) = schema.get("enum").and_then(|e| e.as_array()) {
+        if !allowed.contains(value) {
+            return Err(format!("{}: value not in enum", at(path)));
+        }
+    }
+    if let Some(expected) = schema.get("const") {
+        if expected != value {
+            return Err(format!("{}: expected {}", at(path), expected));
+        }
+    }
+
+    if let Some(s) = value.as_str() {
+        let len = s.chars().count() as u64;
+        if schema.get("minLength").and_then(|n| n.as_u64()).is_some_and(|min| len < min) {
+            return Err(format!("{}: string too short", at(path)));
+        }
+        if schema.get("maxLength").and_then(|n| n.as_u64()).is_some_and(|max| len > max) {
+            return Err(format!("{}: string too long", at(path)));
+        }
+    }
+
+    if let Some(items) = value.as_array() {
+        let len = items.len() as u64;
+        if schema.get("minItems").and_then(|n| n.as_u64()).is_some_and(|min| len < min) {
+            return Err(format!("{}: expected at least {} items", at(path), schema["minItems"]));
+        }
+        if schema.get("maxItems").and_then(|n| n.as_u64()).is_some_and(|max| len > max) {
+            return Err(format!("{}: expected at most {} items", at(path), schema["maxItems"]));
+        }
+        if let Some(item_schema) = schema.get("items") {
+            for (i, item) in items.iter().enumerate() {
+                check(item_schema, item, &format!("{}/{}", path, i))?;
+            }
+        }
+    }
+
+    if let Some(fields) = value.as_object() {
+        for required in schema.get("required").and_then(|r| r.as_array()).into_iter().flatten() {
+            if let Some(name) = required.as_str() {
+                if !fields.contains_key(name) {
+                    return Err(format!("{}: missing required property '{}'", at(path), name));
+                }
+            }
+        }
+
+        let properties = schema.get("properties").and_then(|p| p.as_object());
+        for (name, field) in fields {
+            match properties.and_then(|p| p.get(name)) {
+                Some(field_schema) => check(field_schema, field, &format!("{}/{}", path, name))?,
+                None if schema.get("additionalProperties") == Some(&Value::Bool(false)) => {
+                    return Err(format!("{}: unexpected property '{}'", at(path), name));
+                }
+                None => {}
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -9,22 +9,40 @@ use crate::{
     ingest::{
         normalize::{normalize_transaction, parse_raw_message, validate_normalized},
         bridge::{WsBridge, WsEventDistributor},
+        cdc,
+        dlq::encode_dlq_message,
+        instruction_schema::InstructionSchema,
+        replay::replay_count,
+        writer::BatchWriterPool,
         BatchResult, DlqMessage, IngestStats, NormalizedTransaction, ProcessingError, RawTransaction,
     },
     repository::transactions::TransactionRepository,
 };
 use rdkafka::{
-    consumer::{Consumer, StreamConsumer},
-    producer::{FutureProducer, FutureRecord},
+    consumer::{BaseConsumer, CommitMode, Consumer, StreamConsumer},
+    producer::{FutureProducer, FutureRecord, Producer},
     ClientConfig, Message,
 };
+use std::sync::Arc;
 use std::time::Duration;
+use tokio::sync::oneshot;
 use tokio::time::sleep;
 use tracing::{debug, error, info, warn};
 
+/// Time DLQ messages still queued at shutdown get to reach the broker
+const DLQ_FLUSH_TIMEOUT: Duration = Duration::from_secs(5);
+
 /// Kafka ingestion service
 pub struct KafkaIngestion {
     consumer: StreamConsumer,
+    instruction_schema: Option<InstructionSchema>,
+    processor: Arc<BatchProcessor>,
+    writers: BatchWriterPool<Vec<NormalizedTransaction>>,
+}
+
+/// Writes batches to the database; shared by the writer tasks
+#[derive(Clone)]
+struct BatchProcessor {
     producer: FutureProducer,
     config: KafkaConfig,
     ingest_config: IngestConfig,
@@ -40,9 +58,13 @@ impl KafkaIngestion {
         app_state: AppState,
         ws_bridge: WsBridge,
     ) -> Result<Self, String> {
+        let instruction_schema = InstructionSchema::from_path(&ingest_config.instruction_schema)?;
+        if instruction_schema.is_some() {
+            info!("Validating instructions against {}", ingest_config.instruction_schema);
+        }
+
         // Create consumer
-        let consumer: StreamConsumer = ClientConfig::new()
-            .set("bootstrap.servers", &config.brokers)
+        let consumer: StreamConsumer = client_config(&config)
             .set("group.id", &config.group_id)
             .set("enable.auto.commit", "false")
             .set("session.timeout.ms", &config.session_timeout_ms.to_string())
@@ -52,8 +74,7 @@ impl KafkaIngestion {
             .map_err(|e| format!("Failed to create Kafka consumer: {}", e))?;
 
         // Create producer for DLQ
-        let producer: FutureProducer = ClientConfig::new()
-            .set("bootstrap.servers", &config.brokers)
+        let producer: FutureProducer = client_config(&config)
             .create()
             .map_err(|e| format!("Failed to create Kafka producer: {}", e))?;
 
@@ -63,22 +84,46 @@ impl KafkaIngestion {
             .map_err(|e| format!("Failed to subscribe to topic {}: {}", config.input_topic, e))?;
 
         info!(
-            "Kafka ingestion initialized: brokers={}, group_id={}, input_topic={}",
-            config.brokers, config.group_id, config.input_topic
+            "Kafka ingestion initialized: brokers={}, group_id={}, input_topic={}, security_protocol={}, sasl_mechanism={}, sasl_username={}",
+            config.brokers,
+            config.group_id,
+            config.input_topic,
+            config.security_protocol,
+            config.sasl_mechanism,
+            config.sasl_username
         );
 
-        Ok(Self {
-            consumer,
+        let processor = Arc::new(BatchProcessor {
             producer,
             config,
             ingest_config,
             app_state,
             ws_bridge,
+        });
+
+        // At most `max_inflight_batches` concurrent inserts, plus a bounded backlog
+        let writers = {
+            let processor = processor.clone();
+            BatchWriterPool::spawn(
+                processor.ingest_config.max_inflight_batches as usize,
+                processor.ingest_config.max_pending_batches,
+                move |batch| {
+                    let processor = processor.clone();
+                    async move { processor.process_batch(batch).await }
+                },
+            )
+        };
+
+        Ok(Self {
+            consumer,
+            instruction_schema,
+            processor,
+            writers,
         })
     }
 
-    /// Start the ingestion loop
-    pub async fn run(&mut self) -> Result<(), String> {
+    /// Run the ingestion loop until `shutdown` fires, then flush and commit
+    pub async fn run(self, mut shutdown: oneshot::Receiver<()>) -> Result<(), String> {
         info!("Starting Kafka ingestion loop");
         
         let mut batch = Vec::new();
@@ -86,20 +131,26 @@ impl KafkaIngestion {
         
         loop {
             // Poll for messages
-            match self.consumer.recv().await {
+            let received = tokio::select! {
+                _ = &mut shutdown => break,
+                received = self.consumer.recv() => received,
+            };
+            match received {
                 Ok(message) => {
                     debug!("Received message from partition {}", message.partition());
+                    self.record_stats(|stats| stats.record_message_received());
                     
                     // Process message
                     match self.process_message(&message).await {
                         Ok(Some(normalized)) => {
+                            self.record_stats(|stats| stats.record_message_processed());
                             batch.push(normalized);
                             
                             // Process batch if full or timeout reached
-                            if batch.len() >= self.ingest_config.db_insert_batch_size
-                                || last_poll.elapsed() >= Duration::from_millis(self.config.poll_interval_ms)
+                            if batch.len() >= self.processor.ingest_config.db_insert_batch_size
+                                || last_poll.elapsed() >= Duration::from_millis(self.processor.config.poll_interval_ms)
                             {
-                                self.process_batch(&mut batch).await;
+                                self.dispatch_batch(std::mem::take(&mut batch)).await;
                                 last_poll = std::time::Instant::now();
                             }
                         }
@@ -108,16 +159,72 @@ impl KafkaIngestion {
                         }
                         Err(e) => {
                             error!("Failed to process message: {:?}", e);
+                            self.record_stats(|stats| stats.record_message_failed());
                             self.send_to_dlq(&message, &e).await;
                         }
                     }
                 }
                 Err(e) => {
                     error!("Kafka consumer error: {}", e);
-                    sleep(Duration::from_millis(self.config.retry_backoff_ms)).await;
+                    sleep(Duration::from_millis(self.processor.config.retry_backoff_ms)).await;
                 }
             }
         }
+
+        self.finish(batch).await
+    }
+
+    /// Write everything consumed so far, flush the DLQ producer and commit
+    /// the consumed offsets
+    async fn finish(self, batch: Vec<NormalizedTransaction>) -> Result<(), String> {
+        info!(pending = batch.len(), "Kafka ingestion stopping, writing pending batches");
+        if !batch.is_empty() {
+            self.dispatch_batch(batch).await;
+        }
+        self.writers.close().await;
+
+        let producer = self.processor.producer.clone();
+        tokio::task::spawn_blocking(move || producer.flush(DLQ_FLUSH_TIMEOUT))
+            .await
+            .map_err(|e| format!("DLQ producer flush failed: {}", e))?
+            .map_err(|e| format!("DLQ producer flush failed: {}", e))?;
+
+        // Fails harmlessly when nothing was consumed since startup
+        if let Err(e) = self.consumer.commit_consumer_state(CommitMode::Sync) {
+            warn!("Failed to commit Kafka offsets at shutdown: {}", e);
+        }
+        info!("Kafka ingestion stopped");
+        Ok(())
+    }
+
+    /// Hand a batch to the writers, pausing consumption while the queue is full
+    async fn dispatch_batch(&self, batch: Vec<NormalizedTransaction>) {
+        let batch = match self.writers.try_submit(batch) {
+            Ok(()) => return,
+            Err(batch) => batch,
+        };
+
+        // Stop fetching so Kafka holds the backlog instead of our memory;
+        // nothing is committed for messages that haven't been written
+        warn!("Batch queue saturated, pausing Kafka consumption");
+        self.record_stats(|stats| stats.backpressure_pauses += 1);
+        let assignment = self.consumer.assignment();
+        if let Ok(partitions) = &assignment {
+            if let Err(e) = self.consumer.pause(partitions) {
+                warn!("Failed to pause Kafka consumer: {}", e);
+            }
+        }
+
+        if let Err(e) = self.writers.submit(batch).await {
+            error!("{}, dropping batch", e);
+        }
+
+        if let Ok(partitions) = &assignment {
+            if let Err(e) = self.consumer.resume(partitions) {
+                error!("Failed to resume Kafka consumer: {}", e);
+            }
+        }
+        debug!("Batch queue drained, resumed Kafka consumption");
     }
 
     /// Process individual message
@@ -133,20 +240,68 @@ impl KafkaIngestion {
         })?;
 
         // Parse raw message
-        let raw = parse_raw_message(payload)?;
+        let raw = parse_raw_message(payload, &self.processor.ingest_config)?;
         
         // Normalize transaction
-        let normalized = normalize_transaction(&raw)?;
+        let normalized = normalize_transaction(&raw, &self.processor.ingest_config)?;
+        if normalized.instructions_truncated {
+            self.record_stats(|stats| stats.instructions_truncated += 1);
+        }
         
         // Validate normalized transaction
-        validate_normalized(&normalized)?;
+        validate_normalized(&normalized, self.instruction_schema.as_ref())?;
         
         debug!("Processed transaction: signature={}", normalized.signature);
         Ok(Some(normalized))
     }
 
+    /// Update the shared ingest statistics
+    fn record_stats(&self, update: impl FnOnce(&mut IngestStats)) {
+        self.processor.record_stats(update);
+    }
+
+    /// Send message to DLQ
+    async fn send_to_dlq(&self, message: &rdkafka::message::BorrowedMessage<'_>, error: &ProcessingError) {
+        let dlq_message = DlqMessage {
+            original_message: serde_json::Value::String(
+                String::from_utf8_lossy(message.payload().unwrap_or(&[])).to_string()
+            ),
+            error: format!("{:?}", error),
+            timestamp: chrono::Utc::now(),
+            retry_count: replay_count(message),
+        };
+
+        let encoded = match encode_dlq_message(&dlq_message, self.processor.config.dlq_compress) {
+            Ok(encoded) => encoded,
+            Err(e) => {
+                error!("{}", e);
+                return;
+            }
+        };
+
+        let key = format!("dlq-{}", chrono::Utc::now().timestamp());
+        let mut record = FutureRecord::to(&self.processor.config.dlq_topic)
+            .payload(&encoded.payload)
+            .key(&key);
+        if let Some(headers) = encoded.headers() {
+            record = record.headers(headers);
+        }
+
+        match self.processor.producer.send(record, Duration::from_secs(5)).await {
+            Ok(_) => {
+                debug!("Message sent to DLQ: {}", self.processor.config.dlq_topic);
+                self.record_stats(|stats| stats.record_dlq_sent());
+            }
+            Err((e, _)) => {
+                error!("Failed to send message to DLQ: {}", e);
+            }
+        }
+    }
+}
+
+impl BatchProcessor {
     /// Process batch of transactions
-    async fn process_batch(&self, batch: &mut Vec<NormalizedTransaction>) {
+    async fn process_batch(&self, batch: Vec<NormalizedTransaction>) {
         if batch.is_empty() {
             return;
         }
@@ -167,7 +322,7 @@ impl KafkaIngestion {
         
         // Process batch with retries
         let mut retry_count = 0;
-        let processed_batch = batch.clone();
+        let processed_batch = batch;
         
         while retry_count < self.config.max_retries {
             match repo.bulk_insert_or_ignore(&processed_batch).await {
@@ -176,14 +331,41 @@ impl KafkaIngestion {
                         "Batch processed: total={}, inserted={}, skipped={}",
                         result.processed, result.inserted, result.skipped
                     );
+                    self.record_stats(|stats| {
+                        stats.messages_inserted += result.inserted as u64;
+                        stats.messages_skipped += result.skipped as u64;
+                    });
+                    
+                    // Inserted and skipped rows are both in the DB now
+                    if let Some(filter) = &self.app_state.signature_filter {
+                        for tx in &processed_batch {
+                            filter.insert(&tx.signature);
+                        }
+                    }
                     
                     // Emit WebSocket events for inserted transactions
+                    let inserted = cdc::inserted(&processed_batch, &result);
                     if self.ingest_config.emit_ws_events {
-                        for tx in &processed_batch[..result.inserted] {
+                        for tx in &inserted {
                             let tx_json = serde_json::to_value(tx).unwrap_or_default();
                             self.ws_bridge.emit_transaction_event(tx_json);
                         }
                     }
+
+                    // And to downstream consumers of normalized transactions
+                    let topic = &self.ingest_config.publish_normalized_topic;
+                    if !topic.is_empty() {
+                        let published = cdc::publish_inserted(&inserted, |key, payload| async move {
+                            let record = FutureRecord::to(topic).key(&key).payload(&payload);
+                            self.producer
+                                .send(record, Duration::from_secs(5))
+                                .await
+                                .map(|_| ())
+                                .map_err(|(e, _)| e.to_string())
+                        })
+                        .await;
+                        debug!("Published {} of {} inserted transactions to {}", published, inserted.len(), topic);
+                    }
                     
                     break;
                 }
@@ -205,41 +387,12 @@ impl KafkaIngestion {
                 }
             }
         }
-        
-        batch.clear();
     }
 
-    /// Send message to DLQ
-    async fn send_to_dlq(&self, message: &rdkafka::message::BorrowedMessage<'_>, error: &ProcessingError) {
-        let dlq_message = DlqMessage {
-            original_message: serde_json::Value::String(
-                String::from_utf8_lossy(message.payload().unwrap_or(&[])).to_string()
-            ),
-            error: format!("{:?}", error),
-            timestamp: chrono::Utc::now(),
-            retry_count: 0,
-        };
-
-        let dlq_payload = match serde_json::to_vec(&dlq_message) {
-            Ok(payload) => payload,
-            Err(e) => {
-                error!("Failed to serialize DLQ message: {}", e);
-                return;
-            }
-        };
-
-        let key = format!("dlq-{}", chrono::Utc::now().timestamp());
-        let record = FutureRecord::to(&self.config.dlq_topic)
-            .payload(&dlq_payload)
-            .key(&key);
-
-        match self.producer.send(record, Duration::from_secs(5)).await {
-            Ok(_) => {
-                debug!("Message sent to DLQ: {}", self.config.dlq_topic);
-            }
-            Err((e, _)) => {
-                error!("Failed to send message to DLQ: {}", e);
-            }
+    /// Update the shared ingest statistics
+    fn record_stats(&self, update: impl FnOnce(&mut IngestStats)) {
+        if let Ok(mut stats) = self.app_state.ingest_stats.lock() {
+            update(&mut stats);
         }
     }
 
@@ -252,21 +405,25 @@ impl KafkaIngestion {
             retry_count: 0,
         };
 
-        let dlq_payload = match serde_json::to_vec(&dlq_message) {
-            Ok(payload) => payload,
+        let encoded = match encode_dlq_message(&dlq_message, self.config.dlq_compress) {
+            Ok(encoded) => encoded,
             Err(e) => {
-                error!("Failed to serialize transaction DLQ message: {}", e);
+                error!("{}", e);
                 return;
             }
         };
 
-        let record = FutureRecord::to(&self.config.dlq_topic)
-            .payload(&dlq_payload)
+        let mut record = FutureRecord::to(&self.config.dlq_topic)
+            .payload(&encoded.payload)
             .key(&tx.signature);
+        if let Some(headers) = encoded.headers() {
+            record = record.headers(headers);
+        }
 
         match self.producer.send(record, Duration::from_secs(5)).await {
             Ok(_) => {
                 debug!("Transaction sent to DLQ: {}", tx.signature);
+                self.record_stats(|stats| stats.record_dlq_sent());
             }
             Err((e, _)) => {
                 error!("Failed to send transaction to DLQ: {}", e);
@@ -280,6 +437,7 @@ pub async fn start_kafka_ingestion(
     config: KafkaConfig,
     ingest_config: IngestConfig,
     app_state: AppState,
+    shutdown: oneshot::Receiver<()>,
 ) -> Result<(), String> {
     if !config.enabled {
         info!("Kafka ingestion disabled");
@@ -287,17 +445,100 @@ pub async fn start_kafka_ingestion(
     }
 
     // Create WebSocket bridge
-    let (ws_bridge, ws_receiver) = WsBridge::new();
+    let (ws_bridge, ws_receiver) = WsBridge::new(app_state.ingest_stats.clone());
     
     // Start WebSocket event distributor
-    let mut distributor = WsEventDistributor::new(ws_receiver);
+    let mut distributor = WsEventDistributor::new(ws_receiver, app_state.tx_events.clone(), app_state.ws_fanout.clone());
     tokio::spawn(async move {
         distributor.start_distribution().await;
     });
 
     // Create and start Kafka ingestion
-    let mut ingestion = KafkaIngestion::new(config, ingest_config, app_state, ws_bridge).await?;
+    let ingestion = KafkaIngestion::new(config, ingest_config, app_state, ws_bridge).await?;
     
     info!("Starting Kafka ingestion service");
-    ingestion.run().await
+    ingestion.run(shutdown).await
+}
+
+/// How long an admin operation waits for broker metadata before calling Kafka down
+const BROKER_PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Check that a broker answers for `topic`, so an unreachable cluster is an
+/// error rather than what looks like an empty topic
+pub(crate) async fn probe_brokers(config: &KafkaConfig, topic: &str) -> Result<(), String> {
+    let client_config = client_config(config);
+    let topic = topic.to_string();
+    // fetch_metadata blocks for up to the timeout
+    tokio::task::spawn_blocking(move || {
+        let client: BaseConsumer = client_config
+            .create()
+            .map_err(|e| format!("Failed to create Kafka client: {}", e))?;
+        client
+            .fetch_metadata(Some(&topic), BROKER_PROBE_TIMEOUT)
+            .map(|_| ())
+            .map_err(|e| format!("Kafka brokers unreachable: {}", e))
+    })
+    .await
+    .map_err(|e| format!("Kafka broker probe failed: {}", e))?
+}
+
+/// Broker connection settings shared by the consumer and the DLQ producer
+pub(crate) fn client_config(config: &KafkaConfig) -> ClientConfig {
+    let mut client_config = ClientConfig::new();
+    client_config
+        .set("bootstrap.servers", &config.brokers)
+        .set("security.protocol", &config.security_protocol);
+
+    for (key, value) in [
+        ("sasl.mechanism", &config.sasl_mechanism),
+        ("sasl.username", &config.sasl_username),
+        ("sasl.password", &config.sasl_password),
+        ("ssl.ca.location", &config.ssl_ca_location),
+    ] {
+        if !value.is_empty() {
+            client_config.set(key, value);
+        }
+    }
+
+    client_config
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_client_config_carries_security_settings() {
+        let config = KafkaConfig {
+            security_protocol: "sasl_ssl".to_string(),
+            sasl_mechanism: "SCRAM-SHA-512".to_string(),
+            sasl_username: "ingest".to_string(),
+            sasl_password: "hunter2".to_string(),
+            ssl_ca_location: "/etc/ssl/certs/ca.pem".to_string(),
+            ..KafkaConfig::default()
+        };
+
+        let secured = client_config(&config);
+        assert_eq!(secured.get("bootstrap.servers"), Some(config.brokers.as_str()));
+        assert_eq!(secured.get("security.protocol"), Some("sasl_ssl"));
+        assert_eq!(secured.get("sasl.mechanism"), Some("SCRAM-SHA-512"));
+        assert_eq!(secured.get("sasl.username"), Some("ingest"));
+        assert_eq!(secured.get("sasl.password"), Some("hunter2"));
+        assert_eq!(secured.get("ssl.ca.location"), Some("/etc/ssl/certs/ca.pem"));
+
+        // librdkafka only accepts ssl/sasl_ssl when built with TLS support
+        let mut no_ca = config.clone();
+        no_ca.ssl_ca_location.clear();
+        client_config(&no_ca)
+            .create::<BaseConsumer>()
+            .expect("consumer with sasl_ssl settings should be created");
+
+        // Credentials never show up in debug output
+        assert!(!format!("{:?}", config).contains("hunter2"));
+
+        // Unset SASL/SSL fields are left to librdkafka's defaults
+        let plain = client_config(&KafkaConfig::default());
+        assert_eq!(plain.get("security.protocol"), Some("plaintext"));
+        assert_eq!(plain.get("sasl.mechanism"), None);
+    }
 }
@@ -6,6 +6,12 @@
 pub mod kafka;
 pub mod normalize;
 pub mod bridge;
+pub mod cdc;
+pub mod dlq;
+pub mod dlq_inspect;
+pub mod instruction_schema;
+pub mod replay;
+pub mod writer;
 
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -34,6 +40,11 @@ pub struct NormalizedTransaction {
     pub program_ids: Option<Vec<String>>,
     pub instructions: serde_json::Value,
     pub block_time: Option<i64>,
+    /// Category from `ingest.program_type_map`, if any mapped program was involved
+    pub tx_type: Option<String>,
+    /// Instructions were cut to fit `ingest.max_instructions_bytes`
+    #[serde(skip)]
+    pub instructions_truncated: bool,
 }
 
 /// Batch processing result
@@ -42,6 +53,8 @@ pub struct BatchResult {
     pub processed: usize,
     pub inserted: usize,
     pub skipped: usize,
+    /// Signatures of the rows actually inserted, not skipped as duplicates
+    pub inserted_signatures: Vec<String>,
     pub errors: Vec<ProcessingError>,
 }
 
@@ -67,7 +80,7 @@ pub enum ProcessingError {
 }
 
 /// DLQ message for failed processing
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct DlqMessage {
     pub original_message: serde_json::Value,
     pub error: String,
@@ -83,7 +96,7 @@ pub struct WsEvent {
 }
 
 /// Ingest statistics
-#[derive(Debug, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct IngestStats {
     pub messages_received: u64,
     pub messages_processed: u64,
@@ -92,6 +105,9 @@ pub struct IngestStats {
     pub messages_failed: u64,
     pub dlq_messages_sent: u64,
     pub ws_events_emitted: u64,
+    pub instructions_truncated: u64,
+    /// Times the consumer paused because the batch queue was full
+    pub backpressure_pauses: u64,
     pub last_processed_at: Option<chrono::DateTime<chrono::Utc>>,
 }
 
@@ -3,12 +3,16 @@
 /// Converts raw Kafka messages to normalized database format
 /// with validation and error handling.
 
-use crate::ingest::{NormalizedTransaction, ProcessingError, RawTransaction};
+use crate::config::IngestConfig;
+use crate::ingest::{instruction_schema::InstructionSchema, NormalizedTransaction, ProcessingError, RawTransaction};
+use flate2::read::GzDecoder;
 use serde_json;
+use std::collections::HashMap;
+use std::io::Read;
 use tracing::{debug, error, warn};
 
 /// Normalize raw transaction message
-pub fn normalize_transaction(raw: &RawTransaction) -> Result<NormalizedTransaction, ProcessingError> {
+pub fn normalize_transaction(raw: &RawTransaction, config: &IngestConfig) -> Result<NormalizedTransaction, ProcessingError> {
     // Validate signature
     if raw.signature.is_empty() {
         return Err(ProcessingError::ValidationError {
@@ -57,13 +61,19 @@ pub fn normalize_transaction(raw: &RawTransaction) -> Result<NormalizedTransacti
     } else {
         None
     };
+    let block_time = match block_time {
+        Some(time) => limit_block_time(&raw.signature, time, config)?,
+        None => None,
+    };
     
-    // Normalize instructions to JSON
-    let instructions = match &raw.instructions {
-        Some(inst) => serde_json::Value::Array(inst.clone()),
-        None => serde_json::Value::Array(vec![]),
+    // Normalize instructions to JSON, enforcing the stored size limit
+    let (instructions, instructions_truncated) = match &raw.instructions {
+        Some(inst) => limit_instructions(&raw.signature, inst, config)?,
+        None => (serde_json::Value::Array(vec![]), false),
     };
     
+    let tx_type = classify(raw.program_ids.as_deref(), &instructions, &config.program_type_map);
+
     Ok(NormalizedTransaction {
         signature: raw.signature.clone(),
         slot: raw.slot,
@@ -73,11 +83,151 @@ pub fn normalize_transaction(raw: &RawTransaction) -> Result<NormalizedTransacti
         program_ids: raw.program_ids.clone(),
         instructions,
         block_time,
+        tx_type,
+        instructions_truncated,
     })
 }
 
+/// Derive the transaction's type from the programs it involved.
+///
+/// Programs are checked in order, `program_ids` first and then each
+/// instruction's `program_id`; the first one `program_type_map` lists
+/// decides the type.
+fn classify(
+    program_ids: Option<&[String]>,
+    instructions: &serde_json::Value,
+    program_type_map: &HashMap<String, Vec<String>>,
+) -> Option<String> {
+    let instruction_programs = instructions
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|inst| inst.get("program_id").and_then(|id| id.as_str()));
+
+    program_ids
+        .unwrap_or_default()
+        .iter()
+        .map(String::as_str)
+        .chain(instruction_programs)
+        .find_map(|program| {
+            program_type_map
+                .iter()
+                .find(|(_, programs)| programs.iter().any(|p| p == program))
+                .map(|(tx_type, _)| tx_type.clone())
+        })
+}
+
+/// Slack allowed past the current time when `block_time_max` is 0
+const BLOCK_TIME_FUTURE_SKEW_SECS: i64 = 3600;
+
+/// Apply `block_time_min`/`block_time_max` to a parsed block time.
+///
+/// Out-of-range times are dropped under the "null" policy, so the row is
+/// stored without one; otherwise the message goes to the DLQ.
+fn limit_block_time(signature: &str, block_time: i64, config: &IngestConfig) -> Result<Option<i64>, ProcessingError> {
+    let max = match config.block_time_max {
+        0 => chrono::Utc::now().timestamp() + BLOCK_TIME_FUTURE_SKEW_SECS,
+        max => max,
+    };
+    if (config.block_time_min..=max).contains(&block_time) {
+        return Ok(Some(block_time));
+    }
+
+    if config.block_time_out_of_range_policy == "reject" {
+        warn!(signature = %signature, block_time, min = config.block_time_min, max, "Rejecting transaction with out-of-range block_time");
+        return Err(ProcessingError::ValidationError {
+            field: "block_time".to_string(),
+            reason: format!("block_time {} outside [{}, {}]", block_time, config.block_time_min, max),
+        });
+    }
+
+    warn!(signature = %signature, block_time, min = config.block_time_min, max, "Dropping out-of-range block_time");
+    Ok(None)
+}
+
+/// Apply `max_instructions_bytes` to the serialized instructions array.
+///
+/// Under the "truncate" policy the leading instructions that fit are kept;
+/// otherwise an oversized array is rejected so the message goes to the DLQ.
+fn limit_instructions(
+    signature: &str,
+    instructions: &[serde_json::Value],
+    config: &IngestConfig,
+) -> Result<(serde_json::Value, bool), ProcessingError> {
+    let max_bytes = config.max_instructions_bytes;
+    let sizes: Vec<usize> = instructions
+        .iter()
+        .map(|inst| serde_json::to_vec(inst).map(|v| v.len()).unwrap_or(usize::MAX))
+        .collect();
+
+    // "[" + items joined by "," + "]"
+    let array_size = |n: usize| {
+        sizes[..n]
+            .iter()
+            .fold(2 + n.saturating_sub(1), |acc, size| acc.saturating_add(*size))
+    };
+
+    let total = array_size(sizes.len());
+    if total <= max_bytes {
+        return Ok((serde_json::Value::Array(instructions.to_vec()), false));
+    }
+
+    if config.instructions_oversize_policy != "truncate" {
+        warn!(
+            signature = %signature,
+            size = total,
+            max = max_bytes,
+            "Rejecting transaction with oversized instructions"
+        );
+        return Err(ProcessingError::ValidationError {
+            field: "instructions".to_string(),
+            reason: format!("Instructions too large ({} bytes, max {})", total, max_bytes),
+        });
+    }
+
+    let keep = (0..=sizes.len()).rev().find(|n| array_size(*n) <= max_bytes).unwrap_or(0);
+    warn!(
+        signature = %signature,
+        size = total,
+        max = max_bytes,
+        kept = keep,
+        dropped = instructions.len() - keep,
+        "Truncating oversized instructions"
+    );
+    Ok((serde_json::Value::Array(instructions[..keep].to_vec()), true))
+}
+
+/// Gzip magic bytes
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Check whether a payload starts with the gzip magic bytes
+pub fn is_gzip(payload: &[u8]) -> bool {
+    payload.starts_with(&GZIP_MAGIC)
+}
+
+/// Decompress a gzip payload, refusing to inflate beyond `max_bytes`
+pub fn decompress_gzip(payload: &[u8], max_bytes: usize) -> Result<Vec<u8>, ProcessingError> {
+    let mut decompressed = Vec::new();
+    GzDecoder::new(payload)
+        .take(max_bytes as u64 + 1)
+        .read_to_end(&mut decompressed)
+        .map_err(|e| ProcessingError::ParseError {
+            message: "Invalid gzip payload".to_string(),
+            error: e.to_string(),
+        })?;
+
+    if decompressed.len() > max_bytes {
+        return Err(ProcessingError::ValidationError {
+            field: "message_size".to_string(),
+            reason: format!("Decompressed message too large (max {} bytes)", max_bytes),
+        });
+    }
+
+    Ok(decompressed)
+}
+
 /// Parse JSON message from Kafka
-pub fn parse_raw_message(payload: &[u8]) -> Result<RawTransaction, ProcessingError> {
+pub fn parse_raw_message(payload: &[u8], config: &IngestConfig) -> Result<RawTransaction, ProcessingError> {
     // Check message size
     if payload.len() > 1_048_576 {
         return Err(ProcessingError::ValidationError {
@@ -86,6 +236,16 @@ pub fn parse_raw_message(payload: &[u8]) -> Result<RawTransaction, ProcessingErr
         });
     }
     
+    // Transparently inflate gzip-wrapped payloads
+    let decompressed;
+    let payload = if config.auto_decompress && is_gzip(payload) {
+        decompressed = decompress_gzip(payload, config.max_decompressed_bytes)?;
+        debug!("Decompressed gzip payload: {} bytes", decompressed.len());
+        decompressed.as_slice()
+    } else {
+        payload
+    };
+    
     // Parse JSON
     let raw: RawTransaction = match serde_json::from_slice(payload) {
         Ok(parsed) => parsed,
@@ -103,7 +263,10 @@ pub fn parse_raw_message(payload: &[u8]) -> Result<RawTransaction, ProcessingErr
 }
 
 /// Validate normalized transaction before database insertion
-pub fn validate_normalized(tx: &NormalizedTransaction) -> Result<(), ProcessingError> {
+pub fn validate_normalized(
+    tx: &NormalizedTransaction,
+    instruction_schema: Option<&InstructionSchema>,
+) -> Result<(), ProcessingError> {
     // Check signature format (basic base58 check)
     if tx.signature.len() < 80 || tx.signature.len() > 100 {
         return Err(ProcessingError::ValidationError {
@@ -140,7 +303,210 @@ pub fn validate_normalized(tx: &NormalizedTransaction) -> Result<(), ProcessingE
             });
         }
     }
+
+    // Check instructions against ingest.instruction_schema, when configured
+    if let Some(schema) = instruction_schema {
+        schema.validate(&tx.instructions).map_err(|e| ProcessingError::ValidationError {
+            field: "instructions".to_string(),
+            reason: format!("Instructions do not match instruction_schema: {}", e),
+        })?;
+    }
     
     Ok(())
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::{write::GzEncoder, Compression};
+    use std::io::Write;
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn test_parse_gzip_wrapped_message() {
+        let payload = gzip(br#"{"signature":"sig1","slot":42}"#);
+        let raw = parse_raw_message(&payload, &IngestConfig::default()).unwrap();
+        assert_eq!(raw.signature, "sig1");
+        assert_eq!(raw.slot, 42);
+    }
+
+    #[test]
+    fn test_reject_oversized_decompressed_payload() {
+        let config = IngestConfig {
+            max_decompressed_bytes: 1024,
+            ..IngestConfig::default()
+        };
+        let padding = " ".repeat(4096);
+        let payload = gzip(format!(r#"{{"signature":"sig1","slot":42}}{}"#, padding).as_bytes());
+        assert!(payload.len() < 1024);
+
+        let err = parse_raw_message(&payload, &config).unwrap_err();
+        assert!(matches!(err, ProcessingError::ValidationError { ref field, .. } if field == "message_size"));
+    }
+
+    fn raw_with_instructions(instructions: Vec<serde_json::Value>) -> RawTransaction {
+        RawTransaction {
+            signature: "sig1".to_string(),
+            slot: 42,
+            from: None,
+            to: None,
+            lamports: None,
+            program_ids: None,
+            instructions: Some(instructions),
+            block_time: None,
+        }
+    }
+
+    fn config_with_limit(max_instructions_bytes: usize, policy: &str) -> IngestConfig {
+        IngestConfig {
+            max_instructions_bytes,
+            instructions_oversize_policy: policy.to_string(),
+            ..IngestConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_instructions_just_under_limit_are_kept() {
+        // ["x...x"] serializes to len + 4 bytes
+        let raw = raw_with_instructions(vec![serde_json::json!("x".repeat(96))]);
+        let tx = normalize_transaction(&raw, &config_with_limit(100, "reject")).unwrap();
+        assert_eq!(serde_json::to_vec(&tx.instructions).unwrap().len(), 100);
+        assert!(!tx.instructions_truncated);
+    }
+
+    #[test]
+    fn test_instructions_just_over_limit_are_rejected() {
+        let raw = raw_with_instructions(vec![serde_json::json!("x".repeat(97))]);
+        let err = normalize_transaction(&raw, &config_with_limit(100, "reject")).unwrap_err();
+        assert!(matches!(err, ProcessingError::ValidationError { ref field, .. } if field == "instructions"));
+    }
+
+    #[test]
+    fn test_instruction_schema_accepts_conforming_and_rejects_others() {
+        let schema = InstructionSchema::new(serde_json::json!({
+            "type": "array",
+            "items": {
+                "type": "object",
+                "required": ["program_id", "accounts"],
+                "properties": {
+                    "program_id": { "type": "string", "minLength": 32 },
+                    "accounts": { "type": "array", "items": { "type": "string" } },
+                    "data": { "type": "string" }
+                }
+            }
+        }))
+        .unwrap();
+        let normalized = |instructions: Vec<serde_json::Value>| {
+            let mut raw = raw_with_instructions(instructions);
+            raw.signature = "5".repeat(88);
+            normalize_transaction(&raw, &IngestConfig::default()).unwrap()
+        };
+
+        let conforming = normalized(vec![serde_json::json!({
+            "program_id": "11111111111111111111111111111111",
+            "accounts": ["a", "b"],
+            "data": "3Bxs4h24hBtQy9rw"
+        })]);
+        assert!(validate_normalized(&conforming, Some(&schema)).is_ok());
+
+        // Goes to the DLQ with a reason naming the offending instruction
+        let missing_accounts = normalized(vec![
+            serde_json::json!({ "program_id": "11111111111111111111111111111111", "accounts": [] }),
+            serde_json::json!({ "program_id": "11111111111111111111111111111111" }),
+        ]);
+        match validate_normalized(&missing_accounts, Some(&schema)).unwrap_err() {
+            ProcessingError::ValidationError { field, reason } => {
+                assert_eq!(field, "instructions");
+                assert!(reason.ends_with("/1: missing required property 'accounts'"), "{}", reason);
+            }
+            other => panic!("unexpected error: {:?}", other),
+        }
+        let not_objects = normalized(vec![serde_json::json!("opaque")]);
+        assert!(validate_normalized(&not_objects, Some(&schema)).is_err());
+
+        // Off by default: anything goes
+        assert!(validate_normalized(&not_objects, None).is_ok());
+        assert!(InstructionSchema::from_path("").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_out_of_range_block_times_follow_policy() {
+        let far_future = (chrono::Utc::now() + chrono::Duration::days(365 * 50)).to_rfc3339();
+        let raw = |block_time: &str| RawTransaction {
+            block_time: Some(block_time.to_string()),
+            ..raw_with_instructions(vec![])
+        };
+        let config = |policy: &str| IngestConfig {
+            block_time_out_of_range_policy: policy.to_string(),
+            ..IngestConfig::default()
+        };
+
+        for block_time in ["1970-01-01T00:00:00Z", far_future.as_str()] {
+            let tx = normalize_transaction(&raw(block_time), &config("null")).unwrap();
+            assert_eq!(tx.block_time, None, "{}", block_time);
+
+            let err = normalize_transaction(&raw(block_time), &config("reject")).unwrap_err();
+            assert!(matches!(err, ProcessingError::ValidationError { ref field, .. } if field == "block_time"));
+        }
+
+        // In range passes under either policy, as does an explicit maximum
+        let tx = normalize_transaction(&raw("2024-05-01T12:00:00Z"), &config("reject")).unwrap();
+        assert_eq!(tx.block_time, Some(1_714_564_800));
+        let capped = IngestConfig {
+            block_time_max: 1_700_000_000,
+            ..config("reject")
+        };
+        assert!(normalize_transaction(&raw("2024-05-01T12:00:00Z"), &capped).is_err());
+    }
+
+    #[test]
+    fn test_known_programs_set_the_tx_type() {
+        const SYSTEM: &str = "11111111111111111111111111111111";
+        const JUPITER: &str = "JUP6LkbZbjS1jKKwapdHNy74zcZ3tLUZoi5QNyVTaV4";
+        let config = IngestConfig::default();
+        let tx_type = |program_ids: Option<Vec<&str>>, instructions: Vec<serde_json::Value>| {
+            let raw = RawTransaction {
+                program_ids: program_ids.map(|ids| ids.into_iter().map(String::from).collect()),
+                ..raw_with_instructions(instructions)
+            };
+            normalize_transaction(&raw, &config).unwrap().tx_type
+        };
+
+        assert_eq!(tx_type(Some(vec![SYSTEM]), vec![]).as_deref(), Some("transfer"));
+        // The first mapped program wins, unknown ones are passed over
+        let compute_budget = "ComputeBudget111111111111111111111111111111";
+        assert_eq!(tx_type(Some(vec![compute_budget, JUPITER, SYSTEM]), vec![]).as_deref(), Some("swap"));
+        // Instructions are consulted when program_ids doesn't decide it
+        assert_eq!(
+            tx_type(None, vec![serde_json::json!({ "program_id": JUPITER, "accounts": [] })]).as_deref(),
+            Some("swap")
+        );
+        assert_eq!(tx_type(Some(vec!["Vote111111111111111111111111111111111111111"]), vec![]), None);
+
+        let custom = IngestConfig {
+            program_type_map: HashMap::from([("nft_mint".to_string(), vec![SYSTEM.to_string()])]),
+            ..IngestConfig::default()
+        };
+        let raw = RawTransaction {
+            program_ids: Some(vec![SYSTEM.to_string()]),
+            ..raw_with_instructions(vec![])
+        };
+        assert_eq!(normalize_transaction(&raw, &custom).unwrap().tx_type.as_deref(), Some("nft_mint"));
+    }
+
+    #[test]
+    fn test_instructions_just_over_limit_are_truncated() {
+        let raw = raw_with_instructions(vec![
+            serde_json::json!("x".repeat(45)),
+            serde_json::json!("y".repeat(50)),
+        ]);
+        let tx = normalize_transaction(&raw, &config_with_limit(100, "truncate")).unwrap();
+        assert!(tx.instructions_truncated);
+        assert_eq!(tx.instructions, serde_json::json!(["x".repeat(45)]));
+    }
+}
@@ -0,0 +1,322 @@
+/// DLQ replay
+///
+/// Consumes records from the DLQ topic, unwraps `DlqMessage.original_message`
+/// and re-publishes it to the input topic. Each replayed record carries an
+/// `x-dlq-replay-count` header so a message that keeps failing lands back in
+/// the DLQ with its count, and stops being replayed after
+/// `kafka.dlq_max_replays` attempts.
+
+use crate::{
+    config::KafkaConfig,
+    ingest::{
+        dlq::decode_dlq_message,
+        kafka::{client_config, probe_brokers},
+        DlqMessage, RawTransaction,
+    },
+};
+use futures_util::stream::{self, Stream, StreamExt};
+use rdkafka::{
+    consumer::{CommitMode, Consumer, StreamConsumer},
+    message::{Header, Headers, OwnedHeaders},
+    producer::{FutureProducer, FutureRecord},
+    Message,
+};
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+/// Header carrying how many times a message has been replayed from the DLQ
+pub const REPLAY_COUNT_HEADER: &str = "x-dlq-replay-count";
+
+/// How long to wait for the next DLQ record before treating the topic as drained
+const REPLAY_IDLE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Cap on a DLQ record's inflated size
+pub(crate) const MAX_DLQ_RECORD_BYTES: usize = 16 * 1024 * 1024;
+
+/// A record read from the DLQ topic
+pub struct DlqRecord {
+    pub payload: Vec<u8>,
+    pub encoding: Option<Vec<u8>>,
+}
+
+/// A message to re-publish to the input topic
+pub struct ReplayedMessage {
+    pub key: Option<String>,
+    pub payload: Vec<u8>,
+    pub replay_count: u32,
+}
+
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct ReplayReport {
+    pub replayed: usize,
+    pub malformed: usize,
+    /// Skipped because they already reached `kafka.dlq_max_replays`
+    pub exhausted: usize,
+}
+
+/// Shape written to the DLQ when a normalized batch fails to insert
+#[derive(Deserialize)]
+struct NormalizedOriginal {
+    signature: String,
+    slot: i64,
+    from_pubkey: Option<String>,
+    to_pubkey: Option<String>,
+    lamports: Option<i64>,
+    program_ids: Option<Vec<String>>,
+    instructions: serde_json::Value,
+    block_time: Option<i64>,
+}
+
+/// Turn `original_message` back into an input topic payload
+///
+/// Parse and validation failures store the raw payload as a string; failed
+/// inserts store the normalized transaction, which is mapped back to the raw
+/// message shape.
+fn input_payload(original: &serde_json::Value) -> Option<(Option<String>, Vec<u8>)> {
+    match original {
+        serde_json::Value::String(raw) if !raw.is_empty() => {
+            let key = serde_json::from_str::<RawTransaction>(raw).ok().map(|tx| tx.signature);
+            Some((key, raw.clone().into_bytes()))
+        }
+        serde_json::Value::Object(_) => {
+            let tx: NormalizedOriginal = serde_json::from_value(original.clone()).ok()?;
+            let raw = RawTransaction {
+                signature: tx.signature,
+                slot: tx.slot,
+                from: tx.from_pubkey,
+                to: tx.to_pubkey,
+                lamports: tx.lamports,
+                program_ids: tx.program_ids,
+                instructions: tx.instructions.as_array().cloned(),
+                block_time: tx
+                    .block_time
+                    .and_then(|secs| chrono::DateTime::from_timestamp(secs, 0))
+                    .map(|dt| dt.to_rfc3339()),
+            };
+            let payload = serde_json::to_vec(&raw).ok()?;
+            Some((Some(raw.signature), payload))
+        }
+        _ => None,
+    }
+}
+
+/// Replay DLQ records through `publish`, stopping at the first publish failure
+pub async fn replay_records<S, F, Fut>(
+    records: S,
+    max_replays: u32,
+    max_bytes: usize,
+    mut publish: F,
+) -> Result<ReplayReport, String>
+where
+    S: Stream<Item = DlqRecord>,
+    F: FnMut(ReplayedMessage) -> Fut,
+    Fut: Future<Output = Result<(), String>>,
+{
+    let mut report = ReplayReport::default();
+    let mut records = std::pin::pin!(records);
+
+    while let Some(record) = records.next().await {
+        let message: DlqMessage = match decode_dlq_message(&record.payload, record.encoding.as_deref(), max_bytes) {
+            Ok(message) => message,
+            Err(e) => {
+                warn!("Skipping malformed DLQ record: {:?}", e);
+                report.malformed += 1;
+                continue;
+            }
+        };
+
+        let Some((key, payload)) = input_payload(&message.original_message) else {
+            warn!("Skipping DLQ record without a replayable original message");
+            report.malformed += 1;
+            continue;
+        };
+
+        if message.retry_count >= max_replays {
+            debug!("DLQ record for {:?} already replayed {} times", key, message.retry_count);
+            report.exhausted += 1;
+            continue;
+        }
+
+        publish(ReplayedMessage {
+            key,
+            payload,
+            replay_count: message.retry_count + 1,
+        })
+        .await?;
+        report.replayed += 1;
+    }
+
+    Ok(report)
+}
+
+/// Replay count carried by an input topic record, 0 if it never came from the DLQ
+pub fn replay_count<M: Message>(message: &M) -> u32 {
+    message
+        .headers()
+        .and_then(|headers| headers.iter().find(|header| header.key == REPLAY_COUNT_HEADER))
+        .and_then(|header| header.value)
+        .and_then(|value| std::str::from_utf8(value).ok())
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(0)
+}
+
+/// Records from a DLQ consumer, ending once none arrives within `REPLAY_IDLE_TIMEOUT`
+pub(crate) fn dlq_records(consumer: &StreamConsumer) -> impl Stream<Item = DlqRecord> + '_ {
+    stream::unfold(consumer, |consumer| async move {
+        match tokio::time::timeout(REPLAY_IDLE_TIMEOUT, consumer.recv()).await {
+            Ok(Ok(message)) => {
+                let encoding = message.headers().and_then(|headers| {
+                    headers
+                        .iter()
+                        .find(|header| header.key == crate::ingest::dlq::DLQ_ENCODING_HEADER)
+                        .and_then(|header| header.value.map(<[u8]>::to_vec))
+                });
+                let record = DlqRecord {
+                    payload: message.payload().unwrap_or_default().to_vec(),
+                    encoding,
+                };
+                Some((record, consumer))
+            }
+            Ok(Err(e)) => {
+                warn!("DLQ consumer error, ending read: {}", e);
+                None
+            }
+            // Nothing left to read
+            Err(_) => None,
+        }
+    })
+}
+
+/// Replay up to `max` records from the DLQ topic into the input topic
+///
+/// Offsets are committed only when every consumed record was handled, so a
+/// failed replay picks up the same records next time; the input side ignores
+/// duplicates.
+pub async fn replay_dlq(config: &KafkaConfig, max: usize) -> Result<ReplayReport, String> {
+    probe_brokers(config, &config.dlq_topic).await?;
+    let consumer: StreamConsumer = client_config(config)
+        .set("group.id", format!("{}-dlq-replay", config.group_id))
+        .set("enable.auto.commit", "false")
+        .set("auto.offset.reset", "earliest")
+        .create()
+        .map_err(|e| format!("Failed to create DLQ consumer: {}", e))?;
+    let producer: FutureProducer = client_config(config)
+        .create()
+        .map_err(|e| format!("Failed to create Kafka producer: {}", e))?;
+    consumer
+        .subscribe(&[&config.dlq_topic])
+        .map_err(|e| format!("Failed to subscribe to topic {}: {}", config.dlq_topic, e))?;
+
+    let records = dlq_records(&consumer).take(max);
+
+    let report = replay_records(records, config.dlq_max_replays, MAX_DLQ_RECORD_BYTES, |message| {
+        let producer = &producer;
+        async move {
+            let count = message.replay_count.to_string();
+            let headers = OwnedHeaders::new().insert(Header {
+                key: REPLAY_COUNT_HEADER,
+                value: Some(&count),
+            });
+            let mut record = FutureRecord::to(&config.input_topic)
+                .payload(&message.payload)
+                .headers(headers);
+            if let Some(key) = &message.key {
+                record = record.key(key);
+            }
+            producer
+                .send(record, Duration::from_secs(5))
+                .await
+                .map(|_| ())
+                .map_err(|(e, _)| format!("Failed to re-publish DLQ message: {}", e))
+        }
+    })
+    .await?;
+
+    let consumed = report.replayed + report.malformed + report.exhausted;
+    if consumed > 0 {
+        consumer
+            .commit_consumer_state(CommitMode::Sync)
+            .map_err(|e| format!("Failed to commit DLQ offsets: {}", e))?;
+    }
+
+    info!(
+        "DLQ replay finished: replayed={}, malformed={}, exhausted={}",
+        report.replayed, report.malformed, report.exhausted
+    );
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::IngestConfig,
+        ingest::{dlq::encode_dlq_message, normalize::parse_raw_message},
+    };
+
+    fn record(original: serde_json::Value, retry_count: u32, compress: bool) -> DlqRecord {
+        let message = DlqMessage {
+            original_message: original,
+            error: "DatabaseError".to_string(),
+            timestamp: chrono::Utc::now(),
+            retry_count,
+        };
+        let encoded = encode_dlq_message(&message, compress).unwrap();
+        DlqRecord {
+            encoding: encoded.headers().map(|_| b"gzip".to_vec()),
+            payload: encoded.payload,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_replay_republishes_originals_and_skips_malformed() {
+        let records = vec![
+            // Raw payload that failed parsing or validation
+            record(serde_json::json!(r#"{"signature":"sig1","slot":1,"from":"alice"}"#), 0, false),
+            // Normalized transaction from a failed insert, compressed
+            record(
+                serde_json::json!({
+                    "signature": "sig2", "slot": 2, "from_pubkey": "bob", "to_pubkey": null,
+                    "lamports": 5, "program_ids": null, "instructions": [], "block_time": 1_700_000_000
+                }),
+                1,
+                true,
+            ),
+            DlqRecord { payload: b"not json".to_vec(), encoding: None },
+            record(serde_json::json!(42), 0, false),
+            record(serde_json::json!(r#"{"signature":"sig3","slot":3}"#), 3, false),
+        ];
+
+        let mut published = Vec::new();
+        let report = replay_records(stream::iter(records), 3, 1024 * 1024, |message| {
+            published.push(message);
+            async { Ok(()) }
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(report, ReplayReport { replayed: 2, malformed: 2, exhausted: 1 });
+        assert_eq!(published[0].key.as_deref(), Some("sig1"));
+        assert_eq!(published[0].replay_count, 1);
+        assert_eq!(published[1].replay_count, 2);
+
+        // Both come back in the shape the ingestion consumer expects
+        let sig2 = parse_raw_message(&published[1].payload, &IngestConfig::default()).unwrap();
+        assert_eq!(sig2.signature, "sig2");
+        assert_eq!(sig2.from.as_deref(), Some("bob"));
+        assert!(sig2.block_time.unwrap().starts_with("2023-11-14T22:13:20"));
+        assert!(parse_raw_message(&published[0].payload, &IngestConfig::default()).is_ok());
+
+        // A producer failure stops the replay so offsets aren't committed
+        let failed = replay_records(
+            stream::iter(vec![record(serde_json::json!(r#"{"signature":"sig4","slot":4}"#), 0, false)]),
+            3,
+            1024,
+            |_| async { Err("broker down".to_string()) },
+        )
+        .await;
+        assert_eq!(failed, Err("broker down".to_string()));
+    }
+}
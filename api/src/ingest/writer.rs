@@ -0,0 +1,160 @@
+/// Bounded pool of batch writers
+///
+/// Pending batches wait in a bounded queue drained by a fixed number of
+/// writer tasks, so at most `workers` batches are written concurrently and
+/// at most `queue_capacity` more are buffered in memory.
+
+use std::future::Future;
+use std::sync::Arc;
+use tokio::sync::{mpsc, Mutex};
+use tokio::task::JoinHandle;
+
+/// Handle for submitting batches to the writer tasks
+pub struct BatchWriterPool<T> {
+    sender: mpsc::Sender<T>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl<T: Send + 'static> BatchWriterPool<T> {
+    /// Spawn `workers` tasks that run `write` for each queued batch
+    pub fn spawn<F, Fut>(workers: usize, queue_capacity: usize, write: F) -> Self
+    where
+        F: Fn(T) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let (sender, receiver) = mpsc::channel(queue_capacity.max(1));
+        let receiver = Arc::new(Mutex::new(receiver));
+        let write = Arc::new(write);
+
+        let workers = (0..workers.max(1))
+            .map(|_| {
+                let receiver = receiver.clone();
+                let write = write.clone();
+                tokio::spawn(async move {
+                    loop {
+                        // Only hold the lock while waiting for the next batch
+                        let next = receiver.lock().await.recv().await;
+                        match next {
+                            Some(batch) => write(batch).await,
+                            None => break,
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        Self { sender, workers }
+    }
+
+    /// Queue a batch without waiting; hands it back if the queue is full
+    pub fn try_submit(&self, batch: T) -> Result<(), T> {
+        self.sender.try_send(batch).map_err(|e| match e {
+            mpsc::error::TrySendError::Full(batch) | mpsc::error::TrySendError::Closed(batch) => batch,
+        })
+    }
+
+    /// Queue a batch, waiting for room
+    pub async fn submit(&self, batch: T) -> Result<(), String> {
+        self.sender
+            .send(batch)
+            .await
+            .map_err(|_| "Batch writers have stopped".to_string())
+    }
+
+    /// Stop taking batches and wait until the queued and in-flight ones are written
+    pub async fn close(self) {
+        drop(self.sender);
+        for worker in self.workers {
+            let _ = worker.await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_concurrent_writes_never_exceed_workers() {
+        let active = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+        let done = Arc::new(AtomicUsize::new(0));
+
+        let pool = {
+            let (active, peak, done) = (active.clone(), peak.clone(), done.clone());
+            BatchWriterPool::spawn(3, 2, move |_batch: Vec<u32>| {
+                let (active, peak, done) = (active.clone(), peak.clone(), done.clone());
+                async move {
+                    let now = active.fetch_add(1, Ordering::SeqCst) + 1;
+                    peak.fetch_max(now, Ordering::SeqCst);
+                    // Slow DB insert
+                    tokio::time::sleep(Duration::from_millis(20)).await;
+                    active.fetch_sub(1, Ordering::SeqCst);
+                    done.fetch_add(1, Ordering::SeqCst);
+                }
+            })
+        };
+
+        for i in 0..12 {
+            pool.submit(vec![i]).await.unwrap();
+        }
+        while done.load(Ordering::SeqCst) < 12 {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+
+        assert!(peak.load(Ordering::SeqCst) <= 3);
+        assert_eq!(peak.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_full_queue_hands_batch_back() {
+        let started = Arc::new(AtomicUsize::new(0));
+        let gate = Arc::new(tokio::sync::Semaphore::new(0));
+
+        let pool = {
+            let (started, gate) = (started.clone(), gate.clone());
+            BatchWriterPool::spawn(1, 1, move |_batch: u32| {
+                let (started, gate) = (started.clone(), gate.clone());
+                async move {
+                    started.fetch_add(1, Ordering::SeqCst);
+                    let _ = gate.acquire().await;
+                }
+            })
+        };
+
+        pool.submit(1).await.unwrap();
+        while started.load(Ordering::SeqCst) == 0 {
+            tokio::task::yield_now().await;
+        }
+
+        // One batch in flight, one queued: the next is refused
+        pool.submit(2).await.unwrap();
+        assert_eq!(pool.try_submit(3), Err(3));
+
+        gate.add_permits(1);
+        pool.submit(3).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_close_waits_for_queued_batches() {
+        let written = Arc::new(AtomicUsize::new(0));
+        let pool = {
+            let written = written.clone();
+            BatchWriterPool::spawn(2, 8, move |_batch: u32| {
+                let written = written.clone();
+                async move {
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                    written.fetch_add(1, Ordering::SeqCst);
+                }
+            })
+        };
+
+        for i in 0..6 {
+            pool.submit(i).await.unwrap();
+        }
+        pool.close().await;
+        assert_eq!(written.load(Ordering::SeqCst), 6);
+    }
+}
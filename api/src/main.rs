@@ -1,4 +1,5 @@
 mod app_state;
+mod auth_audit;
 mod cache;
 mod config;
 mod errors;
@@ -6,18 +7,29 @@ mod http;
 mod infra;
 mod ingest;
 mod metrics;
+mod nonce_store;
 mod openapi;
 mod repository;
+mod runtime_stats;
+mod shutdown;
+mod signature_filter;
+mod summary_cache;
+mod waf_lists;
 mod ws;
 mod telemetry;
 
 use app_state::AppState;
 use config::load_config;
-use infra::{kafka, postgres, redis};
+use infra::{kafka, postgres, redis, schema_check};
 use ingest::kafka::start_kafka_ingestion;
 use metrics::AppMetrics;
+use shutdown::ShutdownSequence;
+use std::time::Duration;
 use telemetry::{init_telemetry, otel::shutdown_otel};
 
+/// How often the HTTP drain checks for open non-WebSocket connections
+const HTTP_DRAIN_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     // Load .env file if exists
@@ -30,13 +42,14 @@ async fn main() -> std::io::Result<()> {
     });
 
     // Initialize metrics
-    let metrics = AppMetrics::new().unwrap_or_else(|e| {
+    let metrics = std::sync::Arc::new(AppMetrics::new().unwrap_or_else(|e| {
         eprintln!("Failed to initialize metrics: {}", e);
         std::process::exit(1);
-    });
+    }));
 
     // Initialize telemetry
     init_telemetry(&config.telemetry, &config.otel, &config.sentry);
+    config::log_effective_config(&config);
 
     tracing::info!("Initializing integrations...");
 
@@ -45,63 +58,181 @@ async fn main() -> std::io::Result<()> {
     let redis_conn = redis::init_redis(&config.integrations).await;
     let _kafka_client = kafka::init_kafka(&config.integrations).await;
 
+    // Verify the tables, columns and indexes queries rely on
+    if let Some(pool) = &pg_pool {
+        if let Err(e) = schema_check::check_schema(pool, &config.db).await {
+            tracing::error!(error = %e, "Database schema check failed");
+            if config.db.strict_schema_check {
+                std::process::exit(1);
+            }
+        }
+        if config.db.schema_check_interval_secs > 0 {
+            schema_check::spawn_schema_check(pool.clone(), Duration::from_secs(config.db.schema_check_interval_secs));
+        }
+    }
+
     // Create AppState
-    let app_state = AppState::new(
+    let mut app_state = AppState::new(
         config.service.clone(),
         pg_pool.clone(),
         redis_conn.clone(),
     );
 
-    // Setup graceful shutdown
-    let (tx, rx) = tokio::sync::oneshot::channel::<()>();
-    
-    tokio::spawn(async move {
-        tokio::signal::ctrl_c()
-            .await
-            .expect("Failed to listen for ctrl-c");
-        tracing::info!("Shutdown signal received");
-        let _ = tx.send(());
-    });
+    // Redis commands with the command timeout and per-op metrics
+    let redis_client = redis_conn
+        .clone()
+        .map(|conn| redis::RedisClient::new(conn, &config.integrations).with_metrics(metrics.clone()));
+
+    // Warm up the signature filter from recent rows
+    if config.cache.signature_filter_enabled {
+        let filter = signature_filter::SignatureFilter::new(
+            config.cache.signature_filter_capacity,
+            config.cache.signature_filter_fp_rate,
+        );
+        if let Some(pool) = &pg_pool {
+            let repo = repository::TransactionRepository::new(pool.clone());
+            match repo.recent_signatures(config.cache.signature_filter_warmup_rows).await {
+                Ok(signatures) => {
+                    signatures.iter().for_each(|sig| filter.insert(sig));
+                    // A miss only proves absence if the warmup read the whole
+                    // table and nothing but this instance's ingest adds to it
+                    let whole_table = (signatures.len() as i64) < config.cache.signature_filter_warmup_rows;
+                    if whole_table && config.cache.signature_filter_sole_writer {
+                        filter.mark_complete();
+                    }
+                }
+                Err(e) => tracing::warn!(error = %e, "Failed to warm up signature filter"),
+            }
+        }
+        tracing::info!(
+            loaded = filter.count(),
+            complete = filter.is_complete(),
+            "Signature filter enabled"
+        );
+        app_state.signature_filter = Some(std::sync::Arc::new(filter));
+    }
+
+    // List summaries served from a periodically refreshed value
+    if config.cache.summary_refresh_ms > 0 {
+        if let Some(pool) = &pg_pool {
+            let summaries = std::sync::Arc::new(summary_cache::SummaryCache::new(
+                std::time::Duration::from_millis(config.cache.summary_refresh_ms),
+                config.cache.summary_max_entries,
+            ));
+            let pool = pool.clone();
+            summaries.clone().spawn_refresh(move |filter| {
+                let repo = repository::TransactionRepository::new(pool.clone());
+                async move { repo.get_summary(&filter).await }
+            });
+            app_state.summary_cache = Some(summaries);
+        }
+    }
+
+    // Authentication audit log
+    app_state.auth_audit = auth_audit::AuthAudit::from_config(&config.auth, pg_pool.clone(), redis_client.clone())
+        .map(std::sync::Arc::new);
+
+    // Outstanding wallet-auth nonces
+    app_state.nonce_store = nonce_store::NonceStore::from_config(&config.auth, redis_client.clone())
+        .map(std::sync::Arc::new);
+
+    // WAF ban/grey lists, purged of expired IPs in the background
+    app_state.waf_lists = waf_lists::WafLists::from_config(&config.waf, redis_client.clone())
+        .map(std::sync::Arc::new);
+    if let Some(lists) = &app_state.waf_lists {
+        lists.clone().spawn_purge(std::time::Duration::from_secs(config.waf.list_purge_interval_secs.max(1)));
+    }
+    app_state.waf_reputation = waf_lists::IpReputation::from_config(&config.waf, redis_client.clone())
+        .map(std::sync::Arc::new);
+
+    app_state.query_limits = std::sync::Arc::new(postgres::QueryLimits::new(&config.db.query_limits));
+    app_state.ws_fanout = std::sync::Arc::new(ws::fanout::WsFanout::new(config.ws.fanout_concurrency));
+    app_state.ws_subscriptions = std::sync::Arc::new(ws::registry::SubscriptionRegistry::new(
+        config.ws.max_total_subscriptions,
+        (config.ws.evict_idle_after_secs > 0).then(|| std::time::Duration::from_secs(config.ws.evict_idle_after_secs)),
+    ));
+    app_state.ws_cursors = ws::cursor::CursorStore::from_config(&config.ws, redis_client.clone())
+        .map(std::sync::Arc::new);
 
     // Start Kafka ingestion if enabled
-    if config.kafka.enabled {
+    let kafka = if config.kafka.enabled {
         tracing::info!("Starting Kafka ingestion service");
         let kafka_config = config.kafka.clone();
         let ingest_config = config.ingest.clone();
         let app_state_clone = app_state.clone();
-        
-        tokio::spawn(async move {
-            if let Err(e) = start_kafka_ingestion(kafka_config, ingest_config, app_state_clone).await {
+        let (stop, stopped) = tokio::sync::oneshot::channel();
+
+        let task = tokio::spawn(async move {
+            if let Err(e) = start_kafka_ingestion(kafka_config, ingest_config, app_state_clone, stopped).await {
                 tracing::error!("Kafka ingestion failed: {}", e);
             }
         });
+        Some((stop, task))
     } else {
         tracing::info!("Kafka ingestion disabled");
-    }
+        None
+    };
 
     // Start HTTP server
-    let server = http::start_server(config, app_state, metrics);
-    
+    let runtime_stats = app_state.runtime_stats.clone();
+    let ws_shutdown = app_state.ws_shutdown.clone();
+    let shutdown_timeout = Duration::from_secs(config.deploy.graceful_shutdown_secs);
+    let servers = http::start_server(config, app_state, metrics)?;
+    let handles = servers.handles();
+    let mut server = actix_web::rt::spawn(servers.run());
+
     tokio::select! {
-        result = server => {
-            if let Err(e) = result {
+        result = &mut server => {
+            let result = result.unwrap_or_else(|e| Err(std::io::Error::other(e)));
+            if let Err(e) = &result {
                 tracing::error!(error = %e, "Server error");
-                return Err(e);
             }
+            return result;
         }
-        _ = rx => {
-            tracing::info!("Shutting down gracefully");
+        _ = shutdown::signal() => {
+            tracing::info!("Shutdown signal received, shutting down gracefully");
         }
     }
 
-    // Cleanup integrations
-    if let Some(pool) = pg_pool {
-        tracing::info!("Closing PostgreSQL connection pool");
-        pool.close().await;
-    }
-
-    // Shutdown OpenTelemetry
-    shutdown_otel();
+    ShutdownSequence::new(shutdown_timeout)
+        .phase("stop_accepting", async {
+            for handle in &handles {
+                handle.pause().await;
+            }
+        })
+        .phase("drain_http", runtime_stats.wait_for_http_drain(HTTP_DRAIN_POLL_INTERVAL))
+        .phase("kafka", async move {
+            // Write, flush and commit what was consumed; WS clients still get those events
+            if let Some((stop, task)) = kafka {
+                let _ = stop.send(());
+                let _ = task.await;
+            }
+        })
+        .phase("websocket", async {
+            // Give WebSocket clients their reconnect notice before connections drop
+            if ws_shutdown.send(()).is_ok() {
+                tokio::time::sleep(ws::tx::SHUTDOWN_NOTICE_GRACE).await;
+            }
+            for handle in &handles {
+                handle.stop(true).await;
+            }
+            let _ = server.await;
+        })
+        .phase("telemetry", async {
+            let _ = tokio::task::spawn_blocking(shutdown_otel).await;
+        })
+        .phase("redis", async move {
+            // Connections close once the last handle is dropped
+            drop(redis_client);
+            drop(redis_conn);
+        })
+        .phase("postgres", async move {
+            if let Some(pool) = pg_pool {
+                pool.close().await;
+            }
+        })
+        .run()
+        .await;
 
     tracing::info!("Shutdown complete");
     Ok(())
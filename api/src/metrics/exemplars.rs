@@ -0,0 +1,108 @@
+// Histogram that keeps an exemplar per bucket
+//
+// The prometheus crate can't attach exemplars to observations, so histograms
+// that link to traces are kept here and rendered after the registry's output.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The latest traced observation in a bucket
+#[derive(Debug, Clone)]
+struct Exemplar {
+    trace_id: String,
+    value: f64,
+    /// Seconds since the epoch
+    timestamp: f64,
+}
+
+#[derive(Debug)]
+struct Series {
+    /// Per bucket, not cumulative; the last one is `+Inf`
+    counts: Vec<u64>,
+    exemplars: Vec<Option<Exemplar>>,
+    sum: f64,
+    count: u64,
+}
+
+pub struct ExemplarHistogram {
+    name: &'static str,
+    help: &'static str,
+    label_names: &'static [&'static str],
+    bounds: Vec<f64>,
+    series: Mutex<BTreeMap<Vec<String>, Series>>,
+}
+
+impl ExemplarHistogram {
+    pub fn new(name: &'static str, help: &'static str, label_names: &'static [&'static str], bounds: Vec<f64>) -> Self {
+        Self {
+            name,
+            help,
+            label_names,
+            bounds,
+            series: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Record `value`; with a trace ID it becomes its bucket's exemplar
+    pub fn observe(&self, label_values: &[&str], value: f64, trace_id: Option<&str>) {
+        let bucket = self.bounds.iter().position(|bound| value <= *bound).unwrap_or(self.bounds.len());
+        let mut series = self.series.lock().unwrap();
+        let series = series
+            .entry(label_values.iter().map(|v| v.to_string()).collect())
+            .or_insert_with(|| Series {
+                counts: vec![0; self.bounds.len() + 1],
+                exemplars: vec![None; self.bounds.len() + 1],
+                sum: 0.0,
+                count: 0,
+            });
+
+        series.counts[bucket] += 1;
+        series.sum += value;
+        series.count += 1;
+        if let Some(trace_id) = trace_id {
+            let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs_f64();
+            series.exemplars[bucket] = Some(Exemplar {
+                trace_id: trace_id.to_string(),
+                value,
+                timestamp,
+            });
+        }
+    }
+
+    /// Text exposition; exemplars are only valid in OpenMetrics
+    pub fn render(&self, with_exemplars: bool) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "# HELP {} {}", self.name, self.help);
+        let _ = writeln!(out, "# TYPE {} histogram", self.name);
+
+        for (label_values, series) in self.series.lock().unwrap().iter() {
+            let labels = self
+                .label_names
+                .iter()
+                .zip(label_values)
+                .map(|(name, value)| format!("{}=\"{}\",", name, value))
+                .collect::<String>();
+
+            let mut cumulative = 0;
+            for (i, count) in series.counts.iter().enumerate() {
+                cumulative += count;
+                let le = self.bounds.get(i).map_or("+Inf".to_string(), |bound| bound.to_string());
+                let _ = write!(out, "{}_bucket{{{}le=\"{}\"}} {}", self.name, labels, le, cumulative);
+                if let Some(exemplar) = series.exemplars[i].as_ref().filter(|_| with_exemplars) {
+                    let _ = write!(
+                        out,
+                        " # {{trace_id=\"{}\"}} {} {:.3}",
+                        exemplar.trace_id, exemplar.value, exemplar.timestamp
+                    );
+                }
+                out.push('\n');
+            }
+            let labels = labels.trim_end_matches(',');
+            let _ = writeln!(out, "{}_sum{{{}}} {}", self.name, labels, series.sum);
+            let _ = writeln!(out, "{}_count{{{}}} {}", self.name, labels, series.count);
+        }
+        out
+    }
+}
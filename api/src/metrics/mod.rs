@@ -1,13 +1,249 @@
+mod exemplars;
+
+use crate::http::middleware::waf::{WafAction, WafCategory};
+use exemplars::ExemplarHistogram;
+use prometheus::{Encoder, Histogram, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, Opts, Registry, TextEncoder};
 use std::error::Error;
+use std::time::Duration;
 
 pub struct AppMetrics {
     // Simplified metrics structure for now
     // In a full implementation, these would be actual OpenTelemetry metrics
+    registry: Registry,
+    pub waf_matches_total: IntCounterVec,
+    pub waf_actions_total: IntCounterVec,
+    pub waf_shadow_would_block_total: IntCounterVec,
+    pub waf_redis_checks_skipped_total: IntCounterVec,
+    pub auth_nonce_issued_total: IntCounter,
+    pub auth_verify_total: IntCounterVec,
+    pub redis_command_duration_seconds: HistogramVec,
+    pub redis_command_errors_total: IntCounterVec,
+    pub cache_compression_ratio: Histogram,
+    pub cache_deserialize_errors_total: IntCounter,
+    /// Outside the registry: its buckets carry trace exemplars
+    pub http_request_duration_seconds: ExemplarHistogram,
+}
+
+/// Text format of a `/metrics` scrape
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExpositionFormat {
+    /// Prometheus text 0.0.4, without exemplars
+    Prometheus,
+    /// OpenMetrics 1.0, with exemplars
+    OpenMetrics,
+}
+
+impl ExpositionFormat {
+    /// OpenMetrics when the scraper's `Accept` lists it, as Prometheus does
+    /// with exemplar storage enabled
+    pub fn negotiate(accept: &str) -> Self {
+        if accept.contains("application/openmetrics-text") {
+            Self::OpenMetrics
+        } else {
+            Self::Prometheus
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::Prometheus => "text/plain; version=0.0.4; charset=utf-8",
+            Self::OpenMetrics => "application/openmetrics-text; version=1.0.0; charset=utf-8",
+        }
+    }
+
+    /// Turn a whole Prometheus-text exposition into this format. OpenMetrics
+    /// names counter families without `_total`, allows no blank lines and
+    /// ends with `# EOF`
+    pub fn finish(self, text: String) -> String {
+        if self == Self::Prometheus {
+            return text;
+        }
+
+        let counters: Vec<&str> = text
+            .lines()
+            .filter_map(|line| line.strip_prefix("# TYPE ")?.strip_suffix(" counter"))
+            .filter(|name| name.ends_with("_total"))
+            .collect();
+        let mut out = String::with_capacity(text.len() + 6);
+        for line in text.lines().filter(|line| !line.trim().is_empty()) {
+            let family = counters.iter().find(|name| {
+                ["# HELP ", "# TYPE "]
+                    .iter()
+                    .any(|prefix| line.strip_prefix(prefix).is_some_and(|rest| rest.starts_with(&format!("{} ", name))))
+            });
+            match family {
+                Some(name) => out.push_str(&line.replacen(*name, name.trim_end_matches("_total"), 1)),
+                None => out.push_str(line),
+            }
+            out.push('\n');
+        }
+        out.push_str("# EOF\n");
+        out
+    }
 }
 
 impl AppMetrics {
     pub fn new() -> Result<Self, Box<dyn Error>> {
+        let registry = Registry::new();
+
+        let waf_matches_total = IntCounterVec::new(
+            Opts::new("waf_matches_total", "WAF pattern matches by attack category"),
+            &["category"],
+        )?;
+        let waf_actions_total = IntCounterVec::new(
+            Opts::new("waf_actions_total", "WAF decisions by action and mode"),
+            &["action", "mode"],
+        )?;
+        let waf_shadow_would_block_total = IntCounterVec::new(
+            Opts::new(
+                "waf_shadow_would_block_total",
+                "Requests shadow mode let through that block mode would have rejected",
+            ),
+            &["reason"],
+        )?;
+        let waf_redis_checks_skipped_total = IntCounterVec::new(
+            Opts::new(
+                "waf_redis_checks_skipped_total",
+                "Requests whose ban/grey list checks were skipped because Redis was unavailable",
+            ),
+            &["policy"],
+        )?;
+        let auth_nonce_issued_total =
+            IntCounter::new("auth_nonce_issued_total", "Wallet-auth nonces issued")?;
+        let auth_verify_total = IntCounterVec::new(
+            Opts::new("auth_verify_total", "Wallet signature verifications by result"),
+            &["result"],
+        )?;
+        let redis_command_duration_seconds = HistogramVec::new(
+            HistogramOpts::new("redis_command_duration_seconds", "Redis command latency by operation")
+                .buckets(vec![0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0]),
+            &["op"],
+        )?;
+        let redis_command_errors_total = IntCounterVec::new(
+            Opts::new("redis_command_errors_total", "Failed or timed out Redis commands by operation"),
+            &["op"],
+        )?;
+        let cache_compression_ratio = Histogram::with_opts(
+            HistogramOpts::new("cache_compression_ratio", "Compressed over raw size of cached response bodies")
+                .buckets(vec![0.05, 0.1, 0.2, 0.3, 0.5, 0.75, 1.0]),
+        )?;
+        registry.register(Box::new(waf_matches_total.clone()))?;
+        registry.register(Box::new(waf_actions_total.clone()))?;
+        registry.register(Box::new(waf_shadow_would_block_total.clone()))?;
+        registry.register(Box::new(waf_redis_checks_skipped_total.clone()))?;
+        registry.register(Box::new(auth_nonce_issued_total.clone()))?;
+        registry.register(Box::new(auth_verify_total.clone()))?;
+        registry.register(Box::new(redis_command_duration_seconds.clone()))?;
+        registry.register(Box::new(redis_command_errors_total.clone()))?;
+        let cache_deserialize_errors_total = IntCounter::new(
+            "cache_deserialize_errors_total",
+            "Cached entries that couldn't be decoded and were treated as misses",
+        )?;
+        registry.register(Box::new(cache_compression_ratio.clone()))?;
+        registry.register(Box::new(cache_deserialize_errors_total.clone()))?;
+        let http_request_duration_seconds = ExemplarHistogram::new(
+            "http_request_duration_seconds",
+            "HTTP request latency by method and status",
+            &["method", "status"],
+            vec![0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0],
+        );
+
         tracing::info!("AppMetrics initialized");
-        Ok(Self {})
+        Ok(Self {
+            registry,
+            waf_matches_total,
+            waf_actions_total,
+            waf_shadow_would_block_total,
+            waf_redis_checks_skipped_total,
+            auth_nonce_issued_total,
+            auth_verify_total,
+            redis_command_duration_seconds,
+            redis_command_errors_total,
+            cache_compression_ratio,
+            cache_deserialize_errors_total,
+            http_request_duration_seconds,
+        })
+    }
+
+    pub fn record_waf_match(&self, category: WafCategory) {
+        self.waf_matches_total.with_label_values(&[category.as_str()]).inc();
+    }
+
+    pub fn record_waf_action(&self, action: &WafAction, mode: &str) {
+        // Unknown modes are counted as "other" to keep label cardinality bounded
+        let mode = if matches!(mode, "block" | "shadow") { mode } else { "other" };
+        self.waf_actions_total.with_label_values(&[action.as_str(), mode]).inc();
+    }
+
+    /// `reason` is "score" or "query_too_long"
+    pub fn record_waf_would_block(&self, reason: &'static str) {
+        self.waf_shadow_would_block_total.with_label_values(&[reason]).inc();
     }
-}
\ No newline at end of file
+
+    pub fn record_waf_redis_skipped(&self, policy: &'static str) {
+        self.waf_redis_checks_skipped_total.with_label_values(&[policy]).inc();
+    }
+
+    pub fn record_auth_nonce_issued(&self) {
+        self.auth_nonce_issued_total.inc();
+    }
+
+    /// `result` is "success", "nonce_missing", "nonce_mismatch",
+    /// "invalid_signature" or "invalid_pubkey"
+    pub fn record_auth_verify(&self, result: &'static str) {
+        self.auth_verify_total.with_label_values(&[result]).inc();
+    }
+
+    /// `op` is the `RedisClient` operation name
+    pub fn record_redis_command(&self, op: &'static str, elapsed: Duration, ok: bool) {
+        self.redis_command_duration_seconds
+            .with_label_values(&[op])
+            .observe(elapsed.as_secs_f64());
+        if !ok {
+            self.redis_command_errors_total.with_label_values(&[op]).inc();
+        }
+    }
+
+    pub fn record_cache_compression(&self, raw_bytes: usize, stored_bytes: usize) {
+        if raw_bytes > 0 {
+            self.cache_compression_ratio.observe(stored_bytes as f64 / raw_bytes as f64);
+        }
+    }
+
+    pub fn record_cache_deserialize_error(&self) {
+        self.cache_deserialize_errors_total.inc();
+    }
+
+    /// `trace_id` is the request's sampled trace, kept as an exemplar
+    pub fn record_http_request(&self, method: &str, status: u16, elapsed: Duration, trace_id: Option<&str>) {
+        // Extension methods are counted as "other" to keep label cardinality bounded
+        let method = if matches!(method, "GET" | "HEAD" | "POST" | "PUT" | "PATCH" | "DELETE" | "OPTIONS") {
+            method
+        } else {
+            "other"
+        };
+        self.http_request_duration_seconds
+            .observe(&[method, &status.to_string()], elapsed.as_secs_f64(), trace_id);
+    }
+
+    /// Registered metrics in the Prometheus text format
+    pub fn render(&self) -> String {
+        self.render_as(ExpositionFormat::Prometheus)
+    }
+
+    /// All metrics, with exemplars in OpenMetrics; pass the whole scrape
+    /// through `ExpositionFormat::finish` afterwards
+    pub fn render_as(&self, format: ExpositionFormat) -> String {
+        let mut buffer = Vec::new();
+        if let Err(e) = TextEncoder::new().encode(&self.registry.gather(), &mut buffer) {
+            tracing::error!(error = %e, "Failed to encode metrics");
+        }
+        let mut out = String::from_utf8(buffer).unwrap_or_default();
+        out.push_str(
+            &self
+                .http_request_duration_seconds
+                .render(format == ExpositionFormat::OpenMetrics),
+        );
+        out
+    }
+}
@@ -0,0 +1,351 @@
+// Outstanding wallet-auth nonces per address
+// Supports Redis (sorted set scored by expiry) and in-memory backends
+
+use crate::config::AuthConfig;
+use crate::infra::redis::{RedisClient, RedisLimiter, RedisSaturated};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Mutex;
+use std::time::Instant;
+use subtle::ConstantTimeEq;
+
+/// Result of checking a presented nonce
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NonceCheck {
+    /// The nonce is outstanding for the address
+    Valid,
+    /// The nonce expired, but within `auth.nonce_grace_secs`
+    Grace,
+    /// Other nonces are outstanding, but not this one
+    Mismatch,
+    /// No nonce is outstanding for the address
+    Missing,
+}
+
+/// Compare a stored and a presented nonce in constant time, ignoring
+/// surrounding whitespace
+///
+/// Only the length can leak, and every nonce is the same length. The Redis
+/// backend looks nonces up by member instead, server-side.
+fn nonce_eq(stored: &str, presented: &str) -> bool {
+    stored.trim().as_bytes().ct_eq(presented.trim().as_bytes()).into()
+}
+
+/// Nonce store failure
+#[derive(Debug)]
+pub enum NonceStoreError {
+    /// Too many store operations in flight; answer 503 rather than queue
+    Saturated,
+    /// The backend command failed
+    Redis(String),
+}
+
+impl fmt::Display for NonceStoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Saturated => write!(f, "Nonce store saturated"),
+            Self::Redis(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl From<RedisSaturated> for NonceStoreError {
+    fn from(_: RedisSaturated) -> Self {
+        Self::Saturated
+    }
+}
+
+enum NonceBackend {
+    Redis(RedisClient),
+    /// address -> [(nonce, expires_at_ms)]
+    Memory(Mutex<HashMap<String, Vec<(String, i64)>>>),
+}
+
+/// Bounded set of outstanding nonces per address, each with its own TTL.
+///
+/// Issuing a new nonce keeps the previous ones valid (up to
+/// `max_outstanding_nonces`), so concurrent clients don't invalidate each
+/// other. A verified nonce is consumed individually. Expired nonces are kept
+/// for `nonce_grace_secs` more, as the recently expired ones a late request
+/// may still use.
+pub struct NonceStore {
+    backend: NonceBackend,
+    key_prefix: String,
+    ttl_ms: i64,
+    grace_ms: i64,
+    max_outstanding: usize,
+    limiter: RedisLimiter,
+    /// `auth.nonce_global_max_per_sec`, when set
+    issue_budget: Option<IssueBudget>,
+}
+
+/// Token bucket shared by every nonce request, refilled at `rate` per second
+/// and holding at most one second's worth
+pub struct IssueBudget {
+    rate: f64,
+    /// (tokens, last refill)
+    bucket: Mutex<(f64, Instant)>,
+}
+
+impl IssueBudget {
+    pub fn new(per_sec: u32) -> Self {
+        let rate = per_sec.max(1) as f64;
+        Self {
+            rate,
+            bucket: Mutex::new((rate, Instant::now())),
+        }
+    }
+
+    /// Take a token at `now`; false when the bucket is empty
+    pub fn try_take(&self, now: Instant) -> bool {
+        let mut bucket = self.bucket.lock().unwrap();
+        let (tokens, last) = &mut *bucket;
+        *tokens = (*tokens + now.saturating_duration_since(*last).as_secs_f64() * self.rate).min(self.rate);
+        *last = now.max(*last);
+        if *tokens < 1.0 {
+            return false;
+        }
+        *tokens -= 1.0;
+        true
+    }
+}
+
+fn now_ms() -> i64 {
+    chrono::Utc::now().timestamp_millis()
+}
+
+impl NonceStore {
+    /// Build the store for `auth.nonce_backend`, if available
+    pub fn from_config(config: &AuthConfig, redis: Option<RedisClient>) -> Option<Self> {
+        let backend = match config.nonce_backend.as_str() {
+            "memory" => NonceBackend::Memory(Mutex::new(HashMap::new())),
+            "redis" => NonceBackend::Redis(redis?),
+            other => {
+                tracing::warn!("Unknown nonce backend '{}', falling back to redis", other);
+                NonceBackend::Redis(redis?)
+            }
+        };
+
+        Some(Self {
+            backend,
+            key_prefix: config.redis_key_prefix.clone(),
+            ttl_ms: config.nonce_ttl_secs as i64 * 1000,
+            grace_ms: config.nonce_grace_secs as i64 * 1000,
+            max_outstanding: config.max_outstanding_nonces.max(1),
+            limiter: RedisLimiter::new(config.redis_max_inflight),
+            issue_budget: (config.nonce_global_max_per_sec > 0).then(|| IssueBudget::new(config.nonce_global_max_per_sec)),
+        })
+    }
+
+    /// Whether another nonce may be issued under `auth.nonce_global_max_per_sec`,
+    /// whichever address asks
+    pub fn admit_issue(&self) -> bool {
+        self.issue_budget.as_ref().is_none_or(|budget| budget.try_take(Instant::now()))
+    }
+
+    /// Concurrency limit shared by every store operation
+    pub fn limiter(&self) -> &RedisLimiter {
+        &self.limiter
+    }
+
+    pub(crate) fn key(&self, address: &str) -> String {
+        format!("{}:{}", self.key_prefix, address)
+    }
+
+    /// Add a nonce for the address, evicting expired and oldest ones
+    pub async fn issue(&self, address: &str, nonce: &str) -> Result<(), NonceStoreError> {
+        let _permit = self.limiter.try_acquire()?;
+        let now = now_ms();
+        let expires_at = now + self.ttl_ms;
+
+        match &self.backend {
+            NonceBackend::Redis(client) => client
+                .set_nonce(
+                    &self.key(address),
+                    nonce,
+                    expires_at,
+                    now - self.grace_ms,
+                    self.max_outstanding,
+                    self.ttl_ms + self.grace_ms,
+                )
+                .await
+                .map_err(|e| NonceStoreError::Redis(format!("Redis nonce issue error: {}", e))),
+            NonceBackend::Memory(store) => {
+                let mut store = store.lock().unwrap();
+                let nonces = store.entry(address.to_string()).or_default();
+                nonces.retain(|(_, exp)| *exp > now - self.grace_ms);
+                nonces.push((nonce.to_string(), expires_at));
+                if nonces.len() > self.max_outstanding {
+                    let excess = nonces.len() - self.max_outstanding;
+                    nonces.drain(..excess);
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Check whether the nonce is outstanding, without consuming it
+    pub async fn check(&self, address: &str, nonce: &str) -> Result<NonceCheck, NonceStoreError> {
+        let _permit = self.limiter.try_acquire()?;
+        let now = now_ms();
+
+        match &self.backend {
+            NonceBackend::Redis(client) => {
+                let key = self.key(address);
+                let expires_at = client
+                    .zscore(&key, nonce)
+                    .await
+                    .map_err(|e| NonceStoreError::Redis(format!("Redis ZSCORE error: {}", e)))?;
+                if expires_at.is_some_and(|exp| exp > now as f64) {
+                    return Ok(NonceCheck::Valid);
+                }
+                if expires_at.is_some_and(|exp| exp > (now - self.grace_ms) as f64) {
+                    return Ok(NonceCheck::Grace);
+                }
+
+                let outstanding = client
+                    .zcount_above(&key, now)
+                    .await
+                    .map_err(|e| NonceStoreError::Redis(format!("Redis ZCOUNT error: {}", e)))?;
+                Ok(if outstanding > 0 { NonceCheck::Mismatch } else { NonceCheck::Missing })
+            }
+            NonceBackend::Memory(store) => {
+                let store = store.lock().unwrap();
+                let nonces = store.get(address).map(Vec::as_slice).unwrap_or_default();
+                let live: Vec<&String> = nonces.iter().filter(|(_, exp)| *exp > now).map(|(n, _)| n).collect();
+                let in_grace = nonces
+                    .iter()
+                    .any(|(n, exp)| nonce_eq(n, nonce) && *exp > now - self.grace_ms);
+                Ok(if live.iter().any(|n| nonce_eq(n, nonce)) {
+                    NonceCheck::Valid
+                } else if in_grace {
+                    NonceCheck::Grace
+                } else if live.is_empty() {
+                    NonceCheck::Missing
+                } else {
+                    NonceCheck::Mismatch
+                })
+            }
+        }
+    }
+
+    /// Consume a single nonce; `false` if it was already used or evicted
+    pub async fn consume(&self, address: &str, nonce: &str) -> Result<bool, NonceStoreError> {
+        let _permit = self.limiter.try_acquire()?;
+        match &self.backend {
+            NonceBackend::Redis(client) => client
+                .consume_nonce(&self.key(address), nonce)
+                .await
+                .map_err(|e| NonceStoreError::Redis(format!("Redis ZREM error: {}", e))),
+            NonceBackend::Memory(store) => {
+                let mut store = store.lock().unwrap();
+                let Some(nonces) = store.get_mut(address) else {
+                    return Ok(false);
+                };
+                let before = nonces.len();
+                nonces.retain(|(n, _)| !nonce_eq(n, nonce));
+                Ok(nonces.len() < before)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn memory_store(max_outstanding_nonces: usize) -> NonceStore {
+        let config = AuthConfig {
+            nonce_backend: "memory".to_string(),
+            max_outstanding_nonces,
+            ..AuthConfig::default()
+        };
+        NonceStore::from_config(&config, None).unwrap()
+    }
+
+    #[actix_web::test]
+    async fn test_nonces_compare_by_value() {
+        assert!(nonce_eq("3mJr7AoUXx2Wqd", "3mJr7AoUXx2Wqd"));
+        assert!(nonce_eq("3mJr7AoUXx2Wqd", " 3mJr7AoUXx2Wqd\n"));
+        assert!(!nonce_eq("3mJr7AoUXx2Wqd", "3mJr7AoUXx2Wqe"));
+        assert!(!nonce_eq("3mJr7AoUXx2Wqd", "3mJr7AoUXx2Wq"));
+        assert!(!nonce_eq("3mJr7AoUXx2Wqd", ""));
+
+        let store = memory_store(5);
+        store.issue("addr", "3mJr7AoUXx2Wqd").await.unwrap();
+        assert_eq!(store.check("addr", "3mJr7AoUXx2Wqe").await.unwrap(), NonceCheck::Mismatch);
+        assert!(!store.consume("addr", "3mJr7AoUXx2Wqe").await.unwrap());
+        assert_eq!(store.check("addr", "3mJr7AoUXx2Wqd").await.unwrap(), NonceCheck::Valid);
+        assert!(store.consume("addr", "3mJr7AoUXx2Wqd").await.unwrap());
+    }
+
+    #[actix_web::test]
+    async fn test_older_nonce_stays_valid_and_is_consumed_alone() {
+        let store = memory_store(5);
+        store.issue("addr", "n1").await.unwrap();
+        store.issue("addr", "n2").await.unwrap();
+
+        assert_eq!(store.check("addr", "n1").await.unwrap(), NonceCheck::Valid);
+        assert!(store.consume("addr", "n1").await.unwrap());
+
+        // Single use, and the newer nonce is untouched
+        assert_eq!(store.check("addr", "n1").await.unwrap(), NonceCheck::Mismatch);
+        assert!(!store.consume("addr", "n1").await.unwrap());
+        assert_eq!(store.check("addr", "n2").await.unwrap(), NonceCheck::Valid);
+    }
+
+    #[actix_web::test]
+    async fn test_outstanding_nonces_are_bounded() {
+        let store = memory_store(2);
+        for nonce in ["n1", "n2", "n3"] {
+            store.issue("addr", nonce).await.unwrap();
+        }
+
+        assert_eq!(store.check("addr", "n1").await.unwrap(), NonceCheck::Mismatch);
+        assert_eq!(store.check("addr", "n3").await.unwrap(), NonceCheck::Valid);
+        assert_eq!(store.check("other", "n3").await.unwrap(), NonceCheck::Missing);
+    }
+
+    #[test]
+    fn test_issue_budget_refills_at_its_rate() {
+        let budget = IssueBudget::new(4);
+        let start = Instant::now();
+
+        // A second's worth up front, then nothing until it refills
+        assert!((0..4).all(|_| budget.try_take(start)));
+        assert!(!budget.try_take(start));
+        assert!(budget.try_take(start + std::time::Duration::from_millis(250)));
+        assert!(!budget.try_take(start + std::time::Duration::from_millis(260)));
+
+        // An idle spell doesn't bank more than one second's worth
+        let later = start + std::time::Duration::from_secs(10);
+        assert_eq!((0..10).filter(|_| budget.try_take(later)).count(), 4);
+    }
+
+    #[actix_web::test]
+    async fn test_expired_nonce_is_accepted_only_within_grace() {
+        let config = AuthConfig {
+            nonce_backend: "memory".to_string(),
+            nonce_ttl_secs: 0,
+            ..AuthConfig::default()
+        };
+        let mut store = NonceStore::from_config(&config, None).unwrap();
+        store.grace_ms = 50;
+
+        // Just expired: still usable, once
+        store.issue("addr", "n1").await.unwrap();
+        assert_eq!(store.check("addr", "n1").await.unwrap(), NonceCheck::Grace);
+        assert!(store.consume("addr", "n1").await.unwrap());
+        assert_eq!(store.check("addr", "n1").await.unwrap(), NonceCheck::Missing);
+
+        // Past the grace window it's gone
+        store.issue("addr", "n2").await.unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(80)).await;
+        assert_eq!(store.check("addr", "n2").await.unwrap(), NonceCheck::Missing);
+
+        // Without a grace window an expired nonce is never accepted
+        store.grace_ms = 0;
+        store.issue("addr", "n3").await.unwrap();
+        assert_eq!(store.check("addr", "n3").await.unwrap(), NonceCheck::Missing);
+    }
+}
@@ -3,6 +3,59 @@
 
 use serde_json::json;
 
+const EXAMPLE_SIGNATURE: &str =
+    "5VERv8NMvzbJMEkV8xnrLkEaWRtSz9CosKDYjCJjBRnbJLgp8uirBgmQpjKhoR4tjF3ZpRzrFmBV6UjKdiSZkQUW";
+const EXAMPLE_FROM: &str = "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM";
+const EXAMPLE_TO: &str = "7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJosgAsU";
+const EXAMPLE_PROGRAM_ID: &str = "11111111111111111111111111111111";
+
+/// Example transaction used across response examples
+fn example_transaction() -> serde_json::Value {
+    json!({
+        "signature": EXAMPLE_SIGNATURE,
+        "slot": 245678901,
+        "from_pubkey": EXAMPLE_FROM,
+        "to_pubkey": EXAMPLE_TO,
+        "lamports": 1500000,
+        "program_ids": [EXAMPLE_PROGRAM_ID],
+        "instructions": {
+            "instructions": [
+                { "program_id": EXAMPLE_PROGRAM_ID, "type": "transfer", "lamports": 1500000 }
+            ]
+        },
+        "block_time": 1718000000,
+        "tx_type": "transfer",
+        "created_at": "2024-06-10T06:13:20Z"
+    })
+}
+
+/// Example list item: the transaction with its derived counts
+fn example_summary() -> serde_json::Value {
+    let mut tx = example_transaction();
+    tx["instruction_count"] = json!(1);
+    tx["program_count"] = json!(1);
+    tx
+}
+
+/// JSON media type with schema reference and example
+fn json_content(schema_ref: &str, example: serde_json::Value) -> serde_json::Value {
+    json!({
+        "application/json": {
+            "schema": { "$ref": schema_ref },
+            "example": example
+        }
+    })
+}
+
+/// Error response media type with example
+fn error_content(error: &str, details: Option<&str>) -> serde_json::Value {
+    let mut example = json!({ "error": error });
+    if let Some(details) = details {
+        example["details"] = json!(details);
+    }
+    json_content("#/components/schemas/ErrorResponse", example)
+}
+
 pub fn generate_openapi_spec() -> serde_json::Value {
     json!({
         "openapi": "3.0.3",
@@ -28,37 +81,96 @@ pub fn generate_openapi_spec() -> serde_json::Value {
                             "name": "signature",
                             "in": "query",
                             "description": "Filter by transaction signature (exact match)",
-                            "schema": { "type": "string" }
+                            "schema": { "type": "string" },
+                            "example": EXAMPLE_SIGNATURE
                         },
                         {
                             "name": "from",
                             "in": "query",
                             "description": "Filter by source pubkey",
-                            "schema": { "type": "string" }
+                            "schema": { "type": "string" },
+                            "example": EXAMPLE_FROM
                         },
                         {
                             "name": "to",
                             "in": "query",
                             "description": "Filter by destination pubkey",
-                            "schema": { "type": "string" }
+                            "schema": { "type": "string" },
+                            "example": EXAMPLE_TO
                         },
                         {
                             "name": "program_id",
                             "in": "query",
                             "description": "Filter by program ID (must be in program_ids array)",
-                            "schema": { "type": "string" }
+                            "schema": { "type": "string" },
+                            "example": EXAMPLE_PROGRAM_ID
+                        },
+                        {
+                            "name": "tx_type",
+                            "in": "query",
+                            "description": "Filter by derived transaction type (the keys of ingest.program_type_map)",
+                            "schema": { "type": "string", "pattern": "^[a-z0-9_]{1,32}$" },
+                            "example": "swap"
                         },
                         {
                             "name": "slot_from",
                             "in": "query",
                             "description": "Filter by minimum slot number",
-                            "schema": { "type": "integer", "format": "int64" }
+                            "schema": { "type": "integer", "format": "int64" },
+                            "example": 245000000
                         },
                         {
                             "name": "slot_to",
                             "in": "query",
                             "description": "Filter by maximum slot number",
-                            "schema": { "type": "integer", "format": "int64" }
+                            "schema": { "type": "integer", "format": "int64" },
+                            "example": 246000000
+                        },
+                        {
+                            "name": "signature_prefix",
+                            "in": "query",
+                            "description": "Filter by leading signature characters (base58, 4-88 chars); results are capped at 50",
+                            "schema": { "type": "string", "minLength": 4, "maxLength": 88 },
+                            "example": "5VERv8"
+                        },
+                        {
+                            "name": "address_prefix",
+                            "in": "query",
+                            "description": "Filter by leading characters of the source or destination pubkey (base58, 4-88 chars); results are capped at 50",
+                            "schema": { "type": "string", "minLength": 4, "maxLength": 88 },
+                            "example": "Vote111"
+                        },
+                        {
+                            "name": "address",
+                            "in": "query",
+                            "description": "Filter by source or destination pubkey",
+                            "schema": { "type": "string" },
+                            "example": EXAMPLE_FROM
+                        },
+                        {
+                            "name": "enrich",
+                            "in": "query",
+                            "description": "Add `net_lamports` for `address` (required): `+lamports` if it received the transfer, `-lamports` if it sent it. Only the single stored transfer is counted",
+                            "schema": { "type": "boolean", "default": false }
+                        },
+                        {
+                            "name": "min_lamports",
+                            "in": "query",
+                            "description": "Only transactions moving at least this many lamports",
+                            "schema": { "type": "integer", "format": "int64", "minimum": 0 },
+                            "example": 1000000
+                        },
+                        {
+                            "name": "exclude_zero_lamports",
+                            "in": "query",
+                            "description": "Exclude zero-lamport (instruction-only) transactions",
+                            "schema": { "type": "boolean", "default": false }
+                        },
+                        {
+                            "name": "schema",
+                            "in": "query",
+                            "description": "Response shape: v2 (default) or the legacy v1 shape with `id` and `timestamp` in place of `created_at`; `Accept: application/vnd.blockchain.v1+json` selects v1 as well",
+                            "schema": { "type": "string", "enum": ["v1", "v2"], "default": "v2" }
                         },
                         {
                             "name": "sort_by",
@@ -68,7 +180,8 @@ pub fn generate_openapi_spec() -> serde_json::Value {
                                 "type": "string",
                                 "enum": ["slot", "signature", "block_time"],
                                 "default": "slot"
-                            }
+                            },
+                            "example": "slot"
                         },
                         {
                             "name": "order",
@@ -78,18 +191,20 @@ pub fn generate_openapi_spec() -> serde_json::Value {
                                 "type": "string",
                                 "enum": ["asc", "desc"],
                                 "default": "desc"
-                            }
+                            },
+                            "example": "desc"
                         },
                         {
                             "name": "limit",
                             "in": "query",
-                            "description": "Maximum number of results (1-200)",
+                            "description": "Maximum number of results (1 to pagination.max_limit; defaults shown)",
                             "schema": {
                                 "type": "integer",
                                 "minimum": 1,
                                 "maximum": 200,
                                 "default": 50
-                            }
+                            },
+                            "example": 50
                         },
                         {
                             "name": "offset",
@@ -99,7 +214,8 @@ pub fn generate_openapi_spec() -> serde_json::Value {
                                 "type": "integer",
                                 "minimum": 0,
                                 "default": 0
-                            }
+                            },
+                            "example": 0
                         }
                     ],
                     "responses": {
@@ -111,36 +227,165 @@ pub fn generate_openapi_spec() -> serde_json::Value {
                                     "schema": { "type": "string" }
                                 }
                             },
-                            "content": {
-                                "application/json": {
-                                    "schema": {
-                                        "$ref": "#/components/schemas/TransactionListResponse"
-                                    }
-                                }
-                            }
+                            "content": json_content(
+                                "#/components/schemas/TransactionListResponse",
+                                json!({
+                                    "items": [example_summary()],
+                                    "page": { "limit": 50, "offset": 0, "total": 1 },
+                                    "sort": { "by": "slot", "order": "desc" }
+                                })
+                            )
                         },
                         "304": {
                             "description": "Not Modified (ETag matched)"
                         },
                         "400": {
                             "description": "Bad Request",
+                            "content": error_content("Bad request", Some("limit must be between 1 and 200"))
+                        },
+                        "413": {
+                            "description": "Response exceeds server.max_response_bytes",
+                            "content": error_content(
+                                "Response too large",
+                                Some("Response of 9437184 bytes exceeds the 8388608 byte limit; narrow the filters or reduce limit")
+                            )
+                        },
+                        "503": {
+                            "description": "Service Unavailable",
+                            "content": error_content("Service unavailable", Some("Database not available"))
+                        }
+                    }
+                }
+            },
+            "/api/transactions/poll": {
+                "get": {
+                    "summary": "Long-poll for new transactions",
+                    "description": "Return transactions newer than since_slot, waiting up to timeout_ms (capped by ws.long_poll_max_timeout_ms) for new ones. A WebSocket alternative.",
+                    "tags": ["transactions"],
+                    "parameters": [
+                        {
+                            "name": "since_slot",
+                            "in": "query",
+                            "required": true,
+                            "description": "Only return transactions with a greater slot",
+                            "schema": { "type": "integer", "format": "int64" },
+                            "example": 245678900
+                        },
+                        {
+                            "name": "timeout_ms",
+                            "in": "query",
+                            "description": "Maximum time to wait when nothing is available",
+                            "schema": { "type": "integer", "minimum": 0, "default": 25000 },
+                            "example": 25000
+                        },
+                        {
+                            "name": "limit",
+                            "in": "query",
+                            "description": "Maximum number of results (1 to pagination.max_limit; defaults shown)",
+                            "schema": { "type": "integer", "minimum": 1, "maximum": 200, "default": 50 },
+                            "example": 50
+                        }
+                    ],
+                    "responses": {
+                        "200": {
+                            "description": "New transactions, or an empty list with the latest slot on timeout",
                             "content": {
                                 "application/json": {
-                                    "schema": {
-                                        "$ref": "#/components/schemas/ErrorResponse"
-                                    }
+                                    "example": { "items": [example_transaction()], "latest_slot": 245678901 }
                                 }
                             }
                         },
+                        "400": {
+                            "description": "Bad Request",
+                            "content": error_content("Bad request", Some("limit must be between 1 and 200"))
+                        },
                         "503": {
                             "description": "Service Unavailable",
+                            "content": error_content("Service unavailable", Some("Database not available"))
+                        }
+                    }
+                }
+            },
+            "/api/transactions/changes": {
+                "get": {
+                    "summary": "Changes feed for sync clients",
+                    "description": "Transactions in insertion order (created_at, then signature) after since_cursor. Rows younger than pagination.changes_settle_ms are held back so concurrent inserts are never skipped; following next_cursor delivers every row exactly once.",
+                    "tags": ["transactions"],
+                    "parameters": [
+                        {
+                            "name": "since_cursor",
+                            "in": "query",
+                            "description": "next_cursor from the previous page; omit to start from the oldest row",
+                            "schema": { "type": "string" },
+                            "example": "1731234567123456_5VERv8NMvzbJMEkV8xnrLkEaWRtSz9CosKDYjCJjBRnb"
+                        },
+                        {
+                            "name": "limit",
+                            "in": "query",
+                            "description": "Maximum number of results (up to pagination.max_limit)",
+                            "schema": { "type": "integer", "minimum": 1, "default": 50 },
+                            "example": 100
+                        }
+                    ],
+                    "responses": {
+                        "200": {
+                            "description": "The next page; next_cursor is unchanged when nothing new has settled",
                             "content": {
                                 "application/json": {
-                                    "schema": {
-                                        "$ref": "#/components/schemas/ErrorResponse"
+                                    "example": {
+                                        "items": [example_transaction()],
+                                        "next_cursor": "1731234567123456_5VERv8NMvzbJMEkV8xnrLkEaWRtSz9CosKDYjCJjBRnb",
+                                        "has_more": false
                                     }
                                 }
                             }
+                        },
+                        "400": {
+                            "description": "Bad Request",
+                            "content": error_content("Bad request", Some("since_cursor must be a next_cursor returned by this endpoint"))
+                        },
+                        "503": {
+                            "description": "Service Unavailable",
+                            "content": error_content("Service unavailable", Some("Database not available"))
+                        }
+                    }
+                }
+            },
+            "/api/transactions/stream": {
+                "get": {
+                    "summary": "Stream new transactions (SSE)",
+                    "description": "Server-sent events stream of newly ingested transactions. Each event has `id` set to `<slot>_<signature>`; reconnect with Last-Event-ID to replay the transactions after it. Comment heartbeats keep the connection alive.",
+                    "tags": ["transactions"],
+                    "parameters": [
+                        { "name": "signature", "in": "query", "schema": { "type": "string" } },
+                        { "name": "from", "in": "query", "schema": { "type": "string" }, "example": EXAMPLE_FROM },
+                        { "name": "to", "in": "query", "schema": { "type": "string" }, "example": EXAMPLE_TO },
+                        { "name": "program_id", "in": "query", "schema": { "type": "string" }, "example": EXAMPLE_PROGRAM_ID },
+                        { "name": "slot_from", "in": "query", "schema": { "type": "integer", "format": "int64" } },
+                        { "name": "slot_to", "in": "query", "schema": { "type": "integer", "format": "int64" } },
+                        {
+                            "name": "schema",
+                            "in": "query",
+                            "description": "Event shape, as for the list endpoint",
+                            "schema": { "type": "string", "enum": ["v1", "v2"], "default": "v2" }
+                        },
+                        {
+                            "name": "Last-Event-ID",
+                            "in": "header",
+                            "description": "Resume after this event id; anything else is rejected with 400",
+                            "schema": { "type": "string" },
+                            "example": format!("245678900_{}", EXAMPLE_SIGNATURE)
+                        }
+                    ],
+                    "responses": {
+                        "200": {
+                            "description": "Event stream",
+                            "content": {
+                                "text/event-stream": {
+                                    "schema": { "type": "string" },
+                                    "example": "id: 245678901_5VERv8...\nevent: transaction\ndata: {\"signature\":\"5VERv8...\",\"slot\":245678901}\n\n"
+                                }
+                            }
                         }
                     }
                 }
@@ -156,39 +401,89 @@ pub fn generate_openapi_spec() -> serde_json::Value {
                             "in": "path",
                             "required": true,
                             "description": "Transaction signature",
-                            "schema": { "type": "string" }
+                            "schema": { "type": "string" },
+                            "example": EXAMPLE_SIGNATURE
+                        },
+                        {
+                            "name": "summary",
+                            "in": "query",
+                            "description": "Add `instruction_count` and `program_count`, as in list items",
+                            "schema": { "type": "boolean", "default": false }
+                        },
+                        {
+                            "name": "schema",
+                            "in": "query",
+                            "description": "Response shape: v2 (default) or the legacy v1 shape with `id` and `timestamp` in place of `created_at`; `Accept: application/vnd.blockchain.v1+json` selects v1 as well",
+                            "schema": { "type": "string", "enum": ["v1", "v2"], "default": "v2" }
                         }
                     ],
                     "responses": {
                         "200": {
                             "description": "Successful response",
-                            "content": {
-                                "application/json": {
-                                    "schema": {
-                                        "$ref": "#/components/schemas/SolanaTransaction"
-                                    }
-                                }
-                            }
+                            "content": json_content(
+                                "#/components/schemas/SolanaTransaction",
+                                example_transaction()
+                            )
                         },
                         "404": {
                             "description": "Transaction not found",
+                            "content": error_content("transaction not found", None)
+                        },
+                        "503": {
+                            "description": "Service Unavailable",
+                            "content": error_content("Service unavailable", Some("Database not available"))
+                        }
+                    }
+                }
+            },
+            "/api/transactions/{signature}/status": {
+                "get": {
+                    "summary": "Get transaction status",
+                    "description": "Whether a transaction exists, with its slot and block time; cheaper than fetching the full transaction when polling for confirmation",
+                    "tags": ["transactions"],
+                    "parameters": [
+                        {
+                            "name": "signature",
+                            "in": "path",
+                            "required": true,
+                            "description": "Transaction signature",
+                            "schema": { "type": "string" },
+                            "example": EXAMPLE_SIGNATURE
+                        }
+                    ],
+                    "responses": {
+                        "200": {
+                            "description": "Status; `exists` is false for unknown signatures",
                             "content": {
                                 "application/json": {
-                                    "schema": {
-                                        "$ref": "#/components/schemas/ErrorResponse"
-                                    }
+                                    "example": { "exists": true, "slot": 245678901, "block_time": 1718000000 }
                                 }
                             }
                         },
                         "503": {
                             "description": "Service Unavailable",
+                            "content": error_content("Service unavailable", Some("Database not available"))
+                        }
+                    }
+                }
+            },
+            "/api/transactions/latest-slot": {
+                "get": {
+                    "summary": "Get latest slot",
+                    "description": "Highest stored slot and block time, for checking how current the data is; cached for about a second",
+                    "tags": ["transactions"],
+                    "responses": {
+                        "200": {
+                            "description": "Latest slot; `max_slot` is 0 and `max_block_time` null when nothing is stored",
                             "content": {
                                 "application/json": {
-                                    "schema": {
-                                        "$ref": "#/components/schemas/ErrorResponse"
-                                    }
+                                    "example": { "max_slot": 245678901, "max_block_time": 1718000000, "as_of": "2024-06-10T06:13:20Z" }
                                 }
                             }
+                        },
+                        "503": {
+                            "description": "Service Unavailable",
+                            "content": error_content("Service unavailable", Some("Database not available"))
                         }
                     }
                 }
@@ -242,16 +537,33 @@ pub fn generate_openapi_spec() -> serde_json::Value {
                             "description": "Transaction instructions (JSONB)"
                         },
                         "block_time": { "type": "integer", "format": "int64", "nullable": true, "description": "Unix timestamp" },
+                        "tx_type": {
+                            "type": "string",
+                            "nullable": true,
+                            "description": "Derived at ingestion from the programs involved (e.g. transfer, token, swap)"
+                        },
                         "created_at": { "type": "string", "format": "date-time", "description": "Created timestamp" }
                     }
                 },
+                "TransactionSummary": {
+                    "allOf": [
+                        { "$ref": "#/components/schemas/SolanaTransaction" },
+                        {
+                            "type": "object",
+                            "properties": {
+                                "instruction_count": { "type": "integer", "description": "Entries in `instructions`" },
+                                "program_count": { "type": "integer", "description": "Entries in `program_ids`" }
+                            }
+                        }
+                    ]
+                },
                 "TransactionListResponse": {
                     "type": "object",
                     "properties": {
                         "items": {
                             "type": "array",
                             "items": {
-                                "$ref": "#/components/schemas/SolanaTransaction"
+                                "$ref": "#/components/schemas/TransactionSummary"
                             }
                         },
                         "page": {
@@ -280,6 +592,12 @@ pub fn generate_openapi_spec() -> serde_json::Value {
                             "type": "array",
                             "items": { "type": "string" },
                             "nullable": true
+                        },
+                        "errors": {
+                            "type": "array",
+                            "items": { "type": "string" },
+                            "nullable": true,
+                            "description": "Every invalid query parameter, when validation fails"
                         }
                     }
                 }
@@ -288,4 +606,35 @@ pub fn generate_openapi_spec() -> serde_json::Value {
     })
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
 
+    #[test]
+    fn test_list_response_has_example_items() {
+        let spec = generate_openapi_spec();
+        let example = &spec["paths"]["/api/transactions"]["get"]["responses"]["200"]["content"]
+            ["application/json"]["example"];
+
+        let items = example["items"].as_array().expect("example items");
+        assert!(!items.is_empty());
+        assert_eq!(items[0]["signature"], EXAMPLE_SIGNATURE);
+    }
+
+    #[test]
+    fn test_error_responses_have_examples() {
+        let spec = generate_openapi_spec();
+        let responses = [
+            ("/api/transactions", "400"),
+            ("/api/transactions", "503"),
+            ("/api/transactions/{signature}", "404"),
+            ("/api/transactions/{signature}", "503"),
+        ];
+
+        for (path, status) in responses {
+            let example = &spec["paths"][path]["get"]["responses"][status]["content"]
+                ["application/json"]["example"];
+            assert!(example["error"].is_string(), "{} {} lacks an example", path, status);
+        }
+    }
+}
@@ -0,0 +1,67 @@
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, sqlx::FromRow)]
+pub struct AuthAttempt {
+    pub address: String,
+    pub method: String,
+    pub path: String,
+    pub result: String, // "success" | "failure"
+    pub reason: Option<String>,
+    pub ip: Option<String>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+pub struct AuthAuditRepository {
+    pool: PgPool,
+}
+
+impl AuthAuditRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Append an authentication attempt
+    pub async fn insert(&self, attempt: &AuthAttempt) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+            INSERT INTO auth_audit (address, method, path, result, reason, ip, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            "#,
+        )
+        .bind(&attempt.address)
+        .bind(&attempt.method)
+        .bind(&attempt.path)
+        .bind(&attempt.result)
+        .bind(&attempt.reason)
+        .bind(&attempt.ip)
+        .bind(attempt.created_at)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Get the most recent attempts for an address, newest first
+    pub async fn recent_for_address(
+        &self,
+        address: &str,
+        limit: i64,
+    ) -> Result<Vec<AuthAttempt>, sqlx::Error> {
+        let attempts = sqlx::query_as::<_, AuthAttempt>(
+            r#"
+            SELECT address, method, path, result, reason, ip, created_at
+            FROM auth_audit
+            WHERE address = $1
+            ORDER BY created_at DESC
+            LIMIT $2
+            "#,
+        )
+        .bind(address)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(attempts)
+    }
+}
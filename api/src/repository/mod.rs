@@ -1,11 +1,13 @@
 // Repository layer for database operations
 // Provides abstraction over sqlx for clean data access
 
+pub mod auth_audit;
 pub mod transactions;
 pub mod users;
 
+pub use auth_audit::{AuthAttempt, AuthAuditRepository};
 pub use transactions::{
-    NewTransaction, Pagination, SolanaTransaction, TransactionFilter, TransactionRepository,
+    NewTransaction, Pagination, SolanaTransaction, TransactionFilter, TransactionListRow, TransactionRepository,
 };
 pub use users::{User, UserPermission, UserRepository};
 
@@ -12,9 +12,47 @@ pub struct SolanaTransaction {
     pub program_ids: Option<Vec<String>>,
     pub instructions: serde_json::Value,
     pub block_time: Option<i64>,
+    /// Derived at ingestion from `ingest.program_type_map`
+    pub tx_type: Option<String>,
     pub created_at: chrono::DateTime<chrono::Utc>,
 }
 
+/// A row of `list`: `instructions` is only selected when asked for, its
+/// length always is
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct TransactionListRow {
+    pub signature: String,
+    pub slot: i64,
+    pub from_pubkey: Option<String>,
+    pub to_pubkey: Option<String>,
+    pub lamports: Option<i64>,
+    pub program_ids: Option<Vec<String>>,
+    #[sqlx(default)]
+    pub instructions: Option<serde_json::Value>,
+    pub instruction_count: i32,
+    pub block_time: Option<i64>,
+    pub tx_type: Option<String>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl From<SolanaTransaction> for TransactionListRow {
+    fn from(tx: SolanaTransaction) -> Self {
+        Self {
+            instruction_count: tx.instructions.as_array().map_or(0, |instructions| instructions.len() as i32),
+            signature: tx.signature,
+            slot: tx.slot,
+            from_pubkey: tx.from_pubkey,
+            to_pubkey: tx.to_pubkey,
+            lamports: tx.lamports,
+            program_ids: tx.program_ids,
+            instructions: Some(tx.instructions),
+            block_time: tx.block_time,
+            tx_type: tx.tx_type,
+            created_at: tx.created_at,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct NewTransaction {
     pub signature: String,
@@ -25,16 +63,106 @@ pub struct NewTransaction {
     pub program_ids: Option<Vec<String>>,
     pub instructions: serde_json::Value,
     pub block_time: Option<i64>,
+    pub tx_type: Option<String>,
+}
+
+/// Position in the changes feed: the last row delivered, in insertion order
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChangeCursor {
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub signature: String,
+}
+
+impl ChangeCursor {
+    pub fn of(tx: &SolanaTransaction) -> Self {
+        Self {
+            created_at: tx.created_at,
+            signature: tx.signature.clone(),
+        }
+    }
+
+    /// `<created_at in µs>_<signature>`; opaque to clients. Postgres keeps
+    /// microseconds, so the position round-trips exactly
+    pub fn encode(&self) -> String {
+        format!("{}_{}", self.created_at.timestamp_micros(), self.signature)
+    }
+
+    pub fn decode(cursor: &str) -> Option<Self> {
+        let (micros, signature) = cursor.split_once('_')?;
+        if signature.is_empty() || signature.len() > 128 || !signature.bytes().all(|b| b.is_ascii_alphanumeric()) {
+            return None;
+        }
+        Some(Self {
+            created_at: chrono::DateTime::from_timestamp_micros(micros.parse().ok()?)?,
+            signature: signature.to_string(),
+        })
+    }
+}
+
+/// Position in the SSE stream: the last transaction sent, in `(slot, signature)` order
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StreamCursor {
+    pub slot: i64,
+    pub signature: String,
+}
+
+impl StreamCursor {
+    /// `<slot>_<signature>`, sent as the SSE event id
+    pub fn encode(&self) -> String {
+        format!("{}_{}", self.slot, self.signature)
+    }
+
+    pub fn decode(cursor: &str) -> Option<Self> {
+        let (slot, signature) = cursor.split_once('_')?;
+        if signature.is_empty() || signature.len() > 128 || !signature.bytes().all(|b| b.is_ascii_alphanumeric()) {
+            return None;
+        }
+        Some(Self {
+            slot: slot.parse().ok()?,
+            signature: signature.to_string(),
+        })
+    }
 }
 
+/// Rows an estimated count of a filtered listing stops at
+pub const ESTIMATE_COUNT_CAP: i64 = 10_000;
+
 #[derive(Debug, Clone, Default)]
 pub struct TransactionFilter {
     pub signature: Option<String>,
     pub from_pubkey: Option<String>,
     pub to_pubkey: Option<String>,
     pub program_id: Option<String>,
+    pub tx_type: Option<String>,
     pub slot_from: Option<i64>,
     pub slot_to: Option<i64>,
+    /// Leading characters of the signature; validated as base58 by the caller
+    pub signature_prefix: Option<String>,
+    /// Leading characters of either the source or destination pubkey
+    pub address_prefix: Option<String>,
+    /// Either the source or destination pubkey
+    pub address: Option<String>,
+    pub min_lamports: Option<i64>,
+    /// Drop zero-lamport (instruction-only) transactions
+    pub exclude_zero_lamports: bool,
+}
+
+impl TransactionFilter {
+    /// No conditions at all, so every row matches
+    pub fn is_empty(&self) -> bool {
+        self.signature.is_none()
+            && self.from_pubkey.is_none()
+            && self.to_pubkey.is_none()
+            && self.program_id.is_none()
+            && self.tx_type.is_none()
+            && self.slot_from.is_none()
+            && self.slot_to.is_none()
+            && self.signature_prefix.is_none()
+            && self.address_prefix.is_none()
+            && self.address.is_none()
+            && self.min_lamports.is_none()
+            && !self.exclude_zero_lamports
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -69,7 +197,7 @@ impl TransactionRepository {
         let tx = sqlx::query_as::<_, SolanaTransaction>(
             r#"
             SELECT signature, slot, from_pubkey, to_pubkey, lamports,
-                   program_ids, instructions, block_time, created_at
+                   program_ids, instructions, block_time, tx_type, created_at
             FROM solana_transactions
             WHERE signature = $1
             "#,
@@ -81,15 +209,44 @@ impl TransactionRepository {
         Ok(tx)
     }
 
+    /// Slot and block time for a signature, without the full row
+    pub async fn get_status(&self, signature: &str) -> Result<Option<(i64, Option<i64>)>, sqlx::Error> {
+        sqlx::query_as::<_, (i64, Option<i64>)>(
+            r#"
+            SELECT slot, block_time
+            FROM solana_transactions
+            WHERE signature = $1
+            "#,
+        )
+        .bind(signature)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    /// Get the most recent signatures, newest first
+    pub async fn recent_signatures(&self, limit: i64) -> Result<Vec<String>, sqlx::Error> {
+        sqlx::query_scalar::<_, String>(
+            r#"
+            SELECT signature
+            FROM solana_transactions
+            ORDER BY slot DESC, signature DESC
+            LIMIT $1
+            "#,
+        )
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+    }
+
     /// Insert transaction or ignore if exists (idempotent)
     pub async fn insert_or_ignore(&self, tx: NewTransaction) -> Result<(), sqlx::Error> {
         sqlx::query(
             r#"
             INSERT INTO solana_transactions (
                 signature, slot, from_pubkey, to_pubkey, lamports,
-                program_ids, instructions, block_time
+                program_ids, instructions, block_time, tx_type
             )
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
             ON CONFLICT (signature) DO NOTHING
             "#,
         )
@@ -101,112 +258,33 @@ impl TransactionRepository {
         .bind(&tx.program_ids)
         .bind(&tx.instructions)
         .bind(tx.block_time)
+        .bind(&tx.tx_type)
         .execute(&self.pool)
         .await?;
 
         Ok(())
     }
 
-    /// List transactions with filters, pagination, and ordering
+    /// List transactions with filters, pagination, and ordering; the raw
+    /// `instructions` are only read with `include_instructions`
     pub async fn list(
         &self,
         filter: TransactionFilter,
         pagination: Pagination,
         order_by_slot_desc: bool,
-    ) -> Result<Vec<SolanaTransaction>, sqlx::Error> {
-        let mut query = String::from(
-            r#"
-            SELECT signature, slot, from_pubkey, to_pubkey, lamports,
-                   program_ids, instructions, block_time, created_at
-            FROM solana_transactions
-            WHERE 1=1
-            "#,
-        );
-
-        let mut params: Vec<Box<dyn sqlx::Encode<sqlx::Postgres> + Send + Sync>> = Vec::new();
-        let mut param_index = 1;
-
-        // Build dynamic WHERE clauses
-        if let Some(ref sig) = filter.signature {
-            query.push_str(&format!(" AND signature = ${}", param_index));
-            params.push(Box::new(sig.clone()));
-            param_index += 1;
-        }
-
-        if let Some(ref from) = filter.from_pubkey {
-            query.push_str(&format!(" AND from_pubkey = ${}", param_index));
-            params.push(Box::new(from.clone()));
-            param_index += 1;
-        }
-
-        if let Some(ref to) = filter.to_pubkey {
-            query.push_str(&format!(" AND to_pubkey = ${}", param_index));
-            params.push(Box::new(to.clone()));
-            param_index += 1;
-        }
-
-        if let Some(ref program_id) = filter.program_id {
-            query.push_str(&format!(" AND ${}::text = ANY(program_ids)", param_index));
-            params.push(Box::new(program_id.clone()));
-            param_index += 1;
-        }
-
-        if let Some(slot_from) = filter.slot_from {
-            query.push_str(&format!(" AND slot >= ${}", param_index));
-            params.push(Box::new(slot_from));
-            param_index += 1;
-        }
-
-        if let Some(slot_to) = filter.slot_to {
-            query.push_str(&format!(" AND slot <= ${}", param_index));
-            params.push(Box::new(slot_to));
-            param_index += 1;
-        }
-
-        // Order by
-        if order_by_slot_desc {
-            query.push_str(" ORDER BY slot DESC");
-        } else {
-            query.push_str(" ORDER BY slot ASC");
-        }
-
-        // Pagination
-        query.push_str(&format!(
-            " LIMIT ${} OFFSET ${}",
-            param_index,
-            param_index + 1
-        ));
-        params.push(Box::new(pagination.limit));
-        params.push(Box::new(pagination.offset));
-
-        // Execute query
-        // Note: Dynamic query building with sqlx is complex, so for production
-        // consider using typed query builders or conditional query construction
-        // For now, use a simpler approach without dynamic params
-
-        // Fallback: use basic query without dynamic filters for now
-        let txs = if filter.signature.is_none()
-            && filter.from_pubkey.is_none()
-            && filter.to_pubkey.is_none()
-            && filter.program_id.is_none()
-            && filter.slot_from.is_none()
-            && filter.slot_to.is_none()
-        {
+        include_instructions: bool,
+    ) -> Result<Vec<TransactionListRow>, sqlx::Error> {
+        let txs = if filter.is_empty() {
             // No filters, simple query
-            let order = if order_by_slot_desc {
-                "DESC"
-            } else {
-                "ASC"
-            };
-            sqlx::query_as::<_, SolanaTransaction>(&format!(
+            sqlx::query_as::<_, TransactionListRow>(&format!(
                 r#"
-                SELECT signature, slot, from_pubkey, to_pubkey, lamports,
-                       program_ids, instructions, block_time, created_at
+                SELECT {}
                 FROM solana_transactions
-                ORDER BY slot {}
+                {}
                 LIMIT $1 OFFSET $2
                 "#,
-                order
+                list_columns(include_instructions),
+                slot_order_clause(order_by_slot_desc)
             ))
             .bind(pagination.limit)
             .bind(pagination.offset)
@@ -214,7 +292,7 @@ impl TransactionRepository {
             .await?
         } else {
             // With filters - build conditionally
-            self.list_with_filters(filter, pagination, order_by_slot_desc)
+            self.list_with_filters(filter, pagination, order_by_slot_desc, include_instructions)
                 .await?
         };
 
@@ -226,60 +304,51 @@ impl TransactionRepository {
         filter: TransactionFilter,
         pagination: Pagination,
         order_by_slot_desc: bool,
-    ) -> Result<Vec<SolanaTransaction>, sqlx::Error> {
-        // Build query with all possible filters
-        let order = if order_by_slot_desc {
-            "DESC"
-        } else {
-            "ASC"
-        };
-
-        let mut query_builder = sqlx::QueryBuilder::new(
-            "SELECT signature, slot, from_pubkey, to_pubkey, lamports, program_ids, instructions, block_time, created_at FROM solana_transactions WHERE 1=1",
-        );
-
-        if let Some(ref sig) = filter.signature {
-            query_builder.push(" AND signature = ");
-            query_builder.push_bind(sig);
-        }
-
-        if let Some(ref from) = filter.from_pubkey {
-            query_builder.push(" AND from_pubkey = ");
-            query_builder.push_bind(from);
-        }
-
-        if let Some(ref to) = filter.to_pubkey {
-            query_builder.push(" AND to_pubkey = ");
-            query_builder.push_bind(to);
-        }
-
-        if let Some(ref program_id) = filter.program_id {
-            query_builder.push(" AND ");
-            query_builder.push_bind(program_id);
-            query_builder.push(" = ANY(program_ids)");
-        }
+        include_instructions: bool,
+    ) -> Result<Vec<TransactionListRow>, sqlx::Error> {
+        let txs = list_query(&filter, &pagination, order_by_slot_desc, include_instructions)
+            .build_query_as::<TransactionListRow>()
+            .fetch_all(&self.pool)
+            .await?;
 
-        if let Some(slot_from) = filter.slot_from {
-            query_builder.push(" AND slot >= ");
-            query_builder.push_bind(slot_from);
-        }
+        Ok(txs)
+    }
 
-        if let Some(slot_to) = filter.slot_to {
-            query_builder.push(" AND slot <= ");
-            query_builder.push_bind(slot_to);
-        }
+    /// Highest slot stored so far, 0 when empty
+    pub async fn latest_slot(&self) -> Result<i64, sqlx::Error> {
+        let (slot,): (i64,) = sqlx::query_as("SELECT COALESCE(MAX(slot), 0) FROM solana_transactions")
+            .fetch_one(&self.pool)
+            .await?;
 
-        query_builder.push(format!(" ORDER BY slot {} LIMIT ", order));
-        query_builder.push_bind(pagination.limit);
-        query_builder.push(" OFFSET ");
-        query_builder.push_bind(pagination.offset);
+        Ok(slot)
+    }
 
-        let txs = query_builder
-            .build_query_as::<SolanaTransaction>()
-            .fetch_all(&self.pool)
-            .await?;
+    /// Highest slot and block time stored; 0 and None for an empty table
+    pub async fn latest_tip(&self) -> Result<(i64, Option<i64>), sqlx::Error> {
+        sqlx::query_as("SELECT COALESCE(MAX(slot), 0), MAX(block_time) FROM solana_transactions")
+            .fetch_one(&self.pool)
+            .await
+    }
 
-        Ok(txs)
+    /// Transactions after `cursor` in `(slot, signature)` order (for SSE resume),
+    /// so rows left in the cursor's own slot aren't skipped
+    pub async fn list_after_cursor(
+        &self,
+        cursor: &StreamCursor,
+        limit: i64,
+    ) -> Result<Vec<SolanaTransaction>, sqlx::Error> {
+        sqlx::query_as::<_, SolanaTransaction>(
+            "SELECT signature, slot, from_pubkey, to_pubkey, lamports, program_ids, instructions, block_time, tx_type, created_at
+             FROM solana_transactions
+             WHERE (slot, signature) > ($1, $2)
+             ORDER BY slot ASC, signature ASC
+             LIMIT $3"
+        )
+        .bind(cursor.slot)
+        .bind(&cursor.signature)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
     }
 
     /// Get transactions since a specific slot (for WebSocket resume)
@@ -289,10 +358,10 @@ impl TransactionRepository {
         limit: i64,
     ) -> Result<Vec<SolanaTransaction>, sqlx::Error> {
         let transactions = sqlx::query_as::<_, SolanaTransaction>(
-            "SELECT signature, slot, from_pubkey, to_pubkey, lamports, program_ids, instructions, block_time, created_at 
+            "SELECT signature, slot, from_pubkey, to_pubkey, lamports, program_ids, instructions, block_time, tx_type, created_at 
              FROM solana_transactions 
              WHERE slot > $1 
-             ORDER BY slot ASC 
+             ORDER BY slot ASC, signature ASC 
              LIMIT $2"
         )
         .bind(since_slot)
@@ -303,6 +372,24 @@ impl TransactionRepository {
         Ok(transactions)
     }
 
+    /// Transactions inserted after `after`, oldest first by `(created_at, signature)`
+    ///
+    /// `created_at` is stamped when the inserting transaction starts, so rows
+    /// can become visible out of that order. Rows younger than `settle_ms` are
+    /// held back until every insert that began before them has committed,
+    /// which keeps the feed gap-free as long as no insert outlasts `settle_ms`.
+    pub async fn list_changes(
+        &self,
+        after: Option<&ChangeCursor>,
+        settle_ms: u64,
+        limit: i64,
+    ) -> Result<Vec<SolanaTransaction>, sqlx::Error> {
+        changes_query(after, settle_ms, limit)
+            .build_query_as::<SolanaTransaction>()
+            .fetch_all(&self.pool)
+            .await
+    }
+
     /// Bulk insert or ignore transactions (idempotent)
     pub async fn bulk_insert_or_ignore(
         &self,
@@ -313,11 +400,12 @@ impl TransactionRepository {
                 processed: 0,
                 inserted: 0,
                 skipped: 0,
+                inserted_signatures: vec![],
                 errors: vec![],
             });
         }
 
-        let mut inserted = 0;
+        let mut inserted_signatures = Vec::new();
         let mut skipped = 0;
         let mut errors = Vec::new();
 
@@ -325,7 +413,7 @@ impl TransactionRepository {
         const CHUNK_SIZE: usize = 50;
         for chunk in transactions.chunks(CHUNK_SIZE) {
             let mut query_builder = sqlx::QueryBuilder::new(
-                "INSERT INTO solana_transactions (signature, slot, from_pubkey, to_pubkey, lamports, program_ids, instructions, block_time) VALUES "
+                "INSERT INTO solana_transactions (signature, slot, from_pubkey, to_pubkey, lamports, program_ids, instructions, block_time, tx_type) VALUES "
             );
 
             let mut separated = query_builder.separated(", ");
@@ -339,16 +427,17 @@ impl TransactionRepository {
                 separated.push_bind(&tx.program_ids);
                 separated.push_bind(&tx.instructions);
                 separated.push_bind(tx.block_time);
+                separated.push_bind(&tx.tx_type);
                 separated.push(")");
             }
 
-            query_builder.push(" ON CONFLICT (signature) DO NOTHING");
+            // Conflicting rows aren't returned, so this is exactly what was inserted
+            query_builder.push(" ON CONFLICT (signature) DO NOTHING RETURNING signature");
 
-            match query_builder.build().execute(&self.pool).await {
-                Ok(result) => {
-                    let rows_affected = result.rows_affected();
-                    inserted += rows_affected;
-                    skipped += chunk.len() as u64 - rows_affected;
+            match query_builder.build_query_scalar::<String>().fetch_all(&self.pool).await {
+                Ok(signatures) => {
+                    skipped += chunk.len() - signatures.len();
+                    inserted_signatures.extend(signatures);
                 }
                 Err(e) => {
                     error!("Failed to insert chunk: {}", e);
@@ -364,12 +453,44 @@ impl TransactionRepository {
 
         Ok(crate::ingest::BatchResult {
             processed: transactions.len(),
-            inserted: inserted as usize,
-            skipped: skipped as usize,
+            inserted: inserted_signatures.len(),
+            skipped,
+            inserted_signatures,
             errors,
         })
     }
 
+    /// Like `get_summary`, with an approximate total: the planner's row
+    /// estimate when unfiltered, otherwise a count that stops at
+    /// `ESTIMATE_COUNT_CAP` rows
+    pub async fn get_summary_estimate(
+        &self,
+        filter: &TransactionFilter,
+    ) -> Result<(i64, i64, chrono::DateTime<chrono::Utc>), sqlx::Error> {
+        let mut query_builder = sqlx::QueryBuilder::new("SELECT ");
+        if filter.is_empty() {
+            // -1 until the table has been analyzed
+            query_builder.push(
+                "(SELECT GREATEST(reltuples, 0)::bigint FROM pg_class WHERE oid = 'solana_transactions'::regclass) as total",
+            );
+        } else {
+            query_builder.push("(SELECT COUNT(*) FROM (SELECT 1 FROM solana_transactions WHERE 1=1");
+            push_filters(&mut query_builder, filter);
+            query_builder.push(" LIMIT ");
+            query_builder.push_bind(ESTIMATE_COUNT_CAP);
+            query_builder.push(") capped) as total");
+        }
+        query_builder.push(
+            ", COALESCE(MAX(slot), 0) as max_slot, COALESCE(MAX(created_at), '1970-01-01'::timestamptz) as max_created_at FROM solana_transactions WHERE 1=1",
+        );
+        push_filters(&mut query_builder, filter);
+
+        let (total, max_slot, max_created_at): (Option<i64>, i64, chrono::DateTime<chrono::Utc>) =
+            query_builder.build_query_as().fetch_one(&self.pool).await?;
+
+        Ok((total.unwrap_or(0), max_slot, max_created_at))
+    }
+
     /// Get summary statistics for ETag calculation
     pub async fn get_summary(
         &self,
@@ -379,36 +500,7 @@ impl TransactionRepository {
             "SELECT COUNT(*) as total, COALESCE(MAX(slot), 0) as max_slot, COALESCE(MAX(created_at), '1970-01-01'::timestamptz) as max_created_at FROM solana_transactions WHERE 1=1",
         );
 
-        if let Some(ref sig) = filter.signature {
-            query_builder.push(" AND signature = ");
-            query_builder.push_bind(sig);
-        }
-
-        if let Some(ref from) = filter.from_pubkey {
-            query_builder.push(" AND from_pubkey = ");
-            query_builder.push_bind(from);
-        }
-
-        if let Some(ref to) = filter.to_pubkey {
-            query_builder.push(" AND to_pubkey = ");
-            query_builder.push_bind(to);
-        }
-
-        if let Some(ref program_id) = filter.program_id {
-            query_builder.push(" AND ");
-            query_builder.push_bind(program_id);
-            query_builder.push(" = ANY(program_ids)");
-        }
-
-        if let Some(slot_from) = filter.slot_from {
-            query_builder.push(" AND slot >= ");
-            query_builder.push_bind(slot_from);
-        }
-
-        if let Some(slot_to) = filter.slot_to {
-            query_builder.push(" AND slot <= ");
-            query_builder.push_bind(slot_to);
-        }
+        push_filters(&mut query_builder, filter);
 
         #[derive(sqlx::FromRow)]
         struct Summary {
@@ -431,3 +523,530 @@ impl TransactionRepository {
         ))
     }
 }
+
+// Slot order with the signature as tie-break, so rows sharing a slot keep
+// the same order from one page to the next
+fn slot_order_clause(desc: bool) -> &'static str {
+    if desc {
+        " ORDER BY slot DESC, signature DESC"
+    } else {
+        " ORDER BY slot ASC, signature ASC"
+    }
+}
+
+// Columns of a `TransactionListRow`; the count is taken in SQL so the array
+// itself only leaves the database when it's asked for
+fn list_columns(include_instructions: bool) -> String {
+    format!(
+        "signature, slot, from_pubkey, to_pubkey, lamports, program_ids, {}\
+         CASE jsonb_typeof(instructions) WHEN 'array' THEN jsonb_array_length(instructions) ELSE 0 END AS instruction_count, \
+         block_time, tx_type, created_at",
+        if include_instructions { "instructions, " } else { "" }
+    )
+}
+
+// Filtered, ordered and paginated listing query
+fn list_query<'args>(
+    filter: &'args TransactionFilter,
+    pagination: &Pagination,
+    order_by_slot_desc: bool,
+    include_instructions: bool,
+) -> sqlx::QueryBuilder<'args, sqlx::Postgres> {
+    let mut query_builder = sqlx::QueryBuilder::new(format!(
+        "SELECT {} FROM solana_transactions WHERE 1=1",
+        list_columns(include_instructions)
+    ));
+
+    push_filters(&mut query_builder, filter);
+
+    query_builder.push(slot_order_clause(order_by_slot_desc));
+    query_builder.push(" LIMIT ");
+    query_builder.push_bind(pagination.limit);
+    query_builder.push(" OFFSET ");
+    query_builder.push_bind(pagination.offset);
+    query_builder
+}
+
+// Changes feed page; the settle horizon uses the database clock, the same one
+// that stamped created_at
+fn changes_query<'args>(
+    after: Option<&'args ChangeCursor>,
+    settle_ms: u64,
+    limit: i64,
+) -> sqlx::QueryBuilder<'args, sqlx::Postgres> {
+    let mut query_builder = sqlx::QueryBuilder::new(
+        "SELECT signature, slot, from_pubkey, to_pubkey, lamports, program_ids, instructions, block_time, tx_type, created_at FROM solana_transactions WHERE created_at < NOW() - ",
+    );
+    query_builder.push_bind(settle_ms as f64);
+    query_builder.push(" * INTERVAL '1 millisecond'");
+
+    if let Some(cursor) = after {
+        query_builder.push(" AND (created_at, signature) > (");
+        query_builder.push_bind(cursor.created_at);
+        query_builder.push(", ");
+        query_builder.push_bind(&cursor.signature);
+        query_builder.push(")");
+    }
+
+    query_builder.push(" ORDER BY created_at ASC, signature ASC LIMIT ");
+    query_builder.push_bind(limit);
+    query_builder
+}
+
+// Append the filter's WHERE clauses to a query ending in `WHERE 1=1`
+fn push_filters<'args>(query_builder: &mut sqlx::QueryBuilder<'args, sqlx::Postgres>, filter: &'args TransactionFilter) {
+    if let Some(ref sig) = filter.signature {
+        query_builder.push(" AND signature = ");
+        query_builder.push_bind(sig);
+    }
+
+    if let Some(ref from) = filter.from_pubkey {
+        query_builder.push(" AND from_pubkey = ");
+        query_builder.push_bind(from);
+    }
+
+    if let Some(ref to) = filter.to_pubkey {
+        query_builder.push(" AND to_pubkey = ");
+        query_builder.push_bind(to);
+    }
+
+    if let Some(ref address) = filter.address {
+        query_builder.push(" AND (from_pubkey = ");
+        query_builder.push_bind(address);
+        query_builder.push(" OR to_pubkey = ");
+        query_builder.push_bind(address);
+        query_builder.push(")");
+    }
+
+    // NULL program_ids makes ANY() NULL and an empty array makes it false, so
+    // neither ever matches; `ws::involves_program` follows the same rule
+    if let Some(ref program_id) = filter.program_id {
+        query_builder.push(" AND ");
+        query_builder.push_bind(program_id);
+        query_builder.push(" = ANY(program_ids)");
+    }
+
+    if let Some(ref tx_type) = filter.tx_type {
+        query_builder.push(" AND tx_type = ");
+        query_builder.push_bind(tx_type);
+    }
+
+    if let Some(slot_from) = filter.slot_from {
+        query_builder.push(" AND slot >= ");
+        query_builder.push_bind(slot_from);
+    }
+
+    if let Some(slot_to) = filter.slot_to {
+        query_builder.push(" AND slot <= ");
+        query_builder.push_bind(slot_to);
+    }
+
+    // A NULL lamports column never satisfies either predicate
+    if let Some(min_lamports) = filter.min_lamports {
+        query_builder.push(" AND lamports >= ");
+        query_builder.push_bind(min_lamports);
+    }
+
+    if filter.exclude_zero_lamports {
+        query_builder.push(" AND lamports > 0");
+    }
+
+    // The planner can't turn `LIKE $1 || '%'` into an index range, since the
+    // pattern is only known at run time, so prefixes are spelled out as the
+    // range itself. The pattern operators (~>=~, ~<~) compare bytewise and are
+    // the ones the varchar_pattern_ops indexes serve
+    if let Some(ref prefix) = filter.signature_prefix {
+        query_builder.push(" AND signature ~>=~ ");
+        query_builder.push_bind(prefix);
+        query_builder.push(" AND signature ~<~ ");
+        query_builder.push_bind(prefix_upper_bound(prefix));
+    }
+
+    if let Some(ref prefix) = filter.address_prefix {
+        let upper = prefix_upper_bound(prefix);
+        query_builder.push(" AND ((from_pubkey ~>=~ ");
+        query_builder.push_bind(prefix);
+        query_builder.push(" AND from_pubkey ~<~ ");
+        query_builder.push_bind(upper.clone());
+        query_builder.push(") OR (to_pubkey ~>=~ ");
+        query_builder.push_bind(prefix);
+        query_builder.push(" AND to_pubkey ~<~ ");
+        query_builder.push_bind(upper);
+        query_builder.push("))");
+    }
+}
+
+// The first string past every one starting with `prefix`. Prefixes are
+// validated as base58 by the caller, so the last character is ASCII below 'z'
+// and bumping it can't overflow
+fn prefix_upper_bound(prefix: &str) -> String {
+    let mut upper = prefix.to_string();
+    if let Some(last) = upper.pop() {
+        upper.push(char::from_u32(last as u32 + 1).unwrap_or(char::MAX));
+    }
+    upper
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_prefix_filters_build_range_predicates() {
+        let filter = TransactionFilter {
+            signature_prefix: Some("5VERv8".to_string()),
+            address_prefix: Some("Vote111".to_string()),
+            ..TransactionFilter::default()
+        };
+        let mut query_builder = sqlx::QueryBuilder::new("SELECT signature FROM solana_transactions WHERE 1=1");
+        push_filters(&mut query_builder, &filter);
+
+        assert_eq!(
+            query_builder.sql(),
+            "SELECT signature FROM solana_transactions WHERE 1=1 AND signature ~>=~ $1 AND signature ~<~ $2 \
+             AND ((from_pubkey ~>=~ $3 AND from_pubkey ~<~ $4) OR (to_pubkey ~>=~ $5 AND to_pubkey ~<~ $6))"
+        );
+        assert_eq!(prefix_upper_bound("5VERv8"), "5VERv9");
+        assert_eq!(prefix_upper_bound("Vote11z"), "Vote11{");
+    }
+
+    #[test]
+    fn test_address_filter_matches_either_side() {
+        let filter = TransactionFilter {
+            address: Some("alice".to_string()),
+            ..TransactionFilter::default()
+        };
+        assert!(!filter.is_empty());
+        let mut query_builder = sqlx::QueryBuilder::new("SELECT signature FROM solana_transactions WHERE 1=1");
+        push_filters(&mut query_builder, &filter);
+
+        assert_eq!(
+            query_builder.sql(),
+            "SELECT signature FROM solana_transactions WHERE 1=1 AND (from_pubkey = $1 OR to_pubkey = $2)"
+        );
+    }
+
+    #[test]
+    fn test_tx_type_filter_builds_predicate() {
+        let filter = TransactionFilter {
+            tx_type: Some("swap".to_string()),
+            ..TransactionFilter::default()
+        };
+        assert!(!filter.is_empty());
+
+        let page = list_query(&filter, &Pagination::default(), true, false);
+        assert!(page.sql().contains("WHERE 1=1 AND tx_type = $1 ORDER BY"), "{}", page.sql());
+        assert!(page.sql().starts_with("SELECT signature, slot, from_pubkey, to_pubkey, lamports, program_ids, CASE"), "{}", page.sql());
+        assert!(page.sql().contains("AS instruction_count, block_time, tx_type, created_at FROM"), "{}", page.sql());
+
+        // The raw array is only selected when asked for
+        let page = list_query(&filter, &Pagination::default(), true, true);
+        assert!(page.sql().starts_with("SELECT signature, slot, from_pubkey, to_pubkey, lamports, program_ids, instructions, CASE"), "{}", page.sql());
+    }
+
+    /// Stored `program_ids` against whether a `program_id=prog` filter matches
+    fn program_filter_cases() -> Vec<(Option<Vec<String>>, bool)> {
+        let ids = |ids: &[&str]| Some(ids.iter().map(|id| id.to_string()).collect());
+        vec![
+            (None, false),
+            (ids(&[]), false),
+            (ids(&["other"]), false),
+            (ids(&["other", "prog"]), true),
+        ]
+    }
+
+    fn program_filter() -> TransactionFilter {
+        TransactionFilter {
+            program_id: Some("prog".to_string()),
+            ..TransactionFilter::default()
+        }
+    }
+
+    #[test]
+    fn test_program_filter_semantics_match_the_ws_matcher() {
+        let mut query_builder = sqlx::QueryBuilder::new("SELECT signature FROM solana_transactions WHERE 1=1");
+        let filter = program_filter();
+        push_filters(&mut query_builder, &filter);
+        assert_eq!(query_builder.sql(), "SELECT signature FROM solana_transactions WHERE 1=1 AND $1 = ANY(program_ids)");
+
+        // The same rows, as WS events, are selected the same way
+        for (program_ids, expected) in program_filter_cases() {
+            let event = serde_json::json!({ "signature": "sig", "slot": 1, "program_ids": program_ids });
+            assert_eq!(crate::ws::involves_program(&event, "prog"), expected, "{:?}", program_ids);
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "needs Postgres at DATABASE_URL"]
+    async fn test_program_filter_sql_agrees_with_the_ws_matcher() {
+        let pool = PgPool::connect(&std::env::var("DATABASE_URL").unwrap()).await.unwrap();
+        let filter = program_filter();
+
+        for (program_ids, expected) in program_filter_cases() {
+            // The filter's own predicate, over a single row holding program_ids
+            let mut query_builder = sqlx::QueryBuilder::new("SELECT COUNT(*) FROM (SELECT ");
+            query_builder.push_bind(program_ids.clone());
+            query_builder.push("::text[] AS program_ids) solana_transactions WHERE 1=1");
+            push_filters(&mut query_builder, &filter);
+            let (matched,): (i64,) = query_builder.build_query_as().fetch_one(&pool).await.unwrap();
+
+            assert_eq!(matched == 1, expected, "{:?}", program_ids);
+        }
+    }
+
+    #[test]
+    fn test_lamport_filters_build_predicates() {
+        let filter = TransactionFilter {
+            min_lamports: Some(1_000),
+            exclude_zero_lamports: true,
+            ..TransactionFilter::default()
+        };
+
+        // The summary the ETag is computed from sees the same predicates as the page
+        let mut summary = sqlx::QueryBuilder::new("SELECT COUNT(*) FROM solana_transactions WHERE 1=1");
+        push_filters(&mut summary, &filter);
+        assert_eq!(
+            summary.sql(),
+            "SELECT COUNT(*) FROM solana_transactions WHERE 1=1 AND lamports >= $1 AND lamports > 0"
+        );
+        let page = list_query(&filter, &Pagination::default(), true, false);
+        assert!(page.sql().contains("WHERE 1=1 AND lamports >= $1 AND lamports > 0 ORDER BY"));
+    }
+
+    #[test]
+    fn test_slot_ordering_breaks_ties_by_signature() {
+        let filter = TransactionFilter {
+            slot_from: Some(100),
+            ..TransactionFilter::default()
+        };
+        let page = Pagination { limit: 20, offset: 20 };
+
+        let desc = list_query(&filter, &page, true, false);
+        assert!(desc.sql().ends_with("WHERE 1=1 AND slot >= $1 ORDER BY slot DESC, signature DESC LIMIT $2 OFFSET $3"));
+        let asc = list_query(&filter, &page, false, false);
+        assert!(asc.sql().ends_with("ORDER BY slot ASC, signature ASC LIMIT $2 OFFSET $3"));
+    }
+
+    #[tokio::test]
+    #[ignore = "needs Postgres at DATABASE_URL"]
+    async fn test_same_slot_pages_are_disjoint_complete_and_stable() {
+        let pool = PgPool::connect(&std::env::var("DATABASE_URL").unwrap()).await.unwrap();
+        let repo = TransactionRepository::new(pool.clone());
+        let run = uuid::Uuid::new_v4().simple().to_string();
+
+        // One slot, inserted out of signature order so heap order can't pass for a tiebreak
+        for i in (0..10).map(|i| (i * 7) % 10) {
+            sqlx::query("INSERT INTO solana_transactions (signature, slot, instructions) VALUES ($1, 42, '[]')")
+                .bind(format!("{}{:02}", run, i))
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
+
+        let filter = TransactionFilter {
+            slot_from: Some(42),
+            slot_to: Some(42),
+            signature_prefix: Some(run.clone()),
+            ..TransactionFilter::default()
+        };
+        let signatures = |txs: Vec<TransactionListRow>| txs.into_iter().map(|tx| tx.signature).collect::<Vec<_>>();
+        let mut paged = Vec::new();
+        for offset in [0, 4, 8, 12] {
+            let page = Pagination { limit: 4, offset };
+            let first = signatures(repo.list(filter.clone(), page.clone(), true, false).await.unwrap());
+            let again = signatures(repo.list(filter.clone(), page, true, true).await.unwrap());
+            assert_eq!(again, first, "page at offset {} moved", offset);
+            paged.extend(first);
+        }
+
+        sqlx::query("DELETE FROM solana_transactions WHERE signature LIKE $1 || '%'")
+            .bind(&run)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        // Every row exactly once, in signature order within the slot
+        let expected: Vec<String> = (0..10).rev().map(|i| format!("{}{:02}", run, i)).collect();
+        assert_eq!(paged, expected);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs Postgres at DATABASE_URL"]
+    async fn test_prefix_filters_match_exactly_and_use_the_pattern_ops_indexes() {
+        let pool = PgPool::connect(&std::env::var("DATABASE_URL").unwrap()).await.unwrap();
+        let run = uuid::Uuid::new_v4().simple().to_string();
+        for (suffix, from) in [("a", "x"), ("b", "y"), ("z", "z")] {
+            sqlx::query("INSERT INTO solana_transactions (signature, slot, from_pubkey, instructions) VALUES ($1, 1, $2, '[]')")
+                .bind(format!("{}{}", run, suffix))
+                .bind(format!("{}{}", run, from))
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
+        // Just past the range: the bumped prefix itself
+        sqlx::query("INSERT INTO solana_transactions (signature, slot, instructions) VALUES ($1, 1, '[]')")
+            .bind(prefix_upper_bound(&run))
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let mut conn = pool.acquire().await.unwrap();
+        sqlx::query("SET enable_seqscan = off").execute(&mut *conn).await.unwrap();
+        for (filter, index) in [
+            (
+                TransactionFilter {
+                    signature_prefix: Some(run.clone()),
+                    ..TransactionFilter::default()
+                },
+                "idx_tx_signature_prefix",
+            ),
+            (
+                TransactionFilter {
+                    address_prefix: Some(run.clone()),
+                    ..TransactionFilter::default()
+                },
+                "idx_tx_from_pubkey_prefix",
+            ),
+        ] {
+            let mut query_builder = sqlx::QueryBuilder::new("SELECT signature FROM solana_transactions WHERE 1=1");
+            push_filters(&mut query_builder, &filter);
+            let matched: Vec<(String,)> = query_builder.build_query_as().fetch_all(&mut *conn).await.unwrap();
+            assert_eq!(matched.len(), 3, "{:?}", filter);
+
+            // Whole rows, as the list query reads; a bare signature could be
+            // served by an index-only scan of any index holding it
+            let mut explain = sqlx::QueryBuilder::new("EXPLAIN SELECT * FROM solana_transactions WHERE 1=1");
+            push_filters(&mut explain, &filter);
+            let plan: Vec<(String,)> = explain.build_query_as().fetch_all(&mut *conn).await.unwrap();
+            let plan = plan.into_iter().map(|(line,)| line).collect::<Vec<_>>().join("\n");
+            assert!(plan.contains(index), "{} not used:\n{}", index, plan);
+        }
+        drop(conn);
+
+        sqlx::query("DELETE FROM solana_transactions WHERE signature LIKE $1 || '%' OR signature = $2")
+            .bind(&run)
+            .bind(prefix_upper_bound(&run))
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+
+    #[test]
+    fn test_change_cursor_round_trips_and_rejects_garbage() {
+        let cursor = ChangeCursor {
+            created_at: chrono::DateTime::from_timestamp_micros(1_731_234_567_123_456).unwrap(),
+            signature: "5VERv8NMvzbJMEkV8xnrLkEaWRtSz9CosKDYjCJjBRnb".to_string(),
+        };
+        assert_eq!(ChangeCursor::decode(&cursor.encode()), Some(cursor));
+
+        for garbage in ["", "123", "abc_sig", "123_", "123_sig';--", "_sig"] {
+            assert_eq!(ChangeCursor::decode(garbage), None, "{}", garbage);
+        }
+    }
+
+    #[test]
+    fn test_stream_cursor_round_trips_and_rejects_garbage() {
+        let cursor = StreamCursor {
+            slot: 42,
+            signature: "5VERv8NMvzbJMEkV8xnrLkEaWRtSz9CosKDYjCJjBRnb".to_string(),
+        };
+        assert_eq!(StreamCursor::decode(&cursor.encode()), Some(cursor));
+
+        for garbage in ["", "42", "abc_sig", "42_", "42_sig';--", "_sig"] {
+            assert_eq!(StreamCursor::decode(garbage), None, "{}", garbage);
+        }
+    }
+
+    #[test]
+    fn test_changes_query_orders_by_insertion_behind_the_settle_horizon() {
+        let first = changes_query(None, 2_000, 100);
+        assert!(first.sql().ends_with(
+            "WHERE created_at < NOW() - $1 * INTERVAL '1 millisecond' ORDER BY created_at ASC, signature ASC LIMIT $2"
+        ));
+
+        let cursor = ChangeCursor {
+            created_at: chrono::Utc::now(),
+            signature: "sig".to_string(),
+        };
+        let next = changes_query(Some(&cursor), 2_000, 100);
+        assert!(next.sql().ends_with(
+            "WHERE created_at < NOW() - $1 * INTERVAL '1 millisecond' AND (created_at, signature) > ($2, $3) \
+             ORDER BY created_at ASC, signature ASC LIMIT $4"
+        ));
+    }
+
+    #[tokio::test]
+    #[ignore = "needs Postgres at DATABASE_URL"]
+    async fn test_changes_feed_delivers_concurrent_inserts_exactly_once() {
+        let pool = PgPool::connect(&std::env::var("DATABASE_URL").unwrap()).await.unwrap();
+        let repo = TransactionRepository::new(pool.clone());
+        let run = uuid::Uuid::new_v4().simple().to_string();
+        let new_tx = |signature: String| NewTransaction {
+            signature,
+            slot: 1,
+            from_pubkey: None,
+            to_pubkey: None,
+            lamports: None,
+            program_ids: None,
+            instructions: serde_json::json!([]),
+            block_time: None,
+            tx_type: None,
+        };
+
+        // Start at this run, skipping whatever the table already holds
+        let (start,): (chrono::DateTime<chrono::Utc>,) = sqlx::query_as("SELECT NOW()").fetch_one(&pool).await.unwrap();
+        let settle_ms = 400;
+
+        // A slow insert stamped first but committed after the fast ones
+        let slow = async {
+            let mut tx = pool.begin().await.unwrap();
+            sqlx::query("INSERT INTO solana_transactions (signature, slot, instructions) VALUES ($1, 1, '[]')")
+                .bind(format!("{}slow", run))
+                .execute(&mut *tx)
+                .await
+                .unwrap();
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            tx.commit().await.unwrap();
+        };
+        let fast = async {
+            for i in 0..20 {
+                repo.insert_or_ignore(new_tx(format!("{}fast{:02}", run, i))).await.unwrap();
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        };
+        // A client syncing in small pages the whole time
+        let sync = async {
+            let mut cursor = ChangeCursor {
+                created_at: start,
+                signature: String::new(),
+            };
+            let mut seen = Vec::new();
+            let deadline = tokio::time::Instant::now() + Duration::from_millis(1_500);
+            while tokio::time::Instant::now() < deadline {
+                let page = repo.list_changes(Some(&cursor), settle_ms, 3).await.unwrap();
+                if let Some(last) = page.last() {
+                    cursor = ChangeCursor::of(last);
+                } else {
+                    tokio::time::sleep(Duration::from_millis(20)).await;
+                }
+                seen.extend(page.into_iter().map(|tx| tx.signature).filter(|sig| sig.starts_with(&run)));
+            }
+            seen
+        };
+        let ((), (), seen) = tokio::join!(slow, fast, sync);
+
+        sqlx::query("DELETE FROM solana_transactions WHERE signature LIKE $1 || '%'")
+            .bind(&run)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let mut expected: Vec<String> = (0..20).map(|i| format!("{}fast{:02}", run, i)).collect();
+        expected.push(format!("{}slow", run));
+        let mut delivered = seen.clone();
+        delivered.sort();
+        expected.sort();
+        assert_eq!(delivered, expected, "delivered in order {:?}", seen);
+    }
+}
@@ -105,6 +105,28 @@ impl UserRepository {
         Ok(())
     }
 
+    /// Remove a user's permission for endpoint, returning whether it was held
+    pub async fn remove_permission(
+        &self,
+        pubkey: &str,
+        endpoint: &str,
+        permission: &str,
+    ) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(
+            r#"
+            DELETE FROM user_permissions
+            WHERE pubkey = $1 AND endpoint = $2 AND permission = $3
+            "#,
+        )
+        .bind(pubkey)
+        .bind(endpoint)
+        .bind(permission)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
     /// Check if user has permission for endpoint
     pub async fn has_permission(
         &self,
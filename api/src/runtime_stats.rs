@@ -0,0 +1,118 @@
+// Process-level runtime statistics for admin introspection
+// Tracks uptime and open HTTP/WebSocket connections; everything else is sampled on demand
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Counters shared by every HTTP worker
+pub struct RuntimeStats {
+    started_at: Instant,
+    started_at_utc: chrono::DateTime<chrono::Utc>,
+    http_connections: AtomicUsize,
+    ws_connections: AtomicUsize,
+}
+
+impl RuntimeStats {
+    pub fn new() -> Self {
+        Self {
+            started_at: Instant::now(),
+            started_at_utc: chrono::Utc::now(),
+            http_connections: AtomicUsize::new(0),
+            ws_connections: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn uptime_secs(&self) -> u64 {
+        self.started_at.elapsed().as_secs()
+    }
+
+    pub fn started_at(&self) -> chrono::DateTime<chrono::Utc> {
+        self.started_at_utc
+    }
+
+    pub fn http_connections(&self) -> usize {
+        self.http_connections.load(Ordering::Relaxed)
+    }
+
+    /// Count a connection until the returned guard is dropped
+    pub fn track_connection(self: &Arc<Self>) -> ConnectionGuard {
+        self.http_connections.fetch_add(1, Ordering::Relaxed);
+        ConnectionGuard(self.clone())
+    }
+
+    /// Upgraded WebSocket connections, also counted in `http_connections`
+    pub fn ws_connections(&self) -> usize {
+        self.ws_connections.load(Ordering::Relaxed)
+    }
+
+    /// Count a WebSocket until the returned guard is dropped
+    pub fn track_ws_connection(self: &Arc<Self>) -> WsConnectionGuard {
+        self.ws_connections.fetch_add(1, Ordering::Relaxed);
+        WsConnectionGuard(self.clone())
+    }
+
+    /// Wait until every open HTTP connection is a WebSocket, checking every
+    /// `interval`; WebSockets are closed separately at shutdown
+    pub async fn wait_for_http_drain(&self, interval: Duration) {
+        while self.http_connections() > self.ws_connections() {
+            tokio::time::sleep(interval).await;
+        }
+    }
+}
+
+impl Default for RuntimeStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Held in the connection's extensions; decrements the count on close
+pub struct ConnectionGuard(Arc<RuntimeStats>);
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        self.0.http_connections.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Held by the WebSocket actor; decrements the count when it stops
+pub struct WsConnectionGuard(Arc<RuntimeStats>);
+
+impl Drop for WsConnectionGuard {
+    fn drop(&mut self) {
+        self.0.ws_connections.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Resident set size of this process, from /proc (Linux only)
+pub fn process_rss_bytes() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let kib = status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmRSS:"))?
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse::<u64>()
+        .ok()?;
+    Some(kib * 1024)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_connection_guard_tracks_open_connections() {
+        let stats = Arc::new(RuntimeStats::new());
+        let first = stats.track_connection();
+        let second = stats.track_connection();
+        assert_eq!(stats.http_connections(), 2);
+
+        drop(first);
+        assert_eq!(stats.http_connections(), 1);
+        drop(second);
+        assert_eq!(stats.http_connections(), 0);
+    }
+}
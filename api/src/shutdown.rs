@@ -0,0 +1,132 @@
+/// Ordered shutdown
+///
+/// Resources are torn down one phase at a time, in the order the phases were
+/// added. Each phase gets `deploy.graceful_shutdown_secs`; one that overruns
+/// is logged and abandoned so a hung dependency can't stall the exit.
+
+use futures_util::future::LocalBoxFuture;
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+/// How a shutdown phase ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PhaseOutcome {
+    Completed,
+    TimedOut,
+}
+
+/// Named teardown steps, run in order
+pub struct ShutdownSequence<'a> {
+    timeout: Duration,
+    phases: Vec<(&'static str, LocalBoxFuture<'a, ()>)>,
+}
+
+impl<'a> ShutdownSequence<'a> {
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            phases: Vec::new(),
+        }
+    }
+
+    /// Add a phase; `teardown` isn't polled until every earlier phase is over
+    pub fn phase(mut self, name: &'static str, teardown: impl Future<Output = ()> + 'a) -> Self {
+        self.phases.push((name, Box::pin(teardown)));
+        self
+    }
+
+    /// Run the phases in order, returning how each one ended
+    pub async fn run(self) -> Vec<(&'static str, PhaseOutcome)> {
+        let mut outcomes = Vec::with_capacity(self.phases.len());
+        for (name, teardown) in self.phases {
+            tracing::info!(phase = name, "Shutdown phase started");
+            let started = Instant::now();
+            let outcome = match tokio::time::timeout(self.timeout, teardown).await {
+                Ok(()) => {
+                    tracing::info!(phase = name, elapsed_ms = started.elapsed().as_millis() as u64, "Shutdown phase completed");
+                    PhaseOutcome::Completed
+                }
+                Err(_) => {
+                    tracing::warn!(phase = name, timeout_secs = self.timeout.as_secs(), "Shutdown phase timed out, moving on");
+                    PhaseOutcome::TimedOut
+                }
+            };
+            outcomes.push((name, outcome));
+        }
+        outcomes
+    }
+}
+
+/// Resolves on Ctrl-C, or on SIGTERM where there is one
+pub async fn signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c().await.expect("Failed to listen for ctrl-c");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Failed to listen for SIGTERM")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    #[tokio::test]
+    async fn test_phases_run_in_order_and_a_hung_one_is_abandoned() {
+        let ran = RefCell::new(Vec::new());
+        let record = |name: &'static str, delay: Duration| {
+            let ran = &ran;
+            async move {
+                ran.borrow_mut().push(format!("{} started", name));
+                tokio::time::sleep(delay).await;
+                ran.borrow_mut().push(format!("{} done", name));
+            }
+        };
+
+        let started = Instant::now();
+        let outcomes = ShutdownSequence::new(Duration::from_millis(100))
+            .phase("http", record("http", Duration::from_millis(10)))
+            .phase("kafka", record("kafka", Duration::from_secs(60)))
+            .phase("websocket", record("websocket", Duration::ZERO))
+            .phase("postgres", record("postgres", Duration::from_millis(10)))
+            .run()
+            .await;
+
+        assert_eq!(
+            outcomes,
+            vec![
+                ("http", PhaseOutcome::Completed),
+                ("kafka", PhaseOutcome::TimedOut),
+                ("websocket", PhaseOutcome::Completed),
+                ("postgres", PhaseOutcome::Completed),
+            ]
+        );
+        assert_eq!(
+            *ran.borrow(),
+            [
+                "http started",
+                "http done",
+                "kafka started",
+                "websocket started",
+                "websocket done",
+                "postgres started",
+                "postgres done",
+            ]
+        );
+        // One timeout's worth for the hung phase, not its full minute
+        assert!(started.elapsed() < Duration::from_secs(1), "{:?}", started.elapsed());
+    }
+}
@@ -0,0 +1,183 @@
+// Bloom filter of known transaction signatures
+// Lets lookups short-circuit obvious misses before touching Postgres
+
+use std::collections::hash_map::DefaultHasher;
+use std::future::Future;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+
+/// Fixed-size bloom filter over transaction signatures.
+///
+/// A positive answer may be a false positive and must still be confirmed
+/// against the database. A negative answer only means the signature was never
+/// inserted here, so it is trusted once the filter is marked complete and
+/// otherwise falls back to the database.
+/// Memory is bounded by `capacity` and `fp_rate` at construction time.
+pub struct SignatureFilter {
+    bits: Vec<AtomicU64>,
+    num_bits: u64,
+    num_hashes: u32,
+    capacity: usize,
+    len: AtomicUsize,
+    complete: AtomicBool,
+}
+
+impl SignatureFilter {
+    pub fn new(capacity: usize, fp_rate: f64) -> Self {
+        let capacity = capacity.max(1);
+        let fp_rate = fp_rate.clamp(1e-9, 0.5);
+        let ln2 = std::f64::consts::LN_2;
+
+        // Standard sizing: m = -n ln p / (ln 2)^2, k = m / n * ln 2
+        let num_bits = ((-(capacity as f64) * fp_rate.ln()) / (ln2 * ln2)).ceil().max(64.0) as u64;
+        let num_hashes = ((num_bits as f64 / capacity as f64) * ln2).round().max(1.0) as u32;
+        let words = num_bits.div_ceil(64) as usize;
+
+        Self {
+            bits: (0..words).map(|_| AtomicU64::new(0)).collect(),
+            num_bits: words as u64 * 64,
+            num_hashes,
+            capacity,
+            len: AtomicUsize::new(0),
+            complete: AtomicBool::new(false),
+        }
+    }
+
+    fn hashes(signature: &str) -> (u64, u64) {
+        let mut h1 = DefaultHasher::new();
+        signature.hash(&mut h1);
+        let mut h2 = DefaultHasher::new();
+        (signature, 0x9e37_79b9_7f4a_7c15u64).hash(&mut h2);
+        (h1.finish(), h2.finish() | 1)
+    }
+
+    fn positions(&self, signature: &str) -> impl Iterator<Item = u64> + '_ {
+        let (h1, h2) = Self::hashes(signature);
+        (0..self.num_hashes as u64).map(move |i| h1.wrapping_add(i.wrapping_mul(h2)) % self.num_bits)
+    }
+
+    /// Record a signature as known
+    pub fn insert(&self, signature: &str) {
+        for pos in self.positions(signature) {
+            self.bits[(pos / 64) as usize].fetch_or(1 << (pos % 64), Ordering::Relaxed);
+        }
+
+        let len = self.len.fetch_add(1, Ordering::Relaxed) + 1;
+        if len == self.capacity {
+            tracing::warn!(
+                capacity = self.capacity,
+                "Signature filter reached capacity, false-positive rate will rise"
+            );
+        }
+    }
+
+    /// `false` means the signature was never inserted
+    pub fn might_contain(&self, signature: &str) -> bool {
+        self.positions(signature)
+            .all(|pos| self.bits[(pos / 64) as usize].load(Ordering::Relaxed) & (1 << (pos % 64)) != 0)
+    }
+
+    /// Declare that every stored signature has been inserted, e.g. the warmup
+    /// loaded the whole table and no other process writes to it
+    pub fn mark_complete(&self) {
+        self.complete.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether a miss is definitive
+    pub fn is_complete(&self) -> bool {
+        self.complete.load(Ordering::Relaxed)
+    }
+
+    /// Approximate number of inserted signatures
+    pub fn count(&self) -> usize {
+        self.len.load(Ordering::Relaxed)
+    }
+
+    /// Look up a signature, skipping `lookup` when a complete filter rules it out
+    pub async fn lookup<T, E, F, Fut>(
+        filter: Option<&SignatureFilter>,
+        signature: &str,
+        lookup: F,
+    ) -> Result<Option<T>, E>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<Option<T>, E>>,
+    {
+        if let Some(filter) = filter.filter(|filter| filter.is_complete()) {
+            if !filter.might_contain(signature) {
+                tracing::debug!(signature = %signature, "Signature filter miss, skipping DB lookup");
+                return Ok(None);
+            }
+        }
+
+        lookup().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicU32;
+
+    #[tokio::test]
+    async fn test_absent_signature_skips_lookup() {
+        let filter = SignatureFilter::new(1000, 0.01);
+        filter.insert("known");
+        filter.mark_complete();
+        let calls = AtomicU32::new(0);
+
+        let result: Result<Option<()>, ()> = SignatureFilter::lookup(Some(&filter), "unknown", || async {
+            calls.fetch_add(1, Ordering::SeqCst);
+            Ok(Some(()))
+        })
+        .await;
+
+        assert_eq!(result, Ok(None));
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn test_miss_falls_back_to_lookup_until_complete() {
+        let filter = SignatureFilter::new(1000, 0.01);
+        filter.insert("recent");
+        let calls = AtomicU32::new(0);
+
+        // Older rows, or rows another replica wrote, are only in the DB
+        let result: Result<Option<()>, ()> = SignatureFilter::lookup(Some(&filter), "older", || async {
+            calls.fetch_add(1, Ordering::SeqCst);
+            Ok(Some(()))
+        })
+        .await;
+
+        assert_eq!(result, Ok(Some(())));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_present_signature_still_confirms() {
+        let filter = SignatureFilter::new(1000, 0.01);
+        filter.insert("known");
+        let calls = AtomicU32::new(0);
+
+        // A positive still goes to the DB, which has the final say
+        let result: Result<Option<()>, ()> = SignatureFilter::lookup(Some(&filter), "known", || async {
+            calls.fetch_add(1, Ordering::SeqCst);
+            Ok(None)
+        })
+        .await;
+
+        assert_eq!(result, Ok(None));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_no_false_negatives() {
+        let filter = SignatureFilter::new(500, 0.01);
+        let sigs: Vec<String> = (0..500).map(|i| format!("sig{}", i)).collect();
+        for sig in &sigs {
+            filter.insert(sig);
+        }
+        assert!(sigs.iter().all(|sig| filter.might_contain(sig)));
+        assert_eq!(filter.count(), 500);
+    }
+}
@@ -0,0 +1,238 @@
+/// Periodically refreshed list summaries
+///
+/// With `cache.summary_refresh_ms` set, list requests read the
+/// `(total, max_slot, max_created_at)` summary for their filter from here
+/// instead of running `COUNT(*)` and `MAX` on every request. A background task
+/// recomputes each recently read filter on a jittered interval, so totals and
+/// ETags lag writes by up to one interval but stay stable during ingestion
+/// bursts. Filters nobody read for `IDLE_REFRESHES` intervals are dropped, and
+/// at most `cache.summary_max_entries` filters are kept, least recently read
+/// going first. Concurrent misses on one filter share a single fetch.
+
+use crate::repository::TransactionFilter;
+use rand::Rng;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// `(total, max_slot, max_created_at)` for a filter
+pub type Summary = (i64, i64, chrono::DateTime<chrono::Utc>);
+
+/// Refresh intervals a summary survives without being read
+const IDLE_REFRESHES: u32 = 10;
+
+struct Entry {
+    filter: TransactionFilter,
+    summary: Summary,
+    last_read: Instant,
+}
+
+pub struct SummaryCache {
+    interval: Duration,
+    max_entries: usize,
+    entries: Mutex<HashMap<String, Entry>>,
+    /// Per-filter fetch locks, held while a miss is being computed
+    fetching: Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>,
+}
+
+// Debug output covers every filter field, so equal filters share an entry
+fn key(filter: &TransactionFilter) -> String {
+    format!("{:?}", filter)
+}
+
+impl SummaryCache {
+    pub fn new(interval: Duration, max_entries: usize) -> Self {
+        Self {
+            interval,
+            max_entries: max_entries.max(1),
+            entries: Mutex::new(HashMap::new()),
+            fetching: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn cached(&self, key: &str) -> Option<Summary> {
+        let mut entries = self.entries.lock().unwrap();
+        let entry = entries.get_mut(key)?;
+        entry.last_read = Instant::now();
+        Some(entry.summary)
+    }
+
+    /// Cached summary for `filter`, computed with `fetch` on first use
+    pub async fn get_or_fetch<F, Fut>(&self, filter: TransactionFilter, fetch: F) -> Result<Summary, sqlx::Error>
+    where
+        F: FnOnce(TransactionFilter) -> Fut,
+        Fut: Future<Output = Result<Summary, sqlx::Error>>,
+    {
+        let key = key(&filter);
+        if let Some(summary) = self.cached(&key) {
+            return Ok(summary);
+        }
+
+        // Whoever takes the lock first fetches; the rest find its result
+        let lock = self.fetching.lock().unwrap().entry(key.clone()).or_default().clone();
+        let _guard = lock.lock().await;
+        if let Some(summary) = self.cached(&key) {
+            return Ok(summary);
+        }
+
+        let result = fetch(filter.clone()).await;
+        if let Ok(summary) = result {
+            let mut entries = self.entries.lock().unwrap();
+            if entries.len() >= self.max_entries {
+                let oldest = entries
+                    .iter()
+                    .min_by_key(|(_, entry)| entry.last_read)
+                    .map(|(key, _)| key.clone());
+                if let Some(oldest) = oldest {
+                    entries.remove(&oldest);
+                }
+            }
+            entries.insert(
+                key.clone(),
+                Entry {
+                    filter,
+                    summary,
+                    last_read: Instant::now(),
+                },
+            );
+        }
+        self.fetching.lock().unwrap().remove(&key);
+        result
+    }
+
+    /// Recompute every recently read summary; returns how many were refreshed
+    ///
+    /// A failed recompute keeps serving the previous value.
+    pub async fn refresh<F, Fut>(&self, fetch: F) -> usize
+    where
+        F: Fn(TransactionFilter) -> Fut,
+        Fut: Future<Output = Result<Summary, sqlx::Error>>,
+    {
+        let idle_after = self.interval * IDLE_REFRESHES;
+        let filters: Vec<(String, TransactionFilter)> = {
+            let mut entries = self.entries.lock().unwrap();
+            entries.retain(|_, entry| entry.last_read.elapsed() < idle_after);
+            entries.iter().map(|(key, entry)| (key.clone(), entry.filter.clone())).collect()
+        };
+
+        let mut refreshed = 0;
+        for (key, filter) in filters {
+            match fetch(filter).await {
+                Ok(summary) => {
+                    if let Some(entry) = self.entries.lock().unwrap().get_mut(&key) {
+                        entry.summary = summary;
+                        refreshed += 1;
+                    }
+                }
+                Err(e) => tracing::warn!(error = %e, "Failed to refresh list summary"),
+            }
+        }
+        refreshed
+    }
+
+    /// The interval give or take 10%, so replicas don't hit the DB in lockstep
+    fn next_delay(&self) -> Duration {
+        let ms = self.interval.as_millis() as u64;
+        let jitter = ms / 10;
+        Duration::from_millis(rand::thread_rng().gen_range(ms - jitter..=ms + jitter).max(1))
+    }
+
+    /// Refresh in the background every `cache.summary_refresh_ms`, jittered
+    pub fn spawn_refresh<F, Fut>(self: Arc<Self>, fetch: F)
+    where
+        F: Fn(TransactionFilter) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Summary, sqlx::Error>> + Send,
+    {
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(self.next_delay()).await;
+                let refreshed = self.refresh(&fetch).await;
+                tracing::debug!(refreshed, "List summaries refreshed");
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicI64, Ordering};
+
+    #[tokio::test]
+    async fn test_summary_is_served_cached_and_refreshed_on_interval() {
+        let cache = Arc::new(SummaryCache::new(Duration::from_millis(20), 16));
+        let queries = Arc::new(AtomicI64::new(0));
+        let fetch = {
+            let queries = queries.clone();
+            move |_: TransactionFilter| {
+                let total = queries.fetch_add(1, Ordering::SeqCst) + 1;
+                async move { Ok((total, 7, chrono::DateTime::UNIX_EPOCH)) }
+            }
+        };
+        let filter = TransactionFilter {
+            from_pubkey: Some("alice".to_string()),
+            ..TransactionFilter::default()
+        };
+
+        // The first read computes, later reads don't touch the DB
+        assert_eq!(cache.get_or_fetch(filter.clone(), &fetch).await.unwrap().0, 1);
+        assert_eq!(cache.get_or_fetch(filter.clone(), &fetch).await.unwrap().0, 1);
+        assert_eq!(queries.load(Ordering::SeqCst), 1);
+
+        // Another filter gets its own summary
+        let other = cache.get_or_fetch(TransactionFilter::default(), &fetch).await.unwrap();
+        assert_eq!(other.0, 2);
+
+        // The background task recomputes both
+        cache.clone().spawn_refresh(fetch.clone());
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        let refreshed = cache.get_or_fetch(filter, &fetch).await.unwrap().0;
+        assert!(refreshed > 2, "summary was not refreshed: {}", refreshed);
+        assert!(queries.load(Ordering::SeqCst) >= 4);
+    }
+
+    fn from_pubkey(pubkey: &str) -> TransactionFilter {
+        TransactionFilter {
+            from_pubkey: Some(pubkey.to_string()),
+            ..TransactionFilter::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_entries_are_capped_least_recently_read_first() {
+        let cache = SummaryCache::new(Duration::from_secs(60), 2);
+        let fetch = |_: TransactionFilter| async { Ok((1, 1, chrono::DateTime::UNIX_EPOCH)) };
+
+        cache.get_or_fetch(from_pubkey("a"), fetch).await.unwrap();
+        cache.get_or_fetch(from_pubkey("b"), fetch).await.unwrap();
+        cache.get_or_fetch(from_pubkey("a"), fetch).await.unwrap();
+        cache.get_or_fetch(from_pubkey("c"), fetch).await.unwrap();
+
+        let entries = cache.entries.lock().unwrap();
+        assert_eq!(entries.len(), 2);
+        assert!(entries.contains_key(&key(&from_pubkey("a"))));
+        assert!(!entries.contains_key(&key(&from_pubkey("b"))));
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_misses_share_one_fetch() {
+        let cache = SummaryCache::new(Duration::from_secs(60), 16);
+        let queries = AtomicI64::new(0);
+        let fetch = |_: TransactionFilter| {
+            let total = queries.fetch_add(1, Ordering::SeqCst) + 1;
+            async move {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                Ok((total, 1, chrono::DateTime::UNIX_EPOCH))
+            }
+        };
+
+        let results = futures_util::future::join_all(
+            (0..8).map(|_| cache.get_or_fetch(from_pubkey("a"), fetch)),
+        )
+        .await;
+        assert!(results.iter().all(|result| result.as_ref().unwrap().0 == 1));
+        assert_eq!(queries.load(Ordering::SeqCst), 1);
+        assert!(cache.fetching.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,260 @@
+// WAF ban and grey lists with per-IP expiry
+// Redis keeps each list as a sorted set scored by expiry time, so every IP
+// expires on its own schedule; a background task purges expired members.
+// The optional reputation feed is read-only: something else loads it
+
+use crate::config::WafConfig;
+use crate::infra::redis::RedisClient;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+enum IpListBackend {
+    Redis(RedisClient),
+    /// ip -> expires_at_ms
+    Memory(Mutex<HashMap<String, i64>>),
+}
+
+/// Set of IPs, each with its own TTL
+pub struct IpList {
+    backend: IpListBackend,
+    key: String,
+    ttl_ms: i64,
+}
+
+fn now_ms() -> i64 {
+    chrono::Utc::now().timestamp_millis()
+}
+
+impl IpList {
+    fn new(backend: IpListBackend, key: &str, ttl: Duration) -> Self {
+        Self {
+            backend,
+            key: key.to_string(),
+            ttl_ms: ttl.as_millis() as i64,
+        }
+    }
+
+    /// Add or refresh an IP; other members keep their own expiry
+    pub async fn add(&self, ip: &str) -> Result<(), String> {
+        let now = now_ms();
+        let expires_at = now + self.ttl_ms;
+
+        match &self.backend {
+            IpListBackend::Redis(client) => client
+                .zadd_ttl(&self.key, ip, expires_at, now, self.ttl_ms)
+                .await
+                .map_err(|e| format!("Redis WAF list add error: {}", e)),
+            IpListBackend::Memory(ips) => {
+                ips.lock().unwrap().insert(ip.to_string(), expires_at);
+                Ok(())
+            }
+        }
+    }
+
+    /// Whether the IP is listed and not yet expired
+    pub async fn contains(&self, ip: &str) -> Result<bool, String> {
+        let now = now_ms();
+
+        match &self.backend {
+            IpListBackend::Redis(client) => {
+                let expires_at = client
+                    .zscore(&self.key, ip)
+                    .await
+                    .map_err(|e| format!("Redis ZSCORE error: {}", e))?;
+                Ok(expires_at.is_some_and(|exp| exp > now as f64))
+            }
+            IpListBackend::Memory(ips) => Ok(ips.lock().unwrap().get(ip).is_some_and(|exp| *exp > now)),
+        }
+    }
+
+    /// Number of unexpired IPs
+    pub async fn count(&self) -> Result<u64, String> {
+        let now = now_ms();
+
+        match &self.backend {
+            IpListBackend::Redis(client) => client
+                .zcount_above(&self.key, now)
+                .await
+                .map_err(|e| format!("Redis ZCOUNT error: {}", e)),
+            IpListBackend::Memory(ips) => Ok(ips.lock().unwrap().values().filter(|exp| **exp > now).count() as u64),
+        }
+    }
+
+    /// Drop expired IPs, returning how many were removed
+    pub async fn purge_expired(&self) -> Result<u64, String> {
+        let now = now_ms();
+
+        match &self.backend {
+            IpListBackend::Redis(client) => {
+                let removed = client.zremrangebyscore(&self.key, now).await;
+                match removed {
+                    Ok(removed) => Ok(removed),
+                    // Plain sets from before per-IP expiry; they only ever expired as a whole
+                    Err(e) if e.code() == Some("WRONGTYPE") => {
+                        tracing::warn!(key = %self.key, "Replacing legacy WAF list set");
+                        client
+                            .del(&self.key)
+                            .await
+                            .map_err(|e| format!("Redis DEL error: {}", e))
+                    }
+                    Err(e) => Err(format!("Redis ZREMRANGEBYSCORE error: {}", e)),
+                }
+            }
+            IpListBackend::Memory(ips) => {
+                let mut ips = ips.lock().unwrap();
+                let before = ips.len();
+                ips.retain(|_, exp| *exp > now);
+                Ok((before - ips.len()) as u64)
+            }
+        }
+    }
+}
+
+/// The WAF's ban and grey lists
+pub struct WafLists {
+    pub ban: IpList,
+    pub grey: IpList,
+}
+
+impl WafLists {
+    /// Build the lists for `waf.lists_backend`, if enabled and available
+    pub fn from_config(config: &WafConfig, redis: Option<RedisClient>) -> Option<Self> {
+        if !config.use_redis_lists {
+            return None;
+        }
+
+        let backend = || match config.lists_backend.as_str() {
+            "memory" => Some(IpListBackend::Memory(Mutex::new(HashMap::new()))),
+            "redis" => Some(IpListBackend::Redis(redis.clone()?)),
+            other => {
+                tracing::warn!("Unknown WAF lists backend '{}', falling back to redis", other);
+                Some(IpListBackend::Redis(redis.clone()?))
+            }
+        };
+
+        Some(Self {
+            ban: IpList::new(backend()?, &config.redis_ban_set, Duration::from_secs(config.ban_ttl_secs)),
+            grey: IpList::new(backend()?, &config.redis_grey_set, Duration::from_secs(config.grey_ttl_secs)),
+        })
+    }
+
+    /// Periodically purge expired IPs from both lists
+    pub fn spawn_purge(self: Arc<Self>, interval: Duration) {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                for (name, list) in [("ban", &self.ban), ("grey", &self.grey)] {
+                    match list.purge_expired().await {
+                        Ok(0) => {}
+                        Ok(removed) => tracing::debug!(list = name, removed, "Purged expired WAF list entries"),
+                        Err(e) => tracing::warn!(list = name, error = %e, "Failed to purge WAF list"),
+                    }
+                }
+            }
+        });
+    }
+}
+
+enum ReputationBackend {
+    Redis {
+        client: RedisClient,
+        /// Set once the feed turns out to be a plain set rather than a sorted set
+        plain_set: AtomicBool,
+    },
+    #[cfg(test)]
+    Memory(HashMap<String, u32>),
+}
+
+/// External IP-reputation feed, e.g. a threat list loaded into Redis
+///
+/// A sorted set scores each flagged IP itself; members of a plain set all get
+/// `waf.reputation_default_score`.
+pub struct IpReputation {
+    backend: ReputationBackend,
+    key: String,
+    default_score: u32,
+}
+
+impl IpReputation {
+    /// Build the feed reader when `waf.reputation_set` is set and Redis is available
+    pub fn from_config(config: &WafConfig, redis: Option<RedisClient>) -> Option<Self> {
+        if config.reputation_set.is_empty() {
+            return None;
+        }
+        let Some(client) = redis else {
+            tracing::warn!(key = %config.reputation_set, "Redis unavailable, WAF reputation feed disabled");
+            return None;
+        };
+
+        Some(Self {
+            backend: ReputationBackend::Redis {
+                client,
+                plain_set: AtomicBool::new(false),
+            },
+            key: config.reputation_set.clone(),
+            default_score: config.reputation_default_score,
+        })
+    }
+
+    #[cfg(test)]
+    pub(crate) fn from_scores(scores: HashMap<String, u32>) -> Self {
+        Self {
+            backend: ReputationBackend::Memory(scores),
+            key: "waf:reputation".to_string(),
+            default_score: 0,
+        }
+    }
+
+    /// Score the feed gives the IP, 0 when it isn't flagged
+    pub async fn score(&self, ip: &str) -> Result<u32, String> {
+        match &self.backend {
+            ReputationBackend::Redis { client, plain_set } => {
+                if !plain_set.load(Ordering::Relaxed) {
+                    match client.zscore(&self.key, ip).await {
+                        // Feeds may carry fractional or negative scores
+                        Ok(score) => return Ok(score.map_or(0, |s| s.max(0.0).round() as u32)),
+                        Err(e) if e.code() == Some("WRONGTYPE") => plain_set.store(true, Ordering::Relaxed),
+                        Err(e) => return Err(format!("Redis ZSCORE error: {}", e)),
+                    }
+                }
+                let flagged = client
+                    .sismember(&self.key, ip)
+                    .await
+                    .map_err(|e| format!("Redis SISMEMBER error: {}", e))?;
+                Ok(if flagged { self.default_score } else { 0 })
+            }
+            #[cfg(test)]
+            ReputationBackend::Memory(scores) => Ok(scores.get(ip).copied().unwrap_or(0)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn memory_list(ttl: Duration) -> IpList {
+        IpList::new(IpListBackend::Memory(Mutex::new(HashMap::new())), "waf:ban:ips", ttl)
+    }
+
+    #[tokio::test]
+    async fn test_each_ip_expires_independently() {
+        let bans = memory_list(Duration::from_millis(150));
+        bans.add("10.0.0.1").await.unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        // A later addition doesn't extend the earlier ban
+        bans.add("10.0.0.2").await.unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        assert!(!bans.contains("10.0.0.1").await.unwrap());
+        assert!(bans.contains("10.0.0.2").await.unwrap());
+        assert_eq!(bans.count().await.unwrap(), 1);
+
+        assert_eq!(bans.purge_expired().await.unwrap(), 1);
+        assert!(bans.contains("10.0.0.2").await.unwrap());
+    }
+}
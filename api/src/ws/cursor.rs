@@ -0,0 +1,141 @@
+/// Resume cursors for reconnecting WebSocket clients
+///
+/// A subscription made with a `client_id` records the last slot delivered to
+/// it under `(client_id, subscription)`, where the subscription is identified
+/// by its kind and filters since ids are per connection. Subscribing again with
+/// the same `client_id` and filters resumes from that slot. Cursors expire
+/// after `ws.resume_cursor_ttl_secs`; an expired cursor means a live start.
+
+use crate::config::WsConfig;
+use crate::ws::{SubscriptionKind, TransactionFilters};
+use crate::infra::redis::RedisClient;
+use sha1::{Digest, Sha1};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+enum CursorBackend {
+    Redis(RedisClient),
+    /// key -> (slot, expires_at_ms)
+    Memory(Mutex<HashMap<String, (i64, i64)>>),
+}
+
+pub struct CursorStore {
+    backend: CursorBackend,
+    key_prefix: String,
+    ttl_ms: i64,
+}
+
+fn now_ms() -> i64 {
+    chrono::Utc::now().timestamp_millis()
+}
+
+impl CursorStore {
+    /// Build the store for `ws.resume_cursor_backend`, if available
+    pub fn from_config(config: &WsConfig, redis: Option<RedisClient>) -> Option<Self> {
+        let backend = match config.resume_cursor_backend.as_str() {
+            "none" => return None,
+            "memory" => CursorBackend::Memory(Mutex::new(HashMap::new())),
+            "redis" => CursorBackend::Redis(redis?),
+            other => {
+                tracing::warn!("Unknown resume cursor backend '{}', falling back to redis", other);
+                CursorBackend::Redis(redis?)
+            }
+        };
+        Some(Self {
+            backend,
+            key_prefix: config.resume_cursor_key_prefix.clone(),
+            ttl_ms: config.resume_cursor_ttl_secs as i64 * 1000,
+        })
+    }
+
+    /// Cursor key for a client's subscription with these filters
+    pub fn key(&self, client_id: &str, kind: SubscriptionKind, filters: &TransactionFilters) -> String {
+        // Field order is fixed, so equal filters always hash the same
+        let mut hasher = Sha1::new();
+        hasher.update(serde_json::to_vec(&(kind, filters)).unwrap_or_default());
+        format!("{}:{}:{:x}", self.key_prefix, client_id, hasher.finalize())
+    }
+
+    /// Last delivered slot, unless the cursor expired
+    pub async fn load(&self, key: &str) -> Result<Option<i64>, String> {
+        match &self.backend {
+            CursorBackend::Redis(client) => client
+                .get_i64(key)
+                .await
+                .map_err(|e| format!("Redis GET error: {}", e)),
+            CursorBackend::Memory(store) => {
+                let store = store.lock().unwrap();
+                Ok(store
+                    .get(key)
+                    .filter(|(_, expires_at)| *expires_at > now_ms())
+                    .map(|(slot, _)| *slot))
+            }
+        }
+    }
+
+    /// Record the last delivered slot and restart the TTL
+    pub async fn save(&self, key: &str, slot: i64) -> Result<(), String> {
+        match &self.backend {
+            CursorBackend::Redis(client) => client
+                .set_px(key, slot, self.ttl_ms)
+                .await
+                .map_err(|e| format!("Redis SET error: {}", e)),
+            CursorBackend::Memory(store) => {
+                store
+                    .lock()
+                    .unwrap()
+                    .insert(key.to_string(), (slot, now_ms() + self.ttl_ms));
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store(ttl_secs: u64) -> CursorStore {
+        let config = WsConfig {
+            resume_cursor_backend: "memory".to_string(),
+            resume_cursor_ttl_secs: ttl_secs,
+            ..WsConfig::default()
+        };
+        CursorStore::from_config(&config, None).unwrap()
+    }
+
+    fn filters(from: &str) -> TransactionFilters {
+        TransactionFilters {
+            from: Some(from.to_string()),
+            ..TransactionFilters::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reconnect_resumes_from_stored_cursor() {
+        let store = store(60);
+
+        // First connection delivered up to slot 42
+        let key = store.key("client-1", SubscriptionKind::Transactions, &filters("alice"));
+        store.save(&key, 42).await.unwrap();
+
+        // Reconnecting with the same client and filters finds it
+        let again = store.key("client-1", SubscriptionKind::Transactions, &filters("alice"));
+        assert_eq!(store.load(&again).await.unwrap(), Some(42));
+
+        // Other clients and other filters start live
+        let other_client = store.key("client-2", SubscriptionKind::Transactions, &filters("alice"));
+        assert_eq!(store.load(&other_client).await.unwrap(), None);
+        let other_filters = store.key("client-1", SubscriptionKind::Transactions, &filters("bob"));
+        assert_eq!(store.load(&other_filters).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_expired_cursor_falls_back_to_live() {
+        let store = store(0);
+        let key = store.key("client-1", SubscriptionKind::Transactions, &filters("alice"));
+        store.save(&key, 42).await.unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+        assert_eq!(store.load(&key).await.unwrap(), None);
+    }
+}
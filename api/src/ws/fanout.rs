@@ -0,0 +1,156 @@
+/// Transaction event fan-out to WebSocket subscriptions
+///
+/// Each transaction subscription registers its filters and its connection's
+/// bounded outbound channel. An event is sent to every matching subscription
+/// concurrently, at most `ws.fanout_concurrency` sends at a time, so one slow
+/// connection only occupies one slot instead of delaying everyone behind it.
+
+use crate::ws::{matches_filters, TransactionFilters, WsMessage};
+use futures_util::stream::{self, StreamExt};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Mutex;
+use tokio::sync::mpsc;
+
+/// Outbound messages buffered per connection before fan-out waits on it
+pub const CONNECTION_BUFFER: usize = 64;
+
+struct FanoutTarget {
+    filters: TransactionFilters,
+    sender: mpsc::Sender<WsMessage>,
+}
+
+pub struct WsFanout {
+    /// subscription id -> target
+    targets: Mutex<HashMap<String, FanoutTarget>>,
+    concurrency: usize,
+}
+
+impl WsFanout {
+    pub fn new(concurrency: usize) -> Self {
+        Self {
+            targets: Mutex::new(HashMap::new()),
+            concurrency: concurrency.max(1),
+        }
+    }
+
+    /// Deliver matching events for a subscription through its connection's channel
+    pub fn register(&self, sub_id: &str, filters: TransactionFilters, sender: mpsc::Sender<WsMessage>) {
+        self.targets
+            .lock()
+            .unwrap()
+            .insert(sub_id.to_string(), FanoutTarget { filters, sender });
+    }
+
+    pub fn unregister(&self, sub_id: &str) {
+        self.targets.lock().unwrap().remove(sub_id);
+    }
+
+    /// Number of registered subscriptions
+    pub fn subscription_count(&self) -> usize {
+        self.targets.lock().unwrap().len()
+    }
+
+    /// Send a transaction to every matching subscription, returning how many received it
+    pub async fn publish(&self, tx: &serde_json::Value) -> usize {
+        let matching: Vec<(String, mpsc::Sender<WsMessage>)> = self
+            .targets
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, target)| matches_filters(tx, &target.filters))
+            .map(|(sub_id, target)| (sub_id.clone(), target.sender.clone()))
+            .collect();
+
+        // A full channel makes its send wait (backpressure) without holding up the others
+        let results = fan_out(matching, self.concurrency, |(sub_id, sender)| async move {
+            let message = WsMessage::Event {
+                sub: sub_id.clone(),
+                tx: tx.clone(),
+            };
+            let delivered = sender.send(message).await.is_ok();
+            (sub_id, delivered)
+        })
+        .await;
+
+        // Connections that went away without unsubscribing
+        let mut delivered = 0;
+        let mut targets = self.targets.lock().unwrap();
+        for (sub_id, ok) in results {
+            if ok {
+                delivered += 1;
+            } else {
+                targets.remove(&sub_id);
+            }
+        }
+        delivered
+    }
+}
+
+/// Run `send` for every item, with at most `concurrency` in flight
+async fn fan_out<T, R, F, Fut>(items: Vec<T>, concurrency: usize, send: F) -> Vec<R>
+where
+    F: FnMut(T) -> Fut,
+    Fut: Future<Output = R>,
+{
+    stream::iter(items).map(send).buffer_unordered(concurrency).collect().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    /// Time fanning out to `connections` sends that each take `delay`
+    async fn fan_out_latency(connections: usize, concurrency: usize, delay: Duration) -> Duration {
+        let started = Instant::now();
+        let sent = fan_out((0..connections).collect(), concurrency, |_| tokio::time::sleep(delay)).await;
+        assert_eq!(sent.len(), connections);
+        started.elapsed()
+    }
+
+    #[tokio::test]
+    async fn test_fanout_latency_bounded_by_concurrency() {
+        let delay = Duration::from_millis(20);
+
+        // 256 slow sends, 64 at a time: about four rounds rather than 256
+        let concurrent = fan_out_latency(256, 64, delay).await;
+        assert!(concurrent < delay * 16, "fan-out took {:?}", concurrent);
+
+        // Without concurrency every send waits for the one before it
+        let sequential = fan_out_latency(16, 1, delay).await;
+        assert!(sequential >= delay * 16, "fan-out took {:?}", sequential);
+    }
+
+    #[tokio::test]
+    async fn test_only_matching_live_subscriptions_receive_events() {
+        let fanout = WsFanout::new(8);
+        let (sender, mut receiver) = mpsc::channel(4);
+        let alice = TransactionFilters {
+            from: Some("alice".to_string()),
+            ..TransactionFilters::default()
+        };
+        fanout.register("sub-alice", alice, sender.clone());
+        let bob = TransactionFilters {
+            from: Some("bob".to_string()),
+            ..TransactionFilters::default()
+        };
+        fanout.register("sub-bob", bob, sender);
+
+        let (closed, _) = mpsc::channel(1);
+        fanout.register("sub-gone", TransactionFilters::default(), closed);
+
+        let tx = serde_json::json!({ "signature": "sig1", "slot": 1, "from_pubkey": "alice" });
+        assert_eq!(fanout.publish(&tx).await, 1);
+        match receiver.recv().await {
+            Some(WsMessage::Event { sub, tx }) => {
+                assert_eq!(sub, "sub-alice");
+                assert_eq!(tx["signature"], "sig1");
+            }
+            other => panic!("unexpected message: {:?}", other),
+        }
+
+        // The closed connection was dropped from the registry
+        assert_eq!(fanout.subscription_count(), 2);
+    }
+}
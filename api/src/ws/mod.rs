@@ -3,8 +3,13 @@
 /// Provides live feed of Solana transactions with filtering, rate limiting,
 /// and subscription management.
 
+pub mod cursor;
+pub mod fanout;
+pub mod registry;
+pub mod resume;
 pub mod tx;
 
+use crate::ingest::IngestStats;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use uuid::Uuid;
@@ -15,8 +20,13 @@ use uuid::Uuid;
 pub enum WsMessage {
     // Client messages
     Subscribe {
+        #[serde(default)]
+        kind: SubscriptionKind,
+        #[serde(default)]
         filters: TransactionFilters,
         resume_from_slot: Option<i64>,
+        /// Stable client identity; resumes from the last delivered slot on reconnect
+        client_id: Option<String>,
     },
     Unsubscribe {
         id: String,
@@ -34,6 +44,10 @@ pub enum WsMessage {
         sub: String,
         tx: serde_json::Value, // SolanaTransaction
     },
+    Stats {
+        sub: String,
+        stats: IngestStats,
+    },
     Error {
         code: String,
         message: String,
@@ -41,11 +55,29 @@ pub enum WsMessage {
     Ping {
         ts: u64,
     },
+    /// The subscription was dropped to make room under `ws.max_total_subscriptions`
+    Evicted {
+        id: String,
+    },
     Info {
         message: String,
+        /// Suggested wait before reconnecting, on shutdown notices
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        retry_after_ms: Option<u64>,
     },
 }
 
+/// What a subscription streams to the client
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SubscriptionKind {
+    /// Transaction events matching the filters
+    #[default]
+    Transactions,
+    /// Periodic ingest statistics snapshots (filters are ignored)
+    Stats,
+}
+
 /// Transaction filters for subscriptions
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TransactionFilters {
@@ -70,12 +102,37 @@ impl Default for TransactionFilters {
     }
 }
 
+impl TransactionFilters {
+    /// Filter fields that are set
+    pub fn predicate_count(&self) -> usize {
+        [
+            self.signature.is_some(),
+            self.from.is_some(),
+            self.to.is_some(),
+            self.program_id.is_some(),
+            self.slot_from.is_some(),
+            self.slot_to.is_some(),
+        ]
+        .into_iter()
+        .filter(|set| *set)
+        .count()
+    }
+
+    /// Whether an indexed field narrows the match; slot ranges alone don't
+    pub fn is_selective(&self) -> bool {
+        self.signature.is_some() || self.from.is_some() || self.to.is_some() || self.program_id.is_some()
+    }
+}
+
 /// Active subscription
 #[derive(Debug, Clone)]
 pub struct Subscription {
     pub id: String,
+    pub kind: SubscriptionKind,
     pub filters: TransactionFilters,
     pub created_at: std::time::Instant,
+    /// Resume cursor key, for subscriptions made with a `client_id`
+    pub cursor_key: Option<String>,
 }
 
 /// Connection state for rate limiting
@@ -85,6 +142,9 @@ pub struct ConnectionState {
     pub last_activity: std::time::Instant,
     pub client_msg_count: u32,
     pub client_msg_window_start: std::time::Instant,
+    /// Subscribes and unsubscribes, counted apart from other messages
+    pub sub_op_count: u32,
+    pub sub_op_window_start: std::time::Instant,
     pub event_count: u32,
     pub event_window_start: std::time::Instant,
 }
@@ -97,6 +157,8 @@ impl ConnectionState {
             last_activity: now,
             client_msg_count: 0,
             client_msg_window_start: now,
+            sub_op_count: 0,
+            sub_op_window_start: now,
             event_count: 0,
             event_window_start: now,
         }
@@ -111,6 +173,26 @@ impl ConnectionState {
         self.client_msg_window_start = std::time::Instant::now();
     }
     
+    /// Count a subscribe or unsubscribe at `now`; false once the minute's
+    /// `max_per_min` is used up (0 = unlimited)
+    pub fn record_sub_op(&mut self, now: std::time::Instant, max_per_min: u32) -> bool {
+        if now.duration_since(self.sub_op_window_start) >= std::time::Duration::from_secs(60) {
+            self.sub_op_count = 0;
+            self.sub_op_window_start = now;
+        }
+        self.sub_op_count += 1;
+        max_per_min == 0 || self.sub_op_count <= max_per_min
+    }
+
+    /// Filter fields set across this connection's transaction subscriptions
+    pub fn filter_predicates(&self) -> usize {
+        self.subscriptions
+            .values()
+            .filter(|sub| sub.kind == SubscriptionKind::Transactions)
+            .map(|sub| sub.filters.predicate_count())
+            .sum()
+    }
+
     pub fn reset_event_window(&mut self) {
         self.event_count = 0;
         self.event_window_start = std::time::Instant::now();
@@ -122,6 +204,41 @@ pub fn generate_subscription_id() -> String {
     Uuid::new_v4().to_string()
 }
 
+/// Keep only the instructions run by `program_id`, named directly or by
+/// `program_id_index` into the transaction's `program_ids`
+pub fn project_instructions(tx: &mut serde_json::Value, program_id: &str) {
+    let program_ids: Vec<Option<String>> = tx
+        .get("program_ids")
+        .and_then(|ids| ids.as_array())
+        .map(|ids| ids.iter().map(|id| id.as_str().map(str::to_string)).collect())
+        .unwrap_or_default();
+    let runs_program = |instruction: &serde_json::Value| {
+        if let Some(id) = instruction.get("program_id").and_then(|id| id.as_str()) {
+            return id == program_id;
+        }
+        instruction
+            .get("program_id_index")
+            .and_then(|index| index.as_u64())
+            .and_then(|index| program_ids.get(index as usize))
+            .is_some_and(|id| id.as_deref() == Some(program_id))
+    };
+
+    if let Some(instructions) = tx.get_mut("instructions").and_then(|i| i.as_array_mut()) {
+        instructions.retain(runs_program);
+    }
+}
+
+/// Whether `program_id` is in the transaction's `program_ids`
+///
+/// A missing, null or empty `program_ids` never matches, as with
+/// `$1 = ANY(program_ids)` in the repository's filter, so a live
+/// subscription and a list query select the same transactions.
+pub fn involves_program(tx: &serde_json::Value, program_id: &str) -> bool {
+    tx.get("program_ids")
+        .and_then(|ids| ids.as_array())
+        .is_some_and(|ids| ids.iter().any(|id| id.as_str() == Some(program_id)))
+}
+
 /// Check if transaction matches filters
 pub fn matches_filters(tx: &serde_json::Value, filters: &TransactionFilters) -> bool {
     // Helper to get string field from JSON
@@ -157,11 +274,7 @@ pub fn matches_filters(tx: &serde_json::Value, filters: &TransactionFilters) ->
     
     // Check program_id (in array)
     if let Some(ref program_id) = filters.program_id {
-        if let Some(program_ids) = tx.get("program_ids").and_then(|v| v.as_array()) {
-            if !program_ids.iter().any(|v| v.as_str() == Some(program_id)) {
-                return false;
-            }
-        } else {
+        if !involves_program(tx, program_id) {
             return false;
         }
     }
@@ -187,3 +300,84 @@ pub fn matches_filters(tx: &serde_json::Value, filters: &TransactionFilters) ->
     true
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_subscribe_kind_defaults_to_transactions() {
+        let msg: WsMessage = serde_json::from_str(
+            r#"{"type":"Subscribe","filters":{"program_id":"prog"}}"#,
+        )
+        .unwrap();
+        match msg {
+            WsMessage::Subscribe { kind, filters, .. } => {
+                assert_eq!(kind, SubscriptionKind::Transactions);
+                assert_eq!(filters.program_id.as_deref(), Some("prog"));
+            }
+            other => panic!("unexpected message: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_stats_subscription_and_snapshot() {
+        let msg: WsMessage = serde_json::from_str(r#"{"type":"Subscribe","kind":"stats"}"#).unwrap();
+        assert!(matches!(
+            msg,
+            WsMessage::Subscribe { kind: SubscriptionKind::Stats, .. }
+        ));
+
+        let mut stats = IngestStats::default();
+        stats.record_message_received();
+        stats.record_message_received();
+        stats.record_dlq_sent();
+        let json = serde_json::to_value(WsMessage::Stats {
+            sub: "s1".to_string(),
+            stats,
+        })
+        .unwrap();
+        assert_eq!(json["type"], "Stats");
+        assert_eq!(json["stats"]["messages_received"], 2);
+        assert_eq!(json["stats"]["dlq_messages_sent"], 1);
+    }
+
+    #[test]
+    fn test_program_filter_never_matches_null_or_empty_program_ids() {
+        let filters = TransactionFilters {
+            program_id: Some("prog".to_string()),
+            ..TransactionFilters::default()
+        };
+        let tx = |program_ids: serde_json::Value| serde_json::json!({ "signature": "sig", "slot": 1, "program_ids": program_ids });
+
+        assert!(!matches_filters(&serde_json::json!({ "signature": "sig", "slot": 1 }), &filters));
+        assert!(!matches_filters(&tx(serde_json::Value::Null), &filters));
+        assert!(!matches_filters(&tx(serde_json::json!([])), &filters));
+        assert!(!matches_filters(&tx(serde_json::json!(["other"])), &filters));
+        assert!(matches_filters(&tx(serde_json::json!(["other", "prog"])), &filters));
+
+        // Without a program filter none of them is excluded
+        let unfiltered = TransactionFilters::default();
+        assert!(matches_filters(&tx(serde_json::Value::Null), &unfiltered));
+        assert!(matches_filters(&tx(serde_json::json!([])), &unfiltered));
+    }
+
+    #[test]
+    fn test_subscription_churn_is_throttled_per_minute() {
+        let mut state = ConnectionState::new();
+        let start = state.sub_op_window_start;
+
+        // Rapid subscribe/unsubscribe pairs use up the budget
+        for op in 0..4 {
+            assert!(state.record_sub_op(start + std::time::Duration::from_secs(op), 4));
+        }
+        assert!(!state.record_sub_op(start + std::time::Duration::from_secs(5), 4));
+
+        // The next minute starts a fresh budget
+        assert!(state.record_sub_op(start + std::time::Duration::from_secs(60), 4));
+        assert_eq!(state.sub_op_count, 1);
+
+        // 0 disables the limit
+        let mut unlimited = ConnectionState::new();
+        assert!((0..1000).all(|_| unlimited.record_sub_op(start, 0)));
+    }
+}
@@ -0,0 +1,172 @@
+/// Server-wide WebSocket subscription accounting
+///
+/// `ws.max_total_subscriptions` caps subscriptions across all connections.
+/// When the cap is hit and `ws.evict_idle_after_secs` is set, the subscription
+/// whose connection has been quiet the longest (past that threshold) is evicted
+/// to make room; its connection is told with `WsMessage::Evicted`. Otherwise,
+/// or when that connection's channel is full, the new subscription is refused.
+
+use crate::ws::WsMessage;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::{self, error::TrySendError};
+
+struct Entry {
+    last_active: Instant,
+    /// The owning connection's event channel, for eviction notices
+    notify: mpsc::Sender<WsMessage>,
+}
+
+pub struct SubscriptionRegistry {
+    max_total: usize,
+    evict_idle_after: Option<Duration>,
+    entries: Mutex<HashMap<String, Entry>>,
+}
+
+impl SubscriptionRegistry {
+    /// `max_total` of 0 means unlimited; `evict_idle_after` of None never evicts
+    pub fn new(max_total: usize, evict_idle_after: Option<Duration>) -> Self {
+        Self {
+            max_total,
+            evict_idle_after,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Claim a slot for a new subscription, evicting an idle one if allowed
+    pub fn try_acquire(&self, sub_id: &str, notify: mpsc::Sender<WsMessage>) -> bool {
+        let now = Instant::now();
+        let mut entries = self.entries.lock().unwrap();
+
+        if self.max_total > 0 && entries.len() >= self.max_total {
+            let Some(idle_after) = self.evict_idle_after else {
+                return false;
+            };
+            let oldest = entries
+                .iter()
+                .filter(|(_, entry)| now.duration_since(entry.last_active) >= idle_after)
+                .min_by_key(|(_, entry)| entry.last_active)
+                .map(|(id, _)| id.clone());
+            let Some(oldest) = oldest else {
+                return false;
+            };
+
+            // The connection only drops the subscription from the fan-out once it
+            // hears about it, so keep the entry if the notice can't be queued
+            let notice = WsMessage::Evicted { id: oldest.clone() };
+            match entries[&oldest].notify.try_send(notice) {
+                Ok(()) | Err(TrySendError::Closed(_)) => {
+                    tracing::info!(subscription = %oldest, "Evicting idle WebSocket subscription");
+                    entries.remove(&oldest);
+                }
+                Err(TrySendError::Full(_)) => {
+                    tracing::warn!(
+                        subscription = %oldest,
+                        "Idle WebSocket subscription not evicted: its connection's channel is full"
+                    );
+                    return false;
+                }
+            }
+        }
+
+        entries.insert(
+            sub_id.to_string(),
+            Entry {
+                last_active: now,
+                notify,
+            },
+        );
+        true
+    }
+
+    pub fn release(&self, sub_id: &str) {
+        self.entries.lock().unwrap().remove(sub_id);
+    }
+
+    /// Mark subscriptions active because their connection did something
+    pub fn touch<'a>(&self, sub_ids: impl IntoIterator<Item = &'a String>) {
+        let now = Instant::now();
+        let mut entries = self.entries.lock().unwrap();
+        for id in sub_ids {
+            if let Some(entry) = entries.get_mut(id) {
+                entry.last_active = now;
+            }
+        }
+    }
+
+    /// Subscriptions held across all connections
+    pub fn count(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_global_limit_refuses_across_connections() {
+        let registry = SubscriptionRegistry::new(4, None);
+        let connections: Vec<_> = (0..3).map(|_| mpsc::channel(4)).collect();
+
+        // Two subscriptions on each of two connections fill the server
+        for (conn, (sender, _)) in connections.iter().take(2).enumerate() {
+            for sub in 0..2 {
+                assert!(registry.try_acquire(&format!("c{}-s{}", conn, sub), sender.clone()));
+            }
+        }
+        assert_eq!(registry.count(), 4);
+
+        // A third connection, and the existing ones, are refused
+        assert!(!registry.try_acquire("c2-s0", connections[2].0.clone()));
+        assert!(!registry.try_acquire("c0-s2", connections[0].0.clone()));
+
+        // Releasing frees a slot for anyone
+        registry.release("c1-s1");
+        assert!(registry.try_acquire("c2-s0", connections[2].0.clone()));
+        assert!(!registry.try_acquire("c2-s1", connections[2].0.clone()));
+    }
+
+    #[test]
+    fn test_oldest_idle_subscription_is_evicted_under_pressure() {
+        let registry = SubscriptionRegistry::new(2, Some(Duration::from_millis(30)));
+        let (idle, mut idle_events) = mpsc::channel(4);
+        let (busy, _busy_events) = mpsc::channel(4);
+        assert!(registry.try_acquire("idle", idle));
+        assert!(registry.try_acquire("busy", busy.clone()));
+
+        // Nobody has been idle long enough yet
+        assert!(!registry.try_acquire("new", busy.clone()));
+
+        std::thread::sleep(Duration::from_millis(40));
+        registry.touch(&["busy".to_string()]);
+        assert!(registry.try_acquire("new", busy));
+        assert_eq!(registry.count(), 2);
+        match idle_events.try_recv() {
+            Ok(WsMessage::Evicted { id }) => assert_eq!(id, "idle"),
+            other => panic!("unexpected message: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_eviction_is_skipped_when_the_notice_cannot_be_queued() {
+        let registry = SubscriptionRegistry::new(1, Some(Duration::from_millis(10)));
+        let (idle, mut idle_events) = mpsc::channel(1);
+        let (other, _other_events) = mpsc::channel(4);
+        assert!(registry.try_acquire("idle", idle.clone()));
+        idle.try_send(WsMessage::Ping { ts: 0 }).unwrap();
+
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(!registry.try_acquire("new", other.clone()));
+        assert_eq!(registry.count(), 1);
+
+        // Once the connection drains its channel the eviction goes through
+        idle_events.try_recv().unwrap();
+        assert!(registry.try_acquire("new", other));
+        match idle_events.try_recv() {
+            Ok(WsMessage::Evicted { id }) => assert_eq!(id, "idle"),
+            other => panic!("unexpected message: {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,119 @@
+/// Paced resume backlog
+///
+/// A subscription with `resume_from_slot` is replayed the transactions it
+/// missed, capped at `ws.max_resume_rows`. The backlog is drained a few events
+/// per tick so it shares the connection's `max_events_per_sec` budget with live
+/// events instead of arriving in one burst.
+
+use crate::ws::{matches_filters, TransactionFilters, WsMessage};
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// How often queued backlog events are drained
+pub const RESUME_TICK: Duration = Duration::from_millis(100);
+
+/// Events queued for replay, oldest first
+#[derive(Debug, Default)]
+pub struct ResumeBacklog {
+    queue: VecDeque<WsMessage>,
+}
+
+impl ResumeBacklog {
+    /// Queue the matching transactions among the first `max_rows` rows
+    ///
+    /// Returns whether rows past `max_rows` were dropped.
+    pub fn extend(
+        &mut self,
+        sub_id: &str,
+        rows: Vec<serde_json::Value>,
+        filters: &TransactionFilters,
+        max_rows: usize,
+    ) -> bool {
+        let truncated = rows.len() > max_rows;
+        self.queue.extend(
+            rows.into_iter()
+                .take(max_rows)
+                .filter(|tx| matches_filters(tx, filters))
+                .map(|tx| WsMessage::Event {
+                    sub: sub_id.to_string(),
+                    tx,
+                }),
+        );
+        truncated
+    }
+
+    pub fn pop(&mut self) -> Option<WsMessage> {
+        self.queue.pop_front()
+    }
+
+    /// Put back an event that couldn't be sent this tick
+    pub fn push_front(&mut self, event: WsMessage) {
+        self.queue.push_front(event);
+    }
+
+    /// Drop queued events for a subscription that went away
+    pub fn remove_subscription(&mut self, sub_id: &str) {
+        self.queue
+            .retain(|event| !matches!(event, WsMessage::Event { sub, .. } if sub == sub_id));
+    }
+
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+}
+
+/// Backlog events sent per tick for a `max_events_per_sec` budget
+pub fn events_per_tick(max_events_per_sec: u32) -> usize {
+    let ticks_per_sec = (Duration::from_secs(1).as_millis() / RESUME_TICK.as_millis()) as u32;
+    (max_events_per_sec / ticks_per_sec).max(1) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_large_backlog_is_paced_and_truncated() {
+        let rows: Vec<serde_json::Value> = (1..=5000)
+            .map(|slot| serde_json::json!({ "signature": format!("sig{}", slot), "slot": slot }))
+            .collect();
+
+        let mut backlog = ResumeBacklog::default();
+        assert!(backlog.extend("sub-1", rows, &TransactionFilters::default(), 1000));
+        assert_eq!(backlog.len(), 1000);
+
+        // 100 events/s drains 10 per 100ms tick: 100 ticks, about 10s, not one burst
+        let per_tick = events_per_tick(100);
+        assert_eq!(per_tick, 10);
+        let mut ticks = 0;
+        let mut first = None;
+        while !backlog.is_empty() {
+            ticks += 1;
+            for _ in 0..per_tick {
+                if let Some(WsMessage::Event { tx, .. }) = backlog.pop() {
+                    first.get_or_insert(tx["slot"].as_i64().unwrap());
+                }
+            }
+        }
+        assert_eq!(ticks, 100);
+        assert_eq!(first, Some(1));
+
+        // Within the cap nothing is signaled as truncated, and filters still apply
+        let rows = (1..=10)
+            .map(|slot| serde_json::json!({ "signature": format!("sig{}", slot), "slot": slot }))
+            .collect();
+        let filters = TransactionFilters {
+            slot_from: Some(6),
+            ..TransactionFilters::default()
+        };
+        assert!(!backlog.extend("sub-2", rows, &filters, 1000));
+        assert_eq!(backlog.len(), 5);
+        backlog.remove_subscription("sub-2");
+        assert!(backlog.is_empty());
+        assert_eq!(events_per_tick(3), 1);
+    }
+}
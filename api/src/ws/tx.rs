@@ -5,41 +5,121 @@
 
 use crate::{
     app_state::AppState,
-    config::WsConfig,
-    ws::{ConnectionState, Subscription, TransactionFilters, WsMessage, generate_subscription_id},
+    config::{ServerConfig, WsConfig},
+    errors::ApiError,
+    repository::transactions::TransactionRepository,
+    runtime_stats::WsConnectionGuard,
+    ws::{
+        fanout::CONNECTION_BUFFER,
+        generate_subscription_id,
+        project_instructions,
+        resume::{events_per_tick, ResumeBacklog, RESUME_TICK},
+        ConnectionState, Subscription, SubscriptionKind, TransactionFilters, WsMessage,
+    },
 };
 use actix_web::{
     web::{Data, Payload},
-    Error, HttpRequest, HttpResponse,
+    Error, HttpRequest, HttpResponse, ResponseError,
 };
 use actix_web_actors::ws;
-use actix_web_actors::ws::{Message, ProtocolError, WebsocketContext};
-use actix::{Actor, ActorContext, AsyncContext, StreamHandler};
+use actix_web_actors::ws::{CloseReason, Message, ProtocolError, WebsocketContext};
+use actix::{Actor, ActorContext, ActorFutureExt, AsyncContext, SpawnHandle, StreamHandler};
 use serde_json;
+use rand::Rng;
+use std::collections::HashMap;
 use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, mpsc};
 use tracing::{debug, error, info, warn};
 
+/// Longest accepted `client_id`
+const MAX_CLIENT_ID_LEN: usize = 128;
+
+/// Time for shutdown notices to reach clients before the server stops
+pub const SHUTDOWN_NOTICE_GRACE: Duration = Duration::from_millis(500);
+
+/// The server is shutting down
+struct ServerShutdown;
+
+/// Random reconnect delay within `ws.reconnect_min_ms..=ws.reconnect_max_ms`
+fn reconnect_delay_ms(config: &WsConfig, rng: &mut impl Rng) -> u64 {
+    let min = config.reconnect_min_ms;
+    let max = config.reconnect_max_ms.max(min);
+    rng.gen_range(min..=max)
+}
+
+/// Close frame for a client frame over `ws.max_frame_bytes`
+fn frame_too_large(max: usize) -> CloseReason {
+    CloseReason {
+        code: ws::CloseCode::Size,
+        description: Some(format!("frame exceeds max_frame_bytes={}", max)),
+    }
+}
+
+/// Info notice and close frame announcing a shutdown, both carrying `retry_after_ms`
+fn shutdown_notice(retry_after_ms: u64) -> (WsMessage, CloseReason) {
+    let notice = WsMessage::Info {
+        message: "Server shutting down, reconnect after retry_after_ms".to_string(),
+        retry_after_ms: Some(retry_after_ms),
+    };
+    let reason = CloseReason {
+        code: ws::CloseCode::Restart,
+        description: Some(format!("shutting down; retry_after_ms={}", retry_after_ms)),
+    };
+    (notice, reason)
+}
+
+/// Info notice and close frame for a connection past `ws.max_connection_lifetime_secs`
+fn max_lifetime_notice() -> (WsMessage, CloseReason) {
+    let notice = WsMessage::Info {
+        message: "max_lifetime_reached".to_string(),
+        retry_after_ms: None,
+    };
+    let reason = CloseReason {
+        code: ws::CloseCode::Away,
+        description: Some("max_lifetime_reached".to_string()),
+    };
+    (notice, reason)
+}
+
 /// WebSocket actor for transaction streaming
 pub struct TxWebSocket {
     pub state: ConnectionState,
     pub config: WsConfig,
     pub app_state: AppState,
     pub last_ping: Instant,
+    /// Outbound transaction events, fed by the fan-out
+    events: mpsc::Sender<WsMessage>,
+    events_rx: Option<mpsc::Receiver<WsMessage>>,
+    /// Replayed transactions for `resume_from_slot` subscriptions, not yet sent
+    backlog: ResumeBacklog,
+    backlog_timer: Option<SpawnHandle>,
+    /// Highest slot delivered per cursor key since the last flush
+    cursor_updates: HashMap<String, i64>,
+    _connection: WsConnectionGuard,
 }
 
 impl TxWebSocket {
     pub fn new(config: WsConfig, app_state: AppState) -> Self {
+        let (events, events_rx) = mpsc::channel(CONNECTION_BUFFER);
+        let connection = app_state.runtime_stats.track_ws_connection();
         Self {
             state: ConnectionState::new(),
             config,
             app_state,
             last_ping: Instant::now(),
+            events,
+            events_rx: Some(events_rx),
+            backlog: ResumeBacklog::default(),
+            backlog_timer: None,
+            cursor_updates: HashMap::new(),
+            _connection: connection,
         }
     }
     
     /// Handle incoming WebSocket message
     fn handle_message(&mut self, msg: &str, ctx: &mut WebsocketContext<Self>) {
         self.state.update_activity();
+        self.app_state.ws_subscriptions.touch(self.state.subscriptions.keys());
         
         // Parse JSON message
         let ws_msg: WsMessage = match serde_json::from_str(msg) {
@@ -56,9 +136,14 @@ impl TxWebSocket {
             return;
         }
         
+        let is_sub_op = matches!(ws_msg, WsMessage::Subscribe { .. } | WsMessage::Unsubscribe { .. });
+        if is_sub_op && !self.check_sub_op_rate_limit(ctx) {
+            return;
+        }
+
         match ws_msg {
-            WsMessage::Subscribe { filters, resume_from_slot } => {
-                self.handle_subscribe(filters, resume_from_slot, ctx);
+            WsMessage::Subscribe { kind, filters, resume_from_slot, client_id } => {
+                self.handle_subscribe(kind, filters, resume_from_slot, client_id, ctx);
             }
             WsMessage::Unsubscribe { id } => {
                 self.handle_unsubscribe(&id, ctx);
@@ -75,26 +160,60 @@ impl TxWebSocket {
     }
     
     /// Handle subscription request
-    fn handle_subscribe(&mut self, filters: TransactionFilters, resume_from_slot: Option<i64>, ctx: &mut WebsocketContext<Self>) {
+    fn handle_subscribe(
+        &mut self,
+        kind: SubscriptionKind,
+        filters: TransactionFilters,
+        resume_from_slot: Option<i64>,
+        client_id: Option<String>,
+        ctx: &mut WebsocketContext<Self>,
+    ) {
         // Check subscription limit
         if self.state.subscriptions.len() >= self.config.max_subscriptions_per_conn as usize {
             self.send_error(ctx, "too_many_subscriptions", "Maximum subscriptions exceeded");
             return;
         }
+
+        if let Err((code, message)) = self.check_filters(kind, &filters) {
+            self.send_error(ctx, code, message);
+            return;
+        }
+
+        let client_id = client_id.filter(|id| !id.is_empty());
+        if client_id.as_ref().is_some_and(|id| id.len() > MAX_CLIENT_ID_LEN) {
+            self.send_error(ctx, "invalid_client_id", "client_id is too long");
+            return;
+        }
+        // Cursors only track transaction events
+        let cursor_key = match (&client_id, &self.app_state.ws_cursors) {
+            (Some(client_id), Some(cursors)) if kind == SubscriptionKind::Transactions => {
+                Some(cursors.key(client_id, kind, &filters))
+            }
+            _ => None,
+        };
         
         let sub_id = generate_subscription_id();
+        if !self.app_state.ws_subscriptions.try_acquire(&sub_id, self.events.clone()) {
+            self.send_error(ctx, "server_subscription_limit", "Server-wide subscription limit reached");
+            return;
+        }
         let subscription = Subscription {
             id: sub_id.clone(),
+            kind,
             filters: filters.clone(),
             created_at: Instant::now(),
+            cursor_key: cursor_key.clone(),
         };
         
         self.state.subscriptions.insert(sub_id.clone(), subscription);
+        if kind == SubscriptionKind::Transactions {
+            self.app_state.ws_fanout.register(&sub_id, filters.clone(), self.events.clone());
+        }
         
         // Send ACK
         let ack = WsMessage::Ack {
             id: sub_id.clone(),
-            filters,
+            filters: filters.clone(),
         };
         self.send_message(ctx, &ack);
         
@@ -103,22 +222,203 @@ impl TxWebSocket {
             // Send info message about resume
             let info = WsMessage::Info {
                 message: format!("Resuming from slot {}", slot),
+                retry_after_ms: None,
             };
             self.send_message(ctx, &info);
+            if kind == SubscriptionKind::Transactions {
+                self.load_backlog(sub_id.clone(), filters, slot, ctx);
+            }
+        } else if let Some(cursor_key) = cursor_key {
+            self.resume_from_cursor(sub_id.clone(), filters, cursor_key, ctx);
         }
         
         info!("Client subscribed with {} filters", self.state.subscriptions.len());
     }
     
+    /// Reject over-broad filters and connections already evaluating too many predicates
+    fn check_filters(&self, kind: SubscriptionKind, filters: &TransactionFilters) -> Result<(), (&'static str, &'static str)> {
+        // Stats subscriptions ignore filters
+        if kind != SubscriptionKind::Transactions {
+            return Ok(());
+        }
+        if self.config.require_selective_filter && !filters.is_selective() {
+            return Err(("filter_not_selective", "Filter by signature, from, to or program_id"));
+        }
+        let max = self.config.max_filter_predicates_per_conn as usize;
+        if max > 0 && self.state.filter_predicates() + filters.predicate_count() > max {
+            return Err(("too_many_filter_predicates", "Maximum filter predicates per connection exceeded"));
+        }
+        Ok(())
+    }
+
     /// Handle unsubscription request
     fn handle_unsubscribe(&mut self, id: &str, _ctx: &mut WebsocketContext<Self>) {
         if self.state.subscriptions.remove(id).is_some() {
+            self.app_state.ws_fanout.unregister(id);
+            self.app_state.ws_subscriptions.release(id);
+            self.backlog.remove_subscription(id);
             debug!("Client unsubscribed from {}", id);
         } else {
             warn!("Client tried to unsubscribe from unknown subscription: {}", id);
         }
     }
     
+    /// Resume from the client's stored cursor, or stay live if there is none
+    fn resume_from_cursor(&mut self, sub_id: String, filters: TransactionFilters, cursor_key: String, ctx: &mut WebsocketContext<Self>) {
+        let Some(cursors) = self.app_state.ws_cursors.clone() else {
+            return;
+        };
+        let stored = async move { cursors.load(&cursor_key).await };
+        ctx.spawn(actix::fut::wrap_future::<_, Self>(stored).map(move |stored, act, ctx| {
+            let slot = match stored {
+                Ok(Some(slot)) => slot,
+                Ok(None) => {
+                    debug!("No resume cursor for subscription {}, starting live", sub_id);
+                    return;
+                }
+                Err(e) => {
+                    warn!("Failed to load resume cursor, starting live: {}", e);
+                    return;
+                }
+            };
+            // Unsubscribed while the lookup ran
+            if !act.state.subscriptions.contains_key(&sub_id) {
+                return;
+            }
+
+            let info = WsMessage::Info {
+                message: format!("Resuming from stored cursor at slot {}", slot),
+                retry_after_ms: None,
+            };
+            act.send_message(ctx, &info);
+            act.load_backlog(sub_id, filters, slot, ctx);
+        }));
+    }
+
+    /// Narrow an event's instructions to its subscription's program, with
+    /// `ws.project_matched_instructions`
+    fn project_event(&self, event: WsMessage) -> WsMessage {
+        match event {
+            WsMessage::Event { sub, mut tx } => {
+                let program_id = self
+                    .state
+                    .subscriptions
+                    .get(&sub)
+                    .and_then(|subscription| subscription.filters.program_id.as_deref());
+                if let (true, Some(program_id)) = (self.config.project_matched_instructions, program_id) {
+                    project_instructions(&mut tx, program_id);
+                }
+                WsMessage::Event { sub, tx }
+            }
+            other => other,
+        }
+    }
+
+    /// Send a transaction event within the event budget already checked
+    fn deliver_event(&mut self, ctx: &mut WebsocketContext<Self>, event: WsMessage) {
+        let event = self.project_event(event);
+        self.note_delivered(&event);
+        self.send_message(ctx, &event);
+    }
+
+    /// Advance the cursor of the subscription an event was delivered to
+    fn note_delivered(&mut self, event: &WsMessage) {
+        let WsMessage::Event { sub, tx } = event else { return };
+        let Some(slot) = tx.get("slot").and_then(|slot| slot.as_i64()) else { return };
+        let Some(cursor_key) = self.state.subscriptions.get(sub).and_then(|s| s.cursor_key.clone()) else {
+            return;
+        };
+        let cursor = self.cursor_updates.entry(cursor_key).or_insert(slot);
+        *cursor = (*cursor).max(slot);
+    }
+
+    /// Write advanced cursors to the store in the background
+    fn flush_cursors(&mut self) {
+        if self.cursor_updates.is_empty() {
+            return;
+        }
+        let updates = std::mem::take(&mut self.cursor_updates);
+        let Some(cursors) = self.app_state.ws_cursors.clone() else {
+            return;
+        };
+        tokio::spawn(async move {
+            for (key, slot) in updates {
+                if let Err(e) = cursors.save(&key, slot).await {
+                    warn!("Failed to save resume cursor: {}", e);
+                }
+            }
+        });
+    }
+
+    /// Fetch the transactions after `slot` and queue them for paced replay
+    fn load_backlog(&mut self, sub_id: String, filters: TransactionFilters, slot: i64, ctx: &mut WebsocketContext<Self>) {
+        let Some(pool) = self.app_state.postgres.clone() else {
+            self.send_error(ctx, "resume_unavailable", "Database not available for resume");
+            return;
+        };
+
+        // One extra row tells us whether the backlog was cut off
+        let max_rows = self.config.max_resume_rows as usize;
+        let rows = async move {
+            TransactionRepository::new(pool)
+                .list_since_slot(slot, max_rows as i64 + 1)
+                .await
+        };
+        ctx.spawn(actix::fut::wrap_future::<_, Self>(rows).map(move |rows, act, ctx| {
+            let rows = match rows {
+                Ok(rows) => rows,
+                Err(e) => {
+                    error!("Failed to load resume backlog: {}", e);
+                    act.send_error(ctx, "resume_failed", "Failed to load resume backlog");
+                    return;
+                }
+            };
+            // Unsubscribed while the query ran
+            if !act.state.subscriptions.contains_key(&sub_id) {
+                return;
+            }
+
+            let rows = rows.into_iter().map(|tx| serde_json::to_value(tx).unwrap_or_default()).collect();
+            if act.backlog.extend(&sub_id, rows, &filters, max_rows) {
+                let info = WsMessage::Info {
+                    message: format!(
+                        "Resume backlog truncated to {} transactions after slot {}; fetch the rest over HTTP",
+                        max_rows, slot
+                    ),
+                    retry_after_ms: None,
+                };
+                act.send_message(ctx, &info);
+            }
+            debug!("{} resume events queued after slot {}", act.backlog.len(), slot);
+            act.start_backlog_timer(ctx);
+        }));
+    }
+
+    fn start_backlog_timer(&mut self, ctx: &mut WebsocketContext<Self>) {
+        if self.backlog_timer.is_none() && !self.backlog.is_empty() {
+            self.backlog_timer = Some(ctx.run_interval(RESUME_TICK, |act, ctx| act.drain_backlog(ctx)));
+        }
+    }
+
+    /// Send this tick's share of the backlog, within the event budget
+    fn drain_backlog(&mut self, ctx: &mut WebsocketContext<Self>) {
+        for _ in 0..events_per_tick(self.config.max_events_per_sec) {
+            let Some(event) = self.backlog.pop() else { break };
+            if !self.check_event_rate_limit() {
+                // Live events used up this second; try again next tick
+                self.backlog.push_front(event);
+                break;
+            }
+            self.deliver_event(ctx, event);
+        }
+
+        if self.backlog.is_empty() {
+            if let Some(timer) = self.backlog_timer.take() {
+                ctx.cancel_future(timer);
+            }
+        }
+    }
+    
     /// Check client message rate limit
     fn check_client_rate_limit(&mut self, ctx: &mut WebsocketContext<Self>) -> bool {
         let now = Instant::now();
@@ -140,6 +440,18 @@ impl TxWebSocket {
         true
     }
     
+    /// Check subscribe/unsubscribe churn, closing connections that exceed it
+    fn check_sub_op_rate_limit(&mut self, ctx: &mut WebsocketContext<Self>) -> bool {
+        if self.state.record_sub_op(Instant::now(), self.config.max_sub_ops_per_min) {
+            return true;
+        }
+
+        warn!("Closing WebSocket connection for subscription churn");
+        self.send_error(ctx, "rate_limited", "Too many subscribe/unsubscribe requests");
+        ctx.close(Some(ws::CloseCode::Policy.into()));
+        false
+    }
+
     /// Check event rate limit
     fn check_event_rate_limit(&mut self) -> bool {
         let now = Instant::now();
@@ -175,6 +487,37 @@ impl TxWebSocket {
         }
     }
     
+    /// Send an ingest statistics snapshot to every stats subscription
+    fn send_stats(&self, ctx: &mut WebsocketContext<Self>) {
+        let stats_subs: Vec<String> = self
+            .state
+            .subscriptions
+            .values()
+            .filter(|sub| sub.kind == SubscriptionKind::Stats)
+            .map(|sub| sub.id.clone())
+            .collect();
+
+        if stats_subs.is_empty() {
+            return;
+        }
+
+        let snapshot = match self.app_state.ingest_stats.lock() {
+            Ok(stats) => stats.clone(),
+            Err(_) => {
+                error!("Ingest stats lock poisoned");
+                return;
+            }
+        };
+
+        for sub in stats_subs {
+            let msg = WsMessage::Stats {
+                sub,
+                stats: snapshot.clone(),
+            };
+            self.send_message(ctx, &msg);
+        }
+    }
+    
     /// Send ping to client
     fn send_ping(&mut self, ctx: &mut WebsocketContext<Self>) {
         let ping = WsMessage::Ping {
@@ -187,47 +530,135 @@ impl TxWebSocket {
         self.last_ping = Instant::now();
     }
     
-    /// Check if connection is idle
-    fn is_idle(&self) -> bool {
-        Instant::now().duration_since(self.state.last_activity) >= Duration::from_secs(self.config.idle_timeout_secs)
+    /// Decide what the periodic tick should do; idle close wins over pinging
+    fn tick_action(&self, now: Instant) -> TickAction {
+        if now.duration_since(self.state.last_activity) >= Duration::from_secs(self.config.idle_timeout_secs) {
+            TickAction::CloseIdle
+        } else if now.duration_since(self.last_ping) >= Duration::from_secs(self.config.ping_interval_secs) {
+            TickAction::Ping
+        } else {
+            TickAction::Wait
+        }
     }
-    
-    /// Check if ping is overdue
-    fn is_ping_overdue(&self) -> bool {
-        Instant::now().duration_since(self.last_ping) >= Duration::from_secs(self.config.ping_interval_secs)
+
+    /// Single per-connection timer for ping and idle checks
+    fn on_tick(&mut self, ctx: &mut WebsocketContext<Self>) {
+        self.flush_cursors();
+        match self.tick_action(Instant::now()) {
+            TickAction::CloseIdle => {
+                info!("Closing idle WebSocket connection");
+                ctx.close(Some(ws::CloseCode::Normal.into()));
+            }
+            TickAction::Ping => self.send_ping(ctx),
+            TickAction::Wait => {}
+        }
     }
 }
 
+/// Outcome of a connection tick
+#[derive(Debug, PartialEq, Eq)]
+enum TickAction {
+    CloseIdle,
+    Ping,
+    Wait,
+}
+
 impl Actor for TxWebSocket {
     type Context = WebsocketContext<Self>;
     
     fn started(&mut self, ctx: &mut Self::Context) {
         info!("WebSocket connection established");
         
-        // Start ping timer
-        ctx.run_interval(Duration::from_secs(self.config.ping_interval_secs), |act, ctx| {
-            if act.is_ping_overdue() {
-                act.send_ping(ctx);
-            }
+        // One timer handles both overdue pings and idle timeouts
+        ctx.run_interval(Duration::from_secs(self.config.tick_interval_secs.max(1)), |act, ctx| {
+            act.on_tick(ctx);
         });
         
-        // Start idle timeout check
-        ctx.run_interval(Duration::from_secs(10), |act, ctx| {
-            if act.is_idle() {
-                info!("Closing idle WebSocket connection");
-                ctx.close(Some(ws::CloseCode::Normal.into()));
-            }
+        // Recycle long-lived connections so they spread onto newer instances
+        if self.config.max_connection_lifetime_secs > 0 {
+            ctx.run_later(Duration::from_secs(self.config.max_connection_lifetime_secs), |act, ctx| {
+                let (notice, reason) = max_lifetime_notice();
+                info!("Closing WebSocket at max_connection_lifetime_secs");
+                act.send_message(ctx, &notice);
+                ctx.close(Some(reason));
+            });
+        }
+
+        // Start stats snapshot timer
+        ctx.run_interval(Duration::from_millis(self.config.stats_interval_ms.max(1)), |act, ctx| {
+            act.send_stats(ctx);
         });
+
+        // Transaction events from the fan-out
+        if let Some(events) = self.events_rx.take() {
+            ctx.add_stream(futures_util::stream::unfold(events, |mut events| async move {
+                events.recv().await.map(|event| (event, events))
+            }));
+        }
+
+        // Shutdown notice
+        let shutdown = self.app_state.ws_shutdown.subscribe();
+        ctx.add_stream(futures_util::stream::unfold(shutdown, |mut shutdown| async move {
+            match shutdown.recv().await {
+                Ok(()) | Err(broadcast::error::RecvError::Lagged(_)) => Some((ServerShutdown, shutdown)),
+                Err(broadcast::error::RecvError::Closed) => None,
+            }
+        }));
     }
     
     fn stopped(&mut self, _ctx: &mut Self::Context) {
+        for id in self.state.subscriptions.keys() {
+            self.app_state.ws_fanout.unregister(id);
+            self.app_state.ws_subscriptions.release(id);
+        }
+        self.flush_cursors();
         info!("WebSocket connection closed");
     }
 }
 
+impl StreamHandler<WsMessage> for TxWebSocket {
+    fn handle(&mut self, event: WsMessage, ctx: &mut Self::Context) {
+        // Evicted by the registry: forget it and always tell the client
+        if let WsMessage::Evicted { id } = &event {
+            if self.state.subscriptions.remove(id).is_some() {
+                self.app_state.ws_fanout.unregister(id);
+                self.backlog.remove_subscription(id);
+                self.send_message(ctx, &event);
+            }
+            return;
+        }
+
+        if self.check_event_rate_limit() {
+            self.deliver_event(ctx, event);
+        } else {
+            debug!("Dropping transaction event over max_events_per_sec");
+        }
+    }
+
+    // The actor holds a sender, so the stream only ends on shutdown
+    fn finished(&mut self, _ctx: &mut Self::Context) {}
+}
+
+impl StreamHandler<ServerShutdown> for TxWebSocket {
+    fn handle(&mut self, _: ServerShutdown, ctx: &mut Self::Context) {
+        let retry_after_ms = reconnect_delay_ms(&self.config, &mut rand::thread_rng());
+        let (notice, reason) = shutdown_notice(retry_after_ms);
+        info!("Closing WebSocket for shutdown, retry_after_ms={}", retry_after_ms);
+        self.send_message(ctx, &notice);
+        ctx.close(Some(reason));
+    }
+
+    fn finished(&mut self, _ctx: &mut Self::Context) {}
+}
+
 impl StreamHandler<Result<Message, ProtocolError>> for TxWebSocket {
     fn handle(&mut self, msg: Result<Message, ProtocolError>, ctx: &mut Self::Context) {
         match msg {
+            // Checked before parsing so a huge frame costs no JSON work
+            Ok(Message::Text(text)) if text.len() > self.config.max_frame_bytes => {
+                warn!("Closing WebSocket for an oversized frame of {} bytes", text.len());
+                ctx.close(Some(frame_too_large(self.config.max_frame_bytes)));
+            }
             Ok(Message::Text(text)) => {
                 self.handle_message(&text, ctx);
             }
@@ -242,6 +673,12 @@ impl StreamHandler<Result<Message, ProtocolError>> for TxWebSocket {
                 // Client responded to ping
                 self.last_ping = Instant::now();
             }
+            // The codec refused a frame over max_frame_bytes without buffering it;
+            // the stream ends after the error, which stops the actor
+            Err(ProtocolError::Overflow) => {
+                warn!("Closing WebSocket for a frame over max_frame_bytes");
+                ctx.close(Some(frame_too_large(self.config.max_frame_bytes)));
+            }
             Err(e) => {
                 error!("WebSocket protocol error: {}", e);
                 ctx.stop();
@@ -257,12 +694,381 @@ pub async fn tx_websocket(
     stream: Payload,
     config: Data<WsConfig>,
     app_state: Data<AppState>,
+    server_config: Option<Data<ServerConfig>>,
 ) -> Result<HttpResponse, Error> {
     if !config.enabled {
-        return Ok(HttpResponse::NotFound().finish());
+        return Ok(ApiError::feature_disabled("websocket", server_config.as_ref().map(|c| c.get_ref())).error_response());
     }
     
     let ws = TxWebSocket::new(config.get_ref().clone(), app_state.get_ref().clone());
-    let resp = ws::start(ws, &req, stream)?;
-    Ok(resp)
+    ws::WsResponseBuilder::new(ws, &req, stream)
+        .frame_size(config.max_frame_bytes)
+        .start()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ServiceConfig;
+    use std::sync::Arc;
+
+    fn socket() -> TxWebSocket {
+        let config = WsConfig {
+            ping_interval_secs: 20,
+            idle_timeout_secs: 60,
+            tick_interval_secs: 5,
+            ..WsConfig::default()
+        };
+        TxWebSocket::new(config, AppState::new(ServiceConfig::default(), None, None))
+    }
+
+    #[test]
+    fn test_tick_drives_ping_and_idle_close() {
+        let mut socket = socket();
+        let start = Instant::now();
+        socket.state.last_activity = start;
+        socket.last_ping = start;
+
+        // Ticks every 5s: nothing until the ping is due
+        assert_eq!(socket.tick_action(start + Duration::from_secs(15)), TickAction::Wait);
+        assert_eq!(socket.tick_action(start + Duration::from_secs(20)), TickAction::Ping);
+
+        // The client answered, but sent nothing else: idle close after idle_timeout_secs
+        socket.last_ping = start + Duration::from_secs(55);
+        assert_eq!(socket.tick_action(start + Duration::from_secs(59)), TickAction::Wait);
+        assert_eq!(socket.tick_action(start + Duration::from_secs(60)), TickAction::CloseIdle);
+
+        // Activity keeps the connection open
+        socket.state.last_activity = start + Duration::from_secs(58);
+        assert_eq!(socket.tick_action(start + Duration::from_secs(60)), TickAction::Wait);
+    }
+
+    #[tokio::test]
+    async fn test_delivered_slots_are_saved_for_the_next_connection() {
+        let ws_config = WsConfig {
+            resume_cursor_backend: "memory".to_string(),
+            ..WsConfig::default()
+        };
+        let cursors = Arc::new(crate::ws::cursor::CursorStore::from_config(&ws_config, None).unwrap());
+        let mut socket = socket();
+        socket.app_state.ws_cursors = Some(cursors.clone());
+
+        let filters = TransactionFilters::default();
+        let cursor_key = cursors.key("client-1", SubscriptionKind::Transactions, &filters);
+        for (id, cursor_key) in [("tracked", Some(cursor_key.clone())), ("anonymous", None)] {
+            socket.state.subscriptions.insert(
+                id.to_string(),
+                Subscription {
+                    id: id.to_string(),
+                    kind: SubscriptionKind::Transactions,
+                    filters: filters.clone(),
+                    created_at: Instant::now(),
+                    cursor_key,
+                },
+            );
+        }
+
+        for (sub, slot) in [("tracked", 7), ("tracked", 5), ("anonymous", 9)] {
+            socket.note_delivered(&WsMessage::Event {
+                sub: sub.to_string(),
+                tx: serde_json::json!({ "signature": format!("sig{}", slot), "slot": slot }),
+            });
+        }
+        socket.flush_cursors();
+        tokio::task::yield_now().await;
+
+        // A reconnect with the same client_id and filters resumes after slot 7
+        assert_eq!(cursors.load(&cursor_key).await.unwrap(), Some(7));
+        assert!(socket.cursor_updates.is_empty());
+    }
+
+    #[actix_web::test]
+    async fn test_oversized_frame_closes_with_size_code() {
+        use futures_util::StreamExt;
+
+        let mut socket = socket();
+        socket.config.max_frame_bytes = 64;
+
+        // A masked client text frame (all-zero mask) over the limit
+        let payload = format!(r#"{{"type":"Subscribe","pad":"{}"}}"#, "x".repeat(70));
+        let mut frame = vec![0x81, 0x80 | payload.len() as u8, 0, 0, 0, 0];
+        frame.extend_from_slice(payload.as_bytes());
+        let input = futures_util::stream::once(async move {
+            Ok::<_, actix_web::error::PayloadError>(actix_web::web::Bytes::from(frame))
+        })
+        .chain(futures_util::stream::pending());
+        let mut output = WebsocketContext::create(socket, input);
+
+        // Read server frames until the close frame arrives
+        let mut written = Vec::new();
+        let close_code = tokio::time::timeout(Duration::from_secs(2), async {
+            loop {
+                written.extend_from_slice(&output.next().await.unwrap().unwrap());
+                let mut at = 0;
+                while at + 2 <= written.len() {
+                    let (opcode, len) = (written[at] & 0x0f, (written[at + 1] & 0x7f) as usize);
+                    if opcode == 0x8 && at + 4 <= written.len() {
+                        return u16::from_be_bytes([written[at + 2], written[at + 3]]);
+                    }
+                    at += 2 + len;
+                }
+            }
+        })
+        .await
+        .expect("connection was not closed");
+
+        assert_eq!(close_code, u16::from(ws::CloseCode::Size));
+    }
+
+    #[test]
+    fn test_program_subscription_receives_only_matching_instructions() {
+        let mut socket = socket();
+        socket.config.project_matched_instructions = true;
+        for (id, program_id) in [("by-program", Some("prog")), ("everything", None)] {
+            socket.state.subscriptions.insert(
+                id.to_string(),
+                Subscription {
+                    id: id.to_string(),
+                    kind: SubscriptionKind::Transactions,
+                    filters: TransactionFilters {
+                        program_id: program_id.map(str::to_string),
+                        ..TransactionFilters::default()
+                    },
+                    created_at: Instant::now(),
+                    cursor_key: None,
+                },
+            );
+        }
+        let tx = serde_json::json!({
+            "signature": "sig1",
+            "slot": 1,
+            "program_ids": ["other", "prog"],
+            "instructions": [
+                { "program_id_index": 0, "data": "a" },
+                { "program_id_index": 1, "data": "b" },
+                { "program_id": "prog", "data": "c" },
+                { "program_id": "other", "data": "d" },
+                { "data": "e" }
+            ]
+        });
+        let delivered = |socket: &TxWebSocket, sub: &str| match socket.project_event(WsMessage::Event {
+            sub: sub.to_string(),
+            tx: tx.clone(),
+        }) {
+            WsMessage::Event { tx, .. } => tx,
+            other => panic!("unexpected message: {:?}", other),
+        };
+
+        let projected = delivered(&socket, "by-program");
+        let data: Vec<_> = projected["instructions"].as_array().unwrap().iter().map(|i| i["data"].clone()).collect();
+        assert_eq!(data, [serde_json::json!("b"), serde_json::json!("c")]);
+        assert_eq!(projected["signature"], "sig1");
+
+        // Subscriptions without a program, and the option turned off, get everything
+        assert_eq!(delivered(&socket, "everything"), tx);
+        socket.config.project_matched_instructions = false;
+        assert_eq!(delivered(&socket, "by-program"), tx);
+    }
+
+    #[test]
+    fn test_filters_must_be_selective_and_within_predicate_budget() {
+        let mut socket = socket();
+        socket.config.require_selective_filter = true;
+        socket.config.max_filter_predicates_per_conn = 4;
+        let slots_only = TransactionFilters {
+            slot_from: Some(1),
+            slot_to: Some(1_000_000),
+            ..TransactionFilters::default()
+        };
+        let by_program = TransactionFilters {
+            program_id: Some("prog".to_string()),
+            slot_from: Some(1),
+            ..TransactionFilters::default()
+        };
+
+        // A slot range alone would evaluate every event
+        let refused = socket.check_filters(SubscriptionKind::Transactions, &slots_only);
+        assert_eq!(refused.unwrap_err().0, "filter_not_selective");
+        assert!(socket.check_filters(SubscriptionKind::Transactions, &by_program).is_ok());
+        // Stats subscriptions ignore filters
+        assert!(socket.check_filters(SubscriptionKind::Stats, &TransactionFilters::default()).is_ok());
+
+        // Two predicates held, two more fit, a third doesn't
+        socket.state.subscriptions.insert(
+            "held".to_string(),
+            Subscription {
+                id: "held".to_string(),
+                kind: SubscriptionKind::Transactions,
+                filters: by_program.clone(),
+                created_at: Instant::now(),
+                cursor_key: None,
+            },
+        );
+        assert!(socket.check_filters(SubscriptionKind::Transactions, &by_program).is_ok());
+        let three = TransactionFilters {
+            to: Some("bob".to_string()),
+            ..by_program
+        };
+        let refused = socket.check_filters(SubscriptionKind::Transactions, &three);
+        assert_eq!(refused.unwrap_err().0, "too_many_filter_predicates");
+    }
+
+    #[test]
+    fn test_shutdown_notice_carries_bounded_retry_after() {
+        let config = WsConfig {
+            reconnect_min_ms: 2000,
+            reconnect_max_ms: 5000,
+            ..WsConfig::default()
+        };
+        let mut rng = rand::thread_rng();
+        let delays: Vec<u64> = (0..200).map(|_| reconnect_delay_ms(&config, &mut rng)).collect();
+        assert!(delays.iter().all(|delay| (2000..=5000).contains(delay)));
+        // Jittered rather than one value for everyone
+        assert!(delays.iter().any(|delay| *delay != delays[0]));
+
+        let (notice, reason) = shutdown_notice(delays[0]);
+        let json = serde_json::to_value(&notice).unwrap();
+        assert_eq!(json["type"], "Info");
+        assert_eq!(json["retry_after_ms"], delays[0]);
+        assert_eq!(reason.code, ws::CloseCode::Restart);
+        assert!(reason.description.unwrap().ends_with(&format!("retry_after_ms={}", delays[0])));
+
+        // A max below the min collapses to the min
+        let fixed = WsConfig {
+            reconnect_min_ms: 3000,
+            reconnect_max_ms: 0,
+            ..WsConfig::default()
+        };
+        assert_eq!(reconnect_delay_ms(&fixed, &mut rng), 3000);
+
+        // Other Info messages don't carry the field
+        let info = WsMessage::Info { message: "Resuming".to_string(), retry_after_ms: None };
+        assert!(serde_json::to_value(&info).unwrap().get("retry_after_ms").is_none());
+    }
+
+    /// Next server frame as `(opcode, payload)`; server frames are unmasked, with
+    /// a 7-bit (or 126 + 16-bit) length
+    async fn next_frame(
+        frames: &mut (impl futures_util::Stream<Item = Result<actix_web::web::Bytes, Error>> + Unpin),
+        buf: &mut Vec<u8>,
+    ) -> (u8, Vec<u8>) {
+        use futures_util::StreamExt;
+
+        loop {
+            let header = match buf.get(1).map(|len| len & 0x7f) {
+                Some(126) if buf.len() >= 4 => Some((4, u16::from_be_bytes([buf[2], buf[3]]) as usize)),
+                Some(len) if len < 126 => Some((2, len as usize)),
+                _ => None,
+            };
+            if let Some((header, len)) = header.filter(|(header, len)| buf.len() >= header + len) {
+                let frame = (buf[0] & 0x0f, buf[header..header + len].to_vec());
+                buf.drain(..header + len);
+                return frame;
+            }
+            let chunk = tokio::time::timeout(Duration::from_secs(5), frames.next())
+                .await
+                .expect("no frame from the server")
+                .unwrap()
+                .unwrap();
+            buf.extend_from_slice(&chunk);
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_stats_subscription_receives_rising_snapshots() {
+        use actix_web::{error::PayloadError, web::Bytes};
+        use futures_util::StreamExt;
+
+        let config = WsConfig {
+            stats_interval_ms: 20,
+            ..WsConfig::default()
+        };
+        let socket = TxWebSocket::new(config, AppState::new(ServiceConfig::default(), None, None));
+        let ingest_stats = socket.app_state.ingest_stats.clone();
+
+        // A masked client text frame (all-zero mask) subscribing to stats
+        let payload = r#"{"type":"Subscribe","kind":"stats"}"#;
+        let mut frame = vec![0x81, 0x80 | payload.len() as u8, 0, 0, 0, 0];
+        frame.extend_from_slice(payload.as_bytes());
+        let input = futures_util::stream::once(async move { Ok::<_, PayloadError>(Bytes::from(frame)) })
+            .chain(futures_util::stream::pending());
+        let mut frames = Box::pin(WebsocketContext::create(socket, input));
+        let mut buf = Vec::new();
+
+        let (_, ack) = next_frame(&mut frames, &mut buf).await;
+        let ack: serde_json::Value = serde_json::from_slice(&ack).unwrap();
+        assert_eq!(ack["type"], "Ack");
+
+        // Ingest moves on between ticks, and every snapshot shows it
+        let mut received = Vec::new();
+        while received.len() < 3 {
+            let (opcode, text) = next_frame(&mut frames, &mut buf).await;
+            assert_eq!(opcode, 0x1);
+            let msg: serde_json::Value = serde_json::from_slice(&text).unwrap();
+            assert_eq!(msg["type"], "Stats");
+            assert_eq!(msg["sub"], ack["id"]);
+            received.push(msg["stats"]["messages_received"].as_u64().unwrap());
+            ingest_stats.lock().unwrap().record_message_received();
+        }
+        assert!(received.windows(2).all(|pair| pair[0] < pair[1]), "{:?}", received);
+    }
+
+    #[actix_web::test]
+    async fn test_connection_is_closed_at_max_lifetime() {
+        use actix_web::{error::PayloadError, web::Bytes};
+
+        let config = WsConfig {
+            max_connection_lifetime_secs: 1,
+            ..WsConfig::default()
+        };
+        let socket = TxWebSocket::new(config, AppState::new(ServiceConfig::default(), None, None));
+        let mut frames = Box::pin(WebsocketContext::create(
+            socket,
+            futures_util::stream::pending::<Result<Bytes, PayloadError>>(),
+        ));
+
+        let started = Instant::now();
+        let mut buf = Vec::new();
+        let mut received = Vec::new();
+        while !received.iter().any(|(opcode, _)| *opcode == 0x8) {
+            received.push(next_frame(&mut frames, &mut buf).await);
+        }
+        assert!(started.elapsed() >= Duration::from_secs(1));
+
+        let (opcode, notice) = &received[received.len() - 2];
+        assert_eq!(*opcode, 0x1);
+        let notice: serde_json::Value = serde_json::from_slice(notice).unwrap();
+        assert_eq!(notice, serde_json::json!({ "type": "Info", "message": "max_lifetime_reached" }));
+        let (_, close) = received.last().unwrap();
+        assert_eq!(u16::from_be_bytes([close[0], close[1]]), u16::from(ws::CloseCode::Away));
+    }
+
+    #[actix_web::test]
+    async fn test_disabled_websocket_says_so() {
+        use actix_web::{http::StatusCode, test, web, App};
+
+        let app = |status: u16| {
+            test::init_service(
+                App::new()
+                    .app_data(Data::new(WsConfig {
+                        enabled: false,
+                        ..WsConfig::default()
+                    }))
+                    .app_data(Data::new(AppState::new(ServiceConfig::default(), None, None)))
+                    .app_data(Data::new(ServerConfig {
+                        disabled_feature_status: status,
+                        ..ServerConfig::default()
+                    }))
+                    .route("/ws/tx", web::get().to(tx_websocket)),
+            )
+        };
+
+        for (configured, expected) in [(503, StatusCode::SERVICE_UNAVAILABLE), (404, StatusCode::NOT_FOUND)] {
+            let app = app(configured).await;
+            let resp = test::call_service(&app, test::TestRequest::get().uri("/ws/tx").to_request()).await;
+            assert_eq!(resp.status(), expected);
+            let body: serde_json::Value = test::read_body_json(resp).await;
+            assert_eq!(body, serde_json::json!({ "error": "feature_disabled", "feature": "websocket" }));
+        }
+    }
 }
@@ -1,20 +1,214 @@
-/// Authentication utilities for Solana wallet verification
+//! Authentication utilities for Solana wallet verification
 
 use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use k256::ecdsa::{self, signature::hazmat::PrehashVerifier, RecoveryId};
+use sha3::{Digest, Keccak256};
 use thiserror::Error;
 
 #[derive(Debug, Error)]
 pub enum AuthError {
     #[error("Invalid base58 encoding: {0}")]
     InvalidBase58(String),
-    #[error("Invalid public key length: expected 32 bytes, got {0}")]
+    #[error("Invalid base64 encoding: {0}")]
+    InvalidBase64(String),
+    #[error("Invalid public key length: {0} bytes")]
     InvalidPubkeyLength(usize),
-    #[error("Invalid signature length: expected 64 bytes, got {0}")]
+    #[error("Invalid signature length: {0} bytes")]
     InvalidSignatureLength(usize),
+    #[error("No signature encoding is enabled")]
+    SignatureFormatDisabled,
     #[error("Signature verification failed")]
     VerificationFailed,
     #[error("Invalid public key: {0}")]
     InvalidPubkey(String),
+    #[error("Unknown signature scheme: {0}")]
+    UnknownScheme(String),
+}
+
+/// A signature algorithm wallets sign requests with
+pub trait SignatureScheme: Send + Sync + std::fmt::Debug {
+    /// Name used in `auth.scheme`
+    fn name(&self) -> &'static str;
+
+    /// Decode a base58 wallet address into this scheme's public key
+    fn decode_pubkey(&self, address: &str) -> Result<Vec<u8>, AuthError>;
+
+    /// Whether a decoded signature has a length this scheme accepts
+    fn check_signature_len(&self, len: usize) -> Result<(), AuthError>;
+
+    /// Check `sig` over `message`; a well-formed signature that doesn't match is `Ok(false)`
+    fn verify(&self, pubkey: &[u8], message: &[u8], sig: &[u8]) -> Result<bool, AuthError>;
+
+    /// Check several signatures by the same key, one result per item
+    fn verify_batch(&self, pubkey: &[u8], items: &[(&[u8], &[u8])]) -> Result<Vec<bool>, AuthError> {
+        items.iter().map(|(message, sig)| self.verify(pubkey, message, sig)).collect()
+    }
+}
+
+/// Ed25519, the scheme Solana wallets use
+#[derive(Debug)]
+pub struct Ed25519Scheme;
+
+impl SignatureScheme for Ed25519Scheme {
+    fn name(&self) -> &'static str {
+        "ed25519"
+    }
+
+    fn decode_pubkey(&self, address: &str) -> Result<Vec<u8>, AuthError> {
+        decode_pubkey_b58(address).map(|pubkey| pubkey.to_vec())
+    }
+
+    fn check_signature_len(&self, len: usize) -> Result<(), AuthError> {
+        match len {
+            64 => Ok(()),
+            len => Err(AuthError::InvalidSignatureLength(len)),
+        }
+    }
+
+    fn verify(&self, pubkey: &[u8], message: &[u8], sig: &[u8]) -> Result<bool, AuthError> {
+        let pubkey: &[u8; 32] = pubkey
+            .try_into()
+            .map_err(|_| AuthError::InvalidPubkeyLength(pubkey.len()))?;
+        let sig: &[u8; 64] = sig
+            .try_into()
+            .map_err(|_| AuthError::InvalidSignatureLength(sig.len()))?;
+        verify_ed25519(pubkey, message, sig)
+    }
+
+    fn verify_batch(&self, pubkey: &[u8], items: &[(&[u8], &[u8])]) -> Result<Vec<bool>, AuthError> {
+        let pubkey: &[u8; 32] = pubkey
+            .try_into()
+            .map_err(|_| AuthError::InvalidPubkeyLength(pubkey.len()))?;
+        let items = items
+            .iter()
+            .map(|(message, sig)| {
+                let sig: [u8; 64] = (*sig).try_into().map_err(|_| AuthError::InvalidSignatureLength(sig.len()))?;
+                Ok((*message, sig))
+            })
+            .collect::<Result<Vec<_>, AuthError>>()?;
+        verify_ed25519_batch(pubkey, &items)
+    }
+}
+
+/// ECDSA over secp256k1 as Ethereum wallets sign (`personal_sign`)
+///
+/// The message is hashed as an EIP-191 personal message with Keccak-256. The
+/// public key is SEC1-encoded (33 or 65 bytes); the signature is `r || s`,
+/// optionally followed by the recovery byte `v` (0/1 or 27/28), in which case
+/// the signer is recovered and compared with the key.
+#[derive(Debug)]
+pub struct Secp256k1Scheme;
+
+/// Keccak-256 of `message` as an EIP-191 personal message, what `personal_sign` signs
+pub fn eip191_digest(message: &[u8]) -> [u8; 32] {
+    let mut hasher = Keccak256::new();
+    hasher.update(format!("\x19Ethereum Signed Message:\n{}", message.len()).as_bytes());
+    hasher.update(message);
+    hasher.finalize().into()
+}
+
+impl SignatureScheme for Secp256k1Scheme {
+    fn name(&self) -> &'static str {
+        "secp256k1"
+    }
+
+    /// The address is the base58 SEC1 key, which must be a point on the curve
+    fn decode_pubkey(&self, address: &str) -> Result<Vec<u8>, AuthError> {
+        let bytes = bs58::decode(address)
+            .into_vec()
+            .map_err(|e| AuthError::InvalidBase58(e.to_string()))?;
+        if !matches!(bytes.len(), 33 | 65) {
+            return Err(AuthError::InvalidPubkeyLength(bytes.len()));
+        }
+        ecdsa::VerifyingKey::from_sec1_bytes(&bytes).map_err(|e| AuthError::InvalidPubkey(e.to_string()))?;
+        Ok(bytes)
+    }
+
+    fn check_signature_len(&self, len: usize) -> Result<(), AuthError> {
+        match len {
+            64 | 65 => Ok(()),
+            len => Err(AuthError::InvalidSignatureLength(len)),
+        }
+    }
+
+    fn verify(&self, pubkey: &[u8], message: &[u8], sig: &[u8]) -> Result<bool, AuthError> {
+        let verifying_key =
+            ecdsa::VerifyingKey::from_sec1_bytes(pubkey).map_err(|e| AuthError::InvalidPubkey(e.to_string()))?;
+        let (rs, recovery) = match sig.len() {
+            64 => (sig, None),
+            65 => (&sig[..64], Some(sig[64])),
+            len => return Err(AuthError::InvalidSignatureLength(len)),
+        };
+        let Ok(signature) = ecdsa::Signature::from_slice(rs) else {
+            return Ok(false);
+        };
+        let digest = eip191_digest(message);
+
+        // Wallets aren't all strict about low-S; normalizing flips the recovered y parity
+        let (signature, flipped) = match signature.normalize_s() {
+            Some(normalized) => (normalized, true),
+            None => (signature, false),
+        };
+
+        match recovery {
+            None => Ok(verifying_key.verify_prehash(&digest, &signature).is_ok()),
+            Some(v) => {
+                let Some(recovery_id) = RecoveryId::from_byte(if v >= 27 { v - 27 } else { v }) else {
+                    return Ok(false);
+                };
+                let recovery_id = RecoveryId::new(recovery_id.is_y_odd() ^ flipped, recovery_id.is_x_reduced());
+                Ok(ecdsa::VerifyingKey::recover_from_prehash(&digest, &signature, recovery_id)
+                    .is_ok_and(|recovered| recovered == verifying_key))
+            }
+        }
+    }
+}
+
+/// Every built-in scheme
+pub static SCHEMES: &[&dyn SignatureScheme] = &[&Ed25519Scheme, &Secp256k1Scheme];
+
+/// Look up a signature scheme by its `auth.scheme` name, ignoring case
+pub fn signature_scheme(name: &str) -> Result<&'static dyn SignatureScheme, AuthError> {
+    SCHEMES
+        .iter()
+        .copied()
+        .find(|scheme| scheme.name().eq_ignore_ascii_case(name))
+        .ok_or_else(|| AuthError::UnknownScheme(name.to_string()))
+}
+
+/// The built-in schemes, for callers that pick one in code rather than by name
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SigScheme {
+    Ed25519,
+    Secp256k1,
+}
+
+impl SigScheme {
+    /// The scheme's implementation
+    pub fn scheme(self) -> &'static dyn SignatureScheme {
+        match self {
+            SigScheme::Ed25519 => &Ed25519Scheme,
+            SigScheme::Secp256k1 => &Secp256k1Scheme,
+        }
+    }
+}
+
+impl std::str::FromStr for SigScheme {
+    type Err = AuthError;
+
+    /// Parse an `auth.scheme` name, as [`signature_scheme`] does
+    fn from_str(name: &str) -> Result<Self, AuthError> {
+        match signature_scheme(name)?.name() {
+            "ed25519" => Ok(SigScheme::Ed25519),
+            "secp256k1" => Ok(SigScheme::Secp256k1),
+            other => Err(AuthError::UnknownScheme(other.to_string())),
+        }
+    }
+}
+
+/// Verify a signature with one of the built-in schemes
+pub fn verify_signature(scheme: SigScheme, pubkey: &[u8], message: &[u8], sig: &[u8]) -> Result<bool, AuthError> {
+    scheme.scheme().verify(pubkey, message, sig)
 }
 
 /// Decode Solana public key from base58 string
@@ -32,11 +226,24 @@ pub fn decode_pubkey_b58(addr: &str) -> Result<[u8; 32], AuthError> {
     Ok(result)
 }
 
+/// Decode a base58 signature of any length
+pub fn decode_sig_b58_bytes(sig: &str) -> Result<Vec<u8>, AuthError> {
+    bs58::decode(sig)
+        .into_vec()
+        .map_err(|e| AuthError::InvalidBase58(e.to_string()))
+}
+
+/// Decode a base64 signature of any length
+pub fn decode_sig_b64_bytes(sig: &str) -> Result<Vec<u8>, AuthError> {
+    use base64::Engine;
+    base64::engine::general_purpose::STANDARD
+        .decode(sig)
+        .map_err(|e| AuthError::InvalidBase64(e.to_string()))
+}
+
 /// Decode signature from base58 string
 pub fn decode_sig_b58(sig: &str) -> Result<[u8; 64], AuthError> {
-    let bytes = bs58::decode(sig)
-        .into_vec()
-        .map_err(|e| AuthError::InvalidBase58(e.to_string()))?;
+    let bytes = decode_sig_b58_bytes(sig)?;
     
     if bytes.len() != 64 {
         return Err(AuthError::InvalidSignatureLength(bytes.len()));
@@ -50,10 +257,7 @@ pub fn decode_sig_b58(sig: &str) -> Result<[u8; 64], AuthError> {
 /// Decode signature from base64 string
 #[allow(dead_code)]
 pub fn decode_sig_b64(sig: &str) -> Result<[u8; 64], AuthError> {
-    use base64::Engine;
-    let bytes = base64::engine::general_purpose::STANDARD
-        .decode(sig)
-        .map_err(|e| AuthError::InvalidBase58(format!("base64 decode error: {}", e)))?;
+    let bytes = decode_sig_b64_bytes(sig)?;
     
     if bytes.len() != 64 {
         return Err(AuthError::InvalidSignatureLength(bytes.len()));
@@ -65,12 +269,21 @@ pub fn decode_sig_b64(sig: &str) -> Result<[u8; 64], AuthError> {
 }
 
 /// Build the canonical signing string
+///
+/// Lines are `method`, `path_qs` and `nonce` joined by `separator`, preceded
+/// by `domain` when set so a signature can't be replayed against another app.
+/// With `body_sha256` (hex) a `sha256=<hex>` line follows the nonce, binding
+/// the signature to the request body.
+#[allow(clippy::too_many_arguments)]
 pub fn build_signing_string(
     method: &str,
     path_qs: &str,
     nonce: &str,
     canon_method: &str,
     canon_path: &str,
+    separator: &str,
+    domain: Option<&str>,
+    body_sha256: Option<&str>,
 ) -> String {
     let canonical_method = match canon_method {
         "upper" => method.to_uppercase(),
@@ -83,7 +296,14 @@ pub fn build_signing_string(
         _ => path_qs.to_string(), // "as-is"
     };
     
-    format!("{}\n{}\n{}", canonical_method, canonical_path, nonce)
+    let body_line = body_sha256.map(|hex| format!("sha256={}", hex));
+    let mut lines = Vec::with_capacity(5);
+    if let Some(domain) = domain {
+        lines.push(domain);
+    }
+    lines.extend([canonical_method.as_str(), canonical_path.as_str(), nonce]);
+    lines.extend(body_line.as_deref());
+    lines.join(separator)
 }
 
 /// Verify Ed25519 signature
@@ -99,6 +319,166 @@ pub fn verify_ed25519(pubkey: &[u8; 32], message: &[u8], sig: &[u8; 64]) -> Resu
     }
 }
 
+/// Verify several Ed25519 signatures by the same key, one result per item
+///
+/// All items are checked in a single batch first; only when that fails is
+/// each signature checked on its own to find the bad ones.
+pub fn verify_ed25519_batch(pubkey: &[u8; 32], items: &[(&[u8], [u8; 64])]) -> Result<Vec<bool>, AuthError> {
+    let verifying_key = VerifyingKey::from_bytes(pubkey)
+        .map_err(|e| AuthError::InvalidPubkey(e.to_string()))?;
+
+    let messages: Vec<&[u8]> = items.iter().map(|(message, _)| *message).collect();
+    let signatures: Vec<Signature> = items.iter().map(|(_, sig)| Signature::from_bytes(sig)).collect();
+    let keys = vec![verifying_key; items.len()];
+    if ed25519_dalek::verify_batch(&messages, &signatures, &keys).is_ok() {
+        return Ok(vec![true; items.len()]);
+    }
+
+    Ok(messages
+        .iter()
+        .zip(&signatures)
+        .map(|(message, signature)| verifying_key.verify(message, signature).is_ok())
+        .collect())
+}
+
+/// Verifies wallet-signed requests under one configuration
+///
+/// Holds what the free functions otherwise take on every call: the scheme,
+/// the accepted signature encodings and the signing-string layout.
+#[derive(Debug, Clone)]
+pub struct WalletVerifier {
+    pub scheme: &'static dyn SignatureScheme,
+    /// Accept base58 signatures; tried before base64
+    pub accept_b58: bool,
+    pub accept_b64: bool,
+    /// "upper" | "lower" | "as-is"
+    pub canon_method: String,
+    /// "lower" | "as-is"
+    pub canon_path: String,
+    pub separator: String,
+    /// First line of the signing string, when set
+    pub domain: Option<String>,
+}
+
+impl Default for WalletVerifier {
+    fn default() -> Self {
+        Self {
+            scheme: &Ed25519Scheme,
+            accept_b58: true,
+            accept_b64: false,
+            canon_method: "upper".to_string(),
+            canon_path: "as-is".to_string(),
+            separator: "\n".to_string(),
+            domain: None,
+        }
+    }
+}
+
+impl WalletVerifier {
+    /// Decode a base58 wallet address into a public key for the scheme
+    ///
+    /// Every failure is `InvalidPubkey` or `InvalidPubkeyLength`, so a bad
+    /// address can't be mistaken for a bad signature encoding.
+    pub fn decode_pubkey(&self, address: &str) -> Result<Vec<u8>, AuthError> {
+        self.scheme.decode_pubkey(address).map_err(|e| match e {
+            AuthError::InvalidBase58(e) => AuthError::InvalidPubkey(e),
+            e => e,
+        })
+    }
+
+    /// Decode a signature in the first accepted encoding, checking its length for the scheme
+    pub fn decode_signature(&self, signature: &str) -> Result<Vec<u8>, AuthError> {
+        let bytes = if self.accept_b58 {
+            decode_sig_b58_bytes(signature)?
+        } else if self.accept_b64 {
+            decode_sig_b64_bytes(signature)?
+        } else {
+            return Err(AuthError::SignatureFormatDisabled);
+        };
+        self.scheme.check_signature_len(bytes.len())?;
+        Ok(bytes)
+    }
+
+    /// The canonical signing string, see `build_signing_string`, with the
+    /// request timestamp as the last line when there is one
+    pub fn signing_string(
+        &self,
+        method: &str,
+        path_qs: &str,
+        nonce: &str,
+        timestamp: Option<&str>,
+        body_sha256: Option<&str>,
+    ) -> String {
+        let mut signing_string = build_signing_string(
+            method,
+            path_qs,
+            nonce,
+            &self.canon_method,
+            &self.canon_path,
+            &self.separator,
+            self.domain.as_deref(),
+            body_sha256,
+        );
+        if let Some(timestamp) = timestamp {
+            signing_string.push_str(&self.separator);
+            signing_string.push_str(timestamp);
+        }
+        signing_string
+    }
+
+    /// Verify `signature` by `address` over `message`; a signature that
+    /// doesn't match is `AuthError::VerificationFailed`
+    pub fn verify_message(&self, address: &str, signature: &str, message: &[u8]) -> Result<(), AuthError> {
+        let pubkey = self.decode_pubkey(address)?;
+        let signature = self.decode_signature(signature)?;
+        if self.scheme.verify(&pubkey, message, &signature)? {
+            Ok(())
+        } else {
+            Err(AuthError::VerificationFailed)
+        }
+    }
+
+    /// Verify several signatures by `address`, one result per `(message, signature)`
+    ///
+    /// A signature that doesn't decode is just invalid; an address that
+    /// doesn't decode fails the whole call.
+    pub fn verify_batch(&self, address: &str, items: &[(&[u8], &str)]) -> Result<Vec<bool>, AuthError> {
+        let pubkey = self.decode_pubkey(address)?;
+        let decoded: Vec<(usize, Vec<u8>)> = items
+            .iter()
+            .enumerate()
+            .filter_map(|(i, (_, signature))| Some((i, self.decode_signature(signature).ok()?)))
+            .collect();
+        let batch: Vec<(&[u8], &[u8])> = decoded
+            .iter()
+            .map(|(i, signature)| (items[*i].0, signature.as_slice()))
+            .collect();
+
+        let mut valid = vec![false; items.len()];
+        for ((i, _), result) in decoded.iter().zip(self.scheme.verify_batch(&pubkey, &batch)?) {
+            valid[*i] = result;
+        }
+        Ok(valid)
+    }
+
+    /// Verify a request signed over its method, path and query, nonce, and
+    /// optionally its body hash and timestamp, see `signing_string`
+    #[allow(clippy::too_many_arguments)]
+    pub fn verify_request(
+        &self,
+        address: &str,
+        signature: &str,
+        method: &str,
+        path_qs: &str,
+        nonce: &str,
+        timestamp: Option<&str>,
+        body_sha256: Option<&str>,
+    ) -> Result<(), AuthError> {
+        let message = self.signing_string(method, path_qs, nonce, timestamp, body_sha256);
+        self.verify_message(address, signature, message.as_bytes())
+    }
+}
+
 /// Generate a random nonce as base58 string
 pub fn generate_nonce() -> String {
     use rand::Rng;
@@ -121,7 +501,214 @@ mod tests {
 
     #[test]
     fn test_build_signing_string() {
-        let msg = build_signing_string("GET", "/api/test?foo=bar", "nonce123", "upper", "as-is");
+        let msg = build_signing_string("GET", "/api/test?foo=bar", "nonce123", "upper", "as-is", "\n", None, None);
         assert_eq!(msg, "GET\n/api/test?foo=bar\nnonce123");
     }
+
+    #[test]
+    fn test_signing_string_binds_body_hash() {
+        let msg = build_signing_string("post", "/api/test", "nonce123", "upper", "as-is", "\n", None, Some("ab12"));
+        assert_eq!(msg, "POST\n/api/test\nnonce123\nsha256=ab12");
+
+        let msg = build_signing_string("POST", "/api/test", "n1", "upper", "as-is", " | ", Some("app.example.com"), Some("ab12"));
+        assert_eq!(msg, "app.example.com | POST | /api/test | n1 | sha256=ab12");
+    }
+
+    #[test]
+    fn test_signing_string_with_domain_and_separator() {
+        let msg = build_signing_string("get", "/api/test", "nonce123", "upper", "as-is", " | ", Some("app.example.com"), None);
+        assert_eq!(msg, "app.example.com | GET | /api/test | nonce123");
+    }
+
+    #[test]
+    fn test_signature_for_other_domain_fails() {
+        use ed25519_dalek::{Signer, SigningKey};
+
+        let key = SigningKey::from_bytes(&[3u8; 32]);
+        let pubkey = key.verifying_key().to_bytes();
+        let signed = build_signing_string("GET", "/api/test", "n1", "upper", "as-is", "\n", Some("other.example.com"), None);
+        let sig = key.sign(signed.as_bytes()).to_bytes();
+
+        let expected = build_signing_string("GET", "/api/test", "n1", "upper", "as-is", "\n", Some("app.example.com"), None);
+        assert!(!verify_ed25519(&pubkey, expected.as_bytes(), &sig).unwrap());
+        assert!(verify_ed25519(&pubkey, signed.as_bytes(), &sig).unwrap());
+    }
+
+    #[test]
+    fn test_ed25519_through_scheme_trait() {
+        use ed25519_dalek::{Signer, SigningKey};
+
+        let scheme = signature_scheme("ed25519").unwrap();
+        assert_eq!(scheme.name(), "ed25519");
+
+        let key = SigningKey::from_bytes(&[5u8; 32]);
+        let pubkey = key.verifying_key().to_bytes();
+        let sig = key.sign(b"GET\n/api/test\nn1").to_bytes();
+        assert!(scheme.verify(&pubkey, b"GET\n/api/test\nn1", &sig).unwrap());
+        assert!(!scheme.verify(&pubkey, b"GET\n/api/test\nn2", &sig).unwrap());
+
+        assert!(matches!(scheme.verify(&pubkey[..31], b"m", &sig), Err(AuthError::InvalidPubkeyLength(31))));
+        assert!(matches!(scheme.verify(&pubkey, b"m", &sig[..63]), Err(AuthError::InvalidSignatureLength(63))));
+        assert!(matches!(signature_scheme("rsa"), Err(AuthError::UnknownScheme(_))));
+    }
+
+    #[test]
+    fn test_wallet_verifier_verifies_requests() {
+        use ed25519_dalek::{Signer, SigningKey};
+
+        let key = SigningKey::from_bytes(&[13u8; 32]);
+        let address = bs58::encode(key.verifying_key().to_bytes()).into_string();
+        let verifier = WalletVerifier {
+            domain: Some("app.example.com".to_string()),
+            ..WalletVerifier::default()
+        };
+        let message = verifier.signing_string("get", "/api/test?x=1", "n1", None, None);
+        assert_eq!(message, "app.example.com\nGET\n/api/test?x=1\nn1");
+        let signature = key.sign(message.as_bytes()).to_bytes();
+        let b58 = bs58::encode(signature).into_string();
+
+        verifier.verify_request(&address, &b58, "GET", "/api/test?x=1", "n1", None, None).unwrap();
+        assert!(matches!(
+            verifier.verify_request(&address, &b58, "POST", "/api/test?x=1", "n1", None, None),
+            Err(AuthError::VerificationFailed)
+        ));
+        assert!(matches!(
+            verifier.verify_request("not base58!", &b58, "GET", "/api/test?x=1", "n1", None, None),
+            Err(AuthError::InvalidPubkey(_))
+        ));
+        assert!(matches!(
+            verifier.verify_request(&address, "not base58!", "GET", "/api/test?x=1", "n1", None, None),
+            Err(AuthError::InvalidBase58(_))
+        ));
+
+        // The body hash and the timestamp are signed too, the timestamp last
+        let message = verifier.signing_string("POST", "/api/test", "n2", Some("1700000000"), Some("ab12"));
+        assert_eq!(message, "app.example.com\nPOST\n/api/test\nn2\nsha256=ab12\n1700000000");
+        let signed = bs58::encode(key.sign(message.as_bytes()).to_bytes()).into_string();
+        verifier
+            .verify_request(&address, &signed, "POST", "/api/test", "n2", Some("1700000000"), Some("ab12"))
+            .unwrap();
+        for (timestamp, body_sha256) in [
+            (None, Some("ab12")),
+            (Some("1700000001"), Some("ab12")),
+            (Some("1700000000"), None),
+        ] {
+            assert!(matches!(
+                verifier.verify_request(&address, &signed, "POST", "/api/test", "n2", timestamp, body_sha256),
+                Err(AuthError::VerificationFailed)
+            ));
+        }
+
+        // Only the accepted encodings decode
+        use base64::Engine;
+        let b64 = base64::engine::general_purpose::STANDARD.encode(signature);
+        assert!(verifier.verify_request(&address, &b64, "GET", "/api/test?x=1", "n1", None, None).is_err());
+        let b64_only = WalletVerifier {
+            accept_b58: false,
+            accept_b64: true,
+            ..verifier.clone()
+        };
+        b64_only.verify_request(&address, &b64, "GET", "/api/test?x=1", "n1", None, None).unwrap();
+        let neither = WalletVerifier {
+            accept_b64: false,
+            ..b64_only
+        };
+        assert!(matches!(neither.decode_signature(&b64), Err(AuthError::SignatureFormatDisabled)));
+    }
+
+    #[test]
+    fn test_schemes_verify_their_own_signatures() {
+        use ed25519_dalek::Signer;
+        use k256::ecdsa::SigningKey;
+
+        let message = b"GET\n/api/test\nn1";
+
+        let ed_key = ed25519_dalek::SigningKey::from_bytes(&[5u8; 32]);
+        let ed_pubkey = ed_key.verifying_key().to_bytes();
+        let ed_sig = ed_key.sign(message).to_bytes();
+        let ed25519 = signature_scheme("ed25519").unwrap();
+        assert!(ed25519.verify(&ed_pubkey, message, &ed_sig).unwrap());
+
+        // An Ethereum-style personal_sign signature, with and without `v`
+        let key = SigningKey::from_slice(&[9u8; 32]).unwrap();
+        let pubkey = key.verifying_key().to_sec1_bytes();
+        let (sig, recovery_id) = key.sign_prehash_recoverable(&eip191_digest(message)).unwrap();
+        let rs = sig.to_bytes();
+        let mut rsv = rs.to_vec();
+        rsv.push(27 + recovery_id.to_byte());
+
+        let secp256k1 = signature_scheme("Secp256k1").unwrap();
+        assert_eq!(secp256k1.name(), "secp256k1");
+        assert!(secp256k1.verify(&pubkey, message, &rs).unwrap());
+        assert!(secp256k1.verify(&pubkey, message, &rsv).unwrap());
+        assert!(!secp256k1.verify(&pubkey, b"GET\n/api/test\nn2", &rsv).unwrap());
+        let other = SigningKey::from_slice(&[8u8; 32]).unwrap().verifying_key().to_sec1_bytes();
+        assert!(!secp256k1.verify(&other, message, &rsv).unwrap());
+
+        // Keys and signatures of the other scheme don't pass as this one's
+        assert!(secp256k1.verify(&ed_pubkey, message, &ed_sig).is_err());
+        assert!(matches!(ed25519.verify(&pubkey, message, &rs), Err(AuthError::InvalidPubkeyLength(33))));
+
+        // The same through the enum
+        assert_eq!("SECP256K1".parse::<SigScheme>().unwrap(), SigScheme::Secp256k1);
+        assert!(matches!("rsa".parse::<SigScheme>(), Err(AuthError::UnknownScheme(_))));
+        assert!(verify_signature(SigScheme::Ed25519, &ed_pubkey, message, &ed_sig).unwrap());
+        assert!(verify_signature(SigScheme::Secp256k1, &pubkey, message, &rsv).unwrap());
+        assert!(verify_signature(SigScheme::Ed25519, &pubkey, message, &rs).is_err());
+    }
+
+    #[test]
+    fn test_schemes_decode_their_own_addresses() {
+        use k256::ecdsa::SigningKey;
+
+        let sec1 = SigningKey::from_slice(&[9u8; 32]).unwrap().verifying_key().to_sec1_bytes();
+        let secp_address = bs58::encode(&sec1).into_string();
+        let solana_address = "11111111111111111111111111111111";
+        let (ed25519, secp256k1) = (signature_scheme("ed25519").unwrap(), signature_scheme("secp256k1").unwrap());
+
+        assert_eq!(secp256k1.decode_pubkey(&secp_address).unwrap(), sec1.to_vec());
+        assert_eq!(ed25519.decode_pubkey(solana_address).unwrap(), vec![0u8; 32]);
+        assert!(matches!(ed25519.decode_pubkey(&secp_address), Err(AuthError::InvalidPubkeyLength(33))));
+        assert!(matches!(secp256k1.decode_pubkey(solana_address), Err(AuthError::InvalidPubkeyLength(32))));
+
+        // The right length, but not a point on the curve
+        let mut off_curve = [0xffu8; 33];
+        off_curve[0] = 0x02;
+        let off_curve = bs58::encode(off_curve).into_string();
+        assert!(matches!(secp256k1.decode_pubkey(&off_curve), Err(AuthError::InvalidPubkey(_))));
+        assert_eq!(AuthError::InvalidPubkeyLength(33).to_string(), "Invalid public key length: 33 bytes");
+
+        assert!(ed25519.check_signature_len(64).is_ok());
+        assert!(matches!(ed25519.check_signature_len(65), Err(AuthError::InvalidSignatureLength(65))));
+        assert!(secp256k1.check_signature_len(65).is_ok());
+    }
+
+    #[test]
+    fn test_batch_flags_only_the_bad_signatures() {
+        use ed25519_dalek::{Signer, SigningKey};
+
+        let key = SigningKey::from_bytes(&[7u8; 32]);
+        let pubkey = key.verifying_key().to_bytes();
+        let good = key.sign(b"a").to_bytes();
+        let other = key.sign(b"b").to_bytes();
+
+        let all_valid = verify_ed25519_batch(&pubkey, &[(b"a", good), (b"b", other)]).unwrap();
+        assert_eq!(all_valid, vec![true, true]);
+
+        let mixed = verify_ed25519_batch(&pubkey, &[(b"a", good), (b"a", other), (b"b", other)]).unwrap();
+        assert_eq!(mixed, vec![true, false, true]);
+
+        // The same through the scheme, which checks one at a time for other algorithms
+        let scheme = signature_scheme("ed25519").unwrap();
+        let items: [(&[u8], &[u8]); 3] = [(b"a", &good), (b"a", &other), (b"b", &other)];
+        assert_eq!(scheme.verify_batch(&pubkey, &items).unwrap(), vec![true, false, true]);
+
+        // By address, where a signature that doesn't decode is just invalid
+        let verifier = WalletVerifier::default();
+        let address = bs58::encode(pubkey).into_string();
+        let (good, other) = (bs58::encode(good).into_string(), bs58::encode(other).into_string());
+        let items: [(&[u8], &str); 3] = [(b"a", &good), (b"a", "0OIl"), (b"b", &other)];
+        assert_eq!(verifier.verify_batch(&address, &items).unwrap(), vec![true, false, true]);
+        assert!(matches!(verifier.verify_batch("0OIl", &items), Err(AuthError::InvalidPubkey(_))));
+    }
 }
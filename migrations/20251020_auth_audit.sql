@@ -0,0 +1,18 @@
+-- Authentication audit log
+-- Created: 2025-10-20
+-- Tables: auth_audit
+
+-- One row per wallet authentication attempt (success or failure)
+CREATE TABLE IF NOT EXISTS auth_audit (
+    id BIGSERIAL PRIMARY KEY,
+    address TEXT NOT NULL, -- as sent, so not necessarily a valid key; empty when the header was missing
+    method VARCHAR(16) NOT NULL,
+    path TEXT NOT NULL,
+    result VARCHAR(16) NOT NULL, -- success, failure
+    reason VARCHAR(64), -- nonce_missing, invalid_signature, ...
+    ip VARCHAR(64),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Index for "recent attempts for an address" queries
+CREATE INDEX IF NOT EXISTS idx_auth_audit_address_created ON auth_audit(address, created_at DESC);
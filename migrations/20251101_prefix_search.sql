@@ -0,0 +1,9 @@
+-- Prefix search on signatures and pubkeys
+-- Created: 2025-11-01
+-- Indexes: idx_tx_signature_prefix, idx_tx_from_pubkey_prefix, idx_tx_to_pubkey_prefix
+
+-- Pattern-ops indexes let `LIKE 'abc%'` use an index scan regardless of the
+-- database collation; the columns are VARCHAR, hence varchar_pattern_ops
+CREATE INDEX IF NOT EXISTS idx_tx_signature_prefix ON solana_transactions(signature varchar_pattern_ops);
+CREATE INDEX IF NOT EXISTS idx_tx_from_pubkey_prefix ON solana_transactions(from_pubkey varchar_pattern_ops);
+CREATE INDEX IF NOT EXISTS idx_tx_to_pubkey_prefix ON solana_transactions(to_pubkey varchar_pattern_ops);
@@ -0,0 +1,13 @@
+-- Derived transaction type
+-- Created: 2025-11-10
+-- Columns: solana_transactions.tx_type
+-- Indexes: idx_solana_tx_type_slot
+
+-- Set at ingestion from ingest.program_type_map; NULL when no mapped program
+-- was involved, and for rows ingested before this column existed
+ALTER TABLE solana_transactions ADD COLUMN IF NOT EXISTS tx_type VARCHAR(32);
+
+-- Serves ?tx_type= listings in slot order
+CREATE INDEX IF NOT EXISTS idx_solana_tx_type_slot ON solana_transactions(tx_type, slot DESC);
+
+COMMENT ON COLUMN solana_transactions.tx_type IS 'Derived category (e.g. transfer, token, swap) from the programs involved';
@@ -0,0 +1,7 @@
+-- Changes feed
+-- Created: 2025-11-15
+-- Indexes: idx_solana_tx_created_sig
+
+-- Serves GET /api/transactions/changes, which pages in insertion order
+-- with a (created_at, signature) cursor
+CREATE INDEX IF NOT EXISTS idx_solana_tx_created_sig ON solana_transactions(created_at, signature);
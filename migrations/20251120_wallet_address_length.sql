@@ -0,0 +1,7 @@
+-- Wallet address length
+-- Created: 2025-11-20
+
+-- A compressed secp256k1 key is 33 bytes, up to 45 base58 characters, one
+-- more than VARCHAR(44) holds; widen the columns permissions are granted on
+ALTER TABLE users ALTER COLUMN pubkey TYPE VARCHAR(45);
+ALTER TABLE user_permissions ALTER COLUMN pubkey TYPE VARCHAR(45);